@@ -36,13 +36,13 @@ pub fn callable_module_derive(input: TokenStream) -> TokenStream {
 
         impl #impl_generics Fn<(&Tensor, )> for raddar::nn::Mod<#name #ty_generics> #where_clause {
             extern "rust-call" fn call(&self, input: (&Tensor, )) -> tch::Tensor {
-                self.module().forward(input.0)
+                self.call_with_hooks(input.0, |input| self.module().forward(input))
             }
         }
 
         impl #impl_generics FnMut<(&Tensor, )> for raddar::nn::Mod<#name #ty_generics> #where_clause {
             extern "rust-call" fn call_mut(&mut self, input: (&Tensor, )) -> tch::Tensor {
-                self.module().forward(input.0)
+                self.call_with_hooks(input.0, |input| self.module().forward(input))
             }
         }
 
@@ -50,14 +50,15 @@ pub fn callable_module_derive(input: TokenStream) -> TokenStream {
             type Output = Tensor;
 
             extern "rust-call" fn call_once(self, input: (&Tensor, )) -> Tensor {
-                self.module().forward(input.0)
+                self.call_with_hooks(input.0, |input| self.module().forward(input))
             }
         }
     };
     gen.into()
 }
 
-#[proc_macro_derive(NonParameterModule)]
+/// Implements `NonParameterModule`. The struct can be marked with `#[non_parameter(channel_wise)]` if the module keeps the channels of its input, like activations and pooling layers, see `Trainable::keeps_channels`.
+#[proc_macro_derive(NonParameterModule, attributes(non_parameter))]
 pub fn non_parameter_module_derive(input: TokenStream) -> TokenStream {
     let ast: syn::DeriveInput = syn::parse(input).unwrap();
 
@@ -66,15 +67,235 @@ pub fn non_parameter_module_derive(input: TokenStream) -> TokenStream {
     let generics = &ast.generics;
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
+    let keeps_channels = attribute_options(&ast.attrs, "non_parameter")
+        .iter()
+        .any(|option| option == "channel_wise")
+        .then(|| {
+            quote! {
+                fn keeps_channels(&self) -> bool {
+                    true
+                }
+            }
+        });
     let gen = quote! {
-        impl #impl_generics raddar::nn::NonParameterModule for #name #ty_generics #where_clause {}
+        impl #impl_generics raddar::nn::NonParameterModule for #name #ty_generics #where_clause {
+            #keeps_channels
+        }
     };
     gen.into()
 }
 
+/// Implements `Trainable` by collecting the fields of a struct: `TensorCell` and `Option<TensorCell>` fields are parameters named after the field, `Mod<_>` and `Option<Mod<_>>` fields are child modules named after the field, and the modules in `ModuleDict`, `TrainableDict` or `NamedSequential` fields are child modules named by their keys.
+///
+/// Fields can be marked with `#[trainable(skip)]` to be ignored, `#[trainable(buffer)]` to collect a `TensorCell` as a buffer, or `#[trainable(prefixed)]` to name the modules of a dictionary `field.key`.
+///
+/// The modules of `NamedSequential` fields are fused by `Trainable::fuse_conv_bn`, quantized by `Trainable::quantize_modules` and pruned by `Trainable::prune_channels`, like those of a `Mod<NamedSequential>`.
+#[proc_macro_derive(Trainable, attributes(trainable))]
+pub fn trainable_derive(input: TokenStream) -> TokenStream {
+    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+
+    let fields = match ast.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(syn::FieldsNamed { named, .. }),
+            ..
+        }) => named,
+        _ => panic!("Trainable can only be derived for structs with named fields"),
+    };
+
+    let mut parameters = Vec::new();
+    let mut buffers = Vec::new();
+    let mut children = Vec::new();
+    let mut fused = Vec::new();
+    let mut quantized = Vec::new();
+    let mut pruned = Vec::new();
+    for field in fields {
+        let options = trainable_options(&field);
+        if options.iter().any(|option| option == "skip") {
+            continue;
+        }
+        let ident = field.ident.unwrap();
+        let key = syn::LitStr::new(&ident.to_string(), ident.span());
+        let (optional, kind) = match type_name(&field.ty) {
+            Some(name) if name == "Option" => (true, option_inner(&field.ty).and_then(type_name)),
+            name => (false, name),
+        };
+        let Some(kind) = kind else { continue };
+        match kind.as_str() {
+            "TensorCell" => {
+                let tensors = if options.iter().any(|option| option == "buffer") {
+                    &mut buffers
+                } else {
+                    &mut parameters
+                };
+                tensors.push(if optional {
+                    quote! {
+                        if let Some(tensor) = &self.#ident {
+                            result.insert(#key.to_owned(), tensor.clone());
+                        }
+                    }
+                } else {
+                    quote! { result.insert(#key.to_owned(), self.#ident.clone()); }
+                });
+            }
+            "Mod" => children.push(if optional {
+                quote! {
+                    if let Some(module) = &self.#ident {
+                        result.insert(#key.to_owned(), module.clone() as raddar::nn::Mod<dyn raddar::nn::Trainable>);
+                    }
+                }
+            } else {
+                quote! {
+                    result.insert(#key.to_owned(), self.#ident.clone() as raddar::nn::Mod<dyn raddar::nn::Trainable>);
+                }
+            }),
+            "ModuleDict" | "TrainableDict" | "NamedSequential" if !optional => {
+                let name = if options.iter().any(|option| option == "prefixed") {
+                    quote! { format!("{}.{}", #key, name) }
+                } else {
+                    quote! { name.to_owned() }
+                };
+                children.push(quote! {
+                    for (name, module) in self.#ident.iter() {
+                        result.insert(#name, module.clone() as raddar::nn::Mod<dyn raddar::nn::Trainable>);
+                    }
+                });
+                // The modules of a sequential are fused, quantized and pruned in order, the dictionaries have none.
+                if kind == "NamedSequential" {
+                    fused.push(quote! { raddar::nn::Trainable::fuse_conv_bn(&mut self.#ident) });
+                    quantized.push(quote! { raddar::nn::Trainable::quantize_modules(&mut self.#ident, inputs) });
+                    pruned.push(quote! { raddar::nn::Trainable::prune_channels(&mut self.#ident, amount, importance) });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let name = &ast.ident;
+    let generics = &ast.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let parameters = (!parameters.is_empty()).then(|| {
+        quote! {
+            fn parameters(&self) -> raddar::nn::StateDict {
+                let mut result = raddar::nn::StateDict::new();
+                #(#parameters)*
+                result
+            }
+        }
+    });
+    let buffers = (!buffers.is_empty()).then(|| {
+        quote! {
+            fn buffers(&self) -> raddar::nn::StateDict {
+                let mut result = raddar::nn::StateDict::new();
+                #(#buffers)*
+                result
+            }
+        }
+    });
+    let children = (!children.is_empty()).then(|| {
+        quote! {
+            fn child_modules(&self) -> raddar::nn::TrainableDict {
+                let mut result = raddar::nn::TrainableDict::new();
+                #(#children)*
+                result
+            }
+        }
+    });
+    let fused = (!fused.is_empty()).then(|| {
+        quote! {
+            fn fuse_conv_bn(&mut self) -> usize {
+                0 #(+ #fused)*
+            }
+        }
+    });
+    let quantized = (!quantized.is_empty()).then(|| {
+        quote! {
+            fn quantize_modules(
+                &mut self,
+                inputs: &std::collections::HashMap<String, raddar::nn::QParams>,
+            ) -> usize {
+                0 #(+ #quantized)*
+            }
+        }
+    });
+    let pruned = (!pruned.is_empty()).then(|| {
+        quote! {
+            fn prune_channels(
+                &mut self,
+                amount: f64,
+                importance: raddar::nn::ChannelImportance,
+            ) -> usize {
+                0 #(+ #pruned)*
+            }
+        }
+    });
+    let output = quote! {
+        impl #impl_generics raddar::nn::Trainable for #name #ty_generics #where_clause {
+            #parameters
+            #buffers
+            #children
+            #fused
+            #quantized
+            #pruned
+        }
+    };
+    output.into()
+}
+
+/// Returns the options in the `#[trainable(...)]` attributes of a field, e.g. `skip`.
+fn trainable_options(field: &syn::Field) -> Vec<String> {
+    attribute_options(&field.attrs, "trainable")
+}
+
+/// Returns the options in the `#[name(...)]` attributes among `attrs`, e.g. `skip` for `#[trainable(skip)]`.
+fn attribute_options(attrs: &[syn::Attribute], name: &str) -> Vec<String> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident(name))
+        .flat_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => list.nested.into_iter().collect::<Vec<_>>(),
+            _ => panic!("Expected an attribute like #[{}(option)]", name),
+        })
+        .map(|option| match option {
+            syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.get_ident().is_some() => {
+                path.get_ident().unwrap().to_string()
+            }
+            _ => panic!("Expected an attribute like #[{}(option)]", name),
+        })
+        .collect()
+}
+
+/// Returns the last segment of the path of a type, e.g. `Mod` for `raddar::nn::Mod<Linear>`.
+fn type_name(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string()),
+        _ => None,
+    }
+}
+
+/// Returns `T` for a type `Option<T>`.
+fn option_inner(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    match &path.path.segments.last()?.arguments {
+        syn::PathArguments::AngleBracketed(arguments) => {
+            arguments.args.iter().find_map(|argument| match argument {
+                syn::GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            })
+        }
+        _ => None,
+    }
+}
+
 #[proc_macro_derive(
     ArchitectureBuilder,
     attributes(
+        serde,
         builder,
         builder_field_attr,
         builder_impl_attr,
@@ -84,8 +305,9 @@ pub fn non_parameter_module_derive(input: TokenStream) -> TokenStream {
 )]
 pub fn architecture_builder_derive(input: TokenStream) -> TokenStream {
     let ast: syn::DeriveInput = syn::parse(input.clone()).unwrap();
+    let validators = builder_validators(&ast.attrs);
 
-    let builder_fields = match ast.data {
+    let mut builder_fields = match ast.data {
         syn::Data::Struct(syn::DataStruct {
             fields: syn::Fields::Named(syn::FieldsNamed { named, .. }),
             ..
@@ -100,27 +322,257 @@ pub fn architecture_builder_derive(input: TokenStream) -> TokenStream {
                         || attr.path.is_ident("builder_struct_attr")
                 })
             })
+            .map(|mut field| {
+                field.attrs.retain(|attr| !attr.path.is_ident("saveable"));
+                add_serde_with(&mut field);
+                field
+            })
             .collect::<Vec<_>>(),
         _ => panic!("ArchitectureBuilder can only be used on structs with named fields"),
     };
 
+    // Every builder accepts a device, which is the default device of the layers built by the module. Layers which store their device declare the field themselves.
+    if !builder_fields
+        .iter()
+        .any(|field| field.ident.as_ref().unwrap() == "device")
+    {
+        builder_fields.push(
+            syn::Field::parse_named
+                .parse2(quote! {
+                    #[builder(default = "raddar::util::default_device()")]
+                    #[serde(with = "raddar::util::serde_tch::device")]
+                    pub device: tch::Device
+                })
+                .unwrap(),
+        );
+    }
+
     let name = &ast.ident;
     let generics = &ast.generics;
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
     let config_name = syn::Ident::new(&format!("{}Config", name), name.span());
+    let config_name_str = syn::LitStr::new(&config_name.to_string(), config_name.span());
 
     let builder_name = syn::Ident::new(&format!("{}Builder", name), name.span());
     let builder_name_str = syn::LitStr::new(&builder_name.to_string(), builder_name.span());
     let output = quote! {
-        #[derive(derive_builder::Builder, Clone, Debug)]
+        #[derive(derive_builder::Builder, Clone, Debug, serde::Serialize, serde::Deserialize)]
         #[builder(pattern = "owned", name = #builder_name_str, build_fn(private, name = "build_config"))]
         pub struct #config_name #impl_generics #where_clause {
             #(#builder_fields),*
         }
 
         impl #impl_generics #builder_name #ty_generics #where_clause {
+            /// Builds the module.
+            ///
+            /// # Panics
+            ///
+            /// Panics if a required field is not set, or if the config is rejected by a validator of the module. See [Self::try_build] to handle these errors.
             pub fn build(self) -> raddar::nn::Mod<#name #ty_generics> {
-                raddar::nn::Mod::new(#name::new(self.build_config().unwrap()))
+                self.try_build().unwrap_or_else(|error| panic!("{}", error))
+            }
+
+            /// Builds the module, or returns a [raddar::core::RaddarError::InvalidConfig] if a required field is not set or if the config is rejected by a validator of the module.
+            pub fn try_build(self) -> ::std::result::Result<raddar::nn::Mod<#name #ty_generics>, raddar::core::RaddarError> {
+                self.try_config()?.try_build()
+            }
+
+            /// Returns the config of the module without building it, e.g. to save its hyperparameters with serde.
+            ///
+            /// # Panics
+            ///
+            /// Panics if a required field is not set, or if the config is rejected by a validator of the module. See [Self::try_config] to handle these errors.
+            pub fn config(self) -> #config_name #ty_generics {
+                self.try_config().unwrap_or_else(|error| panic!("{}", error))
+            }
+
+            /// Returns the config of the module without building it, or a [raddar::core::RaddarError::InvalidConfig] if a required field is not set or if the config is rejected by a validator of the module.
+            pub fn try_config(self) -> ::std::result::Result<#config_name #ty_generics, raddar::core::RaddarError> {
+                let config = self.build_config().map_err(|error| raddar::core::RaddarError::InvalidConfig {
+                    config: #config_name_str.to_owned(),
+                    message: error.to_string(),
+                })?;
+                config.validate().map_err(|message| raddar::core::RaddarError::InvalidConfig {
+                    config: #config_name_str.to_owned(),
+                    message,
+                })?;
+                ::std::result::Result::Ok(config)
+            }
+        }
+
+        impl #impl_generics #config_name #ty_generics #where_clause {
+            /// Checks the config with the validators of the module, declared as `#[builder(validate = "fn_name")]` on the module, e.g. that the groups of a convolution divide its channels.
+            pub fn validate(&self) -> ::std::result::Result<(), String> {
+                #(#validators(self)?;)*
+                ::std::result::Result::Ok(())
+            }
+
+            /// Builds the module from its config.
+            ///
+            /// # Panics
+            ///
+            /// Panics if the config is rejected by a validator of the module, see [Self::validate] and [Self::try_build].
+            pub fn build(self) -> raddar::nn::Mod<#name #ty_generics> {
+                self.try_build().unwrap_or_else(|error| panic!("{}", error))
+            }
+
+            /// Builds the module from its config, or returns a [raddar::core::RaddarError::InvalidConfig] if the config is rejected by a validator of the module.
+            pub fn try_build(self) -> ::std::result::Result<raddar::nn::Mod<#name #ty_generics>, raddar::core::RaddarError> {
+                self.validate().map_err(|message| raddar::core::RaddarError::InvalidConfig {
+                    config: #config_name_str.to_owned(),
+                    message,
+                })?;
+                ::std::result::Result::Ok(raddar::util::with_default_device(self.device, || {
+                    raddar::nn::Mod::new(#name::new(self))
+                }))
+            }
+        }
+    };
+    output.into()
+}
+
+/// Returns the validators declared on a module as `#[builder(validate = "fn_name")]`, functions taking its config and returning `Result<(), String>`.
+fn builder_validators(attrs: &[syn::Attribute]) -> Vec<syn::Path> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("builder"))
+        .flat_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => list.nested.into_iter().collect::<Vec<_>>(),
+            _ => panic!("Expected an attribute like #[builder(validate = \"fn_name\")]"),
+        })
+        .map(|option| match option {
+            syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                path,
+                lit: syn::Lit::Str(validator),
+                ..
+            })) if path.is_ident("validate") => validator
+                .parse()
+                .expect("Expected the path of a function in #[builder(validate)]"),
+            _ => panic!("Expected an attribute like #[builder(validate = \"fn_name\")]"),
+        })
+        .collect()
+}
+
+/// Returns the helper of `raddar::util::serde_tch` serializing a field of type `Kind` or `Device`.
+fn serde_tch_helper(ty: &syn::Type) -> Option<&'static str> {
+    match ty {
+        syn::Type::Path(path) => match path.path.segments.last() {
+            Some(segment) if segment.ident == "Kind" => Some("raddar::util::serde_tch::kind"),
+            Some(segment) if segment.ident == "Device" => Some("raddar::util::serde_tch::device"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Serializes the fields of type `Kind` or `Device` of a config with the helpers of `raddar::util::serde_tch`, unless they have a serde attribute already.
+fn add_serde_with(field: &mut syn::Field) {
+    if field.attrs.iter().any(|attr| attr.path.is_ident("serde")) {
+        return;
+    }
+    let helper = match serde_tch_helper(&field.ty) {
+        Some(helper) => helper,
+        None => return,
+    };
+    field.attrs.extend(
+        syn::Attribute::parse_outer
+            .parse2(quote! { #[serde(with = #helper)] })
+            .unwrap(),
+    );
+}
+
+#[proc_macro_derive(
+    SaveableModule,
+    attributes(
+        saveable,
+        builder,
+        builder_field_attr,
+        builder_impl_attr,
+        builder_setter_attr,
+        builder_struct_attr
+    )
+)]
+pub fn saveable_module_derive(input: TokenStream) -> TokenStream {
+    let ast: syn::DeriveInput = syn::parse(input.clone()).unwrap();
+
+    let builder_fields = match ast.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(syn::FieldsNamed { named, .. }),
+            ..
+        }) => named
+            .into_iter()
+            .filter(|field| {
+                field.attrs.iter().any(|attr| {
+                    attr.path.is_ident("builder")
+                        || attr.path.is_ident("builder_field_attr")
+                        || attr.path.is_ident("builder_impl_attr")
+                        || attr.path.is_ident("builder_setter_attr")
+                        || attr.path.is_ident("builder_struct_attr")
+                })
+            })
+            // Fields marked with `#[saveable(skip)]`, e.g. the device, are not saved and take their default value when loaded.
+            .filter(|field| {
+                !field.attrs.iter().any(|attr| {
+                    attr.path.is_ident("saveable")
+                        && attr.tokens.to_string().replace(' ', "") == "(skip)"
+                })
+            })
+            .map(|field| {
+                let helper = serde_tch_helper(&field.ty);
+                (field.ident.unwrap(), helper)
+            })
+            .collect::<Vec<_>>(),
+        _ => panic!("SaveableModule can only be used on structs with named fields"),
+    };
+    let field_names = builder_fields
+        .iter()
+        .map(|(field, _)| syn::LitStr::new(&field.to_string(), field.span()))
+        .collect::<Vec<_>>();
+    // The fields of type `Kind` or `Device`, e.g. the dtype, are saved with the helpers of `raddar::util::serde_tch`, like in the config.
+    let to_values = builder_fields.iter().map(|(field, helper)| match helper {
+        Some(helper) => {
+            let helper: syn::Path = syn::parse_str(helper).unwrap();
+            quote! { #helper::serialize(&self.#field, serde_json::value::Serializer).unwrap() }
+        }
+        None => quote! { serde_json::to_value(&self.#field).unwrap() },
+    });
+    let from_values = builder_fields.iter().map(|(field, helper)| match helper {
+        Some(helper) => {
+            let helper: syn::Path = syn::parse_str(helper).unwrap();
+            quote! { builder.#field(#helper::deserialize(value.clone())?) }
+        }
+        None => quote! { builder.#field(serde_json::from_value(value.clone())?) },
+    });
+
+    let name = &ast.ident;
+    let name_str = syn::LitStr::new(&name.to_string(), name.span());
+    let generics = &ast.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let builder_name = syn::Ident::new(&format!("{}Builder", name), name.span());
+    let output = quote! {
+        impl #impl_generics raddar::nn::SaveableModule for #name #ty_generics #where_clause {
+            fn architecture() -> &'static str {
+                #name_str
+            }
+
+            fn config(&self) -> serde_json::Value {
+                let mut config = serde_json::Map::new();
+                #(
+                    config.insert(#field_names.to_owned(), #to_values);
+                )*
+                serde_json::Value::Object(config)
+            }
+
+            fn from_config(config: &serde_json::Value) -> anyhow::Result<Self> {
+                let mut builder = #builder_name::default();
+                #(
+                    if let Some(value) = config.get(#field_names) {
+                        builder = #from_values;
+                    }
+                )*
+                let config = builder.build_config()?;
+                config.validate().map_err(anyhow::Error::msg)?;
+                Ok(#name::new(config))
             }
         }
     };
@@ -130,6 +582,7 @@ pub fn architecture_builder_derive(input: TokenStream) -> TokenStream {
 #[proc_macro_derive(
     PartialBuilder,
     attributes(
+        serde,
         builder,
         builder_field_attr,
         builder_impl_attr,
@@ -155,6 +608,11 @@ pub fn partial_builder_derive(input: TokenStream) -> TokenStream {
                         || attr.path.is_ident("builder_struct_attr")
                 })
             })
+            .map(|mut field| {
+                field.attrs.retain(|attr| !attr.path.is_ident("saveable"));
+                add_serde_with(&mut field);
+                field
+            })
             .collect::<Vec<_>>(),
         _ => panic!("ArchitectureBuilder can only be used on structs with named fields"),
     };
@@ -163,19 +621,34 @@ pub fn partial_builder_derive(input: TokenStream) -> TokenStream {
     let generics = &ast.generics;
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
     let config_name = syn::Ident::new(&format!("{}Config", name), name.span());
+    let config_name_str = syn::LitStr::new(&config_name.to_string(), config_name.span());
 
     let builder_name = syn::Ident::new(&format!("{}Builder", name), name.span());
     let builder_name_str = syn::LitStr::new(&builder_name.to_string(), builder_name.span());
     let output = quote! {
-        #[derive(derive_builder::Builder, Clone, Debug)]
+        #[derive(derive_builder::Builder, Clone, Debug, serde::Serialize, serde::Deserialize)]
         #[builder(pattern = "owned", name = #builder_name_str, build_fn(private, name = "build_config"))]
         pub struct #config_name #impl_generics #where_clause {
             #(#builder_fields),*
         }
 
         impl #impl_generics #builder_name #ty_generics #where_clause {
+            /// Builds the value.
+            ///
+            /// # Panics
+            ///
+            /// Panics if a required field is not set, see [Self::try_build] to handle this error.
             pub fn build(self) -> #name #ty_generics {
-                #name::new(self.build_config().unwrap())
+                self.try_build().unwrap_or_else(|error| panic!("{}", error))
+            }
+
+            /// Builds the value, or returns a [raddar::core::RaddarError::InvalidConfig] if a required field is not set.
+            pub fn try_build(self) -> ::std::result::Result<#name #ty_generics, raddar::core::RaddarError> {
+                let config = self.build_config().map_err(|error| raddar::core::RaddarError::InvalidConfig {
+                    config: #config_name_str.to_owned(),
+                    message: error.to_string(),
+                })?;
+                ::std::result::Result::Ok(#name::new(config))
             }
         }
     };
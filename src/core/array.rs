@@ -0,0 +1,100 @@
+use linked_hash_map::LinkedHashMap;
+use ndarray::{ArrayBase, ArrayD, Data, Dimension, IxDyn};
+use tch::{kind::Element, Device, Tensor};
+
+use crate::nn::StateDict;
+
+use super::Cellable;
+
+/// Converts an `ndarray` array into a tensor, the entry point of data computed with the Rust scientific ecosystem.
+///
+/// This trait is only available with the `ndarray` feature.
+///
+/// # Examples
+/// ```
+/// let array = ndarray::Array2::<f32>::zeros((3, 4));
+/// let tensor = array.to_tensor();
+/// ```
+pub trait ToTensor {
+    /// Copies the array into a tensor of the same shape and element type, on the cpu.
+    fn to_tensor(&self) -> Tensor;
+}
+
+/// Converts a tensor into an `ndarray` array.
+///
+/// This trait is only available with the `ndarray` feature.
+///
+/// # Examples
+/// ```
+/// let array: ArrayD<f64> = model.forward(&input).to_array();
+/// ```
+pub trait ToArray {
+    /// Copies the tensor into an array of element type `T`, casting it to `T` and moving it to the cpu first if needed.
+    fn to_array<T: Element + Copy>(&self) -> ArrayD<T>;
+}
+
+impl<S, D> ToTensor for ArrayBase<S, D>
+where
+    S: Data,
+    S::Elem: Element + Copy,
+    D: Dimension,
+{
+    fn to_tensor(&self) -> Tensor {
+        let shape: Vec<i64> = self.shape().iter().map(|dim| *dim as i64).collect();
+        // Iterating copies the elements in logical order, whatever the memory layout of the array.
+        let values: Vec<S::Elem> = self.iter().copied().collect();
+        Tensor::of_slice(&values).reshape(&shape)
+    }
+}
+
+impl ToArray for Tensor {
+    fn to_array<T: Element + Copy>(&self) -> ArrayD<T> {
+        let shape: Vec<usize> = self.size().iter().map(|dim| *dim as usize).collect();
+        let values = Vec::<T>::from(
+            &self
+                .detach()
+                .to_kind(T::KIND)
+                .to_device(Device::Cpu)
+                .reshape(&[-1]),
+        );
+        ArrayD::from_shape_vec(IxDyn(&shape), values)
+            .expect("A tensor has as many elements as its shape")
+    }
+}
+
+/// Converting a [StateDict] from and to `ndarray` arrays, e.g. to compute statistics of the weights of a model with `ndarray`.
+///
+/// This trait is only available with the `ndarray` feature.
+pub trait StateDictArrayExt: Sized {
+    /// Creates a `StateDict` from named arrays, in the order of the iterator.
+    fn from_arrays<S, D, I>(arrays: I) -> Self
+    where
+        S: Data,
+        S::Elem: Element + Copy,
+        D: Dimension,
+        I: IntoIterator<Item = (String, ArrayBase<S, D>)>;
+
+    /// Returns the tensors of the `StateDict` as arrays of element type `T`, with their names.
+    fn to_arrays<T: Element + Copy>(&self) -> LinkedHashMap<String, ArrayD<T>>;
+}
+
+impl StateDictArrayExt for StateDict {
+    fn from_arrays<S, D, I>(arrays: I) -> Self
+    where
+        S: Data,
+        S::Elem: Element + Copy,
+        D: Dimension,
+        I: IntoIterator<Item = (String, ArrayBase<S, D>)>,
+    {
+        arrays
+            .into_iter()
+            .map(|(name, array)| (name, array.to_tensor().cell()))
+            .collect()
+    }
+
+    fn to_arrays<T: Element + Copy>(&self) -> LinkedHashMap<String, ArrayD<T>> {
+        self.iter()
+            .map(|(name, tensor)| (name.clone(), tensor.lock().to_array()))
+            .collect()
+    }
+}
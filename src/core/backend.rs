@@ -0,0 +1,314 @@
+use std::f64::consts::PI;
+
+use tch::{Device, Kind, Tensor};
+
+/// The tensor operations the layers of raddar are built on, so that the layer definitions do not depend on a tensor library.
+///
+/// The layers create their parameters and run their forward passes through [DefaultBackend], which is [TchBackend] for now. Another backend, e.g. candle or tract, would implement this trait behind a feature flag, and be selected as [DefaultBackend] by it.
+///
+/// The layers of [nn](crate::nn) and the forward passes of the model architectures are routed through the backend, except the quantized layers and [ScriptModule](crate::nn::ScriptModule), which are libtorch features. The losses are functions of `tch` rather than layers of raddar, so they are not routed either.
+pub trait Backend {
+    /// The tensor type of the backend.
+    type Tensor;
+    /// The device a tensor lives on, e.g. the CPU or a GPU.
+    type Device: Copy;
+    /// The element type of a tensor, e.g. `f32`.
+    type Kind: Copy;
+
+    /// Creates an uninitialized tensor of shape `size`, e.g. a parameter which is initialized afterwards.
+    fn empty(size: &[i64], kind: Self::Kind, device: Self::Device) -> Self::Tensor;
+
+    /// Creates a tensor of shape `size` filled with zeros.
+    fn zeros(size: &[i64], kind: Self::Kind, device: Self::Device) -> Self::Tensor;
+
+    /// Creates a tensor of shape `size` filled with ones.
+    fn ones(size: &[i64], kind: Self::Kind, device: Self::Device) -> Self::Tensor;
+
+    /// Marks `tensor` as a trainable parameter, whose gradient is computed in the backward pass.
+    fn parameter(tensor: Self::Tensor) -> Self::Tensor;
+
+    /// Applies a fully-connected layer, i.e. `input.matmul(weight) + bias`, with a weight of shape `[in, out]`.
+    fn linear(
+        input: &Self::Tensor,
+        weight: &Self::Tensor,
+        bias: Option<&Self::Tensor>,
+    ) -> Self::Tensor;
+
+    /// Applies a convolution with a weight of shape `[out, in / groups, *kernel]`, in as many dimensions as the kernel.
+    #[allow(clippy::too_many_arguments)]
+    fn conv(
+        input: &Self::Tensor,
+        weight: &Self::Tensor,
+        bias: Option<&Self::Tensor>,
+        stride: &[i64],
+        padding: &[i64],
+        dilation: &[i64],
+        groups: i64,
+    ) -> Self::Tensor;
+
+    /// Applies batch normalization over the second dimension of `input`, updating the running statistics in training mode. `cudnn_enabled` is a hint which backends without cuDNN ignore.
+    #[allow(clippy::too_many_arguments)]
+    fn batch_norm(
+        input: &Self::Tensor,
+        weight: Option<&Self::Tensor>,
+        bias: Option<&Self::Tensor>,
+        running_mean: &Self::Tensor,
+        running_var: &Self::Tensor,
+        training: bool,
+        momentum: f64,
+        eps: f64,
+        cudnn_enabled: bool,
+    ) -> Self::Tensor;
+
+    /// Applies layer normalization over the last dimensions of `input`, of shape `shape`. `cudnn_enabled` is a hint which backends without cuDNN ignore.
+    fn layer_norm(
+        input: &Self::Tensor,
+        shape: &[i64],
+        weight: Option<&Self::Tensor>,
+        bias: Option<&Self::Tensor>,
+        eps: f64,
+        cudnn_enabled: bool,
+    ) -> Self::Tensor;
+
+    /// Applies the ReLU activation.
+    fn relu(input: &Self::Tensor) -> Self::Tensor;
+
+    /// Applies the GeLU activation, in its tanh approximation.
+    fn gelu(input: &Self::Tensor) -> Self::Tensor;
+
+    /// Applies the leaky ReLU activation, i.e. `-lambda * input` for the negative elements of `input`.
+    fn leaky_relu(input: &Self::Tensor, lambda: f64) -> Self::Tensor;
+
+    /// Zeroes the elements of `input` with probability `p` and scales the others by `1 / (1 - p)` in training mode, or returns `input` as is otherwise.
+    fn dropout(input: &Self::Tensor, p: f64, training: bool) -> Self::Tensor;
+
+    /// Applies max pooling, in as many dimensions as the kernel.
+    fn max_pool(
+        input: &Self::Tensor,
+        kernel_size: &[i64],
+        stride: &[i64],
+        padding: &[i64],
+        dilation: &[i64],
+        ceil_mode: bool,
+    ) -> Self::Tensor;
+
+    /// Applies average pooling, in as many dimensions as the kernel. `divisor_override` is not supported in 1 dimension.
+    fn avg_pool(
+        input: &Self::Tensor,
+        kernel_size: &[i64],
+        stride: &[i64],
+        padding: &[i64],
+        ceil_mode: bool,
+        count_include_pad: bool,
+        divisor_override: Option<i64>,
+    ) -> Self::Tensor;
+
+    /// Applies max pooling to an output of shape `output_size` in the last dimensions of `input`, as many as `output_size` has.
+    fn adaptive_max_pool(input: &Self::Tensor, output_size: &[i64]) -> Self::Tensor;
+
+    /// Applies average pooling to an output of shape `output_size` in the last dimensions of `input`, as many as `output_size` has.
+    fn adaptive_avg_pool(input: &Self::Tensor, output_size: &[i64]) -> Self::Tensor;
+
+    /// Encodes the integers of `input` as one-hot vectors of length `num_classes` in a new last dimension, of the element type of `input`.
+    fn one_hot(input: &Self::Tensor, num_classes: i64) -> Self::Tensor;
+
+    /// Looks up the rows of `weight` at the integers of `input`, i.e. `one_hot(input).matmul(weight)`.
+    fn embedding(input: &Self::Tensor, weight: &Self::Tensor) -> Self::Tensor;
+
+    /// Flattens the dimensions of `input` from `start_dim` to `end_dim` into one.
+    fn flatten(input: &Self::Tensor, start_dim: i64, end_dim: i64) -> Self::Tensor;
+
+    /// Concatenates `tensors` along the dimension `dim`.
+    fn concat(tensors: &[&Self::Tensor], dim: i64) -> Self::Tensor;
+}
+
+/// The [Backend] of libtorch, through `tch`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TchBackend;
+
+/// The [Backend] used by the layers.
+pub type DefaultBackend = TchBackend;
+
+impl Backend for TchBackend {
+    type Tensor = Tensor;
+    type Device = Device;
+    type Kind = Kind;
+
+    fn empty(size: &[i64], kind: Kind, device: Device) -> Tensor {
+        Tensor::empty(size, (kind, device))
+    }
+
+    fn zeros(size: &[i64], kind: Kind, device: Device) -> Tensor {
+        Tensor::zeros(size, (kind, device))
+    }
+
+    fn ones(size: &[i64], kind: Kind, device: Device) -> Tensor {
+        Tensor::ones(size, (kind, device))
+    }
+
+    fn parameter(tensor: Tensor) -> Tensor {
+        tensor.set_requires_grad(true)
+    }
+
+    fn linear(input: &Tensor, weight: &Tensor, bias: Option<&Tensor>) -> Tensor {
+        match bias {
+            Some(bias) => input.matmul(weight) + bias,
+            None => input.matmul(weight),
+        }
+    }
+
+    fn conv(
+        input: &Tensor,
+        weight: &Tensor,
+        bias: Option<&Tensor>,
+        stride: &[i64],
+        padding: &[i64],
+        dilation: &[i64],
+        groups: i64,
+    ) -> Tensor {
+        match weight.dim() {
+            3 => input.conv1d(weight, bias, stride, padding, dilation, groups),
+            4 => input.conv2d(weight, bias, stride, padding, dilation, groups),
+            5 => input.conv3d(weight, bias, stride, padding, dilation, groups),
+            dim => panic!(
+                "Convolutions need a weight of 3 to 5 dimensions, got {}",
+                dim
+            ),
+        }
+    }
+
+    fn batch_norm(
+        input: &Tensor,
+        weight: Option<&Tensor>,
+        bias: Option<&Tensor>,
+        running_mean: &Tensor,
+        running_var: &Tensor,
+        training: bool,
+        momentum: f64,
+        eps: f64,
+        cudnn_enabled: bool,
+    ) -> Tensor {
+        input.batch_norm(
+            weight,
+            bias,
+            Some(running_mean),
+            Some(running_var),
+            training,
+            momentum,
+            eps,
+            cudnn_enabled,
+        )
+    }
+
+    fn layer_norm(
+        input: &Tensor,
+        shape: &[i64],
+        weight: Option<&Tensor>,
+        bias: Option<&Tensor>,
+        eps: f64,
+        cudnn_enabled: bool,
+    ) -> Tensor {
+        input.layer_norm(shape, weight, bias, eps, cudnn_enabled)
+    }
+
+    fn relu(input: &Tensor) -> Tensor {
+        input.where_self(&input.ge(0), &input.zeros_like())
+    }
+
+    fn gelu(input: &Tensor) -> Tensor {
+        let z = (input + &input.pow_tensor_scalar(3) * 0.044715) * (2.0f64 / PI).sqrt();
+        0.5 * input * (1 + z.tanh())
+    }
+
+    fn leaky_relu(input: &Tensor, lambda: f64) -> Tensor {
+        input.where_self(&input.ge(0), &(-input * lambda))
+    }
+
+    fn dropout(input: &Tensor, p: f64, training: bool) -> Tensor {
+        input.dropout(p, training)
+    }
+
+    fn max_pool(
+        input: &Tensor,
+        kernel_size: &[i64],
+        stride: &[i64],
+        padding: &[i64],
+        dilation: &[i64],
+        ceil_mode: bool,
+    ) -> Tensor {
+        match kernel_size.len() {
+            1 => input.max_pool1d(kernel_size, stride, padding, dilation, ceil_mode),
+            2 => input.max_pool2d(kernel_size, stride, padding, dilation, ceil_mode),
+            3 => input.max_pool3d(kernel_size, stride, padding, dilation, ceil_mode),
+            dim => panic!("Poolings need a kernel of 1 to 3 dimensions, got {}", dim),
+        }
+    }
+
+    fn avg_pool(
+        input: &Tensor,
+        kernel_size: &[i64],
+        stride: &[i64],
+        padding: &[i64],
+        ceil_mode: bool,
+        count_include_pad: bool,
+        divisor_override: Option<i64>,
+    ) -> Tensor {
+        match kernel_size.len() {
+            1 => input.avg_pool1d(kernel_size, stride, padding, ceil_mode, count_include_pad),
+            2 => input.avg_pool2d(
+                kernel_size,
+                stride,
+                padding,
+                ceil_mode,
+                count_include_pad,
+                divisor_override,
+            ),
+            3 => input.avg_pool3d(
+                kernel_size,
+                stride,
+                padding,
+                ceil_mode,
+                count_include_pad,
+                divisor_override,
+            ),
+            dim => panic!("Poolings need a kernel of 1 to 3 dimensions, got {}", dim),
+        }
+    }
+
+    fn adaptive_max_pool(input: &Tensor, output_size: &[i64]) -> Tensor {
+        match output_size.len() {
+            1 => input.adaptive_max_pool1d(output_size).0,
+            2 => input.adaptive_max_pool2d(output_size).0,
+            3 => input.adaptive_max_pool3d(output_size).0,
+            dim => panic!("Poolings need an output of 1 to 3 dimensions, got {}", dim),
+        }
+    }
+
+    fn adaptive_avg_pool(input: &Tensor, output_size: &[i64]) -> Tensor {
+        match output_size.len() {
+            1 => input.adaptive_avg_pool1d(output_size),
+            2 => input.adaptive_avg_pool2d(output_size),
+            3 => input.adaptive_avg_pool3d(output_size),
+            dim => panic!("Poolings need an output of 1 to 3 dimensions, got {}", dim),
+        }
+    }
+
+    fn one_hot(input: &Tensor, num_classes: i64) -> Tensor {
+        let mut size = input.size();
+        size.push(num_classes);
+        let input = input.unsqueeze(-1);
+        Tensor::zeros(&size, (input.kind(), input.device())).scatter(-1, &input, &input.ones_like())
+    }
+
+    fn embedding(input: &Tensor, weight: &Tensor) -> Tensor {
+        Tensor::embedding(weight, input, -1, false, false)
+    }
+
+    fn flatten(input: &Tensor, start_dim: i64, end_dim: i64) -> Tensor {
+        input.flatten(start_dim, end_dim)
+    }
+
+    fn concat(tensors: &[&Tensor], dim: i64) -> Tensor {
+        Tensor::concat(tensors, dim)
+    }
+}
@@ -0,0 +1,110 @@
+use std::{fmt::Display, path::PathBuf};
+
+use tch::Tensor;
+
+/// The errors of the library.
+#[derive(Debug, thiserror::Error)]
+pub enum RaddarError {
+    /// The input of a module does not have the shape it expects.
+    #[error("{module} expected an input of shape {expected}, got {actual:?}")]
+    ShapeMismatch {
+        module: String,
+        expected: Shape,
+        actual: Vec<i64>,
+    },
+
+    /// A builder is missing a required field, or its config is rejected by a validator of the module, e.g. `groups` not dividing the channels of a convolution.
+    #[error("Invalid {config}: {message}")]
+    InvalidConfig { config: String, message: String },
+
+    /// A file of a dataset cannot be read.
+    #[error("Failed to open {}: {source}", .path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    /// An image of a dataset cannot be opened or decoded.
+    #[error("Failed to open image {}: {source}", .path.display())]
+    Image {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+
+    /// A file of a dataset is readable, but does not hold the data expected, e.g. a JSON file whose objects miss the input field.
+    #[error("Invalid data in {}: {message}", .path.display())]
+    InvalidData { path: PathBuf, message: String },
+
+    /// An error raised by libtorch.
+    #[error(transparent)]
+    Torch(#[from] tch::TchError),
+}
+
+/// A dimension of an expected [Shape].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dim {
+    /// A dimension of any size, shown by its name, e.g. `N` for the batch size.
+    Any(&'static str),
+    /// A dimension of a given size.
+    Exact(i64),
+    /// Any number of dimensions of any sizes, shown as `*`. It can only be the first dimension of a shape.
+    Rest,
+}
+
+/// An expected shape of the input of a module, e.g. `[N, 3, H, W]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shape(pub Vec<Dim>);
+
+impl Shape {
+    /// Returns whether `size` matches the shape.
+    pub fn matches(&self, size: &[i64]) -> bool {
+        let (rest, dims) = match self.0.first() {
+            Some(Dim::Rest) => (true, &self.0[1..]),
+            _ => (false, &self.0[..]),
+        };
+        if size.len() < dims.len() || (!rest && size.len() != dims.len()) {
+            return false;
+        }
+        dims.iter()
+            .zip(&size[size.len() - dims.len()..])
+            .all(|(dim, size)| match dim {
+                Dim::Exact(expected) => expected == size,
+                _ => true,
+            })
+    }
+
+    /// Returns `Ok(())` if the shape of `input` matches the shape, or a [RaddarError::ShapeMismatch] naming `module` otherwise.
+    ///
+    /// # Examples
+    /// ```
+    /// use Dim::*;
+    /// Shape(vec![Any("N"), Exact(3), Any("H"), Any("W")]).check("Conv2d", &input)?;
+    /// ```
+    pub fn check(&self, module: &str, input: &Tensor) -> Result<(), RaddarError> {
+        let actual = input.size();
+        if self.matches(&actual) {
+            Ok(())
+        } else {
+            Err(RaddarError::ShapeMismatch {
+                module: module.to_owned(),
+                expected: self.clone(),
+                actual,
+            })
+        }
+    }
+}
+
+impl Display for Shape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let dims = self
+            .0
+            .iter()
+            .map(|dim| match dim {
+                Dim::Any(name) => name.to_string(),
+                Dim::Exact(size) => size.to_string(),
+                Dim::Rest => "*".to_owned(),
+            })
+            .collect::<Vec<_>>();
+        write!(f, "[{}]", dims.join(", "))
+    }
+}
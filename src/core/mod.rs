@@ -1,2 +1,10 @@
+#[cfg(feature = "ndarray")]
+pub use array::*;
+pub use backend::*;
+pub use error::*;
 pub use tensor::*;
+#[cfg(feature = "ndarray")]
+pub mod array;
+pub mod backend;
+pub mod error;
 pub mod tensor;
@@ -0,0 +1,151 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use parking_lot::RwLock;
+use tch::Tensor;
+
+use super::MapDataset;
+
+/// A sample which can be stored in the on-disk cache of a [CachedDataset].
+pub trait CacheableSample: Sized {
+    fn save(&self, path: &Path) -> anyhow::Result<()>;
+
+    fn load(path: &Path) -> anyhow::Result<Self>;
+}
+
+impl CacheableSample for Arc<Tensor> {
+    fn save(&self, path: &Path) -> anyhow::Result<()> {
+        Ok(self.as_ref().save(path)?)
+    }
+
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        Ok(Arc::new(Tensor::load(path)?))
+    }
+}
+
+impl CacheableSample for (Arc<Tensor>, Arc<Tensor>) {
+    fn save(&self, path: &Path) -> anyhow::Result<()> {
+        Ok(Tensor::save_multi(
+            &[("input", &*self.0), ("label", &*self.1)],
+            path,
+        )?)
+    }
+
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let mut tensors = Tensor::load_multi(path)?;
+        anyhow::ensure!(
+            tensors.len() == 2,
+            "{} is not a cached sample",
+            path.display()
+        );
+        let (_, label) = tensors.pop().unwrap();
+        let (_, input) = tensors.pop().unwrap();
+        Ok((Arc::new(input), Arc::new(label)))
+    }
+}
+
+/// A [MapDataset] which memoizes the samples of another one, so that expensive decoding and transformations run only once instead of every epoch.
+///
+/// The samples are kept in memory, and optionally in a directory on disk, so that the cache survives between runs. Note that random augmentations should be applied after the cache, otherwise every epoch sees the same augmented samples.
+///
+/// # Examples
+/// ```
+/// let dataset = CachedDataset::new(images.map_samples(decode_and_resize))
+///     .with_disk_cache("cache/train")?;
+/// let loader = dataset.into_iterable(Some(Arc::new(RandomSampler::new())))
+///     .into_loader_with(
+///         DataLoaderConfigBuilder::default().batch_size(32).build().unwrap(),
+///         StackCollate,
+///     );
+/// ```
+pub struct CachedDataset<D: MapDataset> {
+    pub dataset: D,
+    memory: RwLock<Vec<Option<D::SampleType>>>,
+    disk: Option<DiskCache<D::SampleType>>,
+}
+
+struct DiskCache<S> {
+    dir: PathBuf,
+    save: fn(&S, &Path) -> anyhow::Result<()>,
+    load: fn(&Path) -> anyhow::Result<S>,
+}
+
+impl<D: MapDataset> CachedDataset<D>
+where
+    D::SampleType: Clone + Sync,
+{
+    /// Caches the samples of `dataset` in memory.
+    pub fn new(dataset: D) -> Self {
+        let memory = RwLock::new(vec![None; dataset.len()]);
+        Self {
+            dataset,
+            memory,
+            disk: None,
+        }
+    }
+
+    /// Also caches the samples in `dir`, one file per sample. Samples already in `dir` are loaded instead of being computed.
+    pub fn with_disk_cache<P: AsRef<Path>>(mut self, dir: P) -> anyhow::Result<Self>
+    where
+        D::SampleType: CacheableSample,
+    {
+        std::fs::create_dir_all(dir.as_ref())?;
+        self.disk = Some(DiskCache {
+            dir: dir.as_ref().to_path_buf(),
+            save: <D::SampleType as CacheableSample>::save,
+            load: <D::SampleType as CacheableSample>::load,
+        });
+        Ok(self)
+    }
+
+    /// Returns the number of samples cached in memory.
+    pub fn cached_len(&self) -> usize {
+        self.memory.read().iter().flatten().count()
+    }
+
+    /// Clears the cache in memory. The on-disk cache is kept.
+    pub fn clear(&self) {
+        self.memory
+            .write()
+            .iter_mut()
+            .for_each(|sample| *sample = None);
+    }
+
+    fn load_or_compute(&self, index: usize) -> D::SampleType {
+        let disk = match &self.disk {
+            Some(disk) => disk,
+            None => return self.dataset.get(index),
+        };
+        let path = disk.dir.join(format!("{}.pt", index));
+        if path.exists() {
+            return (disk.load)(&path)
+                .unwrap_or_else(|err| panic!("Failed to load {}: {}", path.display(), err));
+        }
+        let sample = self.dataset.get(index);
+        (disk.save)(&sample, &path)
+            .unwrap_or_else(|err| panic!("Failed to save {}: {}", path.display(), err));
+        sample
+    }
+}
+
+impl<D: MapDataset> MapDataset for CachedDataset<D>
+where
+    D::SampleType: Clone + Sync,
+{
+    type SampleType = D::SampleType;
+
+    fn get(&self, index: usize) -> Self::SampleType {
+        if let Some(sample) = &self.memory.read()[index] {
+            return sample.clone();
+        }
+        let sample = self.load_or_compute(index);
+        self.memory.write()[index] = Some(sample.clone());
+        sample
+    }
+
+    fn len(&self) -> usize {
+        self.dataset.len()
+    }
+}
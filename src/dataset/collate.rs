@@ -0,0 +1,142 @@
+use std::{borrow::Borrow, sync::Arc};
+
+use tch::{Kind, Tensor};
+
+use super::{DataLoader, Dataset};
+
+/// Defines how a collection of samples is merged into a batch.
+///
+/// Unlike [Dataset::collate], which is fixed by the type of the dataset, a [Collate] can be chosen per [DataLoader] with [DataLoader::collate_with].
+pub trait Collate<S>: Clone {
+    type Batch;
+
+    fn collate(&self, samples: Vec<S>) -> Self::Batch;
+}
+
+/// Stacks samples of the same shape into a single `[batch, ...]` tensor.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StackCollate;
+
+impl Collate<Arc<Tensor>> for StackCollate {
+    type Batch = Tensor;
+
+    fn collate(&self, samples: Vec<Arc<Tensor>>) -> Tensor {
+        Tensor::stack(&samples, 0)
+    }
+}
+
+impl Collate<(Arc<Tensor>, Arc<Tensor>)> for StackCollate {
+    type Batch = (Tensor, Tensor);
+
+    fn collate(&self, samples: Vec<(Arc<Tensor>, Arc<Tensor>)>) -> (Tensor, Tensor) {
+        let (inputs, labels): (Vec<_>, Vec<_>) = samples.into_iter().unzip();
+        (Tensor::stack(&inputs, 0), Tensor::stack(&labels, 0))
+    }
+}
+
+/// Pads tensors of different lengths along their first dimension to the longest one, and stacks them into a `[batch, max_length, ...]` tensor.
+///
+/// All other dimensions must match.
+pub fn pad_sequence<T: Borrow<Tensor>>(sequences: &[T], padding_value: f64) -> Tensor {
+    Tensor::pad_sequence(sequences, true, padding_value)
+}
+
+/// A padded batch of variable-length sequences.
+#[derive(Debug)]
+pub struct PaddedBatch {
+    /// The padded sequences, in shape `[batch, max_length, ...]`.
+    pub data: Tensor,
+    /// The original length of each sequence, in shape `[batch]`.
+    pub lengths: Tensor,
+}
+
+impl PaddedBatch {
+    /// Returns a boolean mask in shape `[batch, max_length]`, which is `true` for the positions that are not padding.
+    pub fn mask(&self) -> Tensor {
+        let max_length = self.data.size()[1];
+        Tensor::arange(max_length, (Kind::Int64, self.data.device()))
+            .unsqueeze(0)
+            .lt_tensor(&self.lengths.unsqueeze(1))
+    }
+}
+
+/// Pads variable-length samples along their first dimension, e.g. token sequences or audio clips.
+///
+/// For labelled samples, the labels are padded as well (with `label_padding_value`, e.g. an ignored class index), so the batches can be fed to a [Trainer](crate::train::Trainer) directly.
+#[derive(Debug, Clone, Copy)]
+pub struct PadCollate {
+    pub padding_value: f64,
+    pub label_padding_value: f64,
+}
+
+impl PadCollate {
+    pub fn new(padding_value: f64) -> Self {
+        Self {
+            padding_value,
+            label_padding_value: padding_value,
+        }
+    }
+
+    /// Set the value used to pad labels.
+    pub fn label_padding_value(mut self, label_padding_value: f64) -> Self {
+        self.label_padding_value = label_padding_value;
+        self
+    }
+}
+
+impl Default for PadCollate {
+    fn default() -> Self {
+        Self::new(0.)
+    }
+}
+
+impl Collate<Arc<Tensor>> for PadCollate {
+    type Batch = PaddedBatch;
+
+    fn collate(&self, samples: Vec<Arc<Tensor>>) -> PaddedBatch {
+        let lengths: Vec<i64> = samples.iter().map(|sample| sample.size()[0]).collect();
+        PaddedBatch {
+            data: pad_sequence(&samples, self.padding_value),
+            lengths: Tensor::of_slice(&lengths),
+        }
+    }
+}
+
+impl Collate<(Arc<Tensor>, Arc<Tensor>)> for PadCollate {
+    type Batch = (Tensor, Tensor);
+
+    fn collate(&self, samples: Vec<(Arc<Tensor>, Arc<Tensor>)>) -> (Tensor, Tensor) {
+        let (inputs, labels): (Vec<_>, Vec<_>) = samples.into_iter().unzip();
+        (
+            pad_sequence(&inputs, self.padding_value),
+            pad_sequence(&labels, self.label_padding_value),
+        )
+    }
+}
+
+/// A [DataLoader] whose batches are collated by a [Collate], see [DataLoader::collate_with].
+#[derive(Debug)]
+pub struct CollateLoader<T: Dataset, C: Collate<T::SampleType>> {
+    pub loader: DataLoader<T>,
+    pub collate: C,
+}
+
+impl<T: Dataset, C: Collate<T::SampleType>> Clone for CollateLoader<T, C> {
+    /// Clones the loader, which starts a new epoch like [DataLoader::clone].
+    fn clone(&self) -> Self {
+        Self {
+            loader: self.loader.clone(),
+            collate: self.collate.clone(),
+        }
+    }
+}
+
+impl<T: Dataset, C: Collate<T::SampleType>> Iterator for CollateLoader<T, C> {
+    type Item = C::Batch;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.loader
+            .next_samples()
+            .map(|samples| self.collate.collate(samples))
+    }
+}
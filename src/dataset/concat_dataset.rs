@@ -0,0 +1,93 @@
+use super::{IterableDataset, MapDataset};
+
+/// A [MapDataset] concatenating several map-style datasets, e.g. one per folder or file.
+///
+/// The indices of each dataset follow those of the previous ones. To concatenate datasets of different types, box them as `Box<dyn MapDataset<SampleType = S>>`.
+///
+/// # Examples
+/// ```
+/// let dataset = ConcatDataset::new(vec![
+///     TensorDataset::from_npz("part1.npz", "x", "y")?,
+///     TensorDataset::from_npz("part2.npz", "x", "y")?,
+/// ]);
+/// ```
+pub struct ConcatDataset<D: MapDataset> {
+    pub datasets: Vec<D>,
+    /// The number of samples in the datasets up to each dataset, inclusive.
+    cumulative_sizes: Vec<usize>,
+}
+
+impl<D: MapDataset> ConcatDataset<D> {
+    pub fn new(datasets: Vec<D>) -> Self {
+        let cumulative_sizes = datasets
+            .iter()
+            .scan(0, |total, dataset| {
+                *total += dataset.len();
+                Some(*total)
+            })
+            .collect();
+        Self {
+            datasets,
+            cumulative_sizes,
+        }
+    }
+
+    /// Returns the index of the dataset holding the sample at `index`, and the index of the sample in that dataset.
+    pub fn locate(&self, index: usize) -> (usize, usize) {
+        assert!(
+            index < self.len(),
+            "Index {} is out of range for a dataset of {} samples.",
+            index,
+            self.len()
+        );
+        let dataset = self.cumulative_sizes.partition_point(|size| *size <= index);
+        let offset = if dataset == 0 {
+            0
+        } else {
+            self.cumulative_sizes[dataset - 1]
+        };
+        (dataset, index - offset)
+    }
+}
+
+impl<D: MapDataset> MapDataset for ConcatDataset<D> {
+    type SampleType = D::SampleType;
+
+    fn get(&self, index: usize) -> Self::SampleType {
+        let (dataset, index) = self.locate(index);
+        self.datasets[dataset].get(index)
+    }
+
+    fn len(&self) -> usize {
+        self.cumulative_sizes.last().copied().unwrap_or(0)
+    }
+}
+
+/// An [IterableDataset] chaining several iterable datasets, which are read one after another.
+pub struct ChainDataset<D: IterableDataset> {
+    pub datasets: Vec<D>,
+    current: usize,
+}
+
+impl<D: IterableDataset> ChainDataset<D> {
+    pub fn new(datasets: Vec<D>) -> Self {
+        Self {
+            datasets,
+            current: 0,
+        }
+    }
+}
+
+impl<D: IterableDataset> IterableDataset for ChainDataset<D> {
+    type SampleType = D::SampleType;
+
+    fn next_sample(&mut self) -> Option<Self::SampleType> {
+        while let Some(dataset) = self.datasets.get_mut(self.current) {
+            if let Some(sample) = dataset.next_sample() {
+                return Some(sample);
+            }
+            self.current += 1;
+        }
+        None
+    }
+}
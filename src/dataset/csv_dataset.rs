@@ -0,0 +1,209 @@
+use std::{path::Path, sync::Arc};
+
+use anyhow::{bail, Context};
+use derive_builder::Builder;
+use tch::{Kind, Tensor};
+
+use super::{TensorDataset, UnsupervisedTensorDataset};
+
+/// The configuration for loading a [CsvDataset].
+#[derive(Debug, Clone, Builder)]
+#[builder(pattern = "owned")]
+pub struct CsvDatasetConfig {
+    /// The columns used as features, in order. If `None`, all columns except the target column are used.
+    #[builder(default = "None")]
+    pub feature_columns: Option<Vec<String>>,
+
+    /// The column used as the label. If `None`, the dataset is unlabelled.
+    #[builder(default = "None")]
+    pub target_column: Option<String>,
+
+    /// Whether the target column holds class names rather than numbers. The classes are sorted and mapped to their indices.
+    #[builder(default = "false")]
+    pub categorical_target: bool,
+
+    #[builder(default = "b','")]
+    pub delimiter: u8,
+
+    /// Whether the first row holds the column names. Without headers, the columns are named by their indices, i.e. `"0"`, `"1"`, ...
+    #[builder(default = "true")]
+    pub has_headers: bool,
+
+    /// The kind of the feature tensors.
+    #[builder(default = "Kind::Double")]
+    pub feature_kind: Kind,
+
+    /// The kind of the label tensors. Use `Kind::Int64` for classification.
+    #[builder(default = "Kind::Double")]
+    pub target_kind: Kind,
+}
+
+/// A tabular dataset loaded from a CSV file.
+///
+/// Each input is a 1-dimensional tensor of the feature columns, and each label is a scalar tensor of the target column. Empty fields in the feature columns are read as NaN.
+///
+/// # Examples
+/// ```
+/// let csv = CsvDataset::from_path(
+///     "data/iris.csv",
+///     CsvDatasetConfigBuilder::default()
+///         .target_column(Some("species".to_string()))
+///         .categorical_target(true)
+///         .target_kind(Kind::Int64)
+///         .build()
+///         .unwrap(),
+/// )?;
+/// let dataset = csv.into_tensor_dataset();
+/// ```
+#[derive(Debug, Clone)]
+pub struct CsvDataset {
+    pub feature_columns: Vec<String>,
+    pub target_column: Option<String>,
+    /// The class names of a categorical target, in the order of their indices.
+    pub classes: Option<Vec<String>>,
+    pub inputs: Vec<Arc<Tensor>>,
+    pub labels: Vec<Arc<Tensor>>,
+}
+
+impl CsvDataset {
+    /// Loads a dataset from the CSV file at `path`.
+    pub fn from_path<P: AsRef<Path>>(path: P, config: CsvDatasetConfig) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(config.delimiter)
+            .has_headers(config.has_headers)
+            .from_path(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let records = reader
+            .records()
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        let columns: Vec<String> = if config.has_headers {
+            reader.headers()?.iter().map(str::to_owned).collect()
+        } else {
+            let width = records.first().map_or(0, |record| record.len());
+            (0..width).map(|index| index.to_string()).collect()
+        };
+        let column_index = |name: &str| {
+            columns
+                .iter()
+                .position(|column| column == name)
+                .with_context(|| format!("Column {} not found in {}", name, path.display()))
+        };
+
+        let target = config
+            .target_column
+            .as_deref()
+            .map(column_index)
+            .transpose()?;
+        let feature_columns = match config.feature_columns {
+            Some(feature_columns) => feature_columns,
+            None => columns
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| Some(*index) != target)
+                .map(|(_, column)| column.clone())
+                .collect(),
+        };
+        let features = feature_columns
+            .iter()
+            .map(|column| column_index(column))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut values = Vec::with_capacity(records.len() * features.len());
+        for (row, record) in records.iter().enumerate() {
+            for &column in &features {
+                values.push(parse_field(record.get(column), row, &columns[column])?);
+            }
+        }
+        let inputs = Tensor::of_slice(&values)
+            .reshape(&[records.len() as i64, features.len() as i64])
+            .to_kind(config.feature_kind)
+            .unbind(0)
+            .into_iter()
+            .map(Arc::new)
+            .collect();
+
+        let mut classes = None;
+        let labels = match target {
+            Some(target) => {
+                let fields: Vec<&str> = records
+                    .iter()
+                    .map(|record| record.get(target).unwrap_or(""))
+                    .collect();
+                let targets = if config.categorical_target {
+                    let mut names: Vec<String> =
+                        fields.iter().map(|field| field.to_string()).collect();
+                    names.sort();
+                    names.dedup();
+                    let targets = fields
+                        .iter()
+                        .map(|field| names.binary_search(&field.to_string()).unwrap() as f64)
+                        .collect::<Vec<_>>();
+                    classes = Some(names);
+                    targets
+                } else {
+                    fields
+                        .iter()
+                        .enumerate()
+                        .map(|(row, field)| parse_field(Some(*field), row, &columns[target]))
+                        .collect::<anyhow::Result<Vec<_>>>()?
+                };
+                Tensor::of_slice(&targets)
+                    .to_kind(config.target_kind)
+                    .unbind(0)
+                    .into_iter()
+                    .map(Arc::new)
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            feature_columns,
+            target_column: config.target_column,
+            classes,
+            inputs,
+            labels,
+        })
+    }
+
+    /// Converts the dataset into a `TensorDataset` of features and labels.
+    ///
+    /// # Panics
+    /// Panics if the dataset has no target column.
+    pub fn into_tensor_dataset(self) -> TensorDataset {
+        assert!(
+            self.target_column.is_some(),
+            "The CSV dataset has no target column."
+        );
+        TensorDataset::from_tensors(self.inputs, self.labels)
+    }
+
+    /// Converts the dataset into an `UnsupervisedTensorDataset` of features, dropping the labels.
+    pub fn into_unsupervised(self) -> UnsupervisedTensorDataset {
+        UnsupervisedTensorDataset::from_tensors(self.inputs)
+    }
+}
+
+impl From<CsvDataset> for TensorDataset {
+    fn from(dataset: CsvDataset) -> Self {
+        dataset.into_tensor_dataset()
+    }
+}
+
+fn parse_field(field: Option<&str>, row: usize, column: &str) -> anyhow::Result<f64> {
+    let field = field.unwrap_or("").trim();
+    if field.is_empty() {
+        return Ok(f64::NAN);
+    }
+    match field.parse() {
+        Ok(value) => Ok(value),
+        Err(_) => bail!(
+            "Failed to parse {:?} in row {}, column {} as a number",
+            field,
+            row,
+            column
+        ),
+    }
+}
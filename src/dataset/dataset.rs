@@ -1,9 +1,16 @@
-use std::{cmp::min, sync::Arc};
+use std::{
+    cmp::min,
+    sync::{atomic::AtomicU64, Arc},
+};
 
 use derive_builder::Builder;
 use pariter::IteratorExt;
 use raddar_derive::{DatasetFromIter, DatasetIntoIter};
-use rand::seq::SliceRandom;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::util::seeded_rng;
+
+use super::{sampler::epoch_rng, Collate, CollateLoader, Prefetcher, Sampler};
 
 /// A simple dataset with a vector of inputs and a vector of targets.
 #[derive(Debug, Clone, DatasetIntoIter, DatasetFromIter)]
@@ -145,6 +152,50 @@ where
     {
         self.into_iter().parallel_map(f).flatten().collect()
     }
+
+    /// Randomly splits the dataset into disjoint datasets with the given fractions of the samples, e.g. `&[0.8, 0.1, 0.1]` for train, validation and test sets.
+    ///
+    /// The fractions must sum to 1. The samples left over by rounding go to the first datasets. The samples are shared with the original dataset rather than copied. With a seed, the split is reproducible.
+    ///
+    /// # Examples
+    /// ```
+    /// let mut splits = dataset.split(&[0.8, 0.1, 0.1], Some(42)).into_iter();
+    /// let (train, val, test) = (splits.next().unwrap(), splits.next().unwrap(), splits.next().unwrap());
+    /// ```
+    fn split(self, fractions: &[f64], seed: Option<u64>) -> Vec<Self> {
+        let mut data = self.data();
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => seeded_rng(),
+        };
+        data.shuffle(&mut rng);
+        let mut data = data.into_iter();
+        split_sizes(data.len(), fractions)
+            .into_iter()
+            .map(|size| Self::from_data(data.by_ref().take(size).collect::<Vec<_>>()))
+            .collect()
+    }
+}
+
+/// Computes the sizes of the splits of `len` samples with the given fractions, giving the samples left over by rounding to the first splits.
+pub(crate) fn split_sizes(len: usize, fractions: &[f64]) -> Vec<usize> {
+    assert!(
+        fractions.iter().all(|fraction| *fraction >= 0.),
+        "The fractions must be non-negative."
+    );
+    assert!(
+        (fractions.iter().sum::<f64>() - 1.).abs() < 1e-6,
+        "The fractions must sum to 1."
+    );
+    let mut sizes: Vec<usize> = fractions
+        .iter()
+        .map(|fraction| (fraction * len as f64).floor() as usize)
+        .collect();
+    let left = len - sizes.iter().sum::<usize>();
+    for size in sizes.iter_mut().take(left) {
+        *size += 1;
+    }
+    sizes
 }
 
 impl<T: Send + Sync, U: Send + Sync> Dataset for SimpleDataset<T, U> {
@@ -247,63 +298,181 @@ pub struct DataLoaderConfig {
     #[builder(default = "1")]
     pub batch_size: usize,
 
+    /// Whether to shuffle the samples. The samples are reshuffled every time the `DataLoader` is cloned, i.e. at the beginning of every epoch.
     #[builder(default = "false")]
     pub shuffle: bool,
+
+    /// Whether to drop the last batch if it is smaller than `batch_size`.
+    #[builder(default = "false")]
+    pub drop_last: bool,
+
+    /// The seed of the shuffles. With a seed, the sequence of shuffles over epochs is reproducible. Without a seed, the generator of [seeded_rng](crate::util::seeded_rng) is used.
+    #[builder(default = "None")]
+    pub seed: Option<u64>,
+
+    /// The number of threads collating batches in the background. With 0 workers, batches are collated on the calling thread when requested.
+    #[builder(default = "0")]
+    pub num_workers: usize,
+
+    /// The number of batches each worker prepares ahead of time.
+    #[builder(default = "2")]
+    pub prefetch_factor: usize,
+
+    /// The sampler choosing the samples of each epoch. If set, it takes precedence over `shuffle`.
+    #[builder(default = "None")]
+    pub sampler: Option<Arc<dyn Sampler>>,
 }
 
 /// A data loader iterates over `Dataset`, which can be used to load data in batches.
+///
+/// Iterable datasets are batched by an [IterableLoader](super::IterableLoader) instead, which takes the same [DataLoaderConfig], see [IterableDataset](super::IterableDataset).
 #[derive(Debug)]
 pub struct DataLoader<T: Dataset> {
+    /// The samples of the current epoch, in order.
     pub data: Vec<T::SampleType>,
+    /// All samples of the dataset, from which the samples of each epoch are drawn by the sampler.
+    source: Arc<Vec<T::SampleType>>,
     pub cfg: DataLoaderConfig,
     pub index: usize,
+    /// The number of shuffles so far, shared by all clones of the loader, so that each epoch gets a different shuffle.
+    epoch: Arc<AtomicU64>,
+    /// The background workers, started by the first call of `next` if `num_workers` is positive.
+    prefetcher: Option<Prefetcher<T::BatchType>>,
 }
 
 impl<T: Dataset> DataLoader<T> {
     pub fn new(data: Vec<T::SampleType>, cfg: DataLoaderConfig) -> Self {
         let mut this = Self {
-            data,
+            data: Vec::new(),
+            source: Arc::new(data),
             cfg,
             index: 0,
+            epoch: Arc::new(AtomicU64::new(0)),
+            prefetcher: None,
         };
-        if this.cfg.shuffle {
-            this.shuffle();
-        }
+        this.resample();
         this
     }
 
+    /// Draws the samples of a new epoch, with the sampler if there is one, or else by shuffling the samples if `shuffle` is set.
+    fn resample(&mut self) {
+        if let Some(sampler) = &self.cfg.sampler {
+            self.data = sampler
+                .indices(self.source.len())
+                .into_iter()
+                .map(|index| self.source[index].clone())
+                .collect();
+        } else {
+            self.data = self.source.as_ref().clone();
+            if self.cfg.shuffle {
+                self.shuffle();
+            }
+        }
+    }
+
     pub fn shuffle(&mut self) {
-        let mut rng = rand::thread_rng();
+        let mut rng = epoch_rng(self.cfg.seed, &self.epoch);
         self.data.shuffle(&mut rng);
     }
+
+    /// Returns the samples of the next batch without collating them, or `None` at the end of the epoch.
+    pub fn next_samples(&mut self) -> Option<Vec<T::SampleType>> {
+        if self.index >= self.data.len() {
+            return None;
+        }
+
+        if self.cfg.drop_last && self.data.len() - self.index < self.cfg.batch_size {
+            return None;
+        }
+
+        let batch_size = min(self.cfg.batch_size, self.data.len() - self.index);
+        let batch = self.data[self.index..self.index + batch_size].to_vec();
+        self.index += batch_size;
+        Some(batch)
+    }
+
+    /// Collates the batches with the given [Collate] instead of [Dataset::collate].
+    ///
+    /// Batches are collated on the calling thread, regardless of `num_workers`.
+    pub fn collate_with<C: Collate<T::SampleType>>(self, collate: C) -> CollateLoader<T, C> {
+        CollateLoader {
+            loader: self,
+            collate,
+        }
+    }
+
+    /// Returns the number of batches in an epoch.
+    pub fn num_batches(&self) -> usize {
+        let batch_size = self.cfg.batch_size.max(1);
+        if self.cfg.drop_last {
+            self.data.len() / batch_size
+        } else {
+            (self.data.len() + batch_size - 1) / batch_size
+        }
+    }
 }
 
 impl<T: Dataset> Clone for DataLoader<T> {
     fn clone(&self) -> Self {
         let mut that = Self {
-            data: self.data.clone(),
+            data: Vec::new(),
+            source: self.source.clone(),
             cfg: self.cfg.clone(),
             index: self.index,
+            epoch: self.epoch.clone(),
+            prefetcher: None,
         };
-        if that.cfg.shuffle {
-            that.shuffle();
-        }
+        that.resample();
         that
     }
 }
 
-impl<T: Dataset> Iterator for DataLoader<T> {
+impl<T: Dataset + 'static> DataLoader<T> {
+    /// Starts the workers over the remaining batches of the epoch.
+    fn start_prefetcher(&mut self) {
+        let mut chunks = Vec::new();
+        let mut index = self.index;
+        while index < self.data.len() {
+            let batch_size = min(self.cfg.batch_size, self.data.len() - index);
+            if self.cfg.drop_last && batch_size < self.cfg.batch_size {
+                break;
+            }
+            chunks.push(self.data[index..index + batch_size].to_vec());
+            index += batch_size;
+        }
+        self.prefetcher = Some(Prefetcher::new(
+            chunks,
+            self.cfg.num_workers,
+            self.cfg.prefetch_factor,
+            T::collate::<Vec<T::SampleType>>,
+        ));
+    }
+}
+
+impl<T: Dataset + 'static> Iterator for DataLoader<T> {
     type Item = T::BatchType;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index >= self.data.len() {
-            return None;
+        if self.cfg.num_workers > 0 {
+            if self.prefetcher.is_none() {
+                self.start_prefetcher();
+            }
+            let (batch, size) = self.prefetcher.as_mut().unwrap().next()?;
+            self.index += size;
+            return Some(batch);
         }
 
-        let batch_size = min(self.cfg.batch_size, self.data.len() - self.index);
-        let batch = self.data[self.index..self.index + batch_size].to_vec();
-        self.index += batch_size;
+        self.next_samples().map(T::collate)
+    }
 
-        Some(T::collate(batch))
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.data.len() - self.index.min(self.data.len());
+        let batch_size = self.cfg.batch_size.max(1);
+        let batches = if self.cfg.drop_last {
+            remaining / batch_size
+        } else {
+            (remaining + batch_size - 1) / batch_size
+        };
+        (batches, Some(batches))
     }
 }
@@ -0,0 +1,159 @@
+use std::{
+    collections::HashMap,
+    sync::mpsc::{sync_channel, Receiver},
+    thread,
+};
+
+use tch::{Device, Tensor};
+
+use super::PaddedBatch;
+
+/// A batch which can be moved to a device.
+pub trait ToDevice {
+    fn to_device(self, device: Device) -> Self;
+}
+
+impl ToDevice for Tensor {
+    /// Copies a CPU tensor to a CUDA device through pinned memory, so that the copy is asynchronous.
+    fn to_device(self, device: Device) -> Self {
+        if self.device() == Device::Cpu && matches!(device, Device::Cuda(_)) {
+            self.pin_memory(device)
+                .to_device_(device, self.kind(), true, false)
+        } else {
+            Tensor::to_device(&self, device)
+        }
+    }
+}
+
+impl<A: ToDevice, B: ToDevice> ToDevice for (A, B) {
+    fn to_device(self, device: Device) -> Self {
+        (self.0.to_device(device), self.1.to_device(device))
+    }
+}
+
+impl<A: ToDevice, B: ToDevice, C: ToDevice> ToDevice for (A, B, C) {
+    fn to_device(self, device: Device) -> Self {
+        (
+            self.0.to_device(device),
+            self.1.to_device(device),
+            self.2.to_device(device),
+        )
+    }
+}
+
+impl<T: ToDevice> ToDevice for Vec<T> {
+    fn to_device(self, device: Device) -> Self {
+        self.into_iter().map(|x| x.to_device(device)).collect()
+    }
+}
+
+impl<T: ToDevice> ToDevice for HashMap<String, T> {
+    fn to_device(self, device: Device) -> Self {
+        self.into_iter()
+            .map(|(key, x)| (key, x.to_device(device)))
+            .collect()
+    }
+}
+
+impl ToDevice for PaddedBatch {
+    fn to_device(self, device: Device) -> Self {
+        PaddedBatch {
+            data: self.data.to_device(device),
+            lengths: self.lengths.to_device(device),
+        }
+    }
+}
+
+/// An iterator which moves the batches of another iterator to a device on a background thread, so that the transfer of the next batches overlaps with the computation on the current one.
+///
+/// CPU tensors are first copied into pinned memory, from which the copy to a CUDA device does not block.
+///
+/// Cloning the prefetcher clones the source iterator, so a prefetched [DataLoader](super::DataLoader) can be passed to the [Trainer](crate::train::Trainer) and starts a new epoch every time it is cloned.
+///
+/// # Examples
+/// ```
+/// let loader = dataset
+///     .into_loader(DataLoaderConfigBuilder::default().batch_size(64).num_workers(4).build().unwrap())
+///     .prefetch_to(Device::Cuda(0), 2);
+/// ```
+pub struct DevicePrefetcher<I: Iterator> {
+    source: I,
+    pub device: Device,
+    /// The number of batches kept ready on the device.
+    pub depth: usize,
+    receiver: Option<Receiver<I::Item>>,
+}
+
+impl<I> DevicePrefetcher<I>
+where
+    I: Iterator + Clone + Send + 'static,
+    I::Item: ToDevice + Send + 'static,
+{
+    pub fn new(source: I, device: Device, depth: usize) -> Self {
+        Self {
+            source,
+            device,
+            depth,
+            receiver: None,
+        }
+    }
+
+    fn start(&mut self) {
+        let (sender, receiver) = sync_channel(self.depth.max(1));
+        let source = self.source.clone();
+        let device = self.device;
+        thread::spawn(move || {
+            for batch in source {
+                if sender.send(batch.to_device(device)).is_err() {
+                    break;
+                }
+            }
+        });
+        self.receiver = Some(receiver);
+    }
+}
+
+impl<I> Iterator for DevicePrefetcher<I>
+where
+    I: Iterator + Clone + Send + 'static,
+    I::Item: ToDevice + Send + 'static,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.receiver.is_none() {
+            self.start();
+        }
+        self.receiver.as_ref().unwrap().recv().ok()
+    }
+}
+
+impl<I: Iterator + Clone> Clone for DevicePrefetcher<I> {
+    /// Starts a new pass over a clone of the source iterator.
+    fn clone(&self) -> Self {
+        Self {
+            source: self.source.clone(),
+            device: self.device,
+            depth: self.depth,
+            receiver: None,
+        }
+    }
+}
+
+/// Adds [prefetch_to](PrefetchToDevice::prefetch_to) to iterators over batches.
+pub trait PrefetchToDevice: Iterator + Clone + Send + Sized + 'static
+where
+    Self::Item: ToDevice + Send + 'static,
+{
+    /// Moves the batches to `device` on a background thread, keeping up to `depth` batches ready.
+    fn prefetch_to(self, device: Device, depth: usize) -> DevicePrefetcher<Self> {
+        DevicePrefetcher::new(self, device, depth)
+    }
+}
+
+impl<I> PrefetchToDevice for I
+where
+    I: Iterator + Clone + Send + 'static,
+    I::Item: ToDevice + Send + 'static,
+{
+}
@@ -0,0 +1,109 @@
+use std::{path::Path, sync::Arc};
+
+use anyhow::Context;
+use ndarray::{ArrayD, IxDyn};
+use tch::Tensor;
+
+use crate::core::ToTensor;
+
+use super::{DictTensorDataset, MapDataset, TensorDataset};
+
+/// A dataset of inputs and labels stored as two HDF5 datasets of an .h5 file, whose first dimension indexes the samples.
+///
+/// The samples are read from the file on access, so the file may be larger than the memory. Values are read as `f64`, and converted to `Double` tensors.
+///
+/// This dataset is only available with the `hdf5` feature.
+///
+/// # Examples
+/// ```
+/// let dataset = Hdf5Dataset::open("data/experiment.h5", "train/x", "train/y")?;
+/// let (input, label) = dataset.get(0);
+/// ```
+pub struct Hdf5Dataset {
+    pub file: hdf5::File,
+    pub inputs: hdf5::Dataset,
+    pub labels: hdf5::Dataset,
+}
+
+impl Hdf5Dataset {
+    /// Opens the datasets at the paths `inputs` and `labels` of the .h5 file at `path`.
+    pub fn open<P: AsRef<Path>>(path: P, inputs: &str, labels: &str) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file =
+            hdf5::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let open = |name: &str| {
+            file.dataset(name)
+                .with_context(|| format!("Dataset {} not found in {}", name, path.display()))
+        };
+        let (inputs, labels) = (open(inputs)?, open(labels)?);
+        anyhow::ensure!(
+            first_dim(&inputs) == first_dim(&labels),
+            "The inputs and labels of {} have different numbers of samples",
+            path.display()
+        );
+        Ok(Self {
+            file,
+            inputs,
+            labels,
+        })
+    }
+
+    /// Reads all samples into a [TensorDataset].
+    pub fn load(&self) -> anyhow::Result<TensorDataset> {
+        let inputs = read_tensor(&self.inputs)?;
+        let labels = read_tensor(&self.labels)?;
+        Ok(TensorDataset::from_tensors(
+            inputs.unbind(0).into_iter().map(Arc::new).collect(),
+            labels.unbind(0).into_iter().map(Arc::new).collect(),
+        ))
+    }
+}
+
+impl MapDataset for Hdf5Dataset {
+    type SampleType = (Arc<Tensor>, Arc<Tensor>);
+
+    fn get(&self, index: usize) -> Self::SampleType {
+        let read = |dataset: &hdf5::Dataset| {
+            let rows: ArrayD<f64> = dataset
+                .read_slice::<f64, _, IxDyn>(index..index + 1)
+                .unwrap_or_else(|err| panic!("Failed to read sample {}: {}", index, err));
+            Arc::new(rows.to_tensor().squeeze_dim(0))
+        };
+        (read(&self.inputs), read(&self.labels))
+    }
+
+    fn len(&self) -> usize {
+        first_dim(&self.inputs)
+    }
+}
+
+/// Reads all datasets of the group at `group` in the .h5 file at `path`, e.g. the `x`, `y` and `mask` arrays of an exported experiment.
+///
+/// The first dimension of each dataset indexes the samples.
+pub fn load_hdf5_group<P: AsRef<Path>>(path: P, group: &str) -> anyhow::Result<DictTensorDataset> {
+    let path = path.as_ref();
+    let file =
+        hdf5::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let group = file
+        .group(group)
+        .with_context(|| format!("Group {} not found in {}", group, path.display()))?;
+    let mut inputs = std::collections::HashMap::new();
+    for dataset in group.datasets()? {
+        let name = dataset.name();
+        let name = name.rsplit('/').next().unwrap_or(&name).to_owned();
+        let tensor = read_tensor(&dataset)?;
+        inputs.insert(name, tensor.unbind(0).into_iter().map(Arc::new).collect());
+    }
+    Ok(DictTensorDataset { inputs })
+}
+
+fn first_dim(dataset: &hdf5::Dataset) -> usize {
+    dataset.shape().first().copied().unwrap_or(0)
+}
+
+fn read_tensor(dataset: &hdf5::Dataset) -> anyhow::Result<Tensor> {
+    let array: ArrayD<f64> = dataset
+        .read_dyn::<f64>()
+        .with_context(|| format!("Failed to read {}", dataset.name()))?;
+    Ok(array.to_tensor())
+}
@@ -1,9 +1,11 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use image::{DynamicImage, ImageBuffer, Pixel};
 use walkdir::WalkDir;
 
-use super::{LoadFromImageFolder, UnsupervisedDataset};
+use crate::core::RaddarError;
+
+use super::{LoadFromImageFolder, MapDataset, UnsupervisedDataset};
 
 pub type DynImageDataset = UnsupervisedDataset<DynamicImage>;
 pub type ImageDataset<P: Pixel, Container> = UnsupervisedDataset<ImageBuffer<P, Container>>;
@@ -11,29 +13,95 @@ pub type ImageDataset<P: Pixel, Container> = UnsupervisedDataset<ImageBuffer<P,
 impl LoadFromImageFolder for DynImageDataset {
     type ConfigType = ();
 
-    fn from_image_folder(path: &str, _config: Self::ConfigType) -> Self {
+    /// Opens all files under `path`, in sorted order. Returns an error naming the first file which is not a readable image.
+    fn try_from_image_folder(path: &str, _config: Self::ConfigType) -> Result<Self, RaddarError> {
         let mut inputs = Vec::new();
-        WalkDir::new(path)
+        for entry in WalkDir::new(path)
+            .sort_by_file_name()
             .into_iter()
             .filter_map(|e| e.ok())
             .filter(|e| e.file_type().is_file())
+        {
+            let image = image::open(entry.path()).map_err(|source| RaddarError::Image {
+                path: entry.into_path(),
+                source,
+            })?;
+            inputs.push(Arc::new(image));
+        }
+        Ok(Self::from_vectors(inputs))
+    }
+}
+
+/// An image dataset which holds the paths of the images, and decodes an image only when it is read.
+///
+/// Unlike [DynImageDataset], the memory used does not grow with the size of the images. Use [MapDataset::map_samples] to transform the decoded images, and [MapDataset::into_loader_with] to decode them on worker threads.
+///
+/// # Examples
+/// ```
+/// let loader = LazyImageDataset::from_image_folder("data/train", ())
+///     .map_samples(
+///         Compose::new()
+///             .then(image_mappings::resize(224, 224))
+///             .then(image_mappings::to_tensor_with(DynamicImage::into_rgb8, true, true)),
+///     )
+///     .into_loader_with(
+///         DataLoaderConfigBuilder::default().batch_size(32).num_workers(4).build().unwrap(),
+///         StackCollate,
+///     );
+/// ```
+#[derive(Debug, Clone)]
+pub struct LazyImageDataset {
+    pub paths: Vec<PathBuf>,
+}
+
+impl LazyImageDataset {
+    pub fn from_paths(paths: Vec<PathBuf>) -> Self {
+        Self { paths }
+    }
+}
+
+impl LoadFromImageFolder for LazyImageDataset {
+    type ConfigType = ();
+
+    /// Collects the paths of all files under `path`, in sorted order. The files are not opened until they are read.
+    fn try_from_image_folder(path: &str, _config: Self::ConfigType) -> Result<Self, RaddarError> {
+        let paths = WalkDir::new(path)
+            .sort_by_file_name()
             .into_iter()
-            .for_each(|entry| {
-                let path = entry.path();
-                let image = image::open(path).expect("Failed to open image");
-                inputs.push(Arc::new(image));
-            });
-        Self::from_vectors(inputs)
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .collect();
+        Ok(Self { paths })
+    }
+}
+
+impl MapDataset for LazyImageDataset {
+    type SampleType = Arc<DynamicImage>;
+
+    fn get(&self, index: usize) -> Self::SampleType {
+        let path = &self.paths[index];
+        let image = image::open(path)
+            .unwrap_or_else(|err| panic!("Failed to open image {}: {}", path.display(), err));
+        Arc::new(image)
+    }
+
+    fn len(&self) -> usize {
+        self.paths.len()
     }
 }
 
 pub mod image_mappings {
     use std::{ops::Deref, sync::Arc};
 
-    use image::{DynamicImage, GenericImageView, ImageBuffer, Pixel};
-    use tch::{kind::Element, Tensor};
+    use image::{DynamicImage, GenericImage, GenericImageView, ImageBuffer, Pixel, Rgba};
+    use rand::Rng;
+    use tch::{kind::Element, Kind, Tensor};
 
-    use crate::dataset::{Dataset, UnsupervisedTensorDataset};
+    use crate::{
+        dataset::{Dataset, UnsupervisedTensorDataset},
+        util::seeded_rng,
+    };
 
     use super::DynImageDataset;
 
@@ -71,6 +139,214 @@ pub mod image_mappings {
         }
     }
 
+    /// Crops a random `width` x `height` region of the image.
+    ///
+    /// # Panics
+    /// Panics if the image is smaller than the region.
+    pub fn random_crop(
+        width: u32,
+        height: u32,
+    ) -> impl FnMut(
+        <DynImageDataset as Dataset>::SampleType,
+    ) -> <DynImageDataset as Dataset>::SampleType
+           + Send
+           + Clone
+           + 'static {
+        move |input: Arc<DynamicImage>| {
+            let (w, h) = input.dimensions();
+            assert!(
+                w >= width && h >= height,
+                "Cannot crop {}x{} from an image of {}x{}.",
+                width,
+                height,
+                w,
+                h
+            );
+            let mut rng = seeded_rng();
+            let x = rng.gen_range(0..=w - width);
+            let y = rng.gen_range(0..=h - height);
+            Arc::new(input.crop_imm(x, y, width, height))
+        }
+    }
+
+    /// Flips the image horizontally with probability `p`.
+    pub fn random_horizontal_flip(
+        p: f64,
+    ) -> impl FnMut(
+        <DynImageDataset as Dataset>::SampleType,
+    ) -> <DynImageDataset as Dataset>::SampleType
+           + Send
+           + Clone
+           + 'static {
+        move |input: Arc<DynamicImage>| {
+            if seeded_rng().gen_bool(p) {
+                Arc::new(input.fliph())
+            } else {
+                input
+            }
+        }
+    }
+
+    /// Flips the image vertically with probability `p`.
+    pub fn random_vertical_flip(
+        p: f64,
+    ) -> impl FnMut(
+        <DynImageDataset as Dataset>::SampleType,
+    ) -> <DynImageDataset as Dataset>::SampleType
+           + Send
+           + Clone
+           + 'static {
+        move |input: Arc<DynamicImage>| {
+            if seeded_rng().gen_bool(p) {
+                Arc::new(input.flipv())
+            } else {
+                input
+            }
+        }
+    }
+
+    /// Rotates the image around its center by a random angle in `[-degrees, degrees]`, keeping its size.
+    ///
+    /// Pixels are sampled by nearest neighbour, and the corners uncovered by the rotation are filled with transparent black.
+    pub fn random_rotation(
+        degrees: f32,
+    ) -> impl FnMut(
+        <DynImageDataset as Dataset>::SampleType,
+    ) -> <DynImageDataset as Dataset>::SampleType
+           + Send
+           + Clone
+           + 'static {
+        move |input: Arc<DynamicImage>| {
+            let angle = seeded_rng().gen_range(-degrees..=degrees).to_radians();
+            Arc::new(rotate(&input, angle))
+        }
+    }
+
+    /// Rotates an image counterclockwise by `angle` radians around its center.
+    fn rotate(input: &DynamicImage, angle: f32) -> DynamicImage {
+        let (w, h) = input.dimensions();
+        let (cx, cy) = ((w as f32 - 1.) / 2., (h as f32 - 1.) / 2.);
+        let (sin, cos) = angle.sin_cos();
+        let mut output = input.clone();
+        for y in 0..h {
+            for x in 0..w {
+                // Map each output pixel back to its source pixel.
+                let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+                let sx = (cos * dx - sin * dy + cx).round();
+                let sy = (sin * dx + cos * dy + cy).round();
+                let pixel = if sx >= 0. && sy >= 0. && sx < w as f32 && sy < h as f32 {
+                    input.get_pixel(sx as u32, sy as u32)
+                } else {
+                    Rgba([0, 0, 0, 0])
+                };
+                output.put_pixel(x, y, pixel);
+            }
+        }
+        output
+    }
+
+    /// Randomly changes the brightness, contrast, saturation and hue of the image.
+    ///
+    /// The brightness, contrast and saturation are scaled by a factor drawn from `[1 - x, 1 + x]` for their respective argument `x`, and the hue is shifted by a fraction of the color wheel drawn from `[-hue, hue]`, where `hue` should be at most 0.5. Pass 0 to leave a property unchanged. The alpha channel, if any, is kept.
+    pub fn color_jitter(
+        brightness: f32,
+        contrast: f32,
+        saturation: f32,
+        hue: f32,
+    ) -> impl FnMut(
+        <DynImageDataset as Dataset>::SampleType,
+    ) -> <DynImageDataset as Dataset>::SampleType
+           + Send
+           + Clone
+           + 'static {
+        move |input: Arc<DynamicImage>| {
+            let mut rng = seeded_rng();
+            let mut factor = |x: f32| rng.gen_range((1. - x).max(0.)..=1. + x);
+            let brightness = factor(brightness);
+            let contrast = factor(contrast);
+            let saturation = factor(saturation);
+            let hue = rng.gen_range(-hue..=hue);
+
+            let mut image = input.to_rgba32f();
+            let mean = image.pixels().map(|pixel| grayscale(&pixel.0)).sum::<f32>()
+                / (image.width() * image.height()).max(1) as f32;
+            for pixel in image.pixels_mut() {
+                let mut rgb = [pixel.0[0], pixel.0[1], pixel.0[2]];
+                rgb = rgb.map(|c| c * brightness);
+                let mean = mean * brightness;
+                rgb = rgb.map(|c| mean + (c - mean) * contrast);
+                let gray = grayscale(&rgb);
+                rgb = rgb.map(|c| gray + (c - gray) * saturation);
+                if hue != 0. {
+                    rgb = shift_hue(rgb.map(|c| c.clamp(0., 1.)), hue);
+                }
+                let rgb = rgb.map(|c| c.clamp(0., 1.));
+                pixel.0[..3].copy_from_slice(&rgb);
+            }
+            let image = DynamicImage::ImageRgba32F(image);
+            Arc::new(if input.color().has_alpha() {
+                DynamicImage::ImageRgba8(image.into_rgba8())
+            } else {
+                DynamicImage::ImageRgb8(image.into_rgb8())
+            })
+        }
+    }
+
+    /// Blurs the image with a Gaussian kernel, whose standard deviation is drawn from `[sigma_min, sigma_max]`.
+    pub fn gaussian_blur(
+        sigma_min: f32,
+        sigma_max: f32,
+    ) -> impl FnMut(
+        <DynImageDataset as Dataset>::SampleType,
+    ) -> <DynImageDataset as Dataset>::SampleType
+           + Send
+           + Clone
+           + 'static {
+        move |input: Arc<DynamicImage>| {
+            let sigma = seeded_rng().gen_range(sigma_min..=sigma_max);
+            if sigma <= 0. {
+                return input;
+            }
+            Arc::new(input.blur(sigma))
+        }
+    }
+
+    /// The luma of an RGB color, with the ITU-R 601-2 weights.
+    fn grayscale(rgb: &[f32]) -> f32 {
+        0.299 * rgb[0] + 0.587 * rgb[1] + 0.114 * rgb[2]
+    }
+
+    /// Rotates the hue of an RGB color in `0..1` by `shift` turns of the color wheel.
+    fn shift_hue([r, g, b]: [f32; 3], shift: f32) -> [f32; 3] {
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+        if delta == 0. {
+            return [r, g, b];
+        }
+        let hue = if max == r {
+            ((g - b) / delta).rem_euclid(6.)
+        } else if max == g {
+            (b - r) / delta + 2.
+        } else {
+            (r - g) / delta + 4.
+        } / 6.;
+        let hue = (hue + shift).rem_euclid(1.) * 6.;
+        let x = delta * (1. - (hue % 2. - 1.).abs());
+        let (r, g, b) = match hue as u32 {
+            0 => (delta, x, 0.),
+            1 => (x, delta, 0.),
+            2 => (0., delta, x),
+            3 => (0., x, delta),
+            4 => (x, 0., delta),
+            _ => (delta, 0., x),
+        };
+        [r + min, g + min, b + min]
+    }
+
+    /// Converts an image into a tensor of shape `[height, width, channels]`, keeping the raw subpixel values.
+    ///
+    /// See [to_tensor_with] to get a `[channels, height, width]` tensor in `0..1`, which is what most models expect.
     pub fn to_tensor<
         P: Pixel,
         Container: Deref<Target = [P::Subpixel]> + Clone,
@@ -85,15 +361,95 @@ pub mod image_mappings {
            + 'static
     where
         <P as Pixel>::Subpixel: Element,
+    {
+        to_tensor_with(into_image_buffer, false, false)
+    }
+
+    /// Converts an image into a tensor.
+    ///
+    /// If `scale` is true, integer subpixels are divided by their maximum value, so that the values are in `0..1`, and the tensor is of kind `Float`. If `channels_first` is true, the tensor is permuted to `[channels, height, width]`.
+    ///
+    /// # Examples
+    /// ```
+    /// let dataset: UnsupervisedTensorDataset = images
+    ///     .map(image_mappings::to_tensor_with(DynamicImage::into_rgb8, true, true))
+    ///     .map(image_mappings::imagenet_normalize());
+    /// ```
+    pub fn to_tensor_with<
+        P: Pixel,
+        Container: Deref<Target = [P::Subpixel]> + Clone,
+        F: Fn(DynamicImage) -> ImageBuffer<P, Container> + Send + Clone + 'static,
+    >(
+        into_image_buffer: F,
+        scale: bool,
+        channels_first: bool,
+    ) -> impl FnMut(
+        <DynImageDataset as Dataset>::SampleType,
+    ) -> <UnsupervisedTensorDataset as Dataset>::SampleType
+           + Send
+           + Clone
+           + 'static
+    where
+        <P as Pixel>::Subpixel: Element,
     {
         move |input: Arc<DynamicImage>| {
             let input = (*input).clone();
             let (w, h) = input.dimensions();
             let input = into_image_buffer(input);
             let channels = P::CHANNEL_COUNT as i64;
-            let tensor =
+            let mut tensor =
                 Tensor::of_slice(&input.into_raw()).reshape(&[h as i64, w as i64, channels]);
+            if scale {
+                tensor = match tensor.kind() {
+                    Kind::Uint8 => tensor.to_kind(Kind::Float) / 255.,
+                    Kind::Int8 => tensor.to_kind(Kind::Float) / 127.,
+                    Kind::Int16 => tensor.to_kind(Kind::Float) / 32767.,
+                    Kind::Float | Kind::Double | Kind::Half | Kind::BFloat16 => tensor,
+                    _ => tensor.to_kind(Kind::Float),
+                };
+            }
+            if channels_first {
+                tensor = tensor.permute(&[2, 0, 1]);
+            }
             Arc::new(tensor)
         }
     }
+
+    /// Normalizes each channel of a `[channels, height, width]` tensor with the given mean and standard deviation, i.e. `(input - mean) / std`.
+    pub fn normalize(
+        mean: Vec<f64>,
+        std: Vec<f64>,
+    ) -> impl FnMut(
+        <UnsupervisedTensorDataset as Dataset>::SampleType,
+    ) -> <UnsupervisedTensorDataset as Dataset>::SampleType
+           + Send
+           + Clone
+           + 'static {
+        assert_eq!(
+            mean.len(),
+            std.len(),
+            "The mean and std must have the same number of channels."
+        );
+        move |input: Arc<Tensor>| {
+            let channel_values = |values: &[f64]| {
+                Tensor::of_slice(values)
+                    .view([-1, 1, 1])
+                    .to_kind(input.kind())
+                    .to_device(input.device())
+            };
+            let mean = channel_values(&mean);
+            let std = channel_values(&std);
+            Arc::new((&*input - mean) / std)
+        }
+    }
+
+    /// Normalizes a `[3, height, width]` RGB tensor in `0..1` with the mean and standard deviation of ImageNet, as expected by most pretrained vision models.
+    pub fn imagenet_normalize() -> impl FnMut(
+        <UnsupervisedTensorDataset as Dataset>::SampleType,
+    ) -> <UnsupervisedTensorDataset as Dataset>::SampleType
+           + Send
+           + Clone
+           + 'static {
+        normalize(vec![0.485, 0.456, 0.406], vec![0.229, 0.224, 0.225])
+    }
 }
@@ -0,0 +1,318 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{sync_channel, Receiver},
+        Arc,
+    },
+    thread,
+};
+
+use parking_lot::Mutex;
+
+use super::{Collate, DataLoaderConfig, RepeatDataset, TakeDataset};
+
+/// An iterable-style dataset, which produces its samples one after another, e.g. from a socket, a file stream or a generator.
+///
+/// An iterable dataset may be infinite. Use [IterableDataset::into_loader_with] to batch its samples with the same [DataLoaderConfig] as the [DataLoader](super::DataLoader), which only takes map-style [Dataset](super::Dataset)s since it draws the samples of each epoch by index.
+pub trait IterableDataset: Send {
+    type SampleType: Send + 'static;
+
+    /// Returns the next sample, or `None` if the dataset is exhausted.
+    fn next_sample(&mut self) -> Option<Self::SampleType>;
+
+    /// Creates an [IterableLoader] which batches the samples with `collate`, on worker threads if `cfg.num_workers` is positive.
+    fn into_loader_with<C>(self, cfg: DataLoaderConfig, collate: C) -> IterableLoader<Self, C>
+    where
+        Self: Sized,
+        C: Collate<Self::SampleType>,
+    {
+        IterableLoader::new(self, cfg, collate)
+    }
+
+    /// Reads the dataset `times` times, or forever if `times` is `None`, see [RepeatDataset].
+    fn repeat(self, times: Option<usize>) -> RepeatDataset<Self>
+    where
+        Self: Sized + Clone,
+    {
+        RepeatDataset::new(self, times)
+    }
+
+    /// Stops after the first `n` samples, see [TakeDataset].
+    fn take(self, n: usize) -> TakeDataset<Self>
+    where
+        Self: Sized,
+    {
+        TakeDataset::new(self, n)
+    }
+}
+
+impl<D: IterableDataset + ?Sized> IterableDataset for Box<D> {
+    type SampleType = D::SampleType;
+
+    fn next_sample(&mut self) -> Option<Self::SampleType> {
+        (**self).next_sample()
+    }
+}
+
+/// An [IterableDataset] over an iterator, see [from_iter].
+#[derive(Debug, Clone)]
+pub struct IterDataset<I: Iterator> {
+    pub iter: I,
+}
+
+/// Creates an [IterableDataset] from an iterator.
+pub fn from_iter<I>(iter: I) -> IterDataset<I::IntoIter>
+where
+    I: IntoIterator,
+    I::IntoIter: Send,
+    I::Item: Send + 'static,
+{
+    IterDataset {
+        iter: iter.into_iter(),
+    }
+}
+
+impl<I> IterableDataset for IterDataset<I>
+where
+    I: Iterator + Send,
+    I::Item: Send + 'static,
+{
+    type SampleType = I::Item;
+
+    fn next_sample(&mut self) -> Option<Self::SampleType> {
+        self.iter.next()
+    }
+}
+
+/// An [IterableDataset] over a generator function, see [from_fn].
+pub struct FnDataset<F> {
+    pub f: F,
+}
+
+/// Creates an [IterableDataset] from a function returning the next sample, or `None` when there are no more samples.
+///
+/// # Examples
+/// ```
+/// // An infinite stream of noisy samples of `y = 2x`.
+/// let dataset = from_fn(|| {
+///     let x = Tensor::rand(&[1], (Kind::Double, Device::Cpu));
+///     let y = &x * 2. + Tensor::randn(&[1], (Kind::Double, Device::Cpu)) * 0.1;
+///     Some((Arc::new(x), Arc::new(y)))
+/// });
+/// ```
+pub fn from_fn<S, F>(f: F) -> FnDataset<F>
+where
+    S: Send + 'static,
+    F: FnMut() -> Option<S> + Send,
+{
+    FnDataset { f }
+}
+
+impl<S, F> IterableDataset for FnDataset<F>
+where
+    S: Send + 'static,
+    F: FnMut() -> Option<S> + Send,
+{
+    type SampleType = S;
+
+    fn next_sample(&mut self) -> Option<S> {
+        (self.f)()
+    }
+}
+
+/// An [IterableDataset] over data generated on the fly, e.g. by a simulation, RL rollouts or a network stream.
+///
+/// The samples are consumed as they arrive, without being collected into a `Vec` first. Like the samples of any [IterableDataset], they are batched by an [IterableLoader] with a [DataLoaderConfig], which can read and collate the stream on worker threads.
+///
+/// # Examples
+/// ```
+/// let (sender, receiver) = std::sync::mpsc::sync_channel(64);
+/// std::thread::spawn(move || loop {
+///     let (observation, reward) = env.step();
+///     if sender.send((Arc::new(observation), Arc::new(reward))).is_err() {
+///         break;
+///     }
+/// });
+/// let loader = StreamingDataset::from_receiver(receiver)
+///     .into_loader_with(
+///         DataLoaderConfigBuilder::default()
+///             .batch_size(32)
+///             .num_workers(2)
+///             .build()
+///             .unwrap(),
+///         StackCollate,
+///     )
+///     .batches_per_epoch(100);
+/// trainer.fit(loader, 10);
+/// ```
+pub struct StreamingDataset<S> {
+    source: Box<dyn Iterator<Item = S> + Send>,
+}
+
+impl<S: Send + 'static> StreamingDataset<S> {
+    /// Creates a dataset consuming an iterator.
+    pub fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        I::IntoIter: Send + 'static,
+    {
+        Self {
+            source: Box::new(iter.into_iter()),
+        }
+    }
+
+    /// Creates a dataset consuming the samples sent to a channel. Reading blocks until a sample arrives, and the dataset is exhausted once all senders are dropped.
+    pub fn from_receiver(receiver: Receiver<S>) -> Self {
+        Self {
+            source: Box::new(receiver.into_iter()),
+        }
+    }
+}
+
+impl<S: Send + 'static> IterableDataset for StreamingDataset<S> {
+    type SampleType = S;
+
+    fn next_sample(&mut self) -> Option<S> {
+        self.source.next()
+    }
+}
+
+/// A loader which batches the samples of an [IterableDataset], configured by the same [DataLoaderConfig] as the [DataLoader](super::DataLoader).
+///
+/// All clones of the loader share the same dataset, so a stream continues where the previous epoch stopped instead of restarting. Since the [Trainer](crate::train::Trainer) clones the loader at the beginning of each epoch, use [IterableLoader::batches_per_epoch] to split an infinite dataset into epochs.
+///
+/// With `num_workers` workers, the samples are read and collated in the background, and each worker keeps at most `prefetch_factor` batches ready. Each batch is made of consecutive samples of the dataset, but the batches of different workers may arrive out of order. The order of the samples is given by the dataset, so `shuffle`, `seed` and `sampler` are ignored. If an epoch is stopped early, the batches prefetched for it are dropped.
+pub struct IterableLoader<D: IterableDataset, C: Collate<D::SampleType>> {
+    dataset: Arc<Mutex<D>>,
+    pub collate: C,
+    pub cfg: DataLoaderConfig,
+    /// The maximum number of batches per epoch.
+    pub batches_per_epoch: Option<usize>,
+    batches: usize,
+    /// The batches collated by the workers, started by the first call of `next` if `num_workers` is positive.
+    workers: Option<Receiver<C::Batch>>,
+}
+
+impl<D: IterableDataset, C: Collate<D::SampleType>> IterableLoader<D, C> {
+    pub fn new(dataset: D, cfg: DataLoaderConfig, collate: C) -> Self {
+        Self {
+            dataset: Arc::new(Mutex::new(dataset)),
+            collate,
+            cfg,
+            batches_per_epoch: None,
+            batches: 0,
+            workers: None,
+        }
+    }
+
+    /// Set the maximum number of batches per epoch.
+    pub fn batches_per_epoch(mut self, batches_per_epoch: usize) -> Self {
+        self.batches_per_epoch = Some(batches_per_epoch);
+        self
+    }
+}
+
+/// Reads the samples of the next batch, or `None` if the dataset is exhausted before the batch is full and `drop_last` is set.
+fn read_batch<D: IterableDataset>(
+    dataset: &Mutex<D>,
+    batch_size: usize,
+    drop_last: bool,
+) -> Option<Vec<D::SampleType>> {
+    let samples: Vec<D::SampleType> = {
+        let mut dataset = dataset.lock();
+        (0..batch_size)
+            .map_while(|_| dataset.next_sample())
+            .collect()
+    };
+    if samples.is_empty() || (drop_last && samples.len() < batch_size) {
+        return None;
+    }
+    Some(samples)
+}
+
+impl<D, C> IterableLoader<D, C>
+where
+    D: IterableDataset + 'static,
+    C: Collate<D::SampleType> + Send + 'static,
+    C::Batch: Send + 'static,
+{
+    /// Starts the workers over the remaining batches of the epoch.
+    fn start_workers(&mut self) {
+        let num_workers = self.cfg.num_workers;
+        let (sender, receiver) = sync_channel(num_workers * self.cfg.prefetch_factor.max(1));
+        // The workers claim a batch before reading it, so that they do not consume the samples of the next epoch.
+        let remaining = Arc::new(AtomicUsize::new(
+            self.batches_per_epoch
+                .map_or(usize::MAX, |batches| batches.saturating_sub(self.batches)),
+        ));
+        for _ in 0..num_workers {
+            let dataset = self.dataset.clone();
+            let collate = self.collate.clone();
+            let sender = sender.clone();
+            let remaining = remaining.clone();
+            let (batch_size, drop_last) = (self.cfg.batch_size.max(1), self.cfg.drop_last);
+            thread::spawn(move || {
+                while remaining
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                    .is_ok()
+                {
+                    let samples = match read_batch(&dataset, batch_size, drop_last) {
+                        Some(samples) => samples,
+                        None => break,
+                    };
+                    if sender.send(collate.collate(samples)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        self.workers = Some(receiver);
+    }
+}
+
+impl<D: IterableDataset, C: Collate<D::SampleType>> Clone for IterableLoader<D, C> {
+    /// Starts a new epoch over the same dataset.
+    fn clone(&self) -> Self {
+        Self {
+            dataset: self.dataset.clone(),
+            collate: self.collate.clone(),
+            cfg: self.cfg.clone(),
+            batches_per_epoch: self.batches_per_epoch,
+            batches: 0,
+            workers: None,
+        }
+    }
+}
+
+impl<D, C> Iterator for IterableLoader<D, C>
+where
+    D: IterableDataset + 'static,
+    C: Collate<D::SampleType> + Send + 'static,
+    C::Batch: Send + 'static,
+{
+    type Item = C::Batch;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(batches_per_epoch) = self.batches_per_epoch {
+            if self.batches >= batches_per_epoch {
+                return None;
+            }
+        }
+        let batch = if self.cfg.num_workers > 0 {
+            if self.workers.is_none() {
+                self.start_workers();
+            }
+            // The channel is closed once all workers have stopped, i.e. at the end of the dataset.
+            self.workers.as_ref().unwrap().recv().ok()?
+        } else {
+            let samples = read_batch(
+                &self.dataset,
+                self.cfg.batch_size.max(1),
+                self.cfg.drop_last,
+            )?;
+            self.collate.collate(samples)
+        };
+        self.batches += 1;
+        Some(batch)
+    }
+}
@@ -0,0 +1,112 @@
+use derive_builder::Builder;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::util::seeded_rng;
+
+use super::Dataset;
+
+/// K-fold cross-validation splits.
+///
+/// The samples are split into `k` consecutive folds (after an optional shuffle). Each fold is used once as the validation set, while the other `k - 1` folds form the training set.
+///
+/// # Examples
+/// ```
+/// let kfold = KFoldBuilder::default().k(5).shuffle(true).seed(Some(42)).build().unwrap();
+/// for (train, val) in kfold.split(dataset) {
+///     ...
+/// }
+/// ```
+#[derive(Debug, Clone, Builder)]
+#[builder(pattern = "owned")]
+pub struct KFold {
+    pub k: usize,
+
+    #[builder(default = "false")]
+    pub shuffle: bool,
+
+    /// The seed of the shuffle. If `None`, the shuffle is not reproducible.
+    #[builder(default = "None")]
+    pub seed: Option<u64>,
+}
+
+impl KFold {
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            shuffle: false,
+            seed: None,
+        }
+    }
+
+    /// Returns an iterator over the `(train, val)` splits of the dataset.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is less than 2 or greater than the size of the dataset.
+    pub fn split<T: Dataset>(&self, dataset: T) -> KFoldSplits<T> {
+        let mut data = dataset.data();
+        assert!(
+            self.k >= 2 && self.k <= data.len(),
+            "KFold needs 2 <= k <= {}, got k = {}",
+            data.len(),
+            self.k
+        );
+        if self.shuffle {
+            let mut rng = match self.seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => seeded_rng(),
+            };
+            data.shuffle(&mut rng);
+        }
+        KFoldSplits {
+            data,
+            k: self.k,
+            fold: 0,
+        }
+    }
+}
+
+/// An iterator over the `(train, val)` splits of a [KFold].
+#[derive(Debug, Clone)]
+pub struct KFoldSplits<T: Dataset> {
+    pub data: Vec<T::SampleType>,
+    pub k: usize,
+    pub fold: usize,
+}
+
+impl<T: Dataset> KFoldSplits<T> {
+    /// Returns the range of the samples in the given fold. The first `n % k` folds get one more sample than the others.
+    fn fold_range(&self, fold: usize) -> std::ops::Range<usize> {
+        let (size, remainder) = (self.data.len() / self.k, self.data.len() % self.k);
+        let start = fold * size + fold.min(remainder);
+        let end = start + size + if fold < remainder { 1 } else { 0 };
+        start..end
+    }
+}
+
+impl<T: Dataset> Iterator for KFoldSplits<T> {
+    type Item = (T, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.fold >= self.k {
+            return None;
+        }
+        let range = self.fold_range(self.fold);
+        self.fold += 1;
+        let val = T::from_data(self.data[range.clone()].iter().cloned());
+        let train = T::from_data(
+            self.data[..range.start]
+                .iter()
+                .chain(self.data[range.end..].iter())
+                .cloned(),
+        );
+        Some((train, val))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.k - self.fold;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T: Dataset> ExactSizeIterator for KFoldSplits<T> {}
@@ -1,13 +1,24 @@
 use std::sync::Arc;
 
+use crate::core::RaddarError;
+
 use super::{SimpleDataset, UnsupervisedDataset};
 
 /// A trait for datasets that can be loaded from json files.
-pub trait LoadFromJson {
+pub trait LoadFromJson: Sized {
     type ConfigType;
 
+    /// Load a dataset from a json file, or return an error if the file cannot be read or does not hold the fields of the config.
+    fn try_from_json(path: &str, config: Self::ConfigType) -> Result<Self, RaddarError>;
+
     /// Load a dataset from a json file.
-    fn from_json(path: &str, config: Self::ConfigType) -> Self;
+    ///
+    /// # Panics
+    ///
+    /// Panics if the file cannot be read, see [LoadFromJson::try_from_json] to handle this error.
+    fn from_json(path: &str, config: Self::ConfigType) -> Self {
+        Self::try_from_json(path, config).unwrap_or_else(|error| panic!("{}", error))
+    }
 }
 
 pub struct SimpleDatasetJsonConfig {
@@ -15,41 +26,22 @@ pub struct SimpleDatasetJsonConfig {
     pub label_field: String,
 }
 
-impl<InputType: Clone + Send + Sync, LabelType: Clone + Send + Sync> LoadFromJson for SimpleDataset<InputType, LabelType>
+impl<InputType: Clone + Send + Sync, LabelType: Clone + Send + Sync> LoadFromJson
+    for SimpleDataset<InputType, LabelType>
 where
     InputType: serde::de::DeserializeOwned,
     LabelType: serde::de::DeserializeOwned,
 {
     type ConfigType = SimpleDatasetJsonConfig;
 
-    fn from_json(path: &str, config: Self::ConfigType) -> Self {
-        let file =
-            std::fs::File::open(path).expect(format!("Failed to open file {}", path).as_str());
-        let reader = std::io::BufReader::new(file);
+    fn try_from_json(path: &str, config: Self::ConfigType) -> Result<Self, RaddarError> {
         let mut inputs = Vec::new();
         let mut labels = Vec::new();
-        let json: serde_json::Value =
-            serde_json::from_reader(reader).expect("Failed to parse json");
-        for item in json
-            .as_array()
-            .expect("Input file is not a valid JSON array")
-        {
-            let input = item
-                .get(&config.input_field)
-                .expect("Input field not found in JSON object");
-            let label = item
-                .get(&config.label_field)
-                .expect("Label field not found in JSON object");
-            inputs
-                .push(Arc::new(serde_json::from_value(input.clone()).expect(
-                    "Input field is not compatible with the specified type",
-                )));
-            labels
-                .push(Arc::new(serde_json::from_value(label.clone()).expect(
-                    "Label field is not compatible with the specified type",
-                )));
+        for item in read_json_array(path)? {
+            inputs.push(Arc::new(json_field(path, &item, &config.input_field)?));
+            labels.push(Arc::new(json_field(path, &item, &config.label_field)?));
         }
-        Self::from_vectors(inputs, labels)
+        Ok(Self::from_vectors(inputs, labels))
     }
 }
 
@@ -63,32 +55,67 @@ where
 {
     type ConfigType = UnsupervisedDatasetJsonConfig;
 
-    fn from_json(path: &str, config: Self::ConfigType) -> Self {
-        let file =
-            std::fs::File::open(path).expect(format!("Failed to open file {}", path).as_str());
-        let reader = std::io::BufReader::new(file);
+    fn try_from_json(path: &str, config: Self::ConfigType) -> Result<Self, RaddarError> {
         let mut inputs = Vec::new();
-        let json: serde_json::Value =
-            serde_json::from_reader(reader).expect("Failed to parse json");
-        for item in json
-            .as_array()
-            .expect("Input file is not a valid JSON array")
-        {
-            let input = item
-                .get(&config.input_field)
-                .expect("Input field not found in JSON object");
-            inputs
-                .push(Arc::new(serde_json::from_value(input.clone()).expect(
-                    "Input field is not compatible with the specified type",
-                )));
+        for item in read_json_array(path)? {
+            inputs.push(Arc::new(json_field(path, &item, &config.input_field)?));
         }
-        Self::from_vectors(inputs)
+        Ok(Self::from_vectors(inputs))
     }
 }
 
-pub trait LoadFromImageFolder {
-    type ConfigType;
+/// Reads a json file holding an array of objects.
+fn read_json_array(path: &str) -> Result<Vec<serde_json::Value>, RaddarError> {
+    let file = std::fs::File::open(path).map_err(|source| RaddarError::Io {
+        path: path.into(),
+        source,
+    })?;
+    let invalid = |message: String| RaddarError::InvalidData {
+        path: path.into(),
+        message,
+    };
+    let json: serde_json::Value = serde_json::from_reader(std::io::BufReader::new(file))
+        .map_err(|err| invalid(format!("Failed to parse json: {}", err)))?;
+    match json {
+        serde_json::Value::Array(items) => Ok(items),
+        _ => Err(invalid("Input file is not a valid JSON array".to_owned())),
+    }
+}
 
-    fn from_image_folder(path: &str, config: Self::ConfigType) -> Self;
+/// Deserializes the field `field` of a json object of the file at `path`.
+fn json_field<T: serde::de::DeserializeOwned>(
+    path: &str,
+    item: &serde_json::Value,
+    field: &str,
+) -> Result<T, RaddarError> {
+    let invalid = |message: String| RaddarError::InvalidData {
+        path: path.into(),
+        message,
+    };
+    let value = item
+        .get(field)
+        .ok_or_else(|| invalid(format!("Field {} not found in JSON object", field)))?;
+    serde_json::from_value(value.clone()).map_err(|err| {
+        invalid(format!(
+            "Field {} is not compatible with the specified type: {}",
+            field, err
+        ))
+    })
 }
 
+/// A trait for datasets that can be loaded from a folder of images.
+pub trait LoadFromImageFolder: Sized {
+    type ConfigType;
+
+    /// Load a dataset from the images under `path`, or return an error if an image cannot be opened.
+    fn try_from_image_folder(path: &str, config: Self::ConfigType) -> Result<Self, RaddarError>;
+
+    /// Load a dataset from the images under `path`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an image cannot be opened, see [LoadFromImageFolder::try_from_image_folder] to handle this error.
+    fn from_image_folder(path: &str, config: Self::ConfigType) -> Self {
+        Self::try_from_image_folder(path, config).unwrap_or_else(|error| panic!("{}", error))
+    }
+}
@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tch::Tensor;
+
+use super::{
+    Collate, DataLoaderConfig, DatasetSampleMapping, IterableDataset, MapDataLoader,
+    MetadataDataset, SampleMetadata, Sampler, SimpleDataset, TensorDataset, UnsupervisedDataset,
+    UnsupervisedTensorDataset,
+};
+
+/// A map-style dataset, which gives random access to its samples by index.
+///
+/// Unlike [Dataset](super::Dataset), a map-style dataset does not need to hold its samples in memory, e.g. it can load them from disk in `get`.
+pub trait MapDataset: Send + Sync {
+    type SampleType: Send + 'static;
+
+    /// Returns the sample at `index`.
+    fn get(&self, index: usize) -> Self::SampleType;
+
+    /// Returns the number of samples.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Turns the dataset into an [IterableDataset] visiting the indices given by `sampler`, or all indices in order if it is `None`.
+    ///
+    /// The sampler is asked for new indices whenever the previous ones are exhausted, so the resulting dataset is infinite if a sampler is given.
+    fn into_iterable(self, sampler: Option<Arc<dyn Sampler>>) -> MapDatasetIter<Self>
+    where
+        Self: Sized,
+    {
+        MapDatasetIter::new(self, sampler)
+    }
+
+    /// Lazily maps the samples with `f`, which is called whenever a sample is read.
+    ///
+    /// `f` can be any [DatasetSampleMapping], such as the functions of [image_mappings](super::image_mappings) or a [Compose](super::Compose). Each read calls a fresh clone of `f`, so that concurrent reads do not block each other.
+    fn map_samples<T, F>(self, f: F) -> MappedDataset<Self, F>
+    where
+        Self: Sized,
+        T: Send + 'static,
+        F: DatasetSampleMapping<Self::SampleType, T>,
+    {
+        MappedDataset {
+            dataset: self,
+            f: Mutex::new(f),
+        }
+    }
+
+    /// Attaches `metadata[i]` to the `i`-th sample, so that it flows through [map_samples](MapDataset::map_samples) (with [on_sample](super::on_sample)) and into the batches (with [MetadataCollate](super::MetadataCollate)).
+    fn with_metadata(self, metadata: Vec<SampleMetadata>) -> MetadataDataset<Self>
+    where
+        Self: Sized,
+    {
+        MetadataDataset::new(self, metadata)
+    }
+
+    /// Attaches a weight to each sample, with the indices of the samples as ids, see [with_metadata](MapDataset::with_metadata).
+    fn with_weights(self, weights: &[f64]) -> MetadataDataset<Self>
+    where
+        Self: Sized,
+    {
+        MetadataDataset::from_weights(self, weights)
+    }
+
+    /// Creates a [MapDataLoader], which reads and collates the batches of the dataset, on worker threads if `cfg.num_workers` is positive.
+    fn into_loader_with<C>(self, cfg: DataLoaderConfig, collate: C) -> MapDataLoader<Self, C>
+    where
+        Self: Sized,
+        C: Collate<Self::SampleType>,
+    {
+        MapDataLoader::new(self, cfg, collate)
+    }
+}
+
+/// A [MapDataset] whose samples are mapped lazily, see [MapDataset::map_samples].
+pub struct MappedDataset<D, F> {
+    pub dataset: D,
+    f: Mutex<F>,
+}
+
+impl<D, F, T> MapDataset for MappedDataset<D, F>
+where
+    D: MapDataset,
+    T: Send + 'static,
+    F: DatasetSampleMapping<D::SampleType, T>,
+{
+    type SampleType = T;
+
+    fn get(&self, index: usize) -> T {
+        let mut f = self.f.lock().clone();
+        f(self.dataset.get(index))
+    }
+
+    fn len(&self) -> usize {
+        self.dataset.len()
+    }
+}
+
+/// An [IterableDataset] over a [MapDataset], see [MapDataset::into_iterable].
+#[derive(Debug)]
+pub struct MapDatasetIter<D: MapDataset> {
+    pub dataset: D,
+    pub sampler: Option<Arc<dyn Sampler>>,
+    indices: Vec<usize>,
+    position: usize,
+}
+
+impl<D: MapDataset> MapDatasetIter<D> {
+    pub fn new(dataset: D, sampler: Option<Arc<dyn Sampler>>) -> Self {
+        let indices = match &sampler {
+            Some(sampler) => sampler.indices(dataset.len()),
+            None => (0..dataset.len()).collect(),
+        };
+        Self {
+            dataset,
+            sampler,
+            indices,
+            position: 0,
+        }
+    }
+}
+
+impl<D: MapDataset + Clone> Clone for MapDatasetIter<D> {
+    /// Starts a new pass over the dataset, with new indices from the sampler.
+    fn clone(&self) -> Self {
+        Self::new(self.dataset.clone(), self.sampler.clone())
+    }
+}
+
+impl<D: MapDataset> IterableDataset for MapDatasetIter<D> {
+    type SampleType = D::SampleType;
+
+    fn next_sample(&mut self) -> Option<Self::SampleType> {
+        if self.position >= self.indices.len() {
+            let sampler = self.sampler.as_ref()?;
+            self.indices = sampler.indices(self.dataset.len());
+            self.position = 0;
+            if self.indices.is_empty() {
+                return None;
+            }
+        }
+        let sample = self.dataset.get(self.indices[self.position]);
+        self.position += 1;
+        Some(sample)
+    }
+}
+
+impl<D: MapDataset + ?Sized> MapDataset for Box<D> {
+    type SampleType = D::SampleType;
+
+    fn get(&self, index: usize) -> Self::SampleType {
+        (**self).get(index)
+    }
+
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+}
+
+impl<D: MapDataset + ?Sized> MapDataset for Arc<D> {
+    type SampleType = D::SampleType;
+
+    fn get(&self, index: usize) -> Self::SampleType {
+        (**self).get(index)
+    }
+
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+}
+
+impl MapDataset for TensorDataset {
+    type SampleType = (Arc<Tensor>, Arc<Tensor>);
+
+    fn get(&self, index: usize) -> Self::SampleType {
+        (self.inputs[index].clone(), self.labels[index].clone())
+    }
+
+    fn len(&self) -> usize {
+        self.inputs.len()
+    }
+}
+
+impl MapDataset for UnsupervisedTensorDataset {
+    type SampleType = Arc<Tensor>;
+
+    fn get(&self, index: usize) -> Self::SampleType {
+        self.inputs[index].clone()
+    }
+
+    fn len(&self) -> usize {
+        self.inputs.len()
+    }
+}
+
+impl<T: Send + Sync + 'static, U: Send + Sync + 'static> MapDataset for SimpleDataset<T, U> {
+    type SampleType = (Arc<T>, Arc<U>);
+
+    fn get(&self, index: usize) -> Self::SampleType {
+        (self.inputs[index].clone(), self.labels[index].clone())
+    }
+
+    fn len(&self) -> usize {
+        self.inputs.len()
+    }
+}
+
+impl<T: Send + Sync + 'static> MapDataset for UnsupervisedDataset<T> {
+    type SampleType = Arc<T>;
+
+    fn get(&self, index: usize) -> Self::SampleType {
+        self.inputs[index].clone()
+    }
+
+    fn len(&self) -> usize {
+        self.inputs.len()
+    }
+}
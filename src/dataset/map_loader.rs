@@ -0,0 +1,157 @@
+use std::{
+    cmp::min,
+    sync::{atomic::AtomicU64, Arc},
+};
+
+use rand::seq::SliceRandom;
+
+use super::{sampler::epoch_rng, Collate, DataLoaderConfig, MapDataset, Prefetcher};
+
+/// A data loader over a [MapDataset], which reads the samples by index when their batch is requested.
+///
+/// This is the counterpart of [DataLoader](super::DataLoader) for datasets which do not hold their samples in memory: with `num_workers` workers, the samples are read (e.g. decoded from disk) and collated in the background. All options of [DataLoaderConfig] are supported, and cloning the loader starts a new epoch.
+pub struct MapDataLoader<D: MapDataset, C: Collate<D::SampleType>> {
+    dataset: Arc<D>,
+    pub collate: C,
+    pub cfg: DataLoaderConfig,
+    /// The indices of the samples of the current epoch, in order.
+    pub indices: Vec<usize>,
+    pub index: usize,
+    /// The number of shuffles so far, shared by all clones of the loader, so that each epoch gets a different shuffle.
+    epoch: Arc<AtomicU64>,
+    prefetcher: Option<Prefetcher<C::Batch>>,
+}
+
+impl<D: MapDataset, C: Collate<D::SampleType>> MapDataLoader<D, C> {
+    pub fn new(dataset: D, cfg: DataLoaderConfig, collate: C) -> Self {
+        let mut this = Self {
+            dataset: Arc::new(dataset),
+            collate,
+            cfg,
+            indices: Vec::new(),
+            index: 0,
+            epoch: Arc::new(AtomicU64::new(0)),
+            prefetcher: None,
+        };
+        this.resample();
+        this
+    }
+
+    /// Draws the indices of a new epoch, with the sampler if there is one, or else by shuffling the indices if `shuffle` is set.
+    fn resample(&mut self) {
+        if let Some(sampler) = &self.cfg.sampler {
+            self.indices = sampler.indices(self.dataset.len());
+        } else {
+            self.indices = (0..self.dataset.len()).collect();
+            if self.cfg.shuffle {
+                let mut rng = epoch_rng(self.cfg.seed, &self.epoch);
+                self.indices.shuffle(&mut rng);
+            }
+        }
+    }
+
+    /// Returns the underlying dataset.
+    pub fn dataset(&self) -> &D {
+        &self.dataset
+    }
+
+    /// Returns the number of batches in an epoch.
+    pub fn num_batches(&self) -> usize {
+        let batch_size = self.cfg.batch_size.max(1);
+        if self.cfg.drop_last {
+            self.indices.len() / batch_size
+        } else {
+            (self.indices.len() + batch_size - 1) / batch_size
+        }
+    }
+
+    /// Returns the indices of the next batch, or `None` at the end of the epoch.
+    fn next_indices(&mut self) -> Option<Vec<usize>> {
+        let batch_size = min(self.cfg.batch_size, self.indices.len() - self.index);
+        if batch_size == 0 || (self.cfg.drop_last && batch_size < self.cfg.batch_size) {
+            return None;
+        }
+        let indices = self.indices[self.index..self.index + batch_size].to_vec();
+        self.index += batch_size;
+        Some(indices)
+    }
+}
+
+impl<D, C> MapDataLoader<D, C>
+where
+    D: MapDataset + 'static,
+    C: Collate<D::SampleType> + Send + 'static,
+    C::Batch: Send + 'static,
+{
+    /// Starts the workers over the remaining batches of the epoch.
+    fn start_prefetcher(&mut self) {
+        let mut chunks = Vec::new();
+        while let Some(indices) = self.next_indices() {
+            chunks.push(indices);
+        }
+        let dataset = self.dataset.clone();
+        let collate = self.collate.clone();
+        self.prefetcher = Some(Prefetcher::new(
+            chunks,
+            self.cfg.num_workers,
+            self.cfg.prefetch_factor,
+            move |indices: Vec<usize>| {
+                collate.collate(
+                    indices
+                        .into_iter()
+                        .map(|index| dataset.get(index))
+                        .collect(),
+                )
+            },
+        ));
+    }
+}
+
+impl<D: MapDataset, C: Collate<D::SampleType>> Clone for MapDataLoader<D, C> {
+    /// Starts a new epoch over the same dataset.
+    fn clone(&self) -> Self {
+        let mut that = Self {
+            dataset: self.dataset.clone(),
+            collate: self.collate.clone(),
+            cfg: self.cfg.clone(),
+            indices: Vec::new(),
+            index: 0,
+            epoch: self.epoch.clone(),
+            prefetcher: None,
+        };
+        that.resample();
+        that
+    }
+}
+
+impl<D, C> Iterator for MapDataLoader<D, C>
+where
+    D: MapDataset + 'static,
+    C: Collate<D::SampleType> + Send + 'static,
+    C::Batch: Send + 'static,
+{
+    type Item = C::Batch;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.cfg.num_workers > 0 {
+            if self.prefetcher.is_none() {
+                self.start_prefetcher();
+            }
+            return self
+                .prefetcher
+                .as_mut()
+                .unwrap()
+                .next()
+                .map(|(batch, _)| batch);
+        }
+        let indices = self.next_indices()?;
+        Some(
+            self.collate.collate(
+                indices
+                    .into_iter()
+                    .map(|index| self.dataset.get(index))
+                    .collect(),
+            ),
+        )
+    }
+}
@@ -0,0 +1,167 @@
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use tch::{Device, Tensor};
+
+use super::{Collate, DatasetSampleMapping, MapDataset, ToDevice};
+
+/// Metadata of a sample, such as its id in the dataset, the file it was loaded from, or its weight in the loss.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleMetadata {
+    pub id: usize,
+    pub path: Option<PathBuf>,
+    /// The weight of the sample, 1 by default.
+    pub weight: f64,
+    /// Any other annotations, e.g. the source or the split of the sample.
+    pub extra: HashMap<String, String>,
+}
+
+impl SampleMetadata {
+    pub fn new(id: usize) -> Self {
+        Self {
+            id,
+            path: None,
+            weight: 1.,
+            extra: HashMap::new(),
+        }
+    }
+
+    /// Set the path of the sample.
+    pub fn path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Set the weight of the sample.
+    pub fn weight(mut self, weight: f64) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// Add an annotation to the sample.
+    pub fn with<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.extra.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// A sample carrying its [SampleMetadata].
+///
+/// The metadata is shared, so cloning the sample, mapping it with [on_sample] and collating it with [MetadataCollate] never copies it.
+#[derive(Debug, Clone)]
+pub struct WithMetadata<S> {
+    pub sample: S,
+    pub metadata: Arc<SampleMetadata>,
+}
+
+/// A [MapDataset] whose samples carry metadata, see [MapDataset::with_metadata].
+#[derive(Debug)]
+pub struct MetadataDataset<D> {
+    pub dataset: D,
+    pub metadata: Vec<Arc<SampleMetadata>>,
+}
+
+impl<D: MapDataset> MetadataDataset<D> {
+    /// Attaches `metadata[i]` to the `i`-th sample of `dataset`.
+    pub fn new(dataset: D, metadata: Vec<SampleMetadata>) -> Self {
+        assert_eq!(
+            dataset.len(),
+            metadata.len(),
+            "The number of samples and metadata must be the same."
+        );
+        Self {
+            dataset,
+            metadata: metadata.into_iter().map(Arc::new).collect(),
+        }
+    }
+
+    /// Attaches the weights to the samples, with their indices as ids.
+    pub fn from_weights(dataset: D, weights: &[f64]) -> Self {
+        let metadata = weights
+            .iter()
+            .enumerate()
+            .map(|(id, &weight)| SampleMetadata::new(id).weight(weight))
+            .collect();
+        Self::new(dataset, metadata)
+    }
+}
+
+impl<D: MapDataset> MapDataset for MetadataDataset<D> {
+    type SampleType = WithMetadata<D::SampleType>;
+
+    fn get(&self, index: usize) -> Self::SampleType {
+        WithMetadata {
+            sample: self.dataset.get(index),
+            metadata: self.metadata[index].clone(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.dataset.len()
+    }
+}
+
+/// Applies a mapping to a sample carrying metadata, leaving the metadata unchanged.
+pub fn on_sample<S, T, F>(
+    mut mapping: F,
+) -> impl DatasetSampleMapping<WithMetadata<S>, WithMetadata<T>>
+where
+    F: DatasetSampleMapping<S, T>,
+{
+    move |sample: WithMetadata<S>| WithMetadata {
+        sample: mapping(sample.sample),
+        metadata: sample.metadata,
+    }
+}
+
+/// A batch collated from samples carrying metadata.
+#[derive(Debug)]
+pub struct MetadataBatch<B> {
+    pub batch: B,
+    /// The weights of the samples, in shape `[batch]`.
+    pub weights: Tensor,
+    pub metadata: Vec<Arc<SampleMetadata>>,
+}
+
+impl<B> MetadataBatch<B> {
+    /// Returns the ids of the samples, e.g. to look up the worst predicted samples.
+    pub fn ids(&self) -> Vec<usize> {
+        self.metadata.iter().map(|metadata| metadata.id).collect()
+    }
+
+    /// Averages a per-sample loss in shape `[batch]` with the weights of the samples.
+    pub fn weighted_mean(&self, loss: &Tensor) -> Tensor {
+        let weights = self.weights.to_kind(loss.kind()).to_device(loss.device());
+        (loss * &weights).sum(loss.kind()) / weights.sum(loss.kind())
+    }
+}
+
+impl<B: ToDevice> ToDevice for MetadataBatch<B> {
+    fn to_device(self, device: Device) -> Self {
+        MetadataBatch {
+            batch: self.batch.to_device(device),
+            weights: self.weights.to_device(device),
+            metadata: self.metadata,
+        }
+    }
+}
+
+/// Collates samples carrying metadata, collating the samples themselves with the inner [Collate].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetadataCollate<C>(pub C);
+
+impl<S, C: Collate<S>> Collate<WithMetadata<S>> for MetadataCollate<C> {
+    type Batch = MetadataBatch<C::Batch>;
+
+    fn collate(&self, samples: Vec<WithMetadata<S>>) -> Self::Batch {
+        let (samples, metadata): (Vec<_>, Vec<_>) = samples
+            .into_iter()
+            .map(|sample| (sample.sample, sample.metadata))
+            .unzip();
+        let weights: Vec<f64> = metadata.iter().map(|metadata| metadata.weight).collect();
+        MetadataBatch {
+            batch: self.0.collate(samples),
+            weights: Tensor::of_slice(&weights),
+            metadata,
+        }
+    }
+}
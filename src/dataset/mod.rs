@@ -1,9 +1,50 @@
+pub use cached_dataset::*;
+pub use collate::*;
+pub use concat_dataset::*;
+pub use csv_dataset::*;
 pub use dataset::*;
+pub use device_prefetch::*;
 pub use tensor_dataset::*;
 pub use load_external::*;
 pub use image_dataset::*;
+#[cfg(feature = "hdf5")]
+pub use hdf5_dataset::*;
+pub use iterable_dataset::*;
+pub use kfold::*;
+pub use map_dataset::*;
+pub use map_loader::*;
+pub use metadata::*;
+pub use npz_dataset::*;
+pub use parquet_dataset::*;
+pub use prefetch::*;
+pub use repeat_dataset::*;
+pub use sampler::*;
+pub use stacked_dataset::*;
+pub use subset::*;
+pub use transforms::*;
 
+pub mod cached_dataset;
+pub mod collate;
+pub mod concat_dataset;
+pub mod csv_dataset;
 pub mod dataset;
+pub mod device_prefetch;
 pub mod tensor_dataset;
 pub mod load_external;
-pub mod image_dataset;
\ No newline at end of file
+pub mod image_dataset;
+#[cfg(feature = "hdf5")]
+pub mod hdf5_dataset;
+pub mod iterable_dataset;
+pub mod kfold;
+pub mod map_dataset;
+pub mod map_loader;
+pub mod metadata;
+pub mod npz_dataset;
+pub mod parquet_dataset;
+pub mod prefetch;
+pub mod repeat_dataset;
+pub mod sampler;
+pub mod stacked_dataset;
+pub mod subset;
+pub mod vision;
+pub mod transforms;
\ No newline at end of file
@@ -0,0 +1,89 @@
+use std::{path::Path, sync::Arc};
+
+use anyhow::Context;
+use tch::Tensor;
+
+use super::{TensorDataset, UnsupervisedTensorDataset};
+
+/// Reads the array named `key` from a .npz file.
+fn read_npz_array<P: AsRef<Path>>(path: P, key: &str) -> anyhow::Result<Tensor> {
+    let path = path.as_ref();
+    Tensor::read_npz(path)?
+        .into_iter()
+        .find(|(name, _)| name == key || name.strip_suffix(".npy") == Some(key))
+        .map(|(_, tensor)| tensor)
+        .with_context(|| format!("Array {} not found in {}", key, path.display()))
+}
+
+/// Splits an array along its first dimension into samples.
+fn samples(array: Tensor) -> Vec<Arc<Tensor>> {
+    array.unbind(0).into_iter().map(Arc::new).collect()
+}
+
+fn stack(samples: &[Arc<Tensor>]) -> Tensor {
+    Tensor::stack(samples, 0)
+}
+
+impl TensorDataset {
+    /// Loads a dataset from the arrays `inputs_key` and `labels_key` of a .npz file, e.g. one saved by `numpy.savez("data.npz", x=inputs, y=labels)`.
+    ///
+    /// The first dimension of each array indexes the samples.
+    pub fn from_npz<P: AsRef<Path>>(
+        path: P,
+        inputs_key: &str,
+        labels_key: &str,
+    ) -> anyhow::Result<Self> {
+        let inputs = read_npz_array(&path, inputs_key)?;
+        let labels = read_npz_array(&path, labels_key)?;
+        Ok(Self::from_tensors(samples(inputs), samples(labels)))
+    }
+
+    /// Loads a dataset from two .npy files of inputs and labels.
+    ///
+    /// The first dimension of each array indexes the samples.
+    pub fn from_npy<P: AsRef<Path>, Q: AsRef<Path>>(
+        inputs_path: P,
+        labels_path: Q,
+    ) -> anyhow::Result<Self> {
+        let inputs = Tensor::read_npy(inputs_path)?;
+        let labels = Tensor::read_npy(labels_path)?;
+        Ok(Self::from_tensors(samples(inputs), samples(labels)))
+    }
+
+    /// Saves the inputs and labels as the arrays `inputs_key` and `labels_key` of a .npz file, which can be loaded by `from_npz` or `numpy.load`.
+    pub fn save_npz<P: AsRef<Path>>(
+        &self,
+        path: P,
+        inputs_key: &str,
+        labels_key: &str,
+    ) -> anyhow::Result<()> {
+        Ok(Tensor::write_npz(
+            &[
+                (inputs_key, stack(&self.inputs)),
+                (labels_key, stack(&self.labels)),
+            ],
+            path,
+        )?)
+    }
+}
+
+impl UnsupervisedTensorDataset {
+    /// Loads a dataset from the array `key` of a .npz file.
+    ///
+    /// The first dimension of the array indexes the samples.
+    pub fn from_npz<P: AsRef<Path>>(path: P, key: &str) -> anyhow::Result<Self> {
+        Ok(Self::from_tensors(samples(read_npz_array(path, key)?)))
+    }
+
+    /// Loads a dataset from a .npy file.
+    ///
+    /// The first dimension of the array indexes the samples.
+    pub fn from_npy<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Ok(Self::from_tensors(samples(Tensor::read_npy(path)?)))
+    }
+
+    /// Saves the inputs as the array `key` of a .npz file.
+    pub fn save_npz<P: AsRef<Path>>(&self, path: P, key: &str) -> anyhow::Result<()> {
+        Ok(Tensor::write_npz(&[(key, stack(&self.inputs))], path)?)
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use arrow::{
+    array::{Array, ArrayRef, FixedSizeListArray, Float64Array},
+    compute::cast,
+    datatypes::DataType,
+    record_batch::RecordBatch,
+};
+use derive_builder::Builder;
+use parquet::arrow::{
+    arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder},
+    ProjectionMask,
+};
+use tch::{Kind, Tensor};
+
+use super::{TensorDataset, UnsupervisedTensorDataset};
+
+/// The configuration for reading a [ParquetDataset].
+#[derive(Debug, Clone, Builder)]
+#[builder(pattern = "owned")]
+pub struct ParquetDatasetConfig {
+    /// The columns used as features, in order. If `None`, all columns except the target column are used.
+    #[builder(default = "None")]
+    pub feature_columns: Option<Vec<String>>,
+
+    /// The column used as the label. If `None`, the dataset is unlabelled.
+    #[builder(default = "None")]
+    pub target_column: Option<String>,
+
+    /// The number of rows read at once.
+    #[builder(default = "1024")]
+    pub batch_size: usize,
+
+    /// The kind of the feature tensors.
+    #[builder(default = "Kind::Double")]
+    pub feature_kind: Kind,
+
+    /// The kind of the label tensors. Use `Kind::Int64` for classification.
+    #[builder(default = "Kind::Double")]
+    pub target_kind: Kind,
+}
+
+/// A tabular dataset backed by one or more Parquet files.
+///
+/// The files are read lazily, column by column, so that datasets larger than the memory can be iterated with [ParquetDataset::loader]. Numeric columns become one feature each, and fixed-size list columns (e.g. embeddings) become as many features as their length. Null values are read as NaN.
+///
+/// # Examples
+/// ```
+/// let dataset = ParquetDataset::from_path(
+///     "data/embeddings",
+///     ParquetDatasetConfigBuilder::default()
+///         .feature_columns(Some(vec!["embedding".to_string()]))
+///         .target_column(Some("label".to_string()))
+///         .target_kind(Kind::Int64)
+///         .batch_size(256)
+///         .build()
+///         .unwrap(),
+/// )?;
+/// trainer.fit(dataset.loader(), ...);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ParquetDataset {
+    /// The Parquet files, read in order.
+    pub paths: Vec<PathBuf>,
+    pub config: ParquetDatasetConfig,
+}
+
+impl ParquetDataset {
+    /// Opens the Parquet file at `path`, or all `.parquet` files in it if it is a directory.
+    pub fn from_path<P: AsRef<Path>>(
+        path: P,
+        config: ParquetDatasetConfig,
+    ) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let paths = if path.is_dir() {
+            let mut paths: Vec<PathBuf> = std::fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<_, _>>()?;
+            paths.retain(|path| path.extension().map_or(false, |ext| ext == "parquet"));
+            paths.sort();
+            paths
+        } else {
+            vec![path.to_path_buf()]
+        };
+        let dataset = Self { paths, config };
+        // Check the columns early, rather than on the first read.
+        for path in &dataset.paths {
+            dataset.open(path)?;
+        }
+        Ok(dataset)
+    }
+
+    /// Returns the total number of rows, read from the metadata of the files.
+    pub fn num_rows(&self) -> anyhow::Result<usize> {
+        let mut rows = 0;
+        for path in &self.paths {
+            let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
+            rows += builder.metadata().file_metadata().num_rows() as usize;
+        }
+        Ok(rows)
+    }
+
+    /// Returns an iterator over the batches of features and labels, which reads the files lazily.
+    ///
+    /// Batches have `batch_size` rows, except for the last batch of each file. Cloning the loader restarts from the first file, so it can be passed to the [Trainer](crate::train::Trainer) directly.
+    ///
+    /// # Panics
+    /// Panics if the dataset has no target column.
+    pub fn loader(&self) -> ParquetLoader {
+        assert!(
+            self.config.target_column.is_some(),
+            "The Parquet dataset has no target column."
+        );
+        ParquetLoader {
+            dataset: Arc::new(self.clone()),
+            file: 0,
+            reader: None,
+        }
+    }
+
+    /// Reads all rows into a `TensorDataset`.
+    pub fn load(&self) -> anyhow::Result<TensorDataset> {
+        let mut inputs = Vec::new();
+        let mut labels = Vec::new();
+        for path in &self.paths {
+            for batch in self.open(path)? {
+                let (features, targets) = self.convert(&batch?)?;
+                let targets = targets.context("The Parquet dataset has no target column.")?;
+                inputs.extend(features.unbind(0).into_iter().map(Arc::new));
+                labels.extend(targets.unbind(0).into_iter().map(Arc::new));
+            }
+        }
+        Ok(TensorDataset { inputs, labels })
+    }
+
+    /// Reads the features of all rows into an `UnsupervisedTensorDataset`.
+    pub fn load_unsupervised(&self) -> anyhow::Result<UnsupervisedTensorDataset> {
+        let mut inputs = Vec::new();
+        for path in &self.paths {
+            for batch in self.open(path)? {
+                let (features, _) = self.convert(&batch?)?;
+                inputs.extend(features.unbind(0).into_iter().map(Arc::new));
+            }
+        }
+        Ok(UnsupervisedTensorDataset { inputs })
+    }
+
+    fn feature_columns(&self, columns: &[String]) -> Vec<String> {
+        match &self.config.feature_columns {
+            Some(feature_columns) => feature_columns.clone(),
+            None => columns
+                .iter()
+                .filter(|column| Some(*column) != self.config.target_column.as_ref())
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Opens a file, reading only the feature and target columns.
+    fn open(&self, path: &Path) -> anyhow::Result<ParquetRecordBatchReader> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+        let schema = builder.schema().clone();
+        let columns: Vec<String> = schema
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect();
+        let mut wanted = self.feature_columns(&columns);
+        wanted.extend(self.config.target_column.clone());
+        let indices = wanted
+            .iter()
+            .map(|column| {
+                schema
+                    .index_of(column)
+                    .with_context(|| format!("Column {} not found in {}", column, path.display()))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mask = ProjectionMask::roots(builder.parquet_schema(), indices);
+        Ok(builder
+            .with_projection(mask)
+            .with_batch_size(self.config.batch_size)
+            .build()?)
+    }
+
+    /// Converts a record batch into a tensor of features of shape `[rows, features]`, and a tensor of labels of shape `[rows]` if there is a target column.
+    fn convert(&self, batch: &RecordBatch) -> anyhow::Result<(Tensor, Option<Tensor>)> {
+        let schema = batch.schema();
+        let columns: Vec<String> = schema
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect();
+        let features = self
+            .feature_columns(&columns)
+            .iter()
+            .map(|column| feature_tensor(batch.column(schema.index_of(column)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let features = if features.is_empty() {
+            Tensor::zeros(
+                &[batch.num_rows() as i64, 0],
+                (Kind::Double, tch::Device::Cpu),
+            )
+        } else {
+            Tensor::cat(&features, 1)
+        };
+        let targets = match &self.config.target_column {
+            Some(column) => Some(
+                numeric_tensor(batch.column(schema.index_of(column)?))?
+                    .to_kind(self.config.target_kind),
+            ),
+            None => None,
+        };
+        Ok((features.to_kind(self.config.feature_kind), targets))
+    }
+}
+
+/// Converts a column into a tensor of shape `[rows, width]`.
+fn feature_tensor(array: &ArrayRef) -> anyhow::Result<Tensor> {
+    match array.data_type() {
+        DataType::FixedSizeList(_, width) => {
+            let list = array.as_any().downcast_ref::<FixedSizeListArray>().unwrap();
+            let values = list.values().slice(
+                list.offset() * *width as usize,
+                list.len() * *width as usize,
+            );
+            Ok(numeric_tensor(&values)?.reshape(&[list.len() as i64, *width as i64]))
+        }
+        _ => Ok(numeric_tensor(array)?.unsqueeze(1)),
+    }
+}
+
+/// Converts a numeric column into a 1-dimensional `Double` tensor, with nulls read as NaN.
+fn numeric_tensor(array: &ArrayRef) -> anyhow::Result<Tensor> {
+    let array = cast(array, &DataType::Float64)
+        .with_context(|| format!("Column of type {} is not numeric", array.data_type()))?;
+    let array = array.as_any().downcast_ref::<Float64Array>().unwrap();
+    let values: Vec<f64> = if array.null_count() == 0 {
+        array.values().to_vec()
+    } else {
+        array
+            .iter()
+            .map(|value| value.unwrap_or(f64::NAN))
+            .collect()
+    };
+    Ok(Tensor::of_slice(&values))
+}
+
+/// An iterator over the batches of a [ParquetDataset], see [ParquetDataset::loader].
+pub struct ParquetLoader {
+    dataset: Arc<ParquetDataset>,
+    file: usize,
+    reader: Option<ParquetRecordBatchReader>,
+}
+
+impl Clone for ParquetLoader {
+    /// Starts a new epoch from the first file.
+    fn clone(&self) -> Self {
+        Self {
+            dataset: self.dataset.clone(),
+            file: 0,
+            reader: None,
+        }
+    }
+}
+
+impl Iterator for ParquetLoader {
+    type Item = (Tensor, Tensor);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.reader.is_none() {
+                let path = self.dataset.paths.get(self.file)?;
+                self.reader =
+                    Some(self.dataset.open(path).unwrap_or_else(|err| {
+                        panic!("Failed to read {}: {}", path.display(), err)
+                    }));
+            }
+            match self.reader.as_mut().unwrap().next() {
+                Some(batch) => {
+                    let batch = batch.expect("Failed to read a record batch");
+                    let (features, targets) = self
+                        .dataset
+                        .convert(&batch)
+                        .expect("Failed to convert a record batch");
+                    return Some((features, targets.unwrap()));
+                }
+                None => {
+                    self.reader = None;
+                    self.file += 1;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    sync::mpsc::{sync_channel, Receiver},
+    thread,
+};
+
+/// Collates batches on worker threads, and keeps a bounded number of batches ready ahead of the consumer.
+///
+/// Batches are assigned to the workers round-robin and are received in the same order, so the order of the batches is preserved.
+/// When the prefetcher is dropped, the workers stop after their current batch.
+pub struct Prefetcher<B: Send + 'static> {
+    receivers: Vec<Receiver<B>>,
+    sizes: Vec<usize>,
+    next: usize,
+}
+
+impl<B: Send + 'static> Prefetcher<B> {
+    /// Spawns `num_workers` threads, which apply `collate` to the `chunks` of samples. Each worker keeps at most `prefetch_factor` batches ready.
+    pub fn new<S, F>(
+        chunks: Vec<Vec<S>>,
+        num_workers: usize,
+        prefetch_factor: usize,
+        collate: F,
+    ) -> Self
+    where
+        S: Send + 'static,
+        F: Fn(Vec<S>) -> B + Send + Clone + 'static,
+    {
+        let num_workers = num_workers.max(1);
+        let sizes = chunks.iter().map(Vec::len).collect();
+        let mut assignments: Vec<Vec<Vec<S>>> = (0..num_workers).map(|_| Vec::new()).collect();
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            assignments[i % num_workers].push(chunk);
+        }
+        let receivers = assignments
+            .into_iter()
+            .map(|chunks| {
+                let (sender, receiver) = sync_channel(prefetch_factor.max(1));
+                let collate = collate.clone();
+                thread::spawn(move || {
+                    for chunk in chunks {
+                        if sender.send(collate(chunk)).is_err() {
+                            break;
+                        }
+                    }
+                });
+                receiver
+            })
+            .collect();
+        Self {
+            receivers,
+            sizes,
+            next: 0,
+        }
+    }
+
+    /// Receives the next batch and its number of samples, blocking until it is ready.
+    pub fn next(&mut self) -> Option<(B, usize)> {
+        let size = *self.sizes.get(self.next)?;
+        let batch = self.receivers[self.next % self.receivers.len()]
+            .recv()
+            .expect("A data loader worker panicked");
+        self.next += 1;
+        Some((batch, size))
+    }
+}
+
+impl<B: Send + 'static> std::fmt::Debug for Prefetcher<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Prefetcher")
+            .field("workers", &self.receivers.len())
+            .field("batches", &self.sizes.len())
+            .field("next", &self.next)
+            .finish()
+    }
+}
@@ -0,0 +1,123 @@
+use super::{IterableDataset, MapDataset};
+
+/// An [IterableDataset] reading another iterable dataset several times, or forever, see [IterableDataset::repeat].
+///
+/// Each pass reads a fresh clone of the dataset, so that e.g. a [MapDatasetIter](super::MapDatasetIter) over an `Arc` of a map-style dataset visits the indices of a new epoch of its sampler for every pass.
+///
+/// # Examples
+/// Training for a fixed number of steps rather than epochs, with an epoch of the [Trainer](crate::train::Trainer) every 1000 steps:
+/// ```
+/// let loader = Arc::new(dataset)
+///     .into_iterable(None)
+///     .repeat(None)
+///     .into_loader_with(
+///         DataLoaderConfigBuilder::default().batch_size(32).build().unwrap(),
+///         StackCollate,
+///     )
+///     .batches_per_epoch(1000);
+/// ```
+pub struct RepeatDataset<D: IterableDataset + Clone> {
+    pub dataset: D,
+    /// The number of passes over the dataset, or `None` to repeat it forever.
+    pub times: Option<usize>,
+    current: Option<D>,
+    passes: usize,
+}
+
+impl<D: IterableDataset + Clone> RepeatDataset<D> {
+    pub fn new(dataset: D, times: Option<usize>) -> Self {
+        Self {
+            dataset,
+            times,
+            current: None,
+            passes: 0,
+        }
+    }
+}
+
+impl<D: IterableDataset + Clone> IterableDataset for RepeatDataset<D> {
+    type SampleType = D::SampleType;
+
+    fn next_sample(&mut self) -> Option<Self::SampleType> {
+        if let Some(sample) = self
+            .current
+            .as_mut()
+            .and_then(|dataset| dataset.next_sample())
+        {
+            return Some(sample);
+        }
+        if self.times.map_or(false, |times| self.passes >= times) {
+            return None;
+        }
+        self.passes += 1;
+        let mut dataset = self.dataset.clone();
+        // An empty pass would otherwise be repeated forever.
+        let sample = dataset.next_sample()?;
+        self.current = Some(dataset);
+        Some(sample)
+    }
+}
+
+impl<D: IterableDataset + Clone> Clone for RepeatDataset<D> {
+    /// Starts over from the first pass.
+    fn clone(&self) -> Self {
+        Self::new(self.dataset.clone(), self.times)
+    }
+}
+
+/// A dataset limited to its first `n` samples, see [IterableDataset::take].
+///
+/// As an [IterableDataset], it stops after reading `n` samples. As a [MapDataset], it gives access to the first `n` samples, e.g. for a quick run on a small part of the data.
+pub struct TakeDataset<D> {
+    pub dataset: D,
+    pub n: usize,
+    taken: usize,
+}
+
+impl<D> TakeDataset<D> {
+    pub fn new(dataset: D, n: usize) -> Self {
+        Self {
+            dataset,
+            n,
+            taken: 0,
+        }
+    }
+}
+
+impl<D: IterableDataset> IterableDataset for TakeDataset<D> {
+    type SampleType = D::SampleType;
+
+    fn next_sample(&mut self) -> Option<Self::SampleType> {
+        if self.taken >= self.n {
+            return None;
+        }
+        let sample = self.dataset.next_sample()?;
+        self.taken += 1;
+        Some(sample)
+    }
+}
+
+impl<D: IterableDataset + Clone> Clone for TakeDataset<D> {
+    /// Starts over from the first sample.
+    fn clone(&self) -> Self {
+        Self::new(self.dataset.clone(), self.n)
+    }
+}
+
+impl<D: MapDataset> MapDataset for TakeDataset<D> {
+    type SampleType = D::SampleType;
+
+    fn get(&self, index: usize) -> Self::SampleType {
+        assert!(
+            index < self.len(),
+            "Index {} is out of range for a dataset of {} samples.",
+            index,
+            self.len()
+        );
+        self.dataset.get(index)
+    }
+
+    fn len(&self) -> usize {
+        self.n.min(self.dataset.len())
+    }
+}
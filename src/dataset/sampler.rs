@@ -0,0 +1,349 @@
+use std::{
+    fmt::Debug,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use linked_hash_map::LinkedHashMap;
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    seq::SliceRandom,
+    Rng, SeedableRng,
+};
+
+use crate::util::seeded_rng;
+
+/// Returns the generator of the next epoch. With a seed, the sequence of generators is reproducible; otherwise they are drawn from [seeded_rng].
+pub(crate) fn epoch_rng(seed: Option<u64>, epoch: &AtomicU64) -> StdRng {
+    let epoch = epoch.fetch_add(1, Ordering::SeqCst);
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(epoch)),
+        None => seeded_rng(),
+    }
+}
+
+/// A sampler decides which samples of a dataset are visited in an epoch, and in which order.
+///
+/// A [DataLoader](super::DataLoader) with a sampler asks it for new indices every time the loader is cloned, i.e. at the beginning of every epoch.
+pub trait Sampler: Debug + Send + Sync {
+    /// Returns the indices of the samples of the next epoch, for a dataset with `len` samples.
+    fn indices(&self, len: usize) -> Vec<usize>;
+}
+
+/// Visits all samples in order.
+#[derive(Debug, Clone, Default)]
+pub struct SequentialSampler;
+
+impl Sampler for SequentialSampler {
+    fn indices(&self, len: usize) -> Vec<usize> {
+        (0..len).collect()
+    }
+}
+
+/// Visits the samples in a random order.
+///
+/// Without replacement, every sample is visited once per epoch (truncated to `num_samples` if it is set). With replacement, `num_samples` (by default, the size of the dataset) samples are drawn uniformly.
+#[derive(Debug, Default)]
+pub struct RandomSampler {
+    pub replacement: bool,
+    pub num_samples: Option<usize>,
+    pub seed: Option<u64>,
+    epoch: AtomicU64,
+}
+
+impl RandomSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draw samples with replacement.
+    pub fn replacement(mut self, replacement: bool) -> Self {
+        self.replacement = replacement;
+        self
+    }
+
+    /// Set the number of samples per epoch.
+    pub fn num_samples(mut self, num_samples: usize) -> Self {
+        self.num_samples = Some(num_samples);
+        self
+    }
+
+    /// Make the sequence of epochs reproducible.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+impl Sampler for RandomSampler {
+    fn indices(&self, len: usize) -> Vec<usize> {
+        let mut rng = epoch_rng(self.seed, &self.epoch);
+        let num_samples = self.num_samples.unwrap_or(len);
+        if len == 0 {
+            return Vec::new();
+        }
+        if self.replacement {
+            (0..num_samples).map(|_| rng.gen_range(0..len)).collect()
+        } else {
+            let mut indices: Vec<usize> = (0..len).collect();
+            indices.shuffle(&mut rng);
+            indices.truncate(num_samples);
+            indices
+        }
+    }
+}
+
+/// Draws samples with probabilities proportional to the given weights, e.g. to oversample rare classes.
+///
+/// The weights do not need to sum to one. Without replacement, `num_samples` must not exceed the number of samples with a positive weight.
+#[derive(Debug)]
+pub struct WeightedRandomSampler {
+    pub weights: Vec<f64>,
+    pub num_samples: usize,
+    pub replacement: bool,
+    pub seed: Option<u64>,
+    epoch: AtomicU64,
+}
+
+impl WeightedRandomSampler {
+    /// Creates a sampler drawing `num_samples` samples with replacement.
+    pub fn new(weights: Vec<f64>, num_samples: usize) -> Self {
+        Self {
+            weights,
+            num_samples,
+            replacement: true,
+            seed: None,
+            epoch: AtomicU64::new(0),
+        }
+    }
+
+    /// Draw samples with replacement.
+    pub fn replacement(mut self, replacement: bool) -> Self {
+        self.replacement = replacement;
+        self
+    }
+
+    /// Make the sequence of epochs reproducible.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+impl Sampler for WeightedRandomSampler {
+    fn indices(&self, len: usize) -> Vec<usize> {
+        assert_eq!(
+            self.weights.len(),
+            len,
+            "WeightedRandomSampler has {} weights for {} samples",
+            self.weights.len(),
+            len
+        );
+        let mut rng = epoch_rng(self.seed, &self.epoch);
+        if self.replacement {
+            let distribution = WeightedIndex::new(&self.weights).expect("Invalid sampler weights");
+            return (0..self.num_samples)
+                .map(|_| distribution.sample(&mut rng))
+                .collect();
+        }
+        let mut weights = self.weights.clone();
+        let mut indices = Vec::with_capacity(self.num_samples);
+        for _ in 0..self.num_samples {
+            let distribution = WeightedIndex::new(&weights)
+                .expect("Not enough samples with a positive weight to draw without replacement");
+            let index = distribution.sample(&mut rng);
+            indices.push(index);
+            weights[index] = 0.;
+        }
+        indices
+    }
+}
+
+/// Draws batches with a fixed number of samples from each of a fixed number of classes, e.g. for metric learning or imbalanced datasets.
+///
+/// Every batch consists of `samples_per_class` samples from each of `classes_per_batch` distinct classes, chosen at random, so the batch size of the [DataLoader](super::DataLoader) must be [ClassBalancedSampler::batch_size].
+/// Classes with fewer than `samples_per_class` samples are sampled with replacement.
+///
+/// # Examples
+/// ```
+/// let sampler = ClassBalancedSampler::new(labels, 8, 4);
+/// let loader = dataset.into_loader(
+///     DataLoaderConfigBuilder::default()
+///         .batch_size(sampler.batch_size())
+///         .sampler(Some(Arc::new(sampler)))
+///         .build()
+///         .unwrap(),
+/// );
+/// ```
+#[derive(Debug)]
+pub struct ClassBalancedSampler {
+    /// The indices of the samples of each class.
+    pub classes: Vec<Vec<usize>>,
+    pub classes_per_batch: usize,
+    pub samples_per_class: usize,
+    /// The number of batches per epoch. By default, the number of samples divided by the batch size.
+    pub num_batches: Option<usize>,
+    pub seed: Option<u64>,
+    epoch: AtomicU64,
+}
+
+impl ClassBalancedSampler {
+    /// Creates a sampler from the class label of each sample.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer than `classes_per_batch` classes.
+    pub fn new(labels: Vec<i64>, classes_per_batch: usize, samples_per_class: usize) -> Self {
+        let mut classes: LinkedHashMap<i64, Vec<usize>> = LinkedHashMap::new();
+        for (index, label) in labels.into_iter().enumerate() {
+            classes.entry(label).or_insert_with(Vec::new).push(index);
+        }
+        assert!(
+            classes.len() >= classes_per_batch,
+            "ClassBalancedSampler needs {} classes per batch, but there are only {} classes",
+            classes_per_batch,
+            classes.len()
+        );
+        Self {
+            classes: classes.into_iter().map(|(_, indices)| indices).collect(),
+            classes_per_batch,
+            samples_per_class,
+            num_batches: None,
+            seed: None,
+            epoch: AtomicU64::new(0),
+        }
+    }
+
+    /// Set the number of batches per epoch.
+    pub fn num_batches(mut self, num_batches: usize) -> Self {
+        self.num_batches = Some(num_batches);
+        self
+    }
+
+    /// Make the sequence of epochs reproducible.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// The number of samples in a batch.
+    pub fn batch_size(&self) -> usize {
+        self.classes_per_batch * self.samples_per_class
+    }
+}
+
+impl Sampler for ClassBalancedSampler {
+    fn indices(&self, len: usize) -> Vec<usize> {
+        let mut rng = epoch_rng(self.seed, &self.epoch);
+        let num_batches = self.num_batches.unwrap_or(len / self.batch_size().max(1));
+        let mut indices = Vec::with_capacity(num_batches * self.batch_size());
+        for _ in 0..num_batches {
+            for class in self
+                .classes
+                .choose_multiple(&mut rng, self.classes_per_batch)
+            {
+                if class.len() >= self.samples_per_class {
+                    indices.extend(class.choose_multiple(&mut rng, self.samples_per_class));
+                } else {
+                    indices.extend(
+                        (0..self.samples_per_class).map(|_| class[rng.gen_range(0..class.len())]),
+                    );
+                }
+            }
+        }
+        indices
+    }
+}
+
+/// Groups samples of similar length into the same batches, to minimize the padding of variable-length sequences, e.g. with a [PadCollate](super::PadCollate).
+///
+/// Every epoch, the samples are shuffled and split into pools of `batch_size * pool_batches` samples. Each pool is sorted by length and cut into batches, and the order of the batches is shuffled. Only the last batch can be smaller than `batch_size`, so the batch size of the [DataLoader](super::DataLoader) must be the same as that of the sampler.
+///
+/// # Examples
+/// ```
+/// let lengths = sequences.iter().map(|sequence| sequence.size()[0] as usize).collect();
+/// let sampler = LengthBucketSampler::new(lengths, 32);
+/// let loader = dataset
+///     .into_loader(
+///         DataLoaderConfigBuilder::default()
+///             .batch_size(sampler.batch_size)
+///             .sampler(Some(Arc::new(sampler)))
+///             .build()
+///             .unwrap(),
+///     )
+///     .collate_with(PadCollate::new(0.));
+/// ```
+#[derive(Debug)]
+pub struct LengthBucketSampler {
+    /// The length of each sample.
+    pub lengths: Vec<usize>,
+    pub batch_size: usize,
+    /// The number of batches in a pool of samples sorted together. Larger pools give less padding but less randomness.
+    pub pool_batches: usize,
+    /// Whether to shuffle the samples and the batches. Without shuffling, all samples are sorted by length.
+    pub shuffle: bool,
+    pub seed: Option<u64>,
+    epoch: AtomicU64,
+}
+
+impl LengthBucketSampler {
+    pub fn new(lengths: Vec<usize>, batch_size: usize) -> Self {
+        Self {
+            lengths,
+            batch_size,
+            pool_batches: 100,
+            shuffle: true,
+            seed: None,
+            epoch: AtomicU64::new(0),
+        }
+    }
+
+    /// Set the number of batches in a pool.
+    pub fn pool_batches(mut self, pool_batches: usize) -> Self {
+        self.pool_batches = pool_batches;
+        self
+    }
+
+    /// Shuffle the samples and the batches.
+    pub fn shuffle(mut self, shuffle: bool) -> Self {
+        self.shuffle = shuffle;
+        self
+    }
+
+    /// Make the sequence of epochs reproducible.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+impl Sampler for LengthBucketSampler {
+    fn indices(&self, len: usize) -> Vec<usize> {
+        assert_eq!(
+            self.lengths.len(),
+            len,
+            "LengthBucketSampler has {} lengths for {} samples",
+            self.lengths.len(),
+            len
+        );
+        let mut indices: Vec<usize> = (0..len).collect();
+        if !self.shuffle {
+            indices.sort_by_key(|&index| self.lengths[index]);
+            return indices;
+        }
+
+        let mut rng = epoch_rng(self.seed, &self.epoch);
+        indices.shuffle(&mut rng);
+        let batch_size = self.batch_size.max(1);
+        for pool in indices.chunks_mut(batch_size * self.pool_batches.max(1)) {
+            pool.sort_by_key(|&index| self.lengths[index]);
+        }
+        let mut batches: Vec<&[usize]> = indices.chunks(batch_size).collect();
+        let last = match batches.last() {
+            Some(batch) if batch.len() < batch_size => batches.pop(),
+            _ => None,
+        };
+        batches.shuffle(&mut rng);
+        batches.into_iter().chain(last).flatten().copied().collect()
+    }
+}
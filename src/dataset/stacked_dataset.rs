@@ -0,0 +1,189 @@
+use std::{
+    cmp::min,
+    sync::{atomic::AtomicU64, Arc},
+};
+
+use rand::seq::SliceRandom;
+use tch::{Device, Tensor};
+
+use super::{sampler::epoch_rng, DataLoaderConfig, MapDataset, TensorDataset};
+
+/// A tensor dataset whose inputs and labels are each stored as one tensor in shape `[num_samples, ...]`.
+///
+/// Unlike [TensorDataset], which keeps one tensor per sample and stacks them for every batch, the batches of a [StackedLoader] are sliced from the stored tensors directly, so loading a batch costs a single `narrow` (or `index_select` when shuffled).
+#[derive(Debug)]
+pub struct StackedTensorDataset {
+    pub inputs: Tensor,
+    pub labels: Tensor,
+}
+
+impl StackedTensorDataset {
+    /// Creates a new `StackedTensorDataset` from the given inputs and labels, whose first dimension indexes the samples.
+    pub fn from_tensors(inputs: Tensor, labels: Tensor) -> Self {
+        assert_eq!(
+            inputs.size()[0],
+            labels.size()[0],
+            "The number of inputs and labels must be the same."
+        );
+        assert_eq!(
+            inputs.device(),
+            labels.device(),
+            "The inputs and labels must be on the same device."
+        );
+        Self { inputs, labels }
+    }
+
+    /// Move the inputs and labels to the specific device.
+    pub fn to(self, device: Device) -> Self {
+        Self {
+            inputs: self.inputs.to(device),
+            labels: self.labels.to(device),
+        }
+    }
+
+    /// Creates a [StackedLoader] iterating over the dataset in batches of `[batch_size, ...]`.
+    ///
+    /// `num_workers` and `prefetch_factor` are ignored, since slicing a batch does not need to be parallelized.
+    pub fn into_loader(self, cfg: DataLoaderConfig) -> StackedLoader {
+        StackedLoader::new(self, cfg)
+    }
+}
+
+impl From<TensorDataset> for StackedTensorDataset {
+    fn from(dataset: TensorDataset) -> Self {
+        Self::from_tensors(
+            Tensor::stack(&dataset.inputs, 0),
+            Tensor::stack(&dataset.labels, 0),
+        )
+    }
+}
+
+impl MapDataset for StackedTensorDataset {
+    type SampleType = (Arc<Tensor>, Arc<Tensor>);
+
+    fn get(&self, index: usize) -> Self::SampleType {
+        let index = index as i64;
+        (
+            Arc::new(self.inputs.get(index)),
+            Arc::new(self.labels.get(index)),
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.inputs.size()[0] as usize
+    }
+}
+
+/// A data loader over a [StackedTensorDataset], yielding `(inputs, labels)` batches in shape `[batch_size, ...]`.
+///
+/// Like [DataLoader](super::DataLoader), it honours the `sampler`, `shuffle`, `drop_last` and `seed` of its configuration, and starts a new epoch every time it is cloned.
+#[derive(Debug)]
+pub struct StackedLoader {
+    dataset: Arc<StackedTensorDataset>,
+    pub cfg: DataLoaderConfig,
+    /// The indices of the samples of the current epoch, or `None` if all samples are visited in order.
+    indices: Option<Tensor>,
+    pub index: usize,
+    /// The number of shuffles so far, shared by all clones of the loader.
+    epoch: Arc<AtomicU64>,
+}
+
+impl StackedLoader {
+    pub fn new(dataset: StackedTensorDataset, cfg: DataLoaderConfig) -> Self {
+        let mut this = Self {
+            dataset: Arc::new(dataset),
+            cfg,
+            indices: None,
+            index: 0,
+            epoch: Arc::new(AtomicU64::new(0)),
+        };
+        this.resample();
+        this
+    }
+
+    fn resample(&mut self) {
+        let len = self.dataset.len();
+        let indices = if let Some(sampler) = &self.cfg.sampler {
+            Some(sampler.indices(len))
+        } else if self.cfg.shuffle {
+            let mut indices = (0..len).collect::<Vec<_>>();
+            indices.shuffle(&mut epoch_rng(self.cfg.seed, &self.epoch));
+            Some(indices)
+        } else {
+            None
+        };
+        self.indices = indices.map(|indices| {
+            let indices = indices.into_iter().map(|i| i as i64).collect::<Vec<_>>();
+            Tensor::of_slice(&indices).to(self.dataset.inputs.device())
+        });
+    }
+
+    /// Returns the dataset the batches are sliced from.
+    pub fn dataset(&self) -> &StackedTensorDataset {
+        &self.dataset
+    }
+
+    /// Returns the number of samples visited in an epoch.
+    fn epoch_len(&self) -> usize {
+        match &self.indices {
+            Some(indices) => indices.size()[0] as usize,
+            None => self.dataset.len(),
+        }
+    }
+
+    /// Returns the number of batches in an epoch.
+    pub fn num_batches(&self) -> usize {
+        let batch_size = self.cfg.batch_size.max(1);
+        if self.cfg.drop_last {
+            self.epoch_len() / batch_size
+        } else {
+            (self.epoch_len() + batch_size - 1) / batch_size
+        }
+    }
+}
+
+impl Clone for StackedLoader {
+    fn clone(&self) -> Self {
+        let mut that = Self {
+            dataset: self.dataset.clone(),
+            cfg: self.cfg.clone(),
+            indices: None,
+            index: self.index,
+            epoch: self.epoch.clone(),
+        };
+        that.resample();
+        that
+    }
+}
+
+impl Iterator for StackedLoader {
+    type Item = (Tensor, Tensor);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = self.epoch_len();
+        if self.index >= len {
+            return None;
+        }
+        if self.cfg.drop_last && len - self.index < self.cfg.batch_size {
+            return None;
+        }
+
+        let batch_size = min(self.cfg.batch_size, len - self.index);
+        let start = self.index as i64;
+        self.index += batch_size;
+        let dataset = &self.dataset;
+        match &self.indices {
+            Some(indices) => {
+                let indices = indices.narrow(0, start, batch_size as i64);
+                Some((
+                    dataset.inputs.index_select(0, &indices),
+                    dataset.labels.index_select(0, &indices),
+                ))
+            }
+            None => Some((
+                dataset.inputs.narrow(0, start, batch_size as i64),
+                dataset.labels.narrow(0, start, batch_size as i64),
+            )),
+        }
+    }
+}
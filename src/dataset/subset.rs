@@ -0,0 +1,79 @@
+use super::MapDataset;
+
+/// A view of the samples of a [MapDataset] at the given indices, in order.
+///
+/// The samples are not copied. To take several subsets of the same dataset, wrap it in an `Arc` first.
+///
+/// # Examples
+/// ```
+/// let dataset = Arc::new(dataset);
+/// let train = Subset::new(dataset.clone(), (0..800).collect());
+/// let val = Subset::new(dataset, (800..1000).collect());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Subset<D: MapDataset> {
+    pub dataset: D,
+    pub indices: Vec<usize>,
+}
+
+impl<D: MapDataset> Subset<D> {
+    pub fn new(dataset: D, indices: Vec<usize>) -> Self {
+        let len = dataset.len();
+        assert!(
+            indices.iter().all(|index| *index < len),
+            "The indices of a subset must be less than the size of the dataset ({}).",
+            len
+        );
+        Self { dataset, indices }
+    }
+}
+
+impl<D: MapDataset> MapDataset for Subset<D> {
+    type SampleType = D::SampleType;
+
+    fn get(&self, index: usize) -> Self::SampleType {
+        self.dataset.get(self.indices[index])
+    }
+
+    fn len(&self) -> usize {
+        self.indices.len()
+    }
+}
+
+/// A view of the samples of a [MapDataset] satisfying a predicate, e.g. on their labels.
+///
+/// The predicate is evaluated once for every sample when the view is created, and only the indices of the kept samples are stored.
+///
+/// # Examples
+/// ```
+/// // Keep the digits 0 and 1 of MNIST.
+/// let binary = FilterDataset::new(mnist.train, |(_, label): &(Arc<Tensor>, Arc<Tensor>)| {
+///     i64::from(&**label) < 2
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct FilterDataset<D: MapDataset> {
+    pub dataset: D,
+    pub indices: Vec<usize>,
+}
+
+impl<D: MapDataset> FilterDataset<D> {
+    pub fn new<F: Fn(&D::SampleType) -> bool>(dataset: D, predicate: F) -> Self {
+        let indices = (0..dataset.len())
+            .filter(|index| predicate(&dataset.get(*index)))
+            .collect();
+        Self { dataset, indices }
+    }
+}
+
+impl<D: MapDataset> MapDataset for FilterDataset<D> {
+    type SampleType = D::SampleType;
+
+    fn get(&self, index: usize) -> Self::SampleType {
+        self.dataset.get(self.indices[index])
+    }
+
+    fn len(&self) -> usize {
+        self.indices.len()
+    }
+}
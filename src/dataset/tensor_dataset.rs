@@ -1,9 +1,15 @@
 use std::{collections::HashMap, sync::Arc};
 
+#[cfg(feature = "ndarray")]
+use ndarray::{ArrayBase, ArrayD, Data, Dimension};
 use raddar_derive::{DatasetFromIter, DatasetIntoIter};
+#[cfg(feature = "ndarray")]
+use tch::kind::Element;
 use tch::{Device, Tensor};
 
 use crate::core::TensorIntoIter;
+#[cfg(feature = "ndarray")]
+use crate::core::{ToArray, ToTensor};
 
 use super::{Dataset, SimpleDataset, UnsupervisedDataset};
 
@@ -45,6 +51,12 @@ impl Dataset for TensorDataset {
             .collect()
     }
 
+    /// Creates a dataset sharing the given samples, without copying them.
+    fn from_data<I: IntoIterator<Item = Self::SampleType>>(data: I) -> Self {
+        let (inputs, labels) = data.into_iter().unzip();
+        Self { inputs, labels }
+    }
+
     fn from_batches<I: IntoIterator<Item = Self::BatchType>>(batches: I) -> Self {
         let mut inputs = Vec::new();
         let mut labels = Vec::new();
@@ -75,6 +87,13 @@ impl Dataset for UnsupervisedTensorDataset {
         self.inputs
     }
 
+    /// Creates a dataset sharing the given samples, without copying them.
+    fn from_data<I: IntoIterator<Item = Self::SampleType>>(data: I) -> Self {
+        Self {
+            inputs: data.into_iter().collect(),
+        }
+    }
+
     fn from_batches<I: IntoIterator<Item = Self::BatchType>>(batches: I) -> Self {
         let mut inputs = Vec::new();
         for batch_inputs in batches {
@@ -264,3 +283,122 @@ impl From<UnsupervisedDataset<Tensor>> for UnsupervisedTensorDataset {
         dataset.map(|data| data)
     }
 }
+
+#[cfg(feature = "ndarray")]
+impl TensorDataset {
+    /// Creates a new `TensorDataset` from arrays of inputs and labels, whose first axis indexes the samples.
+    ///
+    /// This constructor is only available with the `ndarray` feature.
+    ///
+    /// # Examples
+    /// ```
+    /// let inputs = Array2::<f32>::zeros((100, 8));
+    /// let labels = Array1::<i64>::zeros(100);
+    /// let dataset = TensorDataset::from_arrays(&inputs, &labels);
+    /// ```
+    pub fn from_arrays<S1, D1, S2, D2>(
+        inputs: &ArrayBase<S1, D1>,
+        labels: &ArrayBase<S2, D2>,
+    ) -> Self
+    where
+        S1: Data,
+        S1::Elem: Element + Copy,
+        D1: Dimension,
+        S2: Data,
+        S2::Elem: Element + Copy,
+        D2: Dimension,
+    {
+        Self::from_tensors(unbind_array(inputs), unbind_array(labels))
+    }
+
+    /// Stacks the inputs and labels into two arrays of element type `T`, whose first axis indexes the samples.
+    ///
+    /// This method is only available with the `ndarray` feature.
+    pub fn to_arrays<T: Element + Copy>(&self) -> (ArrayD<T>, ArrayD<T>) {
+        (
+            Tensor::stack(&self.inputs, 0).to_array(),
+            Tensor::stack(&self.labels, 0).to_array(),
+        )
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl UnsupervisedTensorDataset {
+    /// Creates a new `UnsupervisedTensorDataset` from an array of inputs, whose first axis indexes the samples.
+    ///
+    /// This constructor is only available with the `ndarray` feature.
+    pub fn from_array<S, D>(inputs: &ArrayBase<S, D>) -> Self
+    where
+        S: Data,
+        S::Elem: Element + Copy,
+        D: Dimension,
+    {
+        Self::from_tensors(unbind_array(inputs))
+    }
+
+    /// Stacks the inputs into an array of element type `T`, whose first axis indexes the samples.
+    ///
+    /// This method is only available with the `ndarray` feature.
+    pub fn to_array<T: Element + Copy>(&self) -> ArrayD<T> {
+        Tensor::stack(&self.inputs, 0).to_array()
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl DictTensorDataset {
+    /// Creates a new `DictTensorDataset` from named arrays, whose first axis indexes the samples.
+    ///
+    /// This constructor is only available with the `ndarray` feature.
+    pub fn from_arrays<S, D, I>(arrays: I) -> Self
+    where
+        S: Data,
+        S::Elem: Element + Copy,
+        D: Dimension,
+        I: IntoIterator<Item = (String, ArrayBase<S, D>)>,
+    {
+        Self::from_tensors(
+            arrays
+                .into_iter()
+                .map(|(key, array)| (key, unbind_array(&array)))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<S1, D1, S2, D2> From<(ArrayBase<S1, D1>, ArrayBase<S2, D2>)> for TensorDataset
+where
+    S1: Data,
+    S1::Elem: Element + Copy,
+    D1: Dimension,
+    S2: Data,
+    S2::Elem: Element + Copy,
+    D2: Dimension,
+{
+    fn from((inputs, labels): (ArrayBase<S1, D1>, ArrayBase<S2, D2>)) -> Self {
+        Self::from_arrays(&inputs, &labels)
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<S, D> From<ArrayBase<S, D>> for UnsupervisedTensorDataset
+where
+    S: Data,
+    S::Elem: Element + Copy,
+    D: Dimension,
+{
+    fn from(inputs: ArrayBase<S, D>) -> Self {
+        Self::from_array(&inputs)
+    }
+}
+
+/// Splits an array along its first axis into one tensor per sample.
+#[cfg(feature = "ndarray")]
+fn unbind_array<S, D>(array: &ArrayBase<S, D>) -> Vec<Arc<Tensor>>
+where
+    S: Data,
+    S::Elem: Element + Copy,
+    D: Dimension,
+{
+    array.to_tensor().unbind(0).into_iter().map(Arc::new).collect()
+}
@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use rand::Rng;
+use tch::Tensor;
+
+use crate::util::{seeded_rng, with_seed};
+
+/// A function mapping a sample of a dataset, as accepted by [Dataset::map](super::Dataset::map) and the functions of [image_mappings](super::image_mappings).
+pub trait DatasetSampleMapping<In, Out>: FnMut(In) -> Out + Send + Clone + 'static {}
+
+impl<In, Out, F> DatasetSampleMapping<In, Out> for F where
+    F: FnMut(In) -> Out + Send + Clone + 'static
+{
+}
+
+/// An object-safe [DatasetSampleMapping], so that mappings of different types can be chained.
+trait BoxedMapping<In, Out>: Send {
+    fn apply(&mut self, input: In) -> Out;
+
+    fn clone_box(&self) -> Box<dyn BoxedMapping<In, Out>>;
+}
+
+impl<In: 'static, Out: 'static, F: DatasetSampleMapping<In, Out>> BoxedMapping<In, Out> for F {
+    fn apply(&mut self, input: In) -> Out {
+        self(input)
+    }
+
+    fn clone_box(&self) -> Box<dyn BoxedMapping<In, Out>> {
+        Box::new(self.clone())
+    }
+}
+
+/// A chain of mappings, applied in order, which is itself a [DatasetSampleMapping].
+///
+/// # Examples
+/// ```
+/// let transform = Compose::new()
+///     .then(image_mappings::random_crop(224, 224))
+///     .then(image_mappings::random_horizontal_flip(0.5))
+///     .then(image_mappings::to_tensor_with(DynamicImage::into_rgb8, true, true))
+///     .then(image_mappings::imagenet_normalize());
+/// let dataset: UnsupervisedTensorDataset = images.map(transform);
+/// ```
+///
+/// For labelled samples, wrap the mappings with [on_input], [on_label] or [on_both]:
+/// ```
+/// let transform = Compose::new()
+///     .then(on_both(image_mappings::random_crop(224, 224)))
+///     .then(on_input(image_mappings::color_jitter(0.2, 0.2, 0.2, 0.)));
+/// ```
+pub struct Compose<In, Out> {
+    mapping: Box<dyn BoxedMapping<In, Out>>,
+}
+
+impl<T: 'static> Compose<T, T> {
+    /// Creates an empty chain, which returns the samples unchanged.
+    pub fn new() -> Self {
+        Self::from_mapping(|input: T| input)
+    }
+}
+
+impl<T: 'static> Default for Compose<T, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<In: 'static, Out: 'static> Compose<In, Out> {
+    /// Creates a chain of a single mapping.
+    pub fn from_mapping<F: DatasetSampleMapping<In, Out>>(mapping: F) -> Self {
+        Self {
+            mapping: Box::new(mapping),
+        }
+    }
+
+    /// Appends a mapping to the chain.
+    pub fn then<Next: 'static, F: DatasetSampleMapping<Out, Next>>(
+        self,
+        mut mapping: F,
+    ) -> Compose<In, Next> {
+        let mut first = self;
+        Compose::from_mapping(move |input: In| mapping(first.apply(input)))
+    }
+
+    /// Maps a sample through the whole chain.
+    pub fn apply(&mut self, input: In) -> Out {
+        self.mapping.apply(input)
+    }
+}
+
+impl<In, Out> Clone for Compose<In, Out> {
+    fn clone(&self) -> Self {
+        Self {
+            mapping: self.mapping.clone_box(),
+        }
+    }
+}
+
+impl<In: 'static, Out: 'static> FnOnce<(In,)> for Compose<In, Out> {
+    type Output = Out;
+
+    extern "rust-call" fn call_once(mut self, input: (In,)) -> Out {
+        self.apply(input.0)
+    }
+}
+
+impl<In: 'static, Out: 'static> FnMut<(In,)> for Compose<In, Out> {
+    extern "rust-call" fn call_mut(&mut self, input: (In,)) -> Out {
+        self.apply(input.0)
+    }
+}
+
+/// Applies a mapping to the input of a labelled sample, leaving the label unchanged.
+pub fn on_input<I, J, L, F>(mut mapping: F) -> impl DatasetSampleMapping<(I, L), (J, L)>
+where
+    F: DatasetSampleMapping<I, J>,
+{
+    move |(input, label): (I, L)| (mapping(input), label)
+}
+
+/// Applies a mapping to the label of a labelled sample, leaving the input unchanged.
+pub fn on_label<I, L, M, F>(mut mapping: F) -> impl DatasetSampleMapping<(I, L), (I, M)>
+where
+    F: DatasetSampleMapping<L, M>,
+{
+    move |(input, label): (I, L)| (input, mapping(label))
+}
+
+/// Applies a mapping to both the input and the label of a labelled sample, e.g. an image and its segmentation mask.
+///
+/// Both calls draw the same random numbers from [seeded_rng], so random mappings such as [random_crop](super::image_mappings::random_crop) transform the input and the label consistently.
+pub fn on_both<T, U, F>(mut mapping: F) -> impl DatasetSampleMapping<(T, T), (U, U)>
+where
+    F: DatasetSampleMapping<T, U>,
+{
+    move |(input, label): (T, T)| {
+        let seed = seeded_rng().gen();
+        let input = with_seed(seed, || mapping(input));
+        let label = with_seed(seed, || mapping(label));
+        (input, label)
+    }
+}
+
+/// Lifts a function on tensors to a mapping of the samples of a tensor dataset.
+pub fn tensor_mapping<F>(f: F) -> impl DatasetSampleMapping<Arc<Tensor>, Arc<Tensor>>
+where
+    F: Fn(&Tensor) -> Tensor + Send + Clone + 'static,
+{
+    move |input: Arc<Tensor>| Arc::new(f(&input))
+}
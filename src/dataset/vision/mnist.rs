@@ -0,0 +1,129 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{bail, Context};
+use flate2::read::GzDecoder;
+use tch::{Kind, Tensor};
+
+use crate::dataset::TensorDataset;
+
+const MNIST_URL: &str = "https://ossci-datasets.s3.amazonaws.com/mnist/";
+const FASHION_MNIST_URL: &str =
+    "https://raw.githubusercontent.com/zalandoresearch/fashion-mnist/master/data/fashion/";
+
+const TRAIN_IMAGES: &str = "train-images-idx3-ubyte";
+const TRAIN_LABELS: &str = "train-labels-idx1-ubyte";
+const TEST_IMAGES: &str = "t10k-images-idx3-ubyte";
+const TEST_LABELS: &str = "t10k-labels-idx1-ubyte";
+
+/// The train and test splits of a MNIST-like dataset.
+///
+/// Each sample is a pair of an image of shape `[1, 28, 28]` with values in `0..1`, and a scalar `Int64` label.
+#[derive(Debug)]
+pub struct Mnist {
+    pub train: TensorDataset,
+    pub test: TensorDataset,
+}
+
+/// Loads the MNIST dataset of handwritten digits from `path`.
+///
+/// The IDX files may be either plain or gzipped. If `download` is true, missing files are downloaded into `path` first.
+///
+/// # Examples
+/// ```
+/// let mnist = raddar::dataset::vision::mnist("data/mnist", true)?;
+/// let loader = mnist.train.into_loader(DataLoaderConfigBuilder::default().batch_size(64).build()?);
+/// ```
+pub fn mnist<P: AsRef<Path>>(path: P, download: bool) -> anyhow::Result<Mnist> {
+    load_mnist_like(path.as_ref(), MNIST_URL, download)
+}
+
+/// Loads the Fashion-MNIST dataset of clothing images from `path`, in the same format as [mnist].
+pub fn fashion_mnist<P: AsRef<Path>>(path: P, download: bool) -> anyhow::Result<Mnist> {
+    load_mnist_like(path.as_ref(), FASHION_MNIST_URL, download)
+}
+
+fn load_mnist_like(path: &Path, url: &str, download: bool) -> anyhow::Result<Mnist> {
+    if download {
+        std::fs::create_dir_all(path)?;
+        for name in [TRAIN_IMAGES, TRAIN_LABELS, TEST_IMAGES, TEST_LABELS] {
+            if find_file(path, name).is_none() {
+                download_file(
+                    &format!("{}{}.gz", url, name),
+                    &path.join(format!("{}.gz", name)),
+                )?;
+            }
+        }
+    }
+    Ok(Mnist {
+        train: load_split(path, TRAIN_IMAGES, TRAIN_LABELS)?,
+        test: load_split(path, TEST_IMAGES, TEST_LABELS)?,
+    })
+}
+
+fn load_split(path: &Path, images: &str, labels: &str) -> anyhow::Result<TensorDataset> {
+    let images = read_idx(path, images)?;
+    let labels = read_idx(path, labels)?;
+    let images = images.to_kind(Kind::Double) / 255.;
+    let labels = labels.to_kind(Kind::Int64);
+    let inputs = images
+        .unsqueeze(1)
+        .unbind(0)
+        .into_iter()
+        .map(Arc::new)
+        .collect();
+    let labels = labels.unbind(0).into_iter().map(Arc::new).collect();
+    Ok(TensorDataset::from_tensors(inputs, labels))
+}
+
+fn find_file(path: &Path, name: &str) -> Option<PathBuf> {
+    [path.join(name), path.join(format!("{}.gz", name))]
+        .into_iter()
+        .find(|file| file.exists())
+}
+
+/// Reads an IDX file of unsigned bytes into a `Uint8` tensor.
+fn read_idx(path: &Path, name: &str) -> anyhow::Result<Tensor> {
+    let file = find_file(path, name)
+        .with_context(|| format!("{} not found in {}", name, path.display()))?;
+    let mut reader = BufReader::new(File::open(&file)?);
+    let mut data = Vec::new();
+    if file.extension().map_or(false, |ext| ext == "gz") {
+        GzDecoder::new(reader).read_to_end(&mut data)?;
+    } else {
+        reader.read_to_end(&mut data)?;
+    }
+    if data.len() < 4 || data[0] != 0 || data[1] != 0 || data[2] != 0x08 {
+        bail!("{} is not an IDX file of unsigned bytes", file.display());
+    }
+    let dims = data[3] as usize;
+    let header = 4 + 4 * dims;
+    if data.len() < header {
+        bail!("{} is truncated", file.display());
+    }
+    let shape: Vec<i64> = data[4..header]
+        .chunks(4)
+        .map(|dim| u32::from_be_bytes([dim[0], dim[1], dim[2], dim[3]]) as i64)
+        .collect();
+    let body = &data[header..];
+    if body.len() as i64 != shape.iter().product::<i64>() {
+        bail!("{} does not match its header", file.display());
+    }
+    Ok(Tensor::of_slice(body).reshape(&shape))
+}
+
+fn download_file(url: &str, path: &Path) -> anyhow::Result<()> {
+    let response = ureq::get(url)
+        .call()
+        .with_context(|| format!("Failed to download {}", url))?;
+    // Download into a temporary file, so that an interrupted download is not mistaken for a complete one.
+    let partial = path.with_extension("part");
+    let mut file = File::create(&partial)?;
+    std::io::copy(&mut response.into_reader(), &mut file)?;
+    std::fs::rename(partial, path)?;
+    Ok(())
+}
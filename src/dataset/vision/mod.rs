@@ -0,0 +1,5 @@
+pub use mnist::*;
+pub use voc::*;
+
+pub mod mnist;
+pub mod voc;
@@ -0,0 +1,239 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use image::DynamicImage;
+use tch::Tensor;
+
+use crate::dataset::MapDataset;
+
+/// The 20 object classes of Pascal VOC, in the order of their labels.
+///
+/// The background of the segmentation masks has the value 0, so the value of a class in a mask is its label plus 1.
+pub const VOC_CLASSES: [&str; 20] = [
+    "aeroplane",
+    "bicycle",
+    "bird",
+    "boat",
+    "bottle",
+    "bus",
+    "car",
+    "cat",
+    "chair",
+    "cow",
+    "diningtable",
+    "dog",
+    "horse",
+    "motorbike",
+    "person",
+    "pottedplant",
+    "sheep",
+    "sofa",
+    "train",
+    "tvmonitor",
+];
+
+/// An object annotated in a Pascal VOC image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VocObject {
+    pub class: String,
+    /// The index of the class in [VOC_CLASSES].
+    pub label: i64,
+    /// The bounding box as `[xmin, ymin, xmax, ymax]` in pixels.
+    pub bbox: [f64; 4],
+    pub difficult: bool,
+    pub truncated: bool,
+}
+
+/// The annotation of a Pascal VOC image, parsed from its XML file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VocAnnotation {
+    pub filename: String,
+    pub width: u32,
+    pub height: u32,
+    pub objects: Vec<VocObject>,
+}
+
+/// Parses a Pascal VOC XML annotation.
+pub fn parse_voc_annotation(xml: &str) -> anyhow::Result<VocAnnotation> {
+    let document = roxmltree::Document::parse(xml)?;
+    let root = document.root_element();
+    let child = |node: roxmltree::Node, name: &str| {
+        node.children()
+            .find(|child| child.has_tag_name(name))
+            .with_context(|| format!("Missing <{}> in <{}>", name, node.tag_name().name()))
+    };
+    let text = |node: roxmltree::Node, name: &str| -> anyhow::Result<String> {
+        Ok(child(node, name)?.text().unwrap_or("").trim().to_owned())
+    };
+    let number = |node: roxmltree::Node, name: &str| -> anyhow::Result<f64> {
+        let value = text(node, name)?;
+        value
+            .parse()
+            .with_context(|| format!("<{}> is not a number: {:?}", name, value))
+    };
+    let flag = |node: roxmltree::Node, name: &str| -> bool {
+        text(node, name).map_or(false, |value| value == "1")
+    };
+
+    let size = child(root, "size")?;
+    let objects = root
+        .children()
+        .filter(|node| node.has_tag_name("object"))
+        .map(|object| {
+            let class = text(object, "name")?;
+            let label = VOC_CLASSES
+                .iter()
+                .position(|name| *name == class)
+                .with_context(|| format!("Unknown VOC class {}", class))?
+                as i64;
+            let bndbox = child(object, "bndbox")?;
+            Ok(VocObject {
+                bbox: [
+                    number(bndbox, "xmin")?,
+                    number(bndbox, "ymin")?,
+                    number(bndbox, "xmax")?,
+                    number(bndbox, "ymax")?,
+                ],
+                difficult: flag(object, "difficult"),
+                truncated: flag(object, "truncated"),
+                class,
+                label,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(VocAnnotation {
+        filename: text(root, "filename")?,
+        width: number(size, "width")? as u32,
+        height: number(size, "height")? as u32,
+        objects,
+    })
+}
+
+/// The tasks of Pascal VOC, which use different image sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VocTask {
+    Detection,
+    Segmentation,
+}
+
+/// A sample of a [VocDataset].
+#[derive(Debug, Clone)]
+pub struct VocSample {
+    pub image: Arc<DynamicImage>,
+    /// The bounding boxes of the objects, of shape `[objects, 4]`.
+    pub boxes: Arc<Tensor>,
+    /// The labels of the objects, of shape `[objects]`.
+    pub labels: Arc<Tensor>,
+    /// The class segmentation mask, for the segmentation task. Each pixel holds 0 for the background, the label of its class plus 1, or 255 for the borders of the objects.
+    pub mask: Option<Arc<DynamicImage>>,
+    pub annotation: Arc<VocAnnotation>,
+}
+
+/// The Pascal VOC dataset, read from its standard directory layout (`Annotations`, `ImageSets`, `JPEGImages` and `SegmentationClass`).
+///
+/// The annotations are parsed when the dataset is opened, and the images are decoded when a sample is read.
+///
+/// # Examples
+/// ```
+/// let voc = VocDataset::open("data/VOCdevkit/VOC2012", "train", VocTask::Detection)?;
+/// let sample = voc.get(0);
+/// println!("{:?}", sample.annotation.objects);
+/// ```
+#[derive(Debug, Clone)]
+pub struct VocDataset {
+    pub root: PathBuf,
+    pub task: VocTask,
+    pub ids: Vec<String>,
+    pub annotations: Vec<Arc<VocAnnotation>>,
+    /// Whether to keep the objects marked as difficult.
+    pub keep_difficult: bool,
+}
+
+impl VocDataset {
+    /// Opens the image set `image_set` (e.g. `"train"`, `"val"` or `"trainval"`) of the dataset at `root`.
+    pub fn open<P: AsRef<Path>>(root: P, image_set: &str, task: VocTask) -> anyhow::Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        let set_dir = match task {
+            VocTask::Detection => "Main",
+            VocTask::Segmentation => "Segmentation",
+        };
+        let set_path = root
+            .join("ImageSets")
+            .join(set_dir)
+            .join(format!("{}.txt", image_set));
+        let ids: Vec<String> = std::fs::read_to_string(&set_path)
+            .with_context(|| format!("Failed to read {}", set_path.display()))?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_owned)
+            .collect();
+        let annotations = ids
+            .iter()
+            .map(|id| {
+                let path = root.join("Annotations").join(format!("{}.xml", id));
+                let xml = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                parse_voc_annotation(&xml)
+                    .with_context(|| format!("Failed to parse {}", path.display()))
+                    .map(Arc::new)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self {
+            root,
+            task,
+            ids,
+            annotations,
+            keep_difficult: false,
+        })
+    }
+
+    /// Set whether to keep the objects marked as difficult.
+    pub fn keep_difficult(mut self, keep_difficult: bool) -> Self {
+        self.keep_difficult = keep_difficult;
+        self
+    }
+}
+
+impl MapDataset for VocDataset {
+    type SampleType = VocSample;
+
+    fn get(&self, index: usize) -> Self::SampleType {
+        let id = &self.ids[index];
+        let open = |path: PathBuf| {
+            image::open(&path)
+                .unwrap_or_else(|err| panic!("Failed to open image {}: {}", path.display(), err))
+        };
+        let image = open(self.root.join("JPEGImages").join(format!("{}.jpg", id)));
+        let mask = match self.task {
+            VocTask::Detection => None,
+            VocTask::Segmentation => Some(Arc::new(open(
+                self.root
+                    .join("SegmentationClass")
+                    .join(format!("{}.png", id)),
+            ))),
+        };
+        let annotation = self.annotations[index].clone();
+        let objects: Vec<&VocObject> = annotation
+            .objects
+            .iter()
+            .filter(|object| self.keep_difficult || !object.difficult)
+            .collect();
+        let boxes: Vec<f64> = objects.iter().flat_map(|object| object.bbox).collect();
+        let labels: Vec<i64> = objects.iter().map(|object| object.label).collect();
+        VocSample {
+            image: Arc::new(image),
+            boxes: Arc::new(Tensor::of_slice(&boxes).view([-1, 4])),
+            labels: Arc::new(Tensor::of_slice(&labels)),
+            mask,
+            annotation,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.ids.len()
+    }
+}
@@ -0,0 +1,142 @@
+use std::iter::{Skip, StepBy};
+
+use tch::{no_grad, Device, Kind, Tensor};
+
+use crate::{
+    core::TensorCell,
+    nn::Trainable,
+    train::{Callback, TrainerContext},
+};
+
+use super::ProcessGroup;
+
+/// Splits an iterator into `world_size` shards, and returns the shard of the process with rank `rank`.
+///
+/// Batches are assigned round-robin, so with a loader of `n` batches, each process gets about `n / world_size` batches. All processes should use the same (unshuffled or identically seeded) loader.
+pub fn shard<I: Iterator>(iter: I, rank: usize, world_size: usize) -> StepBy<Skip<I>> {
+    iter.skip(rank).step_by(world_size)
+}
+
+/// A [Callback] that turns a [Trainer](crate::train::Trainer) into a data-parallel trainer over a [ProcessGroup].
+///
+/// - At the beginning of training, the parameters and buffers of rank 0 are broadcast to every process, so all replicas start identical.
+/// - After each backward pass, the gradients are averaged over the processes. Gradients are flattened into buckets of at most `bucket_size` elements, so that the number of collective operations stays small.
+/// - At the end of each epoch, the logs are averaged over the processes, so that later callbacks see global values.
+///
+/// This callback should be the first callback of the trainer, so that the other callbacks see the averaged gradients and logs. Every process should iterate over its own [shard] of the data.
+pub struct DistributedDataParallel<G: ProcessGroup> {
+    pub group: G,
+    /// The maximum number of elements of a gradient bucket.
+    pub bucket_size: usize,
+}
+
+impl<G: ProcessGroup> DistributedDataParallel<G> {
+    /// The default bucket size, which is 25 MB of `f32` values.
+    pub const DEFAULT_BUCKET_SIZE: usize = 25 * 1024 * 1024 / 4;
+
+    pub fn new(group: G) -> Self {
+        Self {
+            group,
+            bucket_size: Self::DEFAULT_BUCKET_SIZE,
+        }
+    }
+
+    /// Sets the maximum number of elements of a gradient bucket.
+    pub fn bucket_size(mut self, bucket_size: usize) -> Self {
+        self.bucket_size = bucket_size.max(1);
+        self
+    }
+
+    /// Broadcasts the given tensors from rank 0 to all processes.
+    pub fn broadcast_tensors<'a, I>(&mut self, tensors: I)
+    where
+        I: IntoIterator<Item = &'a TensorCell>,
+    {
+        for tensor in tensors {
+            let mut tensor = tensor.lock();
+            self.group
+                .broadcast(&mut tensor, 0)
+                .expect("Failed to broadcast a tensor");
+        }
+    }
+
+    /// Averages the gradients of the given parameters over all processes.
+    pub fn average_gradients(&mut self, parameters: &[TensorCell]) {
+        let grads: Vec<Tensor> = parameters
+            .iter()
+            .map(|parameter| parameter.lock().grad())
+            .filter(|grad| grad.defined())
+            .collect();
+
+        // Gradients of the last layers are ready first in backward, so buckets are built in reverse order.
+        let mut bucket: Vec<&Tensor> = Vec::new();
+        let mut bucket_numel = 0;
+        let mut bucket_key: Option<(Kind, Device)> = None;
+        for grad in grads.iter().rev() {
+            let key = (grad.kind(), grad.device());
+            let numel = grad.numel();
+            if !bucket.is_empty()
+                && (bucket_numel + numel > self.bucket_size || bucket_key != Some(key))
+            {
+                self.reduce_bucket(&bucket);
+                bucket.clear();
+                bucket_numel = 0;
+            }
+            bucket.push(grad);
+            bucket_numel += numel;
+            bucket_key = Some(key);
+        }
+        if !bucket.is_empty() {
+            self.reduce_bucket(&bucket);
+        }
+    }
+
+    fn reduce_bucket(&mut self, bucket: &[&Tensor]) {
+        no_grad(|| {
+            let flat: Vec<Tensor> = bucket.iter().map(|grad| grad.flatten(0, -1)).collect();
+            let mut flat = Tensor::cat(&flat, 0);
+            self.group
+                .all_reduce(&mut flat)
+                .expect("Failed to reduce gradients");
+            let flat = flat / self.group.world_size() as f64;
+            let mut offset = 0;
+            for grad in bucket {
+                let numel = grad.numel() as i64;
+                grad.shallow_clone()
+                    .copy_(&flat.narrow(0, offset, numel).view(grad.size().as_slice()));
+                offset += numel;
+            }
+        });
+    }
+}
+
+impl<G: ProcessGroup> Callback for DistributedDataParallel<G> {
+    fn on_train_begin(&mut self, ctx: &mut TrainerContext) {
+        let parameters = ctx.model.parameters();
+        let buffers = ctx.model.buffers();
+        no_grad(|| {
+            self.broadcast_tensors(parameters.values().chain(buffers.values()));
+        });
+    }
+
+    fn on_backward(&mut self, ctx: &mut TrainerContext) {
+        let parameters: Vec<TensorCell> =
+            ctx.model.parameters().into_iter().map(|(_, p)| p).collect();
+        self.average_gradients(&parameters);
+    }
+
+    fn on_epoch_end(&mut self, ctx: &mut TrainerContext) {
+        if ctx.logs.is_empty() {
+            return;
+        }
+        let values: Vec<f64> = ctx.logs.values().copied().collect();
+        let mut values = Tensor::of_slice(&values);
+        self.group
+            .all_reduce(&mut values)
+            .expect("Failed to reduce logs");
+        let values = Vec::<f64>::from(&(values / self.group.world_size() as f64));
+        for (value, average) in ctx.logs.values_mut().zip(values) {
+            *value = average;
+        }
+    }
+}
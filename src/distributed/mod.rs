@@ -0,0 +1,7 @@
+pub use ddp::*;
+pub use process_group::*;
+pub use tcp::*;
+
+pub mod ddp;
+pub mod process_group;
+pub mod tcp;
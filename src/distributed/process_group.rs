@@ -0,0 +1,31 @@
+use tch::Tensor;
+
+/// A group of processes that train the same model together, each on its own shard of the data.
+///
+/// Every process in the group has a unique `rank` in `0..world_size`. The collective operations must be called by all processes of the group in the same order, otherwise they will deadlock.
+///
+/// This trait is the extension point for communication backends. [TcpProcessGroup](super::TcpProcessGroup) is provided as a backend that works everywhere; faster backends such as NCCL or gloo can be plugged in by implementing this trait.
+pub trait ProcessGroup: Send {
+    /// The rank of this process in the group.
+    fn rank(&self) -> usize;
+
+    /// The number of processes in the group.
+    fn world_size(&self) -> usize;
+
+    /// Sums `tensor` over all processes in place, so that every process ends up with the same result.
+    fn all_reduce(&mut self, tensor: &mut Tensor) -> anyhow::Result<()>;
+
+    /// Overwrites `tensor` in place with its value in the process with rank `root`.
+    fn broadcast(&mut self, tensor: &mut Tensor, root: usize) -> anyhow::Result<()>;
+
+    /// Blocks until every process of the group reaches the barrier.
+    fn barrier(&mut self) -> anyhow::Result<()> {
+        let mut tensor = Tensor::zeros(&[1], (tch::Kind::Double, tch::Device::Cpu));
+        self.all_reduce(&mut tensor)
+    }
+
+    /// Returns `true` if this process is the main process, i.e. its rank is 0. Logging and checkpointing are usually only done by the main process.
+    fn is_main_process(&self) -> bool {
+        self.rank() == 0
+    }
+}
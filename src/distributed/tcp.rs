@@ -0,0 +1,222 @@
+use std::{
+    io::{BufReader, BufWriter, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail};
+use tch::{no_grad, Device, Kind, Tensor};
+
+use super::ProcessGroup;
+
+/// A connection to another process of the group.
+struct Peer {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl Peer {
+    fn new(stream: TcpStream) -> anyhow::Result<Self> {
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+        })
+    }
+
+    fn send_u64(&mut self, value: u64) -> anyhow::Result<()> {
+        self.writer.write_all(&value.to_le_bytes())?;
+        Ok(())
+    }
+
+    fn recv_u64(&mut self) -> anyhow::Result<u64> {
+        let mut bytes = [0u8; 8];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// Sends the values of a tensor as a length-prefixed sequence of `f64`.
+    fn send(&mut self, values: &[f64]) -> anyhow::Result<()> {
+        self.send_u64(values.len() as u64)?;
+        for value in values {
+            self.writer.write_all(&value.to_le_bytes())?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn recv(&mut self) -> anyhow::Result<Vec<f64>> {
+        let len = self.recv_u64()? as usize;
+        let mut bytes = vec![0u8; len * 8];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(bytes
+            .chunks_exact(8)
+            .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect())
+    }
+}
+
+/// A [ProcessGroup] communicating over TCP, with a star topology around the process of rank 0.
+///
+/// The process of rank 0 listens on `master_addr`, and the other processes connect to it. Collective operations gather the tensors to rank 0, which reduces them and sends the result back.
+/// Tensors are transferred as `f64` and converted back to their original kind and device, so this backend favours portability over speed.
+///
+/// # Examples
+/// ```
+/// let rank = std::env::var("RANK")?.parse()?;
+/// let world_size = std::env::var("WORLD_SIZE")?.parse()?;
+/// let group = TcpProcessGroup::new("10.0.0.1:29500", rank, world_size)?;
+/// ```
+pub struct TcpProcessGroup {
+    rank: usize,
+    world_size: usize,
+    /// For rank 0, the connections to ranks `1..world_size`. For the other ranks, the connection to rank 0.
+    peers: Vec<Peer>,
+}
+
+impl TcpProcessGroup {
+    /// The default time for connecting to the process of rank 0.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+    /// Joins a process group. This blocks until all processes have joined.
+    pub fn new<A: ToSocketAddrs>(
+        master_addr: A,
+        rank: usize,
+        world_size: usize,
+    ) -> anyhow::Result<Self> {
+        Self::with_timeout(master_addr, rank, world_size, Self::DEFAULT_TIMEOUT)
+    }
+
+    /// Joins a process group, giving up if the process of rank 0 cannot be reached within `timeout`.
+    pub fn with_timeout<A: ToSocketAddrs>(
+        master_addr: A,
+        rank: usize,
+        world_size: usize,
+        timeout: Duration,
+    ) -> anyhow::Result<Self> {
+        if rank >= world_size {
+            bail!(
+                "Rank {} is out of range for world size {}",
+                rank,
+                world_size
+            );
+        }
+        let addr: SocketAddr = master_addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow!("Invalid master address"))?;
+        let peers = if rank == 0 {
+            let listener = TcpListener::bind(addr)?;
+            let mut peers: Vec<Option<Peer>> = (1..world_size).map(|_| None).collect();
+            for _ in 1..world_size {
+                let (stream, _) = listener.accept()?;
+                let mut peer = Peer::new(stream)?;
+                let peer_rank = peer.recv_u64()? as usize;
+                if peer_rank == 0 || peer_rank >= world_size || peers[peer_rank - 1].is_some() {
+                    bail!("Unexpected rank {} joined the process group", peer_rank);
+                }
+                peers[peer_rank - 1] = Some(peer);
+            }
+            peers.into_iter().map(Option::unwrap).collect()
+        } else {
+            let start = Instant::now();
+            let stream = loop {
+                match TcpStream::connect(addr) {
+                    Ok(stream) => break stream,
+                    Err(err) if start.elapsed() > timeout => return Err(err.into()),
+                    Err(_) => thread::sleep(Duration::from_millis(100)),
+                }
+            };
+            let mut peer = Peer::new(stream)?;
+            peer.send_u64(rank as u64)?;
+            peer.writer.flush()?;
+            vec![peer]
+        };
+        Ok(Self {
+            rank,
+            world_size,
+            peers,
+        })
+    }
+
+    fn to_values(tensor: &Tensor) -> Vec<f64> {
+        Vec::<f64>::from(&tensor.to_kind(Kind::Double).to(Device::Cpu).flatten(0, -1))
+    }
+
+    fn from_values(values: &[f64], like: &Tensor) -> Tensor {
+        Tensor::of_slice(values)
+            .view(like.size().as_slice())
+            .to_kind(like.kind())
+            .to(like.device())
+    }
+}
+
+impl ProcessGroup for TcpProcessGroup {
+    fn rank(&self) -> usize {
+        self.rank
+    }
+
+    fn world_size(&self) -> usize {
+        self.world_size
+    }
+
+    fn all_reduce(&mut self, tensor: &mut Tensor) -> anyhow::Result<()> {
+        if self.world_size == 1 {
+            return Ok(());
+        }
+        let mut values = Self::to_values(tensor);
+        if self.rank == 0 {
+            for peer in self.peers.iter_mut() {
+                let other = peer.recv()?;
+                if other.len() != values.len() {
+                    bail!("Mismatched tensor sizes in all_reduce");
+                }
+                values.iter_mut().zip(other).for_each(|(a, b)| *a += b);
+            }
+            for peer in self.peers.iter_mut() {
+                peer.send(&values)?;
+            }
+        } else {
+            self.peers[0].send(&values)?;
+            values = self.peers[0].recv()?;
+        }
+        no_grad(|| tensor.copy_(&Self::from_values(&values, tensor)));
+        Ok(())
+    }
+
+    fn broadcast(&mut self, tensor: &mut Tensor, root: usize) -> anyhow::Result<()> {
+        if self.world_size == 1 {
+            return Ok(());
+        }
+        if root >= self.world_size {
+            bail!(
+                "Root {} is out of range for world size {}",
+                root,
+                self.world_size
+            );
+        }
+        // The tensor is routed through rank 0 when the root is another process.
+        let values = if self.rank == 0 {
+            let values = if root == 0 {
+                Self::to_values(tensor)
+            } else {
+                self.peers[root - 1].recv()?
+            };
+            for (i, peer) in self.peers.iter_mut().enumerate() {
+                if i + 1 != root {
+                    peer.send(&values)?;
+                }
+            }
+            values
+        } else if self.rank == root {
+            let values = Self::to_values(tensor);
+            self.peers[0].send(&values)?;
+            values
+        } else {
+            self.peers[0].recv()?
+        };
+        no_grad(|| tensor.copy_(&Self::from_values(&values, tensor)));
+        Ok(())
+    }
+}
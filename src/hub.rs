@@ -0,0 +1,215 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context};
+use parking_lot::{const_rwlock, RwLock};
+use sha2::{Digest, Sha256};
+
+use crate::nn::{StateDict, StateDictExt};
+
+/// Where to download pretrained weights from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PretrainedWeights {
+    pub url: String,
+    /// The SHA-256 hash of the file, in lowercase hex. If set, the file is verified after downloading.
+    pub sha256: Option<String>,
+    /// The directory to cache the file in. If not set, it is [cache_dir()].
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl PretrainedWeights {
+    pub fn new<S: Into<String>>(url: S) -> Self {
+        Self {
+            url: url.into(),
+            sha256: None,
+            cache_dir: None,
+        }
+    }
+
+    /// Set the SHA-256 hash of the file.
+    pub fn sha256<S: Into<String>>(mut self, sha256: S) -> Self {
+        self.sha256 = Some(sha256.into().to_lowercase());
+        self
+    }
+
+    /// Set the directory to cache the file in, instead of [cache_dir()].
+    pub fn cache_dir<P: Into<PathBuf>>(mut self, cache_dir: P) -> Self {
+        self.cache_dir = Some(cache_dir.into());
+        self
+    }
+}
+
+static REGISTRY: RwLock<Vec<(String, PretrainedWeights)>> = const_rwlock(Vec::new());
+
+/// The weights available without registration: the torchvision ImageNet weights, as converted to safetensors by timm.
+const BUILTIN_WEIGHTS: &[(&str, &str)] = &[
+    (
+        "resnet18",
+        "https://huggingface.co/timm/resnet18.tv_in1k/resolve/main/model.safetensors",
+    ),
+    (
+        "resnet50",
+        "https://huggingface.co/timm/resnet50.tv_in1k/resolve/main/model.safetensors",
+    ),
+    (
+        "densenet121",
+        "https://huggingface.co/timm/densenet121.tv_in1k/resolve/main/model.safetensors",
+    ),
+];
+
+/// Registers the weights of a pretrained model under `name`, replacing any weights already registered under it.
+pub fn register_weights<S: Into<String>>(name: S, weights: PretrainedWeights) {
+    let name = name.into();
+    let mut registry = REGISTRY.write();
+    registry.retain(|(registered, _)| *registered != name);
+    registry.push((name, weights));
+}
+
+/// Returns the weights registered under `name`, or the builtin weights of that name, i.e. `resnet18`, `resnet50` and `densenet121`.
+pub fn registered_weights(name: &str) -> Option<PretrainedWeights> {
+    REGISTRY
+        .read()
+        .iter()
+        .find(|(registered, _)| registered == name)
+        .map(|(_, weights)| weights.clone())
+        .or_else(|| {
+            BUILTIN_WEIGHTS
+                .iter()
+                .find(|(builtin, _)| *builtin == name)
+                .map(|(_, url)| PretrainedWeights::new(*url))
+        })
+}
+
+/// Returns the directory where pretrained weights are cached.
+///
+/// It is `$RADDAR_HOME/hub` if `RADDAR_HOME` is set, otherwise `raddar/hub` in `$XDG_CACHE_HOME` or `~/.cache`.
+pub fn cache_dir() -> PathBuf {
+    if let Some(home) = env::var_os("RADDAR_HOME") {
+        return PathBuf::from(home).join("hub");
+    }
+    let cache = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(env::temp_dir);
+    cache.join("raddar").join("hub")
+}
+
+/// Returns the SHA-256 hash of a file, in lowercase hex.
+pub fn sha256_file<P: AsRef<Path>>(path: P) -> anyhow::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 1 << 16];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Downloads a file into [cache_dir], unless it is already there, and returns its path.
+///
+/// If `sha256` is given, the downloaded file is verified against it, and a cached file with another hash is downloaded again.
+pub fn download_cached(url: &str, sha256: Option<&str>) -> anyhow::Result<PathBuf> {
+    let name = url
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| anyhow!("Can not find a file name in {}", url))?;
+    download_cached_as(url, name, sha256)
+}
+
+/// Downloads a file into [cache_dir] under the file name `name`, unless it is already there, and returns its path, see [download_cached].
+pub fn download_cached_as(url: &str, name: &str, sha256: Option<&str>) -> anyhow::Result<PathBuf> {
+    download_cached_in(&cache_dir(), url, name, sha256)
+}
+
+/// Downloads a file into `dir` under the file name `name`, unless it is already there, and returns its path, see [download_cached].
+pub fn download_cached_in(
+    dir: &Path,
+    url: &str,
+    name: &str,
+    sha256: Option<&str>,
+) -> anyhow::Result<PathBuf> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create the cache directory {}", dir.display()))?;
+    let path = dir.join(name);
+    let verified = |path: &Path| -> anyhow::Result<bool> {
+        match sha256 {
+            Some(sha256) => Ok(sha256_file(path)? == sha256.to_lowercase()),
+            None => Ok(true),
+        }
+    };
+    if path.exists() && verified(&path)? {
+        return Ok(path);
+    }
+
+    let response = ureq::get(url)
+        .call()
+        .with_context(|| format!("Failed to download {}", url))?;
+    // Download into a temporary file, so that an interrupted download is not mistaken for a complete one.
+    let partial = path.with_extension("part");
+    io::copy(&mut response.into_reader(), &mut File::create(&partial)?)?;
+    if !verified(&partial)? {
+        fs::remove_file(&partial)?;
+        bail!(
+            "The file downloaded from {} does not match its SHA-256 hash {}",
+            url,
+            sha256.unwrap()
+        );
+    }
+    fs::rename(&partial, &path)?;
+    Ok(path)
+}
+
+/// Reads a [StateDict] from a .safetensors, .npz or .ot file, depending on its extension.
+pub fn load_state_dict<P: AsRef<Path>>(path: P) -> anyhow::Result<StateDict> {
+    let path = path.as_ref();
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("safetensors") => StateDict::from_safetensors(path),
+        Some("npz") => StateDict::from_npz(path),
+        Some("ot") => StateDict::from_ot(path),
+        _ => bail!(
+            "Unsupported weights file {}, expected .safetensors, .npz or .ot",
+            path.display()
+        ),
+    }
+}
+
+/// Downloads weights into [cache_dir], unless they are already there, and reads them.
+pub fn load_state_dict_from_url(url: &str, sha256: Option<&str>) -> anyhow::Result<StateDict> {
+    load_state_dict(download_cached(url, sha256)?)
+}
+
+/// Downloads the weights registered under `name`, unless they are already cached in their [PretrainedWeights::cache_dir], and reads them.
+///
+/// The weights are cached under `name`, e.g. `resnet18.safetensors`, since many hubs give the same file name to the weights of all models.
+pub fn load_pretrained(name: &str) -> anyhow::Result<StateDict> {
+    let weights = registered_weights(name).ok_or_else(|| {
+        anyhow!(
+            "No pretrained weights are registered for {}, see hub::register_weights",
+            name
+        )
+    })?;
+    let extension = Path::new(&weights.url)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("safetensors");
+    let dir = weights.cache_dir.clone().unwrap_or_else(cache_dir);
+    load_state_dict(download_cached_in(
+        &dir,
+        &weights.url,
+        &format!("{}.{}", name, extension),
+        weights.sha256.as_deref(),
+    )?)
+}
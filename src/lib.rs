@@ -15,6 +15,12 @@ extern crate self as raddar;
 
 pub mod core;
 pub mod dataset;
+pub mod distributed;
+pub mod hub;
+pub mod metrics;
 pub mod nn;
 pub mod optim;
+pub mod train;
 pub mod util;
+
+pub use util::{deterministic, inference_mode, set_default_device, set_default_dtype, set_seed};
@@ -0,0 +1,152 @@
+use tch::{Kind, Tensor};
+
+use super::Metric;
+
+/// Computes the cumulative true positives and false positives at each distinct threshold, in descending order of scores.
+///
+/// Both `scores` and `labels` should be 1d tensors. Everything stays on the device of `scores`.
+fn cumulative_counts(scores: &Tensor, labels: &Tensor) -> (Tensor, Tensor) {
+    let order = scores.argsort(0, true);
+    let scores = scores.index_select(0, &order);
+    let labels = labels.index_select(0, &order).to_kind(Kind::Double);
+    let tps = labels.cumsum(0, Kind::Double);
+    let fps = (1. - &labels).cumsum(0, Kind::Double);
+
+    // Only keep the last position of each group of tied scores.
+    let n = scores.size()[0];
+    let distinct = Tensor::cat(
+        &[
+            scores
+                .narrow(0, 1, n - 1)
+                .ne_tensor(&scores.narrow(0, 0, n - 1)),
+            Tensor::ones(&[1], (Kind::Bool, scores.device())),
+        ],
+        0,
+    );
+    (tps.masked_select(&distinct), fps.masked_select(&distinct))
+}
+
+/// Prepends a zero to a 1d tensor.
+fn with_origin(tensor: &Tensor) -> Tensor {
+    Tensor::cat(
+        &[tensor.zeros_like().narrow(0, 0, 1), tensor.shallow_clone()],
+        0,
+    )
+}
+
+/// Computes the area under the ROC curve of a binary problem with the trapezoidal rule.
+pub fn binary_roc_auc(scores: &Tensor, labels: &Tensor) -> f64 {
+    let (tps, fps) = cumulative_counts(scores, labels);
+    let (tpr, fpr) = (with_origin(&tps), with_origin(&fps));
+    let n = tpr.size()[0];
+    let widths = fpr.narrow(0, 1, n - 1) - fpr.narrow(0, 0, n - 1);
+    let heights = (tpr.narrow(0, 1, n - 1) + tpr.narrow(0, 0, n - 1)) / 2.;
+    let area = f64::from((widths * heights).sum(Kind::Double));
+    let (positives, negatives) = (f64::from(tps.get(-1)), f64::from(fps.get(-1)));
+    if positives == 0. || negatives == 0. {
+        return f64::NAN;
+    }
+    area / positives / negatives
+}
+
+/// Computes the area under the precision-recall curve of a binary problem, as the average precision.
+pub fn binary_pr_auc(scores: &Tensor, labels: &Tensor) -> f64 {
+    let (tps, fps) = cumulative_counts(scores, labels);
+    let precision = &tps / (&tps + &fps);
+    let recall = with_origin(&tps);
+    let n = recall.size()[0];
+    let steps = recall.narrow(0, 1, n - 1) - recall.narrow(0, 0, n - 1);
+    let area = f64::from((steps * precision).sum(Kind::Double));
+    let positives = f64::from(tps.get(-1));
+    if positives == 0. {
+        return f64::NAN;
+    }
+    area / positives
+}
+
+/// The curve whose area is computed by [Auc].
+#[derive(Debug, Clone, Copy)]
+pub enum Curve {
+    /// The receiver operating characteristic curve.
+    Roc,
+    /// The precision-recall curve.
+    PrecisionRecall,
+}
+
+/// The area under a ROC or precision-recall curve.
+///
+/// For binary problems, the output should be the scores of the positive class in shape `[N]`, and the target should be 0 or 1. For multi-class problems, the output should be scores in shape `[N, C]`, and the target should be class indices; the one-vs-rest areas are averaged over classes.
+///
+/// Scores and labels are accumulated on their device, and the area is computed there too, so only the final scalars are copied to the CPU.
+#[derive(Debug)]
+pub struct Auc {
+    pub curve: Curve,
+    scores: Vec<Tensor>,
+    labels: Vec<Tensor>,
+}
+
+impl Auc {
+    pub fn new(curve: Curve) -> Self {
+        Self {
+            curve,
+            scores: Vec::new(),
+            labels: Vec::new(),
+        }
+    }
+
+    pub fn roc() -> Self {
+        Self::new(Curve::Roc)
+    }
+
+    pub fn pr() -> Self {
+        Self::new(Curve::PrecisionRecall)
+    }
+
+    fn binary(&self, scores: &Tensor, labels: &Tensor) -> f64 {
+        match self.curve {
+            Curve::Roc => binary_roc_auc(scores, labels),
+            Curve::PrecisionRecall => binary_pr_auc(scores, labels),
+        }
+    }
+}
+
+impl Metric for Auc {
+    fn name(&self) -> String {
+        match self.curve {
+            Curve::Roc => "roc_auc".to_owned(),
+            Curve::PrecisionRecall => "pr_auc".to_owned(),
+        }
+    }
+
+    fn update(&mut self, output: &Tensor, target: &Tensor) {
+        self.scores.push(output.detach());
+        self.labels
+            .push(target.detach().to_device(output.device()).flatten(0, -1));
+    }
+
+    fn compute(&self) -> f64 {
+        if self.scores.is_empty() {
+            return f64::NAN;
+        }
+        let scores = Tensor::cat(&self.scores, 0);
+        let labels = Tensor::cat(&self.labels, 0);
+        if scores.dim() == 1 {
+            return self.binary(&scores, &labels);
+        }
+        let num_classes = scores.size()[1];
+        let areas: Vec<f64> = (0..num_classes)
+            .map(|class| self.binary(&scores.select(1, class), &labels.eq(class)))
+            .filter(|area| !area.is_nan())
+            .collect();
+        if areas.is_empty() {
+            f64::NAN
+        } else {
+            areas.iter().sum::<f64>() / areas.len() as f64
+        }
+    }
+
+    fn reset(&mut self) {
+        self.scores.clear();
+        self.labels.clear();
+    }
+}
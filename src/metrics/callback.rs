@@ -0,0 +1,38 @@
+use tch::no_grad;
+
+use crate::train::{Callback, TrainerContext};
+
+use super::Metric;
+
+/// A callback that evaluates a set of [Metric]s on the training batches, and adds the results to the epoch logs.
+pub struct MetricsCallback {
+    pub metrics: Vec<Box<dyn Metric>>,
+}
+
+impl MetricsCallback {
+    pub fn new(metrics: Vec<Box<dyn Metric>>) -> Self {
+        Self { metrics }
+    }
+}
+
+impl Callback for MetricsCallback {
+    fn on_epoch_begin(&mut self, _ctx: &mut TrainerContext) {
+        self.metrics.iter_mut().for_each(|metric| metric.reset());
+    }
+
+    fn on_batch_end(&mut self, ctx: &mut TrainerContext) {
+        if let (Some(output), Some(label)) = (&ctx.output, &ctx.label) {
+            no_grad(|| {
+                self.metrics
+                    .iter_mut()
+                    .for_each(|metric| metric.update(output, label));
+            });
+        }
+    }
+
+    fn on_epoch_end(&mut self, ctx: &mut TrainerContext) {
+        for metric in self.metrics.iter() {
+            ctx.logs.insert(metric.name(), metric.compute());
+        }
+    }
+}
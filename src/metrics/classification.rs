@@ -0,0 +1,216 @@
+use tch::{Kind, Tensor};
+
+use super::{ConfusionMatrix, Metric};
+
+/// Converts model outputs to predicted class indices.
+///
+/// If the output has one more dimension than the target, it is treated as scores over classes in the last dimension. Otherwise, it is treated as class indices already.
+pub fn predicted_classes(output: &Tensor, target: &Tensor) -> Tensor {
+    if output.dim() == target.dim() + 1 {
+        output.argmax(-1, false)
+    } else {
+        output.shallow_clone()
+    }
+}
+
+/// The ratio of correctly classified samples.
+#[derive(Debug, Default, Clone)]
+pub struct Accuracy {
+    correct: i64,
+    total: i64,
+}
+
+impl Accuracy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Metric for Accuracy {
+    fn name(&self) -> String {
+        "accuracy".to_owned()
+    }
+
+    fn update(&mut self, output: &Tensor, target: &Tensor) {
+        let prediction = predicted_classes(output, target);
+        let correct = prediction
+            .eq_tensor(&target.to_kind(prediction.kind()))
+            .sum(Kind::Int64);
+        self.correct += i64::from(&correct);
+        self.total += target.numel() as i64;
+    }
+
+    fn compute(&self) -> f64 {
+        if self.total == 0 {
+            0.
+        } else {
+            self.correct as f64 / self.total as f64
+        }
+    }
+
+    fn reset(&mut self) {
+        self.correct = 0;
+        self.total = 0;
+    }
+}
+
+/// The ratio of samples whose target is among the `k` highest scored classes.
+///
+/// The output should be scores over classes in the last dimension, and the target should be class indices.
+#[derive(Debug, Clone)]
+pub struct TopKAccuracy {
+    pub k: i64,
+    correct: i64,
+    total: i64,
+}
+
+impl TopKAccuracy {
+    pub fn new(k: i64) -> Self {
+        Self {
+            k,
+            correct: 0,
+            total: 0,
+        }
+    }
+}
+
+impl Metric for TopKAccuracy {
+    fn name(&self) -> String {
+        format!("top{}_accuracy", self.k)
+    }
+
+    fn update(&mut self, output: &Tensor, target: &Tensor) {
+        let (_, top_k) = output.topk(self.k, -1, true, true);
+        let correct = top_k
+            .eq_tensor(&target.to_kind(Kind::Int64).unsqueeze(-1))
+            .any_dim(-1, false)
+            .sum(Kind::Int64);
+        self.correct += i64::from(&correct);
+        self.total += target.numel() as i64;
+    }
+
+    fn compute(&self) -> f64 {
+        if self.total == 0 {
+            0.
+        } else {
+            self.correct as f64 / self.total as f64
+        }
+    }
+
+    fn reset(&mut self) {
+        self.correct = 0;
+        self.total = 0;
+    }
+}
+
+/// The averaging strategy of multi-class precision, recall and F1 score.
+#[derive(Debug, Clone, Copy)]
+pub enum Average {
+    /// The unweighted mean of per-class scores.
+    Macro,
+    /// The score computed from the total true positives, false positives and false negatives.
+    Micro,
+    /// The mean of per-class scores, weighted by the number of samples of each class.
+    Weighted,
+}
+
+fn ratio(numerator: f64, denominator: f64) -> f64 {
+    if denominator == 0. {
+        0.
+    } else {
+        numerator / denominator
+    }
+}
+
+/// Averages a per-class score computed from `(true positives, false positives, false negatives)`.
+fn average_score<F: Fn(f64, f64, f64) -> f64>(
+    matrix: &ConfusionMatrix,
+    average: Average,
+    score: F,
+) -> f64 {
+    let stats = matrix.class_stats();
+    match average {
+        Average::Macro => {
+            stats
+                .iter()
+                .map(|&(tp, fp, fn_)| score(tp, fp, fn_))
+                .sum::<f64>()
+                / stats.len().max(1) as f64
+        }
+        Average::Micro => {
+            let (tp, fp, fn_) = stats.iter().fold((0., 0., 0.), |acc, &(tp, fp, fn_)| {
+                (acc.0 + tp, acc.1 + fp, acc.2 + fn_)
+            });
+            score(tp, fp, fn_)
+        }
+        Average::Weighted => {
+            let support: f64 = stats.iter().map(|&(tp, _, fn_)| tp + fn_).sum();
+            ratio(
+                stats
+                    .iter()
+                    .map(|&(tp, fp, fn_)| score(tp, fp, fn_) * (tp + fn_))
+                    .sum(),
+                support,
+            )
+        }
+    }
+}
+
+macro_rules! confusion_metric {
+    ($(#[$doc:meta])* $name:ident, $key:expr, $score:expr) => {
+        $(#[$doc])*
+        #[derive(Debug)]
+        pub struct $name {
+            pub average: Average,
+            matrix: ConfusionMatrix,
+        }
+
+        impl $name {
+            pub fn new(num_classes: i64, average: Average) -> Self {
+                Self {
+                    average,
+                    matrix: ConfusionMatrix::new(num_classes),
+                }
+            }
+        }
+
+        impl Metric for $name {
+            fn name(&self) -> String {
+                $key.to_owned()
+            }
+
+            fn update(&mut self, output: &Tensor, target: &Tensor) {
+                self.matrix.update(output, target);
+            }
+
+            fn compute(&self) -> f64 {
+                average_score(&self.matrix, self.average, $score)
+            }
+
+            fn reset(&mut self) {
+                self.matrix.reset();
+            }
+        }
+    };
+}
+
+confusion_metric!(
+    /// Multi-class precision, i.e. the ratio of correct predictions among the predictions of a class.
+    Precision,
+    "precision",
+    |tp, fp, _| ratio(tp, tp + fp)
+);
+
+confusion_metric!(
+    /// Multi-class recall, i.e. the ratio of correct predictions among the samples of a class.
+    Recall,
+    "recall",
+    |tp, _, fn_| ratio(tp, tp + fn_)
+);
+
+confusion_metric!(
+    /// Multi-class F1 score, i.e. the harmonic mean of precision and recall.
+    F1Score,
+    "f1",
+    |tp, fp, fn_| ratio(2. * tp, 2. * tp + fp + fn_)
+);
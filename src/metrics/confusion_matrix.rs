@@ -0,0 +1,128 @@
+use std::{fmt::Display, fs::File, io::Write, path::Path};
+
+use tch::{Kind, Tensor};
+
+use super::{predicted_classes, Metric};
+
+/// An accumulator of a confusion matrix, where rows are target classes and columns are predicted classes.
+///
+/// The counts are kept on the device of the model outputs, and only copied to the CPU when they are read.
+#[derive(Debug)]
+pub struct ConfusionMatrix {
+    pub num_classes: i64,
+    counts: Option<Tensor>,
+}
+
+impl ConfusionMatrix {
+    pub fn new(num_classes: i64) -> Self {
+        Self {
+            num_classes,
+            counts: None,
+        }
+    }
+
+    /// Returns the confusion matrix as a nested vector, where `matrix[target][prediction]` is the number of samples.
+    pub fn to_vec(&self) -> Vec<Vec<i64>> {
+        let n = self.num_classes as usize;
+        match &self.counts {
+            Some(counts) => {
+                let flat = Vec::<i64>::from(&counts.to_kind(Kind::Int64));
+                flat.chunks(n).map(|row| row.to_vec()).collect()
+            }
+            None => vec![vec![0; n]; n],
+        }
+    }
+
+    /// Writes the confusion matrix to a CSV file, with a header row of predicted classes.
+    pub fn to_csv<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let mut file = File::create(path)?;
+        let header = (0..self.num_classes)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        writeln!(file, "target,{}", header)?;
+        for (i, row) in self.to_vec().iter().enumerate() {
+            let row = row
+                .iter()
+                .map(|count| count.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            writeln!(file, "{},{}", i, row)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the per-class statistics `(true positives, false positives, false negatives)`.
+    pub fn class_stats(&self) -> Vec<(f64, f64, f64)> {
+        let matrix = self.to_vec();
+        (0..matrix.len())
+            .map(|i| {
+                let tp = matrix[i][i] as f64;
+                let predicted = matrix.iter().map(|row| row[i]).sum::<i64>() as f64;
+                let actual = matrix[i].iter().sum::<i64>() as f64;
+                (tp, predicted - tp, actual - tp)
+            })
+            .collect()
+    }
+}
+
+impl Metric for ConfusionMatrix {
+    fn name(&self) -> String {
+        "confusion_matrix".to_owned()
+    }
+
+    fn update(&mut self, output: &Tensor, target: &Tensor) {
+        let prediction = predicted_classes(output, target).to_kind(Kind::Int64);
+        let index = target.to_kind(Kind::Int64).to_device(prediction.device()) * self.num_classes
+            + prediction;
+        let counts = index
+            .flatten(0, -1)
+            .bincount::<Tensor>(None, self.num_classes * self.num_classes);
+        self.counts = Some(match self.counts.take() {
+            Some(total) => total + counts,
+            None => counts,
+        });
+    }
+
+    /// Returns the accuracy computed from the confusion matrix.
+    fn compute(&self) -> f64 {
+        let matrix = self.to_vec();
+        let correct: i64 = (0..matrix.len()).map(|i| matrix[i][i]).sum();
+        let total: i64 = matrix.iter().flatten().sum();
+        if total == 0 {
+            0.
+        } else {
+            correct as f64 / total as f64
+        }
+    }
+
+    fn reset(&mut self) {
+        self.counts = None;
+    }
+}
+
+impl Display for ConfusionMatrix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let matrix = self.to_vec();
+        let width = matrix
+            .iter()
+            .flatten()
+            .map(|count| count.to_string().len())
+            .max()
+            .unwrap_or(1)
+            .max(self.num_classes.to_string().len());
+        write!(f, "{:>width$} |", "", width = width)?;
+        for i in 0..self.num_classes {
+            write!(f, " {:>width$}", i, width = width)?;
+        }
+        writeln!(f)?;
+        for (i, row) in matrix.iter().enumerate() {
+            write!(f, "{:>width$} |", i, width = width)?;
+            for count in row {
+                write!(f, " {:>width$}", count, width = width)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
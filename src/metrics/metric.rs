@@ -0,0 +1,16 @@
+use tch::Tensor;
+
+/// A streaming metric, which accumulates statistics batch by batch and computes the final value at the end of an epoch.
+pub trait Metric {
+    /// The name of the metric, used as the key in logs.
+    fn name(&self) -> String;
+
+    /// Accumulates the statistics of a batch of model outputs and targets.
+    fn update(&mut self, output: &Tensor, target: &Tensor);
+
+    /// Computes the metric over all batches seen since the last reset.
+    fn compute(&self) -> f64;
+
+    /// Clears the accumulated statistics.
+    fn reset(&mut self);
+}
@@ -0,0 +1,13 @@
+pub use auc::*;
+pub use callback::*;
+pub use classification::*;
+pub use confusion_matrix::*;
+pub use metric::*;
+pub use regression::*;
+
+pub mod auc;
+pub mod callback;
+pub mod classification;
+pub mod confusion_matrix;
+pub mod metric;
+pub mod regression;
@@ -0,0 +1,126 @@
+use tch::{Kind, Tensor};
+
+use super::Metric;
+
+/// Sums a tensor into a scalar on the CPU.
+fn sum(tensor: &Tensor) -> f64 {
+    f64::from(tensor.sum(Kind::Double))
+}
+
+/// Mean absolute error.
+#[derive(Debug, Default, Clone)]
+pub struct MeanAbsoluteError {
+    sum_absolute_error: f64,
+    count: i64,
+}
+
+impl MeanAbsoluteError {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Metric for MeanAbsoluteError {
+    fn name(&self) -> String {
+        "mae".to_owned()
+    }
+
+    fn update(&mut self, output: &Tensor, target: &Tensor) {
+        self.sum_absolute_error += sum(&(output - target).abs());
+        self.count += target.numel() as i64;
+    }
+
+    fn compute(&self) -> f64 {
+        if self.count == 0 {
+            0.
+        } else {
+            self.sum_absolute_error / self.count as f64
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Root mean squared error.
+#[derive(Debug, Default, Clone)]
+pub struct RootMeanSquaredError {
+    sum_squared_error: f64,
+    count: i64,
+}
+
+impl RootMeanSquaredError {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Metric for RootMeanSquaredError {
+    fn name(&self) -> String {
+        "rmse".to_owned()
+    }
+
+    fn update(&mut self, output: &Tensor, target: &Tensor) {
+        self.sum_squared_error += sum(&(output - target).square());
+        self.count += target.numel() as i64;
+    }
+
+    fn compute(&self) -> f64 {
+        if self.count == 0 {
+            0.
+        } else {
+            (self.sum_squared_error / self.count as f64).sqrt()
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Coefficient of determination (R²).
+///
+/// The total sum of squares is computed from running sums of the targets, so the targets of all batches don't need to be kept.
+#[derive(Debug, Default, Clone)]
+pub struct R2Score {
+    sum_squared_error: f64,
+    sum_target: f64,
+    sum_squared_target: f64,
+    count: i64,
+}
+
+impl R2Score {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Metric for R2Score {
+    fn name(&self) -> String {
+        "r2".to_owned()
+    }
+
+    fn update(&mut self, output: &Tensor, target: &Tensor) {
+        self.sum_squared_error += sum(&(output - target).square());
+        self.sum_target += sum(target);
+        self.sum_squared_target += sum(&target.square());
+        self.count += target.numel() as i64;
+    }
+
+    fn compute(&self) -> f64 {
+        if self.count == 0 {
+            return 0.;
+        }
+        let total_sum_of_squares =
+            self.sum_squared_target - self.sum_target * self.sum_target / self.count as f64;
+        if total_sum_of_squares == 0. {
+            return 0.;
+        }
+        1. - self.sum_squared_error / total_sum_of_squares
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
@@ -1,24 +1,28 @@
-use std::f64::consts::PI;
-
 use raddar_derive::NonParameterModule;
 use tch::Tensor;
 
-use crate::nn::Module;
+use crate::{
+    core::{Backend, DefaultBackend},
+    nn::Module,
+};
 
 /// GeLU activation function.
 ///
 /// See [Gaussian Error Linear Units (GELUs)](https://arxiv.org/abs/1606.08415).
 #[derive(Debug, NonParameterModule)]
+#[non_parameter(channel_wise)]
 pub struct GeLU;
 
 /// ReLU activation function.
 ///
 /// See [Rectified Linear Units Improve Restricted Boltzmann Machines](https://www.cs.toronto.edu/~fritz/absps/reluICML.pdf).
 #[derive(Debug, NonParameterModule)]
+#[non_parameter(channel_wise)]
 pub struct ReLU;
 
 /// Leaky ReLU activation function.
 #[derive(Debug, NonParameterModule)]
+#[non_parameter(channel_wise)]
 pub struct LeakyReLU {
     pub lambda: f64,
 }
@@ -31,23 +35,18 @@ impl LeakyReLU {
 
 impl Module for GeLU {
     fn forward(&self, input: &Tensor) -> Tensor {
-        let z = (input + &input.pow_tensor_scalar(3) * 0.044715) * (2.0f64 / PI).sqrt();
-        0.5 * input * (1 + z.tanh())
+        DefaultBackend::gelu(input)
     }
 }
 impl Module for ReLU {
     fn forward(&self, input: &Tensor) -> Tensor {
-        let y = input.zeros_like();
-        let condition = input.ge(0);
-        input.where_self(&condition, &y)
+        DefaultBackend::relu(input)
     }
 }
 
 impl Module for LeakyReLU {
     fn forward(&self, input: &Tensor) -> Tensor {
-        let y = -input * self.lambda;
-        let condition = input.ge(0);
-        input.where_self(&condition, &y)
+        DefaultBackend::leaky_relu(input, self.lambda)
     }
 }
 
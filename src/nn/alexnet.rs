@@ -1,22 +1,24 @@
-use raddar_derive::{ArchitectureBuilder, CallableModule};
+use raddar_derive::{ArchitectureBuilder, CallableModule, SaveableModule, Trainable};
 use tch::Tensor;
 
 use crate::{
+    core::{Backend, DefaultBackend},
     nn::{
         AdaptiveAveragePooling2D, AdaptiveAveragePooling2DBuilder, Conv2dBuilder, DropoutBuilder,
-        LinearBuilder, MaxPooling2DBuilder, Module, ReLU, Sequential, Trainable,
+        LazyLinearBuilder, LinearBuilder, MaxPooling2DBuilder, Module, ReLU, Sequential,
     },
     seq,
 };
 
-use super::{Mod, TrainableDict};
+use super::{pretrained::TorchvisionWeights, KeyMap, Mod};
 
 /// AlexNet architecture.
 ///
 /// See [ImageNet Classification with Deep Convolutional Neural Networks](https://papers.nips.cc/paper/4824-imagenet-classification-with-deep-convolutional-neural-networks.pdf).
-#[derive(Debug, CallableModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, ArchitectureBuilder, SaveableModule, Trainable)]
 pub struct AlexNet {
     pub features: Mod<Sequential>,
+    #[trainable(skip)]
     pub avgpool: Mod<AdaptiveAveragePooling2D>,
     pub classifier: Mod<Sequential>,
 
@@ -27,20 +29,11 @@ pub struct AlexNet {
     pub dropout: f64,
 }
 
-impl Trainable for AlexNet {
-    fn child_modules(&self) -> TrainableDict {
-        let mut result = TrainableDict::new();
-        result.insert("features".to_owned(), self.features.clone());
-        result.insert("classifier".to_owned(), self.classifier.clone());
-        result
-    }
-}
-
 impl Module for AlexNet {
     fn forward(&self, input: &Tensor) -> Tensor {
         let mut output = (self.features)(input);
         output = (self.avgpool)(&output);
-        output = output.flatten(1, 3);
+        output = DefaultBackend::flatten(&output, 1, 3);
         output = (self.classifier)(&output);
         output
     }
@@ -103,10 +96,7 @@ impl AlexNet {
             .build();
         let classifier = seq!(
             DropoutBuilder::default().p(config.dropout).build(),
-            LinearBuilder::default()
-                .input_dim(9216)
-                .output_dim(4096)
-                .build(),
+            LazyLinearBuilder::default().output_dim(4096).build(),
             Mod::new(ReLU),
             DropoutBuilder::default().p(config.dropout).build(),
             LinearBuilder::default()
@@ -128,6 +118,9 @@ impl AlexNet {
         }
     }
 }
+/// Creates a randomly initialized AlexNet.
+///
+/// The `_pretrained` flag is ignored, since no AlexNet weights are builtin. Register them as `alexnet` and call [alexnet_pretrained] instead.
 pub fn alexnet(num_classes: i64, dropout: f64, _pretrained: bool) -> Mod<AlexNet> {
     let model = AlexNetBuilder::default()
         .num_classes(num_classes)
@@ -135,3 +128,22 @@ pub fn alexnet(num_classes: i64, dropout: f64, _pretrained: bool) -> Mod<AlexNet
         .build();
     model
 }
+
+/// Creates an AlexNet initialized with the ImageNet weights of torchvision, which must be registered as `alexnet` with [register_weights](crate::hub::register_weights).
+///
+/// If `num_classes` is not 1000, the last layer of the classifier is left randomly initialized, to be fine-tuned.
+pub fn alexnet_pretrained(num_classes: i64, dropout: f64) -> anyhow::Result<Mod<AlexNet>> {
+    let model = alexnet(num_classes, dropout, false);
+    TorchvisionWeights {
+        name: "alexnet",
+        key_map: KeyMap::new().strip_prefix("module."),
+        linear_weights: &[
+            "classifier.1.weight",
+            "classifier.4.weight",
+            "classifier.6.weight",
+        ],
+        head: "classifier.6.",
+    }
+    .load_into(&model, num_classes)?;
+    Ok(model)
+}
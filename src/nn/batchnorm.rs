@@ -1,13 +1,36 @@
-use raddar_derive::{ArchitectureBuilder, CallableModule};
-use tch::{Device, Kind, Tensor};
+use raddar_derive::{ArchitectureBuilder, CallableModule, SaveableModule};
+use tch::{no_grad, Device, Kind, Tensor};
 
-use super::{module::Module, StateDict, Trainable};
-use crate::core::{Cellable, TensorCell};
+use super::{module::Module, select_channels, ChannelImportance, InitScheme, StateDict, Trainable};
+use crate::core::{Backend, Cellable, DefaultBackend, Dim, RaddarError, Shape, TensorCell};
+
+/// Returns the scale and shift of the channels computed by a batch normalization in evaluation mode, i.e. `(x - running_mean) / sqrt(running_var + eps) * weight + bias`.
+fn eval_affine(
+    running_mean: &TensorCell,
+    running_var: &TensorCell,
+    weight: Option<&TensorCell>,
+    bias: Option<&TensorCell>,
+    eps: f64,
+) -> (Tensor, Tensor) {
+    no_grad(|| {
+        let scale = (&*running_var.lock() + eps).rsqrt();
+        let scale = match weight {
+            Some(weight) => scale * &*weight.lock(),
+            None => scale,
+        };
+        let shift = -(&*running_mean.lock() * &scale);
+        let shift = match bias {
+            Some(bias) => shift + &*bias.lock(),
+            None => shift,
+        };
+        (scale, shift)
+    })
+}
 
 /// A batch normalization layer in 1 dimension.
 ///
 /// See [Batch Normalization: Accelerating Deep Network Training by Reducing Internal Covariate Shift](https://arxiv.org/abs/1502.03167).
-#[derive(Debug, CallableModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, ArchitectureBuilder, SaveableModule)]
 pub struct BatchNorm1d {
     #[builder]
     pub num_features: i64,
@@ -21,6 +44,11 @@ pub struct BatchNorm1d {
     pub affine: bool,
     #[builder(default = "true")]
     pub training: bool,
+    #[builder(default = "raddar::util::default_dtype()")]
+    pub dtype: Kind,
+    #[builder(default = "raddar::util::default_device()")]
+    #[saveable(skip)]
+    pub device: Device,
     pub bn_weight: Option<TensorCell>,
     pub bn_bias: Option<TensorCell>,
     pub running_mean: TensorCell,
@@ -39,12 +67,39 @@ impl Trainable for BatchNorm1d {
         }
         result
     }
-    fn static_tensors(&self) -> StateDict {
+    fn buffers(&self) -> StateDict {
         let mut result = StateDict::new();
         result.insert("running_mean".to_owned(), self.running_mean.clone());
         result.insert("running_var".to_owned(), self.running_var.clone());
         result
     }
+
+    fn train(&mut self, mode: bool) {
+        self.training = mode;
+    }
+
+    fn is_training(&self) -> bool {
+        self.training
+    }
+
+    fn reset_parameters(&self) {
+        InitScheme::Constant(1.).init_parameter(self, "weight");
+        InitScheme::Constant(0.).init_parameter(self, "bias");
+    }
+
+    fn keeps_channels(&self) -> bool {
+        true
+    }
+
+    fn batch_norm_affine(&self) -> Option<(Tensor, Tensor)> {
+        Some(eval_affine(
+            &self.running_mean,
+            &self.running_var,
+            self.bn_weight.as_ref(),
+            self.bn_bias.as_ref(),
+            self.eps,
+        ))
+    }
 }
 
 impl Module for BatchNorm1d {
@@ -56,17 +111,27 @@ impl Module for BatchNorm1d {
         let bn_weight = bn_weight.as_deref();
         let bn_bias = self.bn_bias.as_ref().map(|bias| bias.lock());
         let bn_bias = bn_bias.as_deref();
-        input.batch_norm(
+        DefaultBackend::batch_norm(
+            input,
             bn_weight,
             bn_bias,
-            Some(&running_mean),
-            Some(&running_var),
+            &running_mean,
+            &running_var,
             self.training,
             self.momentum,
             self.eps,
             self.cudnn_enabled,
         )
     }
+
+    fn try_forward(&self, input: &Tensor) -> Result<Tensor, RaddarError> {
+        let mut shape = vec![Dim::Any("N"), Dim::Exact(self.num_features)];
+        if input.dim() == 3 {
+            shape.push(Dim::Any("L"));
+        }
+        Shape(shape).check("BatchNorm1d", input)?;
+        Ok(self.forward(input))
+    }
 }
 
 /// A batch normalization layer in 2 dimensions.
@@ -76,24 +141,31 @@ impl BatchNorm1d {
     pub fn new(config: BatchNorm1dConfig) -> BatchNorm1d {
         let bn_weight = if config.affine {
             Some(
-                Tensor::ones(&[config.num_features], (Kind::Double, Device::Cpu))
-                    .set_requires_grad(true)
-                    .cell(),
+                DefaultBackend::parameter(DefaultBackend::ones(
+                    &[config.num_features],
+                    config.dtype,
+                    config.device,
+                ))
+                .cell(),
             )
         } else {
             None
         };
         let bn_bias = if config.affine {
             Some(
-                Tensor::zeros(&[config.num_features], (Kind::Double, Device::Cpu))
-                    .set_requires_grad(true)
-                    .cell(),
+                DefaultBackend::parameter(DefaultBackend::zeros(
+                    &[config.num_features],
+                    config.dtype,
+                    config.device,
+                ))
+                .cell(),
             )
         } else {
             None
         };
-        let running_mean = Tensor::zeros(&[config.num_features], (Kind::Double, Device::Cpu));
-        let running_var = Tensor::ones(&[config.num_features], (Kind::Double, Device::Cpu));
+        let running_mean =
+            DefaultBackend::zeros(&[config.num_features], config.dtype, config.device);
+        let running_var = DefaultBackend::ones(&[config.num_features], config.dtype, config.device);
         BatchNorm1d {
             num_features: config.num_features,
             eps: config.eps,
@@ -103,13 +175,15 @@ impl BatchNorm1d {
             bn_weight,
             bn_bias,
             training: config.training,
+            dtype: config.dtype,
+            device: config.device,
             running_mean: running_mean.cell(),
             running_var: running_var.cell(),
         }
     }
 }
 
-#[derive(Debug, CallableModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, ArchitectureBuilder, SaveableModule)]
 pub struct BatchNorm2d {
     #[builder]
     pub num_features: i64,
@@ -123,6 +197,11 @@ pub struct BatchNorm2d {
     pub affine: bool,
     #[builder(default = "true")]
     pub training: bool,
+    #[builder(default = "raddar::util::default_dtype()")]
+    pub dtype: Kind,
+    #[builder(default = "raddar::util::default_device()")]
+    #[saveable(skip)]
+    pub device: Device,
     pub bn_weight: Option<TensorCell>,
     pub bn_bias: Option<TensorCell>,
     pub running_mean: TensorCell,
@@ -141,12 +220,58 @@ impl Trainable for BatchNorm2d {
         }
         result
     }
-    fn static_tensors(&self) -> StateDict {
+    fn buffers(&self) -> StateDict {
         let mut result = StateDict::new();
         result.insert("running_mean".to_owned(), self.running_mean.clone());
         result.insert("running_var".to_owned(), self.running_var.clone());
         result
     }
+
+    fn train(&mut self, mode: bool) {
+        self.training = mode;
+    }
+
+    fn is_training(&self) -> bool {
+        self.training
+    }
+
+    fn reset_parameters(&self) {
+        InitScheme::Constant(1.).init_parameter(self, "weight");
+        InitScheme::Constant(0.).init_parameter(self, "bias");
+    }
+
+    fn keeps_channels(&self) -> bool {
+        true
+    }
+
+    fn batch_norm_affine(&self) -> Option<(Tensor, Tensor)> {
+        Some(eval_affine(
+            &self.running_mean,
+            &self.running_var,
+            self.bn_weight.as_ref(),
+            self.bn_bias.as_ref(),
+            self.eps,
+        ))
+    }
+
+    fn channel_importance(&self, criterion: ChannelImportance) -> Option<Tensor> {
+        match (criterion, &self.bn_weight) {
+            (ChannelImportance::BatchNormGamma, Some(weight)) => {
+                Some(no_grad(|| weight.lock().abs().to_kind(Kind::Double)))
+            }
+            _ => None,
+        }
+    }
+
+    fn select_output_channels(&mut self, indices: &Tensor) {
+        let tensors = [&self.bn_weight, &self.bn_bias];
+        for tensor in tensors.into_iter().flatten() {
+            select_channels(tensor, 0, indices);
+        }
+        select_channels(&self.running_mean, 0, indices);
+        select_channels(&self.running_var, 0, indices);
+        self.num_features = indices.size()[0];
+    }
 }
 
 impl Module for BatchNorm2d {
@@ -158,41 +283,60 @@ impl Module for BatchNorm2d {
         let bn_weight = bn_weight.as_deref();
         let bn_bias = self.bn_bias.as_ref().map(|bias| bias.lock());
         let bn_bias = bn_bias.as_deref();
-        input.batch_norm(
+        DefaultBackend::batch_norm(
+            input,
             bn_weight,
             bn_bias,
-            Some(&running_mean),
-            Some(&running_var),
+            &running_mean,
+            &running_var,
             self.training,
             self.momentum,
             self.eps,
             self.cudnn_enabled,
         )
     }
+
+    fn try_forward(&self, input: &Tensor) -> Result<Tensor, RaddarError> {
+        Shape(vec![
+            Dim::Any("N"),
+            Dim::Exact(self.num_features),
+            Dim::Any("H"),
+            Dim::Any("W"),
+        ])
+        .check("BatchNorm2d", input)?;
+        Ok(self.forward(input))
+    }
 }
 
 impl BatchNorm2d {
     pub fn new(config: BatchNorm2dConfig) -> BatchNorm2d {
         let bn_weight = if config.affine {
             Some(
-                Tensor::ones(&[config.num_features], (Kind::Double, Device::Cpu))
-                    .set_requires_grad(true)
-                    .cell(),
+                DefaultBackend::parameter(DefaultBackend::ones(
+                    &[config.num_features],
+                    config.dtype,
+                    config.device,
+                ))
+                .cell(),
             )
         } else {
             None
         };
         let bn_bias = if config.affine {
             Some(
-                Tensor::zeros(&[config.num_features], (Kind::Double, Device::Cpu))
-                    .set_requires_grad(true)
-                    .cell(),
+                DefaultBackend::parameter(DefaultBackend::zeros(
+                    &[config.num_features],
+                    config.dtype,
+                    config.device,
+                ))
+                .cell(),
             )
         } else {
             None
         };
-        let running_mean = Tensor::zeros(&[config.num_features], (Kind::Double, Device::Cpu));
-        let running_var = Tensor::ones(&[config.num_features], (Kind::Double, Device::Cpu));
+        let running_mean =
+            DefaultBackend::zeros(&[config.num_features], config.dtype, config.device);
+        let running_var = DefaultBackend::ones(&[config.num_features], config.dtype, config.device);
         BatchNorm2d {
             num_features: config.num_features,
             eps: config.eps,
@@ -202,6 +346,8 @@ impl BatchNorm2d {
             bn_weight,
             bn_bias,
             training: config.training,
+            dtype: config.dtype,
+            device: config.device,
             running_mean: running_mean.cell(),
             running_var: running_var.cell(),
         }
@@ -211,7 +357,7 @@ impl BatchNorm2d {
 /// A batch normalization layer in 3 dimensions.
 ///
 /// See [Batch Normalization: Accelerating Deep Network Training by Reducing Internal Covariate Shift](https://arxiv.org/abs/1502.03167).
-#[derive(Debug, CallableModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, ArchitectureBuilder, SaveableModule)]
 pub struct BatchNorm3d {
     #[builder]
     pub num_features: i64,
@@ -225,6 +371,11 @@ pub struct BatchNorm3d {
     pub affine: bool,
     #[builder(default = "true")]
     pub training: bool,
+    #[builder(default = "raddar::util::default_dtype()")]
+    pub dtype: Kind,
+    #[builder(default = "raddar::util::default_device()")]
+    #[saveable(skip)]
+    pub device: Device,
     pub bn_weight: Option<TensorCell>,
     pub bn_bias: Option<TensorCell>,
     pub running_mean: TensorCell,
@@ -243,12 +394,39 @@ impl Trainable for BatchNorm3d {
         }
         result
     }
-    fn static_tensors(&self) -> StateDict {
+    fn buffers(&self) -> StateDict {
         let mut result = StateDict::new();
         result.insert("running_mean".to_owned(), self.running_mean.clone());
         result.insert("running_var".to_owned(), self.running_var.clone());
         result
     }
+
+    fn train(&mut self, mode: bool) {
+        self.training = mode;
+    }
+
+    fn is_training(&self) -> bool {
+        self.training
+    }
+
+    fn reset_parameters(&self) {
+        InitScheme::Constant(1.).init_parameter(self, "weight");
+        InitScheme::Constant(0.).init_parameter(self, "bias");
+    }
+
+    fn keeps_channels(&self) -> bool {
+        true
+    }
+
+    fn batch_norm_affine(&self) -> Option<(Tensor, Tensor)> {
+        Some(eval_affine(
+            &self.running_mean,
+            &self.running_var,
+            self.bn_weight.as_ref(),
+            self.bn_bias.as_ref(),
+            self.eps,
+        ))
+    }
 }
 
 impl Module for BatchNorm3d {
@@ -260,41 +438,61 @@ impl Module for BatchNorm3d {
         let bn_weight = bn_weight.as_deref();
         let bn_bias = self.bn_bias.as_ref().map(|bias| bias.lock());
         let bn_bias = bn_bias.as_deref();
-        input.batch_norm(
+        DefaultBackend::batch_norm(
+            input,
             bn_weight,
             bn_bias,
-            Some(&running_mean),
-            Some(&running_var),
+            &running_mean,
+            &running_var,
             self.training,
             self.momentum,
             self.eps,
             self.cudnn_enabled,
         )
     }
+
+    fn try_forward(&self, input: &Tensor) -> Result<Tensor, RaddarError> {
+        Shape(vec![
+            Dim::Any("N"),
+            Dim::Exact(self.num_features),
+            Dim::Any("D"),
+            Dim::Any("H"),
+            Dim::Any("W"),
+        ])
+        .check("BatchNorm3d", input)?;
+        Ok(self.forward(input))
+    }
 }
 
 impl BatchNorm3d {
     pub fn new(config: BatchNorm3dConfig) -> BatchNorm3d {
         let bn_weight = if config.affine {
             Some(
-                Tensor::ones(&[config.num_features], (Kind::Double, Device::Cpu))
-                    .set_requires_grad(true)
-                    .cell(),
+                DefaultBackend::parameter(DefaultBackend::ones(
+                    &[config.num_features],
+                    config.dtype,
+                    config.device,
+                ))
+                .cell(),
             )
         } else {
             None
         };
         let bn_bias = if config.affine {
             Some(
-                Tensor::zeros(&[config.num_features], (Kind::Double, Device::Cpu))
-                    .set_requires_grad(true)
-                    .cell(),
+                DefaultBackend::parameter(DefaultBackend::zeros(
+                    &[config.num_features],
+                    config.dtype,
+                    config.device,
+                ))
+                .cell(),
             )
         } else {
             None
         };
-        let running_mean = Tensor::zeros(&[config.num_features], (Kind::Double, Device::Cpu));
-        let running_var = Tensor::ones(&[config.num_features], (Kind::Double, Device::Cpu));
+        let running_mean =
+            DefaultBackend::zeros(&[config.num_features], config.dtype, config.device);
+        let running_var = DefaultBackend::ones(&[config.num_features], config.dtype, config.device);
         BatchNorm3d {
             num_features: config.num_features,
             eps: config.eps,
@@ -304,6 +502,8 @@ impl BatchNorm3d {
             bn_weight,
             bn_bias,
             training: config.training,
+            dtype: config.dtype,
+            device: config.device,
             running_mean: running_mean.cell(),
             running_var: running_var.cell(),
         }
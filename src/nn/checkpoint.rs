@@ -0,0 +1,138 @@
+use std::cell::RefCell;
+
+use raddar_derive::CallableModule;
+use tch::{no_grad, Device, Kind, Tensor};
+
+use super::{Mod, Module, ModuleMode, Trainable, TrainableDict};
+
+/// A segment of the graph cut at the input and the output of a module, whose backward pass is run by [backward_checkpoints].
+struct Segment {
+    /// The original input, which is still attached to the graph before the segment.
+    input: Tensor,
+    /// A detached copy of the input, which collects the gradient of the segment with respect to its input.
+    input_leaf: Tensor,
+    /// The detached output returned to the rest of the graph.
+    output_leaf: Tensor,
+    /// Backpropagates a gradient of the output through the segment, into its parameters and `input_leaf`.
+    backward: Box<dyn FnOnce(&Tensor)>,
+}
+
+thread_local! {
+    static SEGMENTS: RefCell<Vec<Segment>> = RefCell::new(Vec::new());
+}
+
+/// Cuts the graph around a segment which computed its output from `input_leaf`, a detached copy of `input`, and returns the detached output to use in its place. `backward` is called with the gradient of the output by [backward_checkpoints].
+pub(crate) fn cut_segment(
+    input: &Tensor,
+    input_leaf: Tensor,
+    output: &Tensor,
+    backward: Box<dyn FnOnce(&Tensor)>,
+) -> Tensor {
+    let output_leaf = output.detach().set_requires_grad(true);
+    SEGMENTS.with(|segments| {
+        segments.borrow_mut().push(Segment {
+            input: input.shallow_clone(),
+            input_leaf,
+            output_leaf: output_leaf.shallow_clone(),
+            backward,
+        })
+    });
+    output_leaf
+}
+
+/// Returns `true` if gradients are currently recorded, i.e. we are not inside `no_grad`.
+fn grad_enabled() -> bool {
+    (Tensor::zeros(&[1], (Kind::Float, Device::Cpu)).set_requires_grad(true) * 1.).requires_grad()
+}
+
+/// A wrapper that trades compute for memory: the intermediate activations of the wrapped module are not kept during the forward pass, and are recomputed in the backward pass.
+///
+/// Since the backward pass of libtorch cannot call back into Rust, the recomputation is done by [backward_checkpoints], which must be called in place of `loss.backward()`.
+/// [Trainer](crate::train::Trainer) does this automatically.
+///
+/// Checkpointing is only active in `Train` mode with gradients enabled. Note that stochastic layers such as dropout draw new random numbers when recomputed.
+///
+/// # Examples
+/// ```
+/// let model = seq!(
+///     Mod::new(Checkpoint::new(resnet_block)),
+///     LinearBuilder::default().input_dim(512).output_dim(10).build(),
+/// );
+/// let loss = model(&inputs).mse_loss(&labels, Reduction::Mean);
+/// backward_checkpoints(&loss);
+/// ```
+#[derive(Debug, CallableModule)]
+pub struct Checkpoint<T: Module + 'static> {
+    pub module: Mod<T>,
+}
+
+impl<T: Module + 'static> Checkpoint<T> {
+    pub fn new(module: Mod<T>) -> Self {
+        Self { module }
+    }
+}
+
+impl<T: Module + 'static> Trainable for Checkpoint<T> {
+    fn child_modules(&self) -> TrainableDict {
+        let mut children = TrainableDict::new();
+        children.insert("module".to_owned(), self.module.clone());
+        children
+    }
+}
+
+impl<T: Module + 'static> Module for Checkpoint<T> {
+    fn forward(&self, input: &Tensor) -> Tensor {
+        if let ModuleMode::Eval = self.module.mode() {
+            return self.module.module().forward(input);
+        }
+        if !grad_enabled() {
+            return self.module.module().forward(input);
+        }
+        let input_leaf = input.detach().set_requires_grad(input.requires_grad());
+        let output = no_grad(|| self.module.module().forward(&input_leaf));
+        let module = self.module.clone();
+        let recomputed_input = input_leaf.shallow_clone();
+        cut_segment(
+            input,
+            input_leaf,
+            &output,
+            Box::new(move |grad| {
+                let output = module.module().forward(&recomputed_input);
+                if output.requires_grad() {
+                    // Backward of `sum(output * grad)` is the vector-Jacobian product of the segment with `grad`.
+                    (output * grad).sum(Kind::Double).backward();
+                }
+            }),
+        )
+    }
+}
+
+/// Runs the backward pass of `loss`, through the [Checkpoint]s of the current thread and the modules wrapped by a [Profiler::with_backward](super::Profiler::with_backward).
+///
+/// Segments are recomputed in reverse order, and the gradients reaching their inputs are backpropagated with `loss` in a single backward pass at the end. So the graph before a segment is only traversed once, even if its input also reaches the loss through a skip connection, like `input + checkpoint(input)`.
+/// The recorded segments are cleared afterwards, so this should be called once per backward pass, even if some segments did not contribute to the loss.
+pub fn backward_checkpoints(loss: &Tensor) {
+    let segments = SEGMENTS.with(|segments| std::mem::take(&mut *segments.borrow_mut()));
+    if segments.is_empty() {
+        loss.backward();
+        return;
+    }
+    let mut roots = vec![loss.to_kind(Kind::Double)];
+    for segment in segments.into_iter().rev() {
+        // The gradient of the output only flows from the loss and the inputs of the later segments, so the graph before the segments is not traversed here. The output is added with a zero weight in case it does not reach them.
+        let root =
+            Tensor::stack(&roots, 0).sum(Kind::Double) + segment.output_leaf.sum(Kind::Double) * 0.;
+        let grad = Tensor::run_backward(&[root], &[&segment.output_leaf], true, false).remove(0);
+        (segment.backward)(&grad);
+        let input_grad = segment.input_leaf.grad();
+        if segment.input.requires_grad() && input_grad.defined() {
+            roots.push((&segment.input * input_grad).sum(Kind::Double));
+        }
+    }
+    Tensor::stack(&roots, 0).sum(Kind::Double).backward();
+}
+
+/// Discards the recorded [Checkpoint] segments of the current thread without running backward, e.g. after a forward pass whose loss is not backpropagated.
+pub fn clear_checkpoints() {
+    SEGMENTS.with(|segments| segments.borrow_mut().clear());
+}
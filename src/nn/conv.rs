@@ -1,14 +1,87 @@
-use raddar_derive::{ArchitectureBuilder, CallableModule};
+use raddar_derive::{ArchitectureBuilder, CallableModule, SaveableModule};
 use tch::{no_grad, Device, Kind, Tensor};
 
-use crate::core::{Cellable, TensorCell};
+use crate::core::{Backend, Cellable, DefaultBackend, Dim, RaddarError, Shape, TensorCell};
+
+use super::{
+    filter_norms, select_channels, ChannelImportance, InitScheme, Mod, Module, QParams,
+    QuantizedConv2d, StateDict, Trainable,
+};
+
+/// Folds a scale and a shift of the output channels into the weight and bias of a convolution, adding a bias if it has none. Returns `false` if their number of channels is not that of the convolution.
+fn fold_affine(
+    weight: &TensorCell,
+    bias: &mut Option<TensorCell>,
+    scale: &Tensor,
+    shift: &Tensor,
+) -> bool {
+    let mut weight = weight.lock();
+    if scale.size() != [weight.size()[0]] {
+        return false;
+    }
+    let scale = scale.to_kind(weight.kind()).to_device(weight.device());
+    let shift = shift.to_kind(weight.kind()).to_device(weight.device());
+    no_grad(|| {
+        // The scale multiplies the output channels, i.e. the first dimension of the weight.
+        let mut shape = vec![1; weight.dim()];
+        shape[0] = -1;
+        *weight *= scale.reshape(&shape);
+        match bias {
+            Some(bias) => {
+                let mut bias = bias.lock();
+                *bias *= &scale;
+                *bias += &shift;
+            }
+            None => *bias = Some(DefaultBackend::parameter(shift).cell()),
+        }
+    });
+    true
+}
 
-use super::{Module, StateDict, Trainable};
+/// Checks the hyperparameters shared by the convolutions, so that invalid configs fail when the layer is built rather than in libtorch.
+fn validate_conv(
+    in_channel: i64,
+    out_channel: i64,
+    kernel_size: &[i64],
+    stride: &[i64],
+    padding: &[i64],
+    dilation: &[i64],
+    groups: i64,
+) -> Result<(), String> {
+    if in_channel <= 0 || out_channel <= 0 {
+        return Err(format!(
+            "channels must be positive, got in_channel = {} and out_channel = {}",
+            in_channel, out_channel
+        ));
+    }
+    if groups <= 0 || in_channel % groups != 0 || out_channel % groups != 0 {
+        return Err(format!(
+            "groups = {} must divide in_channel = {} and out_channel = {}",
+            groups, in_channel, out_channel
+        ));
+    }
+    if kernel_size
+        .iter()
+        .chain(stride)
+        .chain(dilation)
+        .any(|size| *size <= 0)
+    {
+        return Err(format!(
+            "kernel_size {:?}, stride {:?} and dilation {:?} must be positive",
+            kernel_size, stride, dilation
+        ));
+    }
+    if padding.iter().any(|size| *size < 0) {
+        return Err(format!("padding {:?} must not be negative", padding));
+    }
+    Ok(())
+}
 
 /// A Convolution layer in 1 dimension.
 ///
 /// See [Convolutional Neural Networks for Sentence Classification](https://arxiv.org/abs/1408.5882).
-#[derive(Debug, CallableModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, ArchitectureBuilder, SaveableModule)]
+#[builder(validate = "validate_conv1d")]
 pub struct Conv1d {
     pub conv_weight: TensorCell,
     pub conv_bias: Option<TensorCell>,
@@ -36,6 +109,25 @@ pub struct Conv1d {
 
     #[builder(default = "true")]
     pub bias: bool,
+
+    #[builder(default = "raddar::util::default_dtype()")]
+    pub dtype: Kind,
+
+    #[builder(default = "raddar::util::default_device()")]
+    #[saveable(skip)]
+    pub device: Device,
+}
+
+fn validate_conv1d(config: &Conv1dConfig) -> Result<(), String> {
+    validate_conv(
+        config.in_channel,
+        config.out_channel,
+        &config.kernel_size,
+        &config.stride,
+        &config.padding,
+        &config.dilation,
+        config.groups,
+    )
 }
 
 impl Trainable for Conv1d {
@@ -47,6 +139,17 @@ impl Trainable for Conv1d {
         }
         result
     }
+
+    fn reset_parameters(&self) {
+        InitScheme::KaimingUniform.init_parameter(self, "weight");
+        InitScheme::FanInUniform.init_parameter(self, "bias");
+    }
+
+    fn fold_channel_affine(&mut self, scale: &Tensor, shift: &Tensor) -> bool {
+        let folded = fold_affine(&self.conv_weight, &mut self.conv_bias, scale, shift);
+        self.bias = self.conv_bias.is_some();
+        folded
+    }
 }
 
 impl Module for Conv1d {
@@ -54,7 +157,8 @@ impl Module for Conv1d {
         let weight = self.conv_weight.lock();
         let bias = self.conv_bias.as_ref().map(|bias| bias.lock());
         let bias = bias.as_deref();
-        input.conv1d(
+        DefaultBackend::conv(
+            input,
             &weight,
             bias,
             &self.stride,
@@ -63,20 +167,29 @@ impl Module for Conv1d {
             self.groups,
         )
     }
+
+    fn try_forward(&self, input: &Tensor) -> Result<Tensor, RaddarError> {
+        let channels = self.conv_weight.lock().size()[1] * self.groups;
+        Shape(vec![Dim::Any("N"), Dim::Exact(channels), Dim::Any("L")]).check("Conv1d", input)?;
+        Ok(self.forward(input))
+    }
 }
 
 impl Conv1d {
     pub fn new(config: Conv1dConfig) -> Conv1d {
-        let size: [i64; 3] = [config.out_channel, config.in_channel, config.kernel_size[0]];
-        let mut conv_weight =
-            Tensor::empty(&size, (Kind::Double, Device::Cpu)).set_requires_grad(true);
-        let mut conv_bias = Tensor::empty(&[config.out_channel], (Kind::Double, Device::Cpu))
-            .set_requires_grad(true);
-        no_grad(|| {
-            conv_weight.init(tch::nn::Init::KaimingUniform);
-            conv_bias.init(tch::nn::Init::KaimingUniform);
-        });
-        Conv1d {
+        let size: [i64; 3] = [
+            config.out_channel,
+            config.in_channel / config.groups,
+            config.kernel_size[0],
+        ];
+        let conv_weight =
+            DefaultBackend::parameter(DefaultBackend::empty(&size, config.dtype, config.device));
+        let conv_bias = DefaultBackend::parameter(DefaultBackend::empty(
+            &[config.out_channel],
+            config.dtype,
+            config.device,
+        ));
+        let conv = Conv1d {
             conv_weight: conv_weight.cell(),
             conv_bias: if config.bias {
                 Some(conv_bias.cell())
@@ -91,12 +204,17 @@ impl Conv1d {
             dilation: config.dilation,
             groups: config.groups,
             bias: config.bias,
-        }
+            dtype: config.dtype,
+            device: config.device,
+        };
+        conv.reset_parameters();
+        conv
     }
 }
 
 /// A Convolution layer in 2 dimensions.
-#[derive(Debug, CallableModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, ArchitectureBuilder, SaveableModule)]
+#[builder(validate = "validate_conv2d")]
 pub struct Conv2d {
     pub conv_weight: TensorCell,
     pub conv_bias: Option<TensorCell>,
@@ -120,6 +238,23 @@ pub struct Conv2d {
     pub groups: i64,
     #[builder(default = "true")]
     pub bias: bool,
+    #[builder(default = "raddar::util::default_dtype()")]
+    pub dtype: Kind,
+    #[builder(default = "raddar::util::default_device()")]
+    #[saveable(skip)]
+    pub device: Device,
+}
+
+fn validate_conv2d(config: &Conv2dConfig) -> Result<(), String> {
+    validate_conv(
+        config.in_channel,
+        config.out_channel,
+        &config.kernel_size,
+        &config.stride,
+        &config.padding,
+        &config.dilation,
+        config.groups,
+    )
 }
 
 impl Trainable for Conv2d {
@@ -131,15 +266,65 @@ impl Trainable for Conv2d {
         }
         result
     }
+
+    fn reset_parameters(&self) {
+        InitScheme::KaimingUniform.init_parameter(self, "weight");
+        InitScheme::FanInUniform.init_parameter(self, "bias");
+    }
+
+    fn fold_channel_affine(&mut self, scale: &Tensor, shift: &Tensor) -> bool {
+        let folded = fold_affine(&self.conv_weight, &mut self.conv_bias, scale, shift);
+        self.bias = self.conv_bias.is_some();
+        folded
+    }
+
+    fn quantize(&self, input: QParams) -> Option<Mod<dyn Module>> {
+        let weight = self.conv_weight.lock();
+        let bias = self.conv_bias.as_ref().map(|bias| bias.lock());
+        Some(Mod::new(QuantizedConv2d::new(
+            &weight,
+            bias.as_deref(),
+            self.stride,
+            self.padding,
+            self.dilation,
+            self.groups,
+            input,
+        )))
+    }
+
+    fn prunable_channels(&self) -> Option<(i64, i64)> {
+        (self.groups == 1).then_some((self.in_channel, self.out_channel))
+    }
+
+    fn channel_importance(&self, criterion: ChannelImportance) -> Option<Tensor> {
+        match criterion {
+            ChannelImportance::L1Norm => Some(filter_norms(&self.conv_weight)),
+            ChannelImportance::BatchNormGamma => None,
+        }
+    }
+
+    fn select_output_channels(&mut self, indices: &Tensor) {
+        select_channels(&self.conv_weight, 0, indices);
+        if let Some(bias) = &self.conv_bias {
+            select_channels(bias, 0, indices);
+        }
+        self.out_channel = indices.size()[0];
+    }
+
+    fn select_input_channels(&mut self, indices: &Tensor) {
+        select_channels(&self.conv_weight, 1, indices);
+        self.in_channel = indices.size()[0];
+    }
 }
 
 impl Module for Conv2d {
     fn forward(&self, input: &Tensor) -> Tensor {
-        let weight = &self.conv_weight.lock();
+        let weight = self.conv_weight.lock();
         let bias = self.conv_bias.as_ref().map(|bias| bias.lock());
         let bias = bias.as_deref();
-        input.conv2d(
-            weight,
+        DefaultBackend::conv(
+            input,
+            &weight,
             bias,
             &self.stride,
             &self.padding,
@@ -147,27 +332,37 @@ impl Module for Conv2d {
             self.groups,
         )
     }
+
+    fn try_forward(&self, input: &Tensor) -> Result<Tensor, RaddarError> {
+        let channels = self.conv_weight.lock().size()[1] * self.groups;
+        Shape(vec![
+            Dim::Any("N"),
+            Dim::Exact(channels),
+            Dim::Any("H"),
+            Dim::Any("W"),
+        ])
+        .check("Conv2d", input)?;
+        Ok(self.forward(input))
+    }
 }
 
 impl Conv2d {
     pub fn new(config: Conv2dConfig) -> Conv2d {
         let size: [i64; 4] = [
             config.out_channel,
-            config.in_channel,
+            config.in_channel / config.groups,
             config.kernel_size[0],
             config.kernel_size[1],
         ];
-        let mut conv_weight =
-            Tensor::empty(&size, (Kind::Double, Device::Cpu)).set_requires_grad(true);
-        let mut conv_bias = Tensor::empty(&[config.out_channel], (Kind::Double, Device::Cpu))
-            .set_requires_grad(true);
-
-        no_grad(|| {
-            conv_weight.init(tch::nn::Init::KaimingUniform);
-            conv_bias.init(tch::nn::Init::KaimingUniform);
-        });
-
-        Conv2d {
+        let conv_weight =
+            DefaultBackend::parameter(DefaultBackend::empty(&size, config.dtype, config.device));
+        let conv_bias = DefaultBackend::parameter(DefaultBackend::empty(
+            &[config.out_channel],
+            config.dtype,
+            config.device,
+        ));
+
+        let conv = Conv2d {
             conv_weight: conv_weight.cell(),
             conv_bias: if config.bias {
                 Some(conv_bias.cell())
@@ -182,12 +377,17 @@ impl Conv2d {
             dilation: config.dilation,
             groups: config.groups,
             bias: config.bias,
-        }
+            dtype: config.dtype,
+            device: config.device,
+        };
+        conv.reset_parameters();
+        conv
     }
 }
 
 /// A convolution layer in 3 dimensions.
-#[derive(Debug, CallableModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, ArchitectureBuilder, SaveableModule)]
+#[builder(validate = "validate_conv3d")]
 pub struct Conv3d {
     pub conv_weight: TensorCell,
     pub conv_bias: Option<TensorCell>,
@@ -215,6 +415,25 @@ pub struct Conv3d {
 
     #[builder(default = "true")]
     pub bias: bool,
+
+    #[builder(default = "raddar::util::default_dtype()")]
+    pub dtype: Kind,
+
+    #[builder(default = "raddar::util::default_device()")]
+    #[saveable(skip)]
+    pub device: Device,
+}
+
+fn validate_conv3d(config: &Conv3dConfig) -> Result<(), String> {
+    validate_conv(
+        config.in_channel,
+        config.out_channel,
+        &config.kernel_size,
+        &config.stride,
+        &config.padding,
+        &config.dilation,
+        config.groups,
+    )
 }
 
 impl Trainable for Conv3d {
@@ -226,15 +445,27 @@ impl Trainable for Conv3d {
         }
         result
     }
+
+    fn reset_parameters(&self) {
+        InitScheme::KaimingUniform.init_parameter(self, "weight");
+        InitScheme::FanInUniform.init_parameter(self, "bias");
+    }
+
+    fn fold_channel_affine(&mut self, scale: &Tensor, shift: &Tensor) -> bool {
+        let folded = fold_affine(&self.conv_weight, &mut self.conv_bias, scale, shift);
+        self.bias = self.conv_bias.is_some();
+        folded
+    }
 }
 
 impl Module for Conv3d {
     fn forward(&self, input: &Tensor) -> Tensor {
-        let weight = &self.conv_weight.lock();
+        let weight = self.conv_weight.lock();
         let bias = self.conv_bias.as_ref().map(|bias| bias.lock());
         let bias = bias.as_deref();
-        input.conv3d(
-            weight,
+        DefaultBackend::conv(
+            input,
+            &weight,
             bias,
             &self.stride,
             &self.padding,
@@ -242,28 +473,39 @@ impl Module for Conv3d {
             self.groups,
         )
     }
+
+    fn try_forward(&self, input: &Tensor) -> Result<Tensor, RaddarError> {
+        let channels = self.conv_weight.lock().size()[1] * self.groups;
+        Shape(vec![
+            Dim::Any("N"),
+            Dim::Exact(channels),
+            Dim::Any("D"),
+            Dim::Any("H"),
+            Dim::Any("W"),
+        ])
+        .check("Conv3d", input)?;
+        Ok(self.forward(input))
+    }
 }
 
 impl Conv3d {
     pub fn new(config: Conv3dConfig) -> Conv3d {
         let size: [i64; 5] = [
             config.out_channel,
-            config.in_channel,
+            config.in_channel / config.groups,
             config.kernel_size[0],
             config.kernel_size[1],
             config.kernel_size[2],
         ];
-        let mut conv_weight =
-            Tensor::empty(&size, (Kind::Double, Device::Cpu)).set_requires_grad(true);
-        let mut conv_bias = Tensor::empty(&[config.out_channel], (Kind::Double, Device::Cpu))
-            .set_requires_grad(true);
-
-        no_grad(|| {
-            conv_weight.init(tch::nn::Init::KaimingUniform);
-            conv_bias.init(tch::nn::Init::KaimingUniform);
-        });
-
-        Conv3d {
+        let conv_weight =
+            DefaultBackend::parameter(DefaultBackend::empty(&size, config.dtype, config.device));
+        let conv_bias = DefaultBackend::parameter(DefaultBackend::empty(
+            &[config.out_channel],
+            config.dtype,
+            config.device,
+        ));
+
+        let conv = Conv3d {
             conv_weight: conv_weight.cell(),
             conv_bias: if config.bias {
                 Some(conv_bias.cell())
@@ -278,6 +520,10 @@ impl Conv3d {
             dilation: config.dilation,
             groups: config.groups,
             bias: config.bias,
-        }
+            dtype: config.dtype,
+            device: config.device,
+        };
+        conv.reset_parameters();
+        conv
     }
 }
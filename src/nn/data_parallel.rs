@@ -0,0 +1,127 @@
+use raddar_derive::CallableModule;
+use tch::{no_grad, Device, Tensor};
+
+use super::{Mod, Module, Trainable, TrainableDict};
+
+/// A wrapper that runs a module on several devices in parallel, by splitting the input batch along the first dimension.
+///
+/// The wrapped module lives on the first device and owns the parameters. Every other device holds a replica, whose parameters are refreshed from the wrapped module before each forward pass as differentiable copies.
+/// Therefore the gradients computed on the replicas flow back to the parameters of the wrapped module and are summed by autograd, and any optimizer over `parameters()` works unchanged.
+///
+/// Buffers (e.g. the running statistics of batch normalization) are copied to the replicas, but only the ones updated by the wrapped module are kept.
+///
+/// # Examples
+/// ```
+/// let model = Mod::new(DataParallel::new(
+///     || LinearBuilder::default().input_dim(10).output_dim(1).build(),
+///     vec![Device::Cuda(0), Device::Cuda(1)],
+/// ));
+/// let output = model(&input);
+/// ```
+#[derive(Debug, CallableModule)]
+pub struct DataParallel<T: Module + Send + Sync + 'static> {
+    pub module: Mod<T>,
+    pub replicas: Vec<Mod<T>>,
+    pub devices: Vec<Device>,
+}
+
+impl<T: Module + Send + Sync + 'static> DataParallel<T> {
+    /// Creates the wrapped module and its replicas with `factory`, one per device.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `devices` is empty.
+    pub fn new<F>(factory: F, devices: Vec<Device>) -> Self
+    where
+        F: Fn() -> Mod<T>,
+    {
+        assert!(
+            !devices.is_empty(),
+            "DataParallel needs at least one device"
+        );
+        let module = factory().to(devices[0]);
+        let replicas = devices[1..]
+            .iter()
+            .map(|&device| factory().to(device))
+            .collect();
+        let this = Self {
+            module,
+            replicas,
+            devices,
+        };
+        this.replicate();
+        this
+    }
+
+    /// Creates a [DataParallel] over all available CUDA devices, or over the CPU if there is none.
+    pub fn all_devices<F>(factory: F) -> Self
+    where
+        F: Fn() -> Mod<T>,
+    {
+        let devices = match tch::Cuda::device_count() {
+            0 => vec![Device::Cpu],
+            count => (0..count as usize).map(Device::Cuda).collect(),
+        };
+        Self::new(factory, devices)
+    }
+
+    /// Refreshes the parameters and buffers of the replicas from the wrapped module.
+    ///
+    /// The parameters of the replicas are copies that track gradients to the wrapped module.
+    fn replicate(&self) {
+        let parameters = self.module.parameters();
+        let buffers = self.module.buffers();
+        for (replica, &device) in self.replicas.iter().zip(self.devices[1..].iter()) {
+            for (name, parameter) in replica.parameters() {
+                if let Some(source) = parameters.get(&name) {
+                    *parameter.lock() = source.lock().to(device);
+                }
+            }
+            no_grad(|| {
+                for (name, tensor) in replica.buffers() {
+                    if let Some(source) = buffers.get(&name) {
+                        *tensor.lock() = source.lock().to(device);
+                    }
+                }
+            });
+            replica.set_training(self.module.is_training());
+        }
+    }
+}
+
+impl<T: Module + Send + Sync + 'static> Trainable for DataParallel<T> {
+    fn child_modules(&self) -> TrainableDict {
+        let mut children = TrainableDict::new();
+        children.insert("module".to_owned(), self.module.clone());
+        children
+    }
+}
+
+impl<T: Module + Send + Sync + 'static> Module for DataParallel<T> {
+    fn forward(&self, input: &Tensor) -> Tensor {
+        if self.replicas.is_empty() {
+            return self.module.module().forward(&input.to(self.devices[0]));
+        }
+        self.replicate();
+        let chunks = input.chunk(self.devices.len() as i64, 0);
+        let outputs: Vec<Tensor> = std::thread::scope(|scope| {
+            let handles: Vec<_> = std::iter::once(&self.module)
+                .chain(self.replicas.iter())
+                .zip(self.devices.iter())
+                .zip(chunks)
+                .map(|((module, &device), chunk)| {
+                    scope.spawn(move || module.module().forward(&chunk.to(device)))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("A DataParallel replica panicked"))
+                .collect()
+        });
+        let outputs: Vec<Tensor> = outputs
+            .iter()
+            .map(|output| output.to(self.devices[0]))
+            .collect();
+        Tensor::cat(&outputs, 0)
+    }
+}
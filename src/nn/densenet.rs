@@ -1,12 +1,12 @@
-use raddar_derive::{ArchitectureBuilder, CallableModule};
+use raddar_derive::{ArchitectureBuilder, CallableModule, SaveableModule, Trainable};
 use tch::Tensor;
 
-use crate::seq;
+use crate::core::{Backend, DefaultBackend};
 
 use super::{
-    AdaptiveAveragePooling2DBuilder, AveragePooling2DBuilder, BatchNorm2dBuilder, Conv2dBuilder,
-    DropoutBuilder, Linear, LinearBuilder, MaxPooling2DBuilder, Mod, Module, ModuleDict,
-    NamedSequential, ReLU, Trainable, TrainableDict,
+    pretrained::TorchvisionWeights, AdaptiveAveragePooling2D, AdaptiveAveragePooling2DBuilder,
+    AveragePooling2DBuilder, BatchNorm2dBuilder, Conv2dBuilder, Dropout, DropoutBuilder, KeyMap,
+    Linear, LinearBuilder, MaxPooling2DBuilder, Mod, Module, ModuleDict, NamedSequential, ReLU,
 };
 
 pub fn transition(num_input_features: i64, num_output_features: i64) -> Mod<NamedSequential> {
@@ -38,10 +38,10 @@ pub fn transition(num_input_features: i64, num_output_features: i64) -> Mod<Name
     Mod::new(res)
 }
 
-#[derive(Debug, CallableModule)]
+#[derive(Debug, CallableModule, Trainable)]
 pub struct DenseLayer {
     modules: ModuleDict,
-    drop_rate: f64,
+    dropout: Option<Mod<Dropout>>,
 }
 impl Module for DenseLayer {
     fn forward(&self, input: &Tensor) -> Tensor {
@@ -55,22 +55,12 @@ impl Module for DenseLayer {
         let bottleneck_output = conv1(&relu1(&norm1(input)));
         let new_features = conv2(&relu2(&norm2(&bottleneck_output)));
         // println!("denselayer output {:?}", new_features.size());
-        if self.drop_rate > 0. {
-            let dropout = DropoutBuilder::default().p(self.drop_rate).build();
-            dropout(&new_features)
-        } else {
-            new_features
+        match &self.dropout {
+            Some(dropout) => dropout(&new_features),
+            None => new_features,
         }
     }
 }
-impl Trainable for DenseLayer {
-    fn child_modules(&self) -> TrainableDict {
-        self.modules
-            .iter()
-            .map(|(key, value)| (key.to_owned(), value.clone() as Mod<dyn Trainable>))
-            .collect()
-    }
-}
 impl DenseLayer {
     pub fn new(
         num_input_features: i64,
@@ -114,7 +104,8 @@ impl DenseLayer {
                 .bias(false)
                 .build(),
         );
-        DenseLayer { modules, drop_rate }
+        let dropout = (drop_rate > 0.).then(|| DropoutBuilder::default().p(drop_rate).build());
+        DenseLayer { modules, dropout }
     }
 }
 
@@ -131,7 +122,7 @@ pub fn denselayer(
         drop_rate,
     ))
 }
-#[derive(Debug, CallableModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, ArchitectureBuilder, SaveableModule, Trainable)]
 pub struct DenseBlock {
     #[builder]
     pub num_layers: i64,
@@ -150,19 +141,11 @@ impl Module for DenseBlock {
         let mut output = input.clone();
         for (_, layer) in &self.layers {
             let new_features = layer(&output);
-            output = Tensor::concat(&[output, new_features], 1);
+            output = DefaultBackend::concat(&[&output, &new_features], 1);
         }
         output
     }
 }
-impl Trainable for DenseBlock {
-    fn child_modules(&self) -> TrainableDict {
-        self.layers
-            .iter()
-            .map(|(key, value)| (key.to_owned(), value.clone() as Mod<dyn Trainable>))
-            .collect()
-    }
-}
 impl DenseBlock {
     pub fn new(config: DenseBlockConfig) -> DenseBlock {
         let mut layers = ModuleDict::new();
@@ -187,9 +170,14 @@ impl DenseBlock {
         }
     }
 }
-#[derive(Debug, CallableModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, ArchitectureBuilder, SaveableModule, Trainable)]
 pub struct DenseNet {
+    #[trainable(prefixed)]
     pub features: NamedSequential,
+    #[trainable(skip)]
+    pub relu: Mod<ReLU>,
+    #[trainable(skip)]
+    pub avgpool: Mod<AdaptiveAveragePooling2D>,
     pub classifier: Mod<Linear>,
     #[builder(default = "32")]
     pub growth_rate: i64,
@@ -207,17 +195,12 @@ pub struct DenseNet {
 impl Module for DenseNet {
     fn forward(&self, input: &Tensor) -> Tensor {
         let features = (self.features)(input);
-        let temp_relu = seq!(Mod::new(ReLU));
-        let temp_avgpool = AdaptiveAveragePooling2DBuilder::default()
-            .output_size([1, 1])
-            .build();
-        let mut out = temp_avgpool(&temp_relu(&features));
-        out = out.flatten(1, 3);
+        let mut out = (self.avgpool)(&(self.relu)(&features));
+        out = DefaultBackend::flatten(&out, 1, 3);
         out = (self.classifier)(&out);
         out
     }
 }
-impl Trainable for DenseNet {}
 impl DenseNet {
     pub fn new(config: DenseNetConfig) -> DenseNet {
         let mut features = NamedSequential::default();
@@ -283,6 +266,10 @@ impl DenseNet {
             .build();
         DenseNet {
             features,
+            relu: Mod::new(ReLU),
+            avgpool: AdaptiveAveragePooling2DBuilder::default()
+                .output_size([1, 1])
+                .build(),
             classifier,
             growth_rate: config.growth_rate,
             block_config: config.block_config,
@@ -303,6 +290,23 @@ pub fn densenet121(num_classes: i64, drop_rate: f64) -> Mod<DenseNet> {
         .build()
 }
 
+/// DenseNet121 initialized with the ImageNet weights of torchvision, see [densenet121].
+///
+/// If `num_classes` is not 1000, the classifier is left randomly initialized, to be fine-tuned.
+pub fn densenet121_pretrained(num_classes: i64, drop_rate: f64) -> anyhow::Result<Mod<DenseNet>> {
+    let model = densenet121(num_classes, drop_rate);
+    TorchvisionWeights {
+        name: "densenet121",
+        key_map: KeyMap::new()
+            .strip_prefix("module.")
+            .skip(r"num_batches_tracked$"),
+        linear_weights: &["classifier.weight"],
+        head: "classifier.",
+    }
+    .load_into(&model, num_classes)?;
+    Ok(model)
+}
+
 pub fn densenet161(num_classes: i64, drop_rate: f64) -> Mod<DenseNet> {
     DenseNetBuilder::default()
         .num_classes(num_classes)
@@ -1,9 +1,13 @@
-use super::Module;
-use raddar_derive::{ArchitectureBuilder, CallableModule, NonParameterModule};
+use super::{Module, Trainable};
+use crate::core::{Backend, DefaultBackend};
+use raddar_derive::{ArchitectureBuilder, CallableModule, SaveableModule};
 use tch::Tensor;
 
 /// A dropout layer.
-#[derive(ArchitectureBuilder, Debug, CallableModule, NonParameterModule)]
+///
+/// Dropout is only applied in training mode, so it is disabled after [Mod::eval](super::Mod::eval).
+#[derive(ArchitectureBuilder, Debug, CallableModule, SaveableModule)]
+#[builder(validate = "validate_dropout")]
 pub struct Dropout {
     #[builder(default = "0.5")]
     p: f64,
@@ -11,9 +15,31 @@ pub struct Dropout {
     train: bool,
 }
 
+fn validate_dropout(config: &DropoutConfig) -> Result<(), String> {
+    if (0. ..=1.).contains(&config.p) {
+        Ok(())
+    } else {
+        Err(format!("p = {} must be in [0, 1]", config.p))
+    }
+}
+
+impl Trainable for Dropout {
+    fn train(&mut self, mode: bool) {
+        self.train = mode;
+    }
+
+    fn is_training(&self) -> bool {
+        self.train
+    }
+
+    fn keeps_channels(&self) -> bool {
+        true
+    }
+}
+
 impl Module for Dropout {
     fn forward(&self, input: &Tensor) -> Tensor {
-        input.dropout(self.p, self.train)
+        DefaultBackend::dropout(input, self.p, self.train)
     }
 }
 
@@ -1,7 +1,10 @@
 use raddar_derive::{CallableModule, NonParameterModule};
-use tch::{no_grad, Device, Kind, Tensor};
+use tch::no_grad;
 
-use crate::core::{Cellable, TensorCell};
+use crate::{
+    core::{Backend, Cellable, DefaultBackend, TensorCell},
+    util::{default_device, default_dtype},
+};
 
 use super::{Module, StateDict, Trainable};
 
@@ -15,13 +18,7 @@ pub struct OneHot {
 
 impl Module for OneHot {
     fn forward(&self, input: &tch::Tensor) -> tch::Tensor {
-        let mut original_size = input.size(); //(32,1)
-        original_size.push(self.num_classes); //(32,1,10)
-        let one_hot = Tensor::zeros(&original_size, (input.kind(), input.device()));
-        let input = input.unsqueeze(-1); //(32,1,1)
-        let one_hot = one_hot.scatter(-1, &input, &input.ones_like());
-        // one_hot.squeeze_dim(-2)
-        one_hot
+        DefaultBackend::one_hot(input, self.num_classes)
     }
 }
 
@@ -39,16 +36,15 @@ pub struct Embedding {
     pub num_embeddings: i64,
     pub embedding_dim: i64,
     pub weight: TensorCell,
-    one_hot: OneHot,
 }
 
 impl Embedding {
     pub fn new(num_embeddings: i64, embedding_dim: i64) -> Self {
-        let mut weight = Tensor::empty(
+        let mut weight = DefaultBackend::parameter(DefaultBackend::empty(
             &[num_embeddings, embedding_dim],
-            (Kind::Double, Device::Cpu),
-        )
-        .set_requires_grad(true);
+            default_dtype(),
+            default_device(),
+        ));
 
         no_grad(|| {
             weight.init(tch::nn::Init::Uniform { lo: 0., up: 1. });
@@ -58,7 +54,6 @@ impl Embedding {
             num_embeddings,
             embedding_dim,
             weight: weight.cell(),
-            one_hot: OneHot::new(num_embeddings as i64),
         }
     }
 }
@@ -73,8 +68,6 @@ impl Trainable for Embedding {
 
 impl Module for Embedding {
     fn forward(&self, input: &tch::Tensor) -> tch::Tensor {
-        let one_hotted = (self.one_hot)(input);
-        let weight = self.weight.lock();
-        one_hotted.type_as(&weight).matmul(&weight)
+        DefaultBackend::embedding(input, &self.weight.lock())
     }
 }
@@ -0,0 +1,59 @@
+use crate::util::DropGuard;
+
+use super::{Mod, Module, StateDict, Trainable};
+
+/// Runs the forward pass of `model` on `input` with the tensors in `parameters` in place of its own parameters and buffers of the same names, e.g. `0.weight`. Tensors missing from `parameters` are the module's own.
+///
+/// The given tensors are used as they are, so the output is differentiable with respect to them, which is what meta-learning like MAML needs to differentiate through an update of the parameters. The tensors of the module are restored afterwards, even if the forward pass panics. The module should not be called from another thread meanwhile.
+///
+/// # Panics
+///
+/// Panics if a name in `parameters` is not a parameter or a buffer of the module.
+///
+/// # Examples
+/// ```
+/// let loss = loss_fn(&model(&support_x), &support_y);
+/// let grads = Tensor::run_backward(&[loss], &weights, true, true);
+/// let adapted: StateDict = names
+///     .iter()
+///     .zip(weights.iter().zip(grads))
+///     .map(|(name, (weight, grad))| (name.clone(), (weight - grad * 0.01).cell()))
+///     .collect();
+/// let query_loss = loss_fn(&functional_call(&model, &adapted, &query_x), &query_y);
+/// ```
+pub fn functional_call<T, I: 'static, O: 'static>(
+    model: &Mod<T>,
+    parameters: &StateDict,
+    input: &I,
+) -> O
+where
+    T: Module<I, O> + ?Sized,
+{
+    let state_dict = model.state_dict();
+    for name in parameters.keys() {
+        assert!(
+            state_dict.contains_key(name),
+            "{} is not a parameter or a buffer of the module",
+            name
+        );
+    }
+    let mut originals: Vec<_> = parameters
+        .iter()
+        .map(|(name, tensor)| {
+            let cell = state_dict[name].clone();
+            // Lock the given tensor first, since it may be the cell of the module itself.
+            let tensor = tensor.lock().shallow_clone();
+            let original = std::mem::replace(&mut *cell.lock(), tensor);
+            (cell, original)
+        })
+        .collect();
+    let _guard = DropGuard::new(
+        (),
+        Box::new(move |_| {
+            for (cell, original) in originals.drain(..) {
+                *cell.lock() = original;
+            }
+        }),
+    );
+    model.call_with_hooks(input, |input| model.module().forward(input))
+}
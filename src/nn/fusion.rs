@@ -0,0 +1,18 @@
+use super::{Mod, Trainable};
+
+/// Folds every batch normalization which follows a convolution in a [Sequential](super::Sequential) or [NamedSequential](super::NamedSequential) of `model` into the weight and bias of the convolution, and removes it. Returns the number of fused pairs.
+///
+/// In evaluation mode, a batch normalization scales and shifts each channel with constants, which the convolution before it can compute for free, so fusing saves a pass over the activations of every convolution of the ResNet, DenseNet and VGG (with batch normalization) families. The fused model gives the same outputs as the original model in evaluation mode, but it cannot be trained like it, so fuse a model once it is trained, e.g. before serving it with an [InferenceEngine](super::InferenceEngine).
+///
+/// Convolutions without a bias, like those of ResNet, get one. Batch normalizations before a convolution, like in the dense layers of DenseNet, cannot be folded and are kept.
+///
+/// # Examples
+/// ```
+/// let mut model = resnet50(1000);
+/// model.load_npz("resnet50.npz")?;
+/// model.eval(true);
+/// fuse_conv_bn(&mut model);
+/// ```
+pub fn fuse_conv_bn<T: Trainable + ?Sized>(model: &mut Mod<T>) -> usize {
+    model.fuse_conv_bn()
+}
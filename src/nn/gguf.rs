@@ -0,0 +1,381 @@
+use std::{collections::HashMap, fs::File, io::Read, path::Path};
+
+use anyhow::{anyhow, bail, Context};
+use serde_json::{json, Value};
+use tch::{Kind, Tensor};
+
+use crate::core::Cellable;
+
+use super::StateDict;
+
+/// The magic number at the start of a GGUF file, "GGUF" in little endian.
+const GGUF_MAGIC: u32 = 0x4655_4747;
+
+/// The alignment of the tensor data when the file does not give `general.alignment`.
+const DEFAULT_ALIGNMENT: u64 = 32;
+
+/// The number of elements in a block of the quantized types.
+const QK: usize = 32;
+
+/// The number of elements in a super-block of the k-quantized types.
+const QK_K: usize = 256;
+
+/// The types of tensors in a GGUF file, as numbered by ggml.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GgmlType {
+    F32,
+    F16,
+    Q4_0,
+    Q4_1,
+    Q5_0,
+    Q8_0,
+    Q4_K,
+    Q6_K,
+    I8,
+    I16,
+    I32,
+    I64,
+    F64,
+    BF16,
+}
+
+impl GgmlType {
+    /// Returns the type numbered `id` by ggml, or an error if it is not supported.
+    pub fn from_id(id: u32) -> anyhow::Result<Self> {
+        Ok(match id {
+            0 => GgmlType::F32,
+            1 => GgmlType::F16,
+            2 => GgmlType::Q4_0,
+            3 => GgmlType::Q4_1,
+            6 => GgmlType::Q5_0,
+            8 => GgmlType::Q8_0,
+            12 => GgmlType::Q4_K,
+            14 => GgmlType::Q6_K,
+            24 => GgmlType::I8,
+            25 => GgmlType::I16,
+            26 => GgmlType::I32,
+            27 => GgmlType::I64,
+            28 => GgmlType::F64,
+            30 => GgmlType::BF16,
+            id => bail!("Unsupported ggml type {}", id),
+        })
+    }
+
+    /// Returns the kind of an unquantized type, or `None` for quantized types.
+    pub fn kind(&self) -> Option<Kind> {
+        match self {
+            GgmlType::F32 => Some(Kind::Float),
+            GgmlType::F16 => Some(Kind::Half),
+            GgmlType::BF16 => Some(Kind::BFloat16),
+            GgmlType::F64 => Some(Kind::Double),
+            GgmlType::I8 => Some(Kind::Int8),
+            GgmlType::I16 => Some(Kind::Int16),
+            GgmlType::I32 => Some(Kind::Int),
+            GgmlType::I64 => Some(Kind::Int64),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of elements and the number of bytes of a block of a quantized type, or `None` for unquantized types.
+    fn block(&self) -> Option<(usize, usize)> {
+        match self {
+            GgmlType::Q4_0 => Some((QK, 18)),
+            GgmlType::Q4_1 => Some((QK, 20)),
+            GgmlType::Q5_0 => Some((QK, 22)),
+            GgmlType::Q8_0 => Some((QK, 34)),
+            GgmlType::Q4_K => Some((QK_K, 144)),
+            GgmlType::Q6_K => Some((QK_K, 210)),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of bytes holding `numel` elements of this type, or `None` if it overflows.
+    fn size_in_bytes(&self, numel: usize) -> Option<usize> {
+        match self.block() {
+            Some((block_size, block_bytes)) => (numel / block_size).checked_mul(block_bytes),
+            None => numel.checked_mul(self.kind().unwrap().elt_size_in_bytes()),
+        }
+    }
+}
+
+/// Reads a GGUF file, the format of llama.cpp, returning its tensors in the order of the file and its metadata.
+///
+/// Quantized tensors (`Q4_0`, `Q4_1`, `Q5_0`, `Q8_0`, `Q4_K` and `Q6_K`) are dequantized to `Kind::Float`, while the other tensors keep their kind. The shapes are given in the order of PyTorch, i.e. the reverse of the dimensions stored by ggml. Note that the names of the tensors are those of llama.cpp (e.g. `blk.0.attn_q.weight`), so they usually need a [KeyMap](super::KeyMap) to be loaded into a module.
+///
+/// # Examples
+/// ```
+/// let (state_dict, metadata) = read_gguf("model.gguf")?;
+/// println!("{}", metadata["general.architecture"]);
+/// model.load_mapped(state_dict, &KeyMap::new().rename(r"^blk\.", "layers."));
+/// ```
+pub fn read_gguf<P: AsRef<Path>>(path: P) -> anyhow::Result<(StateDict, HashMap<String, Value>)> {
+    let path = path.as_ref();
+    let mut buffer = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut buffer))
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    parse_gguf(&buffer).with_context(|| format!("Invalid GGUF file {}", path.display()))
+}
+
+fn parse_gguf(buffer: &[u8]) -> anyhow::Result<(StateDict, HashMap<String, Value>)> {
+    let mut reader = GgufReader { buffer, pos: 0 };
+    if reader.u32()? != GGUF_MAGIC {
+        bail!("The file does not start with the GGUF magic number");
+    }
+    let version = reader.u32()?;
+    if !(2..=3).contains(&version) {
+        bail!("Unsupported GGUF version {}", version);
+    }
+    let tensor_count = reader.u64()?;
+    let metadata_count = reader.u64()?;
+
+    let mut metadata = HashMap::new();
+    for _ in 0..metadata_count {
+        let key = reader.string()?;
+        let value_type = reader.u32()?;
+        let value = reader
+            .value(value_type)
+            .with_context(|| format!("Metadata {}", key))?;
+        metadata.insert(key, value);
+    }
+
+    let mut infos = Vec::new();
+    for _ in 0..tensor_count {
+        let name = reader.string()?;
+        let n_dims = reader.u32()?;
+        let mut shape = (0..n_dims)
+            .map(|_| reader.u64().map(|dim| dim as i64))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        shape.reverse();
+        let ggml_type =
+            GgmlType::from_id(reader.u32()?).with_context(|| format!("Tensor {}", name))?;
+        let offset = reader.u64()?;
+        infos.push((name, shape, ggml_type, offset));
+    }
+
+    let alignment = metadata
+        .get("general.alignment")
+        .and_then(Value::as_u64)
+        .unwrap_or(DEFAULT_ALIGNMENT);
+    let start = (reader.pos as u64 + alignment - 1) / alignment * alignment;
+    let data = buffer.get(start as usize..).unwrap_or(&[]);
+
+    let mut state_dict = StateDict::new();
+    for (name, shape, ggml_type, offset) in infos {
+        let numel = shape
+            .iter()
+            .try_fold(1usize, |numel, dim| numel.checked_mul(*dim as usize))
+            .ok_or_else(|| anyhow!("Tensor {} of shape {:?} is too large", name, shape))?;
+        if let Some((block_size, _)) = ggml_type.block() {
+            if numel % block_size != 0 {
+                bail!(
+                    "Tensor {} of type {:?} has {} elements, which is not a multiple of the block size {}",
+                    name,
+                    ggml_type,
+                    numel,
+                    block_size
+                );
+            }
+        }
+        let begin = offset as usize;
+        let end = ggml_type
+            .size_in_bytes(numel)
+            .and_then(|size| begin.checked_add(size))
+            .filter(|end| *end <= data.len())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Tensor {} of type {:?} and shape {:?} at offset {} is out of the {} bytes of data",
+                    name,
+                    ggml_type,
+                    shape,
+                    begin,
+                    data.len()
+                )
+            })?;
+        let bytes = &data[begin..end];
+        let tensor = match ggml_type.kind() {
+            Some(kind) => Tensor::of_data_size(bytes, &shape, kind),
+            None => Tensor::of_slice(&dequantize(ggml_type, bytes)).reshape(&shape),
+        };
+        state_dict.insert(name, tensor.cell());
+    }
+    Ok((state_dict, metadata))
+}
+
+/// Dequantizes the blocks of a quantized tensor.
+fn dequantize(ggml_type: GgmlType, bytes: &[u8]) -> Vec<f32> {
+    let mut values = Vec::with_capacity(bytes.len() * 2);
+    match ggml_type {
+        GgmlType::Q4_0 => {
+            for block in bytes.chunks_exact(18) {
+                let d = f16_to_f32(&block[0..2]);
+                let qs = &block[2..];
+                values.extend(qs.iter().map(|q| ((q & 0xF) as i32 - 8) as f32 * d));
+                values.extend(qs.iter().map(|q| ((q >> 4) as i32 - 8) as f32 * d));
+            }
+        }
+        GgmlType::Q4_1 => {
+            for block in bytes.chunks_exact(20) {
+                let d = f16_to_f32(&block[0..2]);
+                let m = f16_to_f32(&block[2..4]);
+                let qs = &block[4..];
+                values.extend(qs.iter().map(|q| (q & 0xF) as f32 * d + m));
+                values.extend(qs.iter().map(|q| (q >> 4) as f32 * d + m));
+            }
+        }
+        GgmlType::Q5_0 => {
+            for block in bytes.chunks_exact(22) {
+                let d = f16_to_f32(&block[0..2]);
+                let qh = u32::from_le_bytes(block[2..6].try_into().unwrap());
+                let qs = &block[6..];
+                values.extend(qs.iter().enumerate().map(|(j, q)| {
+                    let high = ((qh >> j) << 4) & 0x10;
+                    (((q & 0xF) as u32 | high) as i32 - 16) as f32 * d
+                }));
+                values.extend(qs.iter().enumerate().map(|(j, q)| {
+                    let high = (qh >> (j + 12)) & 0x10;
+                    (((q >> 4) as u32 | high) as i32 - 16) as f32 * d
+                }));
+            }
+        }
+        GgmlType::Q8_0 => {
+            for block in bytes.chunks_exact(34) {
+                let d = f16_to_f32(&block[0..2]);
+                values.extend(block[2..].iter().map(|q| *q as i8 as f32 * d));
+            }
+        }
+        GgmlType::Q4_K => {
+            for block in bytes.chunks_exact(144) {
+                let d = f16_to_f32(&block[0..2]);
+                let dmin = f16_to_f32(&block[2..4]);
+                let scales = &block[4..16];
+                // Each 32 bytes of quants hold two sub-blocks of 32 elements, in the low then in the high nibbles.
+                for (j, qs) in block[16..].chunks_exact(32).enumerate() {
+                    let (scale1, min1) = scale_min_k4(2 * j, scales);
+                    let (scale2, min2) = scale_min_k4(2 * j + 1, scales);
+                    let (d1, m1, d2, m2) = (d * scale1, dmin * min1, d * scale2, dmin * min2);
+                    values.extend(qs.iter().map(|q| (q & 0xF) as f32 * d1 - m1));
+                    values.extend(qs.iter().map(|q| (q >> 4) as f32 * d2 - m2));
+                }
+            }
+        }
+        GgmlType::Q6_K => {
+            for block in bytes.chunks_exact(210) {
+                let (ql, rest) = block.split_at(128);
+                let (qh, rest) = rest.split_at(64);
+                let (scales, d) = rest.split_at(16);
+                let d = f16_to_f32(d);
+                // Each half of the super-block holds 128 elements, with their low 4 bits in 64 bytes and their high 2 bits in 32 bytes.
+                for half in 0..2 {
+                    let ql = &ql[64 * half..];
+                    let qh = &qh[32 * half..];
+                    let scales = &scales[8 * half..];
+                    let mut half_values = [0f32; 128];
+                    for l in 0..32 {
+                        let quants = [
+                            (ql[l] & 0xF) | ((qh[l] & 3) << 4),
+                            (ql[l + 32] & 0xF) | (((qh[l] >> 2) & 3) << 4),
+                            (ql[l] >> 4) | (((qh[l] >> 4) & 3) << 4),
+                            (ql[l + 32] >> 4) | (((qh[l] >> 6) & 3) << 4),
+                        ];
+                        for (k, q) in quants.iter().enumerate() {
+                            let scale = scales[l / 16 + 2 * k] as i8 as f32;
+                            half_values[l + 32 * k] = d * scale * (*q as i32 - 32) as f32;
+                        }
+                    }
+                    values.extend(half_values);
+                }
+            }
+        }
+        ggml_type => unreachable!("{:?} is not quantized", ggml_type),
+    }
+    values
+}
+
+/// Unpacks the 6-bit scale and min of the sub-block `j` of a `Q4_K` super-block.
+fn scale_min_k4(j: usize, scales: &[u8]) -> (f32, f32) {
+    let (scale, min) = if j < 4 {
+        (scales[j] & 63, scales[j + 4] & 63)
+    } else {
+        (
+            (scales[j + 4] & 0xF) | ((scales[j - 4] >> 6) << 4),
+            (scales[j + 4] >> 4) | ((scales[j] >> 6) << 4),
+        )
+    };
+    (scale as f32, min as f32)
+}
+
+/// Converts a little endian IEEE half precision float to `f32`.
+fn f16_to_f32(bytes: &[u8]) -> f32 {
+    let bits = u16::from_le_bytes([bytes[0], bytes[1]]) as u32;
+    let sign = (bits & 0x8000) << 16;
+    let exponent = (bits >> 10) & 0x1F;
+    let mantissa = bits & 0x3FF;
+    let bits = match (exponent, mantissa) {
+        (0, 0) => sign,
+        (0, _) => {
+            // Subnormal, normalize the mantissa.
+            let shift = mantissa.leading_zeros() - 21;
+            sign | ((113 - shift) << 23) | (((mantissa << shift) & 0x3FF) << 13)
+        }
+        (0x1F, _) => sign | 0x7F80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 112) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+struct GgufReader<'a> {
+    buffer: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> GgufReader<'a> {
+    fn bytes(&mut self, n: usize) -> anyhow::Result<&'a [u8]> {
+        let bytes = self
+            .buffer
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| anyhow!("Unexpected end of file at byte {}", self.pos))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> anyhow::Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let len = self.u64()? as usize;
+        Ok(String::from_utf8(self.bytes(len)?.to_vec())?)
+    }
+
+    fn value(&mut self, value_type: u32) -> anyhow::Result<Value> {
+        Ok(match value_type {
+            0 => json!(self.bytes(1)?[0]),
+            1 => json!(self.bytes(1)?[0] as i8),
+            2 => json!(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap())),
+            3 => json!(i16::from_le_bytes(self.bytes(2)?.try_into().unwrap())),
+            4 => json!(self.u32()?),
+            5 => json!(self.u32()? as i32),
+            6 => json!(f32::from_bits(self.u32()?)),
+            7 => json!(self.bytes(1)?[0] != 0),
+            8 => json!(self.string()?),
+            9 => {
+                let item_type = self.u32()?;
+                let len = self.u64()?;
+                Value::Array(
+                    (0..len)
+                        .map(|_| self.value(item_type))
+                        .collect::<anyhow::Result<_>>()?,
+                )
+            }
+            10 => json!(self.u64()?),
+            11 => json!(self.u64()? as i64),
+            12 => json!(f64::from_bits(self.u64()?)),
+            value_type => bail!("Unsupported metadata type {}", value_type),
+        })
+    }
+}
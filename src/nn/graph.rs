@@ -0,0 +1,61 @@
+use std::fmt::Write;
+
+use super::{summary::module_kind, Mod, Trainable};
+
+/// Returns a Graphviz graph of the module hierarchy of `model` in the DOT language, with a node for each module labelled by its name, its type and the shapes of its own parameters and buffers, and an edge from each module to its child modules.
+///
+/// Render it with `dot -Tsvg model.dot -o model.svg`.
+///
+/// # Examples
+/// ```
+/// let model = seq!(
+///     LinearBuilder::default().input_dim(784).output_dim(256).build(),
+///     Mod::new(ReLU),
+///     LinearBuilder::default().input_dim(256).output_dim(10).build(),
+/// );
+/// std::fs::write("model.dot", to_dot(&model))?;
+/// ```
+pub fn to_dot<T: Trainable + ?Sized>(model: &Mod<T>) -> String {
+    let mut dot = String::from("digraph model {\n    node [shape=box];\n");
+    let mut next_id = 1;
+    let module = model.module();
+    write_node(&mut dot, 0, &module_kind(&*module), &*module);
+    drop(module);
+    write_children(&mut dot, 0, &model.children(), &mut next_id);
+    dot.push_str("}\n");
+    dot
+}
+
+fn write_children(
+    dot: &mut String,
+    parent_id: usize,
+    children: &linked_hash_map::LinkedHashMap<String, Mod<dyn Trainable>>,
+    next_id: &mut usize,
+) {
+    for (name, child) in children {
+        let id = *next_id;
+        *next_id += 1;
+        let module = child.module();
+        write_node(
+            dot,
+            id,
+            &format!("{} ({})", name, module_kind(&*module)),
+            &*module,
+        );
+        drop(module);
+        writeln!(dot, "    n{} -> n{};", parent_id, id).unwrap();
+        write_children(dot, id, &child.children(), next_id);
+    }
+}
+
+fn write_node<T: Trainable + ?Sized>(dot: &mut String, id: usize, title: &str, module: &T) {
+    let mut label = escape(title);
+    for (name, tensor) in module.state_dict() {
+        write!(label, "\\n{}: {:?}", escape(&name), tensor.lock().size()).unwrap();
+    }
+    writeln!(dot, "    n{} [label=\"{}\"];", id, label).unwrap();
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
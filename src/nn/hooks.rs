@@ -0,0 +1,237 @@
+use std::{
+    any::Any,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+};
+
+use parking_lot::RwLock;
+use tch::Tensor;
+
+use crate::core::RaddarError;
+
+use super::{Mod, Trainable};
+
+/// A hook called after the forward pass of a module, with the path of the module, its input and its output.
+pub type ForwardHook = Arc<dyn Fn(&str, &Tensor, &Tensor) + Send + Sync>;
+
+/// A hook called before the forward pass of a module, with the path of the module and its input.
+pub type ForwardPreHook = Arc<dyn Fn(&str, &Tensor) + Send + Sync>;
+
+/// A hook called after the backward pass with the path of a parameter and its gradient, which can be modified in place.
+pub type BackwardHook = Arc<dyn Fn(&str, &mut Tensor) + Send + Sync>;
+
+static NEXT_HOOK_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The hooks registered on a module, with the ids identifying them in their [HookHandle]s.
+pub struct Hooks<H>(RwLock<Vec<(usize, H)>>);
+
+impl<H> std::fmt::Debug for Hooks<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Hooks").field(&self.0.read().len()).finish()
+    }
+}
+
+impl<H> Default for Hooks<H> {
+    fn default() -> Self {
+        Self(RwLock::new(Vec::new()))
+    }
+}
+
+impl<H: Clone + Send + Sync + 'static> Hooks<H> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn register(self: &Arc<Self>, hook: H) -> HookHandle {
+        let id = NEXT_HOOK_ID.fetch_add(1, Ordering::Relaxed);
+        self.0.write().push((id, hook));
+        let hooks = Arc::downgrade(self);
+        HookHandle {
+            remove: Box::new(move || {
+                if let Some(hooks) = Weak::upgrade(&hooks) {
+                    hooks.0.write().retain(|(hook_id, _)| *hook_id != id);
+                }
+            }),
+        }
+    }
+
+    /// Returns the registered hooks, so that they can be called without holding the lock.
+    pub fn get(&self) -> Vec<H> {
+        self.0.read().iter().map(|(_, hook)| hook.clone()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.read().is_empty()
+    }
+}
+
+/// A handle to a registered hook, which can be used to remove it.
+///
+/// Dropping the handle does not remove the hook.
+pub struct HookHandle {
+    remove: Box<dyn FnOnce() + Send + Sync>,
+}
+
+impl HookHandle {
+    /// Remove the hook from the module it is registered on.
+    pub fn remove(self) {
+        (self.remove)();
+    }
+}
+
+impl std::fmt::Debug for HookHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HookHandle").finish()
+    }
+}
+
+impl<T: Trainable + ?Sized> Mod<T> {
+    /// Register a hook called after every forward pass of the module, with the path of the module (see [Mod::path]), its input and its output.
+    ///
+    /// Hooks are only called when the module is called through its [Mod], e.g. `model(&input)`, and only for modules from `Tensor` to `Tensor`.
+    ///
+    /// # Examples
+    /// ```
+    /// let features = Arc::new(Mutex::new(Vec::new()));
+    /// let captured = features.clone();
+    /// let handle = backbone.register_forward_hook(move |_, _, output| {
+    ///     captured.lock().push(output.detach());
+    /// });
+    /// model(&input);
+    /// handle.remove();
+    /// ```
+    pub fn register_forward_hook<F>(&self, hook: F) -> HookHandle
+    where
+        F: Fn(&str, &Tensor, &Tensor) + Send + Sync + 'static,
+    {
+        self.forward_hooks.register(Arc::new(hook))
+    }
+
+    /// Register a hook called before every forward pass of the module, with the path of the module and its input.
+    ///
+    /// Like [Mod::register_forward_hook], hooks are only called when the module is called through its [Mod].
+    pub fn register_forward_pre_hook<F>(&self, hook: F) -> HookHandle
+    where
+        F: Fn(&str, &Tensor) + Send + Sync + 'static,
+    {
+        self.forward_pre_hooks.register(Arc::new(hook))
+    }
+
+    /// Register a hook called with the gradient of every parameter of the module and its child modules, after the backward pass. The hook receives the path of the parameter from the root of the model, e.g. `layer1.0.conv1.weight`.
+    ///
+    /// The gradient can be modified in place, e.g. to clip it per layer. libtorch does not let Rust hook into the autograd engine, so backward hooks are called by [Mod::run_backward_hooks], which the [Trainer](crate::train::Trainer) calls after each backward pass, before the callbacks. In a custom training loop, call it after `loss.backward()`.
+    ///
+    /// # Examples
+    /// ```
+    /// model.register_backward_hook(|_, grad| {
+    ///     let _ = grad.clamp_(-1., 1.);
+    /// });
+    /// loss.backward();
+    /// model.run_backward_hooks();
+    /// optimizer.step();
+    /// ```
+    pub fn register_backward_hook<F>(&self, hook: F) -> HookHandle
+    where
+        F: Fn(&str, &mut Tensor) + Send + Sync + 'static,
+    {
+        self.backward_hooks.register(Arc::new(hook))
+    }
+
+    /// Register a backward hook for the parameter of the module named `name`, e.g. `weight` or `0.bias`, see [Mod::register_backward_hook].
+    pub fn register_parameter_hook<F>(&self, name: &str, hook: F) -> HookHandle
+    where
+        F: Fn(&mut Tensor) + Send + Sync + 'static,
+    {
+        let path = join_path(&self.path(), name);
+        self.register_backward_hook(move |parameter, grad| {
+            if parameter == path {
+                hook(grad);
+            }
+        })
+    }
+
+    /// Call the backward hooks of the module and its child modules with the current gradients of their parameters. Parameters without a gradient are skipped.
+    pub fn run_backward_hooks(&self) {
+        let hooks = self.backward_hooks.get();
+        if !hooks.is_empty() {
+            let path = self.path();
+            for (name, parameter) in self.parameters() {
+                let mut grad = parameter.lock().grad();
+                if !grad.defined() {
+                    continue;
+                }
+                let name = join_path(&path, &name);
+                for hook in hooks.iter() {
+                    hook(&name, &mut grad);
+                }
+            }
+        }
+        let children: Vec<_> = self.children.read().values().cloned().collect();
+        for child in children {
+            child.run_backward_hooks();
+        }
+    }
+
+    /// Returns the path of the module from the root of the model, made of the names of the module and its ancestors joined with dots, e.g. `layer1.0.conv1`. The path of the root is empty.
+    pub fn path(&self) -> String {
+        let mut names = Vec::new();
+        let mut current = Arc::as_ptr(&self.arc) as *const ();
+        let mut parent = self.parent();
+        while let Some(module) = parent {
+            let name = module
+                .children
+                .read()
+                .iter()
+                .find(|(_, child)| Arc::as_ptr(&child.arc) as *const () == current)
+                .map(|(name, _)| name.clone());
+            match name {
+                Some(name) => names.push(name),
+                None => break,
+            }
+            current = Arc::as_ptr(&module.arc) as *const ();
+            parent = module.parent();
+        }
+        names.reverse();
+        names.join(".")
+    }
+
+    /// Run `forward` on `input`, calling the forward hooks of the module around it if the input and output are tensors. A successful `Result` of a tensor, as returned by [Mod::try_forward], counts as a tensor.
+    pub fn call_with_hooks<I: 'static, O: 'static>(
+        &self,
+        input: &I,
+        forward: impl FnOnce(&I) -> O,
+    ) -> O {
+        if self.forward_pre_hooks.is_empty() && self.forward_hooks.is_empty() {
+            return forward(input);
+        }
+        let path = self.path();
+        let tensor_input = (input as &dyn Any).downcast_ref::<Tensor>();
+        if let Some(tensor_input) = tensor_input {
+            for hook in self.forward_pre_hooks.get() {
+                hook(&path, tensor_input);
+            }
+        }
+        let output = forward(input);
+        let tensor_output = (&output as &dyn Any).downcast_ref::<Tensor>().or_else(|| {
+            (&output as &dyn Any)
+                .downcast_ref::<Result<Tensor, RaddarError>>()
+                .and_then(|output| output.as_ref().ok())
+        });
+        if let (Some(tensor_input), Some(tensor_output)) = (tensor_input, tensor_output) {
+            for hook in self.forward_hooks.get() {
+                hook(&path, tensor_input, tensor_output);
+            }
+        }
+        output
+    }
+}
+
+fn join_path(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{}.{}", prefix, name)
+    }
+}
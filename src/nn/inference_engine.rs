@@ -0,0 +1,175 @@
+use std::{
+    sync::mpsc::{channel, Receiver, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use anyhow::bail;
+use parking_lot::Mutex;
+use tch::Tensor;
+
+use super::{Mod, Module};
+
+/// A request to an [InferenceEngine]: a batch of inputs and the channel to send its outputs to.
+struct Request {
+    input: Tensor,
+    output: Sender<Tensor>,
+}
+
+/// Serves a model to many threads, by batching their requests into a single forward pass.
+///
+/// The engine owns the model and runs it on a worker thread. Each request is a batch of inputs, e.g. of shape `[1, 3, 224, 224]` for a single image. The worker waits for up to `max_latency` after the first pending request for more requests, until they hold `max_batch_size` samples, then concatenates them, runs [Mod::infer] and sends back to each request its slice of the output. Requests whose inputs have different shapes (apart from the batch dimension) are run in separate forward passes.
+///
+/// The engine is `Sync`, so share it between threads with an `Arc`, e.g. in the state of a web service. Dropping it stops the worker after the pending requests.
+///
+/// # Examples
+/// ```
+/// let engine = Arc::new(InferenceEngine::new(
+///     || resnet50(1000),
+///     32,
+///     Duration::from_millis(5),
+/// ));
+/// let handles: Vec<_> = images
+///     .into_iter()
+///     .map(|image| {
+///         let engine = engine.clone();
+///         std::thread::spawn(move || engine.infer(&image.unsqueeze(0)).unwrap())
+///     })
+///     .collect();
+/// ```
+pub struct InferenceEngine {
+    sender: Option<Mutex<Sender<Request>>>,
+    worker: Option<JoinHandle<()>>,
+    pub max_batch_size: usize,
+    pub max_latency: Duration,
+}
+
+impl InferenceEngine {
+    /// Spawns the worker thread, which creates the model with `factory` and runs it on batches of at most `max_batch_size` samples, waiting at most `max_latency` to fill a batch.
+    ///
+    /// The model is created on the worker thread since modules are not sent between threads, e.g. load a checkpoint in `factory`.
+    pub fn new<T, F>(factory: F, max_batch_size: usize, max_latency: Duration) -> Self
+    where
+        T: Module + ?Sized + 'static,
+        F: FnOnce() -> Mod<T> + Send + 'static,
+    {
+        let max_batch_size = max_batch_size.max(1);
+        let (sender, receiver) = channel();
+        let worker = thread::spawn(move || {
+            let model = factory();
+            while let Some(requests) = next_batch(&receiver, max_batch_size, max_latency) {
+                run_batch(&model, requests);
+            }
+        });
+        Self {
+            sender: Some(Mutex::new(sender)),
+            worker: Some(worker),
+            max_batch_size,
+            max_latency,
+        }
+    }
+
+    /// Queues a batch of inputs, and returns the channel receiving its outputs once its batch has run.
+    ///
+    /// Returns an error if the input is a scalar, since it has no batch dimension.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the worker has stopped because the model panicked.
+    pub fn submit(&self, input: &Tensor) -> anyhow::Result<Receiver<Tensor>> {
+        if input.dim() == 0 {
+            bail!(
+                "The input of an inference engine must have a batch dimension, but it is a scalar"
+            );
+        }
+        let (output, receiver) = channel();
+        self.sender
+            .as_ref()
+            .unwrap()
+            .lock()
+            .send(Request {
+                input: input.shallow_clone(),
+                output,
+            })
+            .expect("The inference engine has stopped");
+        Ok(receiver)
+    }
+
+    /// Runs a batch of inputs in the next batch of the engine, and waits for its outputs.
+    ///
+    /// Returns an error if the input is a scalar, see [InferenceEngine::submit].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the worker has stopped because the model panicked.
+    pub fn infer(&self, input: &Tensor) -> anyhow::Result<Tensor> {
+        Ok(self
+            .submit(input)?
+            .recv()
+            .expect("The inference engine has stopped"))
+    }
+}
+
+impl Drop for InferenceEngine {
+    fn drop(&mut self) {
+        // Closing the channel stops the worker once it has run the pending requests.
+        drop(self.sender.take());
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for InferenceEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InferenceEngine")
+            .field("max_batch_size", &self.max_batch_size)
+            .field("max_latency", &self.max_latency)
+            .finish()
+    }
+}
+
+/// Waits for the next request, then collects more requests until they hold `max_batch_size` samples or `max_latency` has passed. Returns `None` once the engine is dropped and all requests are run.
+fn next_batch(
+    receiver: &Receiver<Request>,
+    max_batch_size: usize,
+    max_latency: Duration,
+) -> Option<Vec<Request>> {
+    let first = receiver.recv().ok()?;
+    let deadline = Instant::now() + max_latency;
+    let mut size = first.input.size().first().copied().unwrap_or(1) as usize;
+    let mut requests = vec![first];
+    while size < max_batch_size {
+        match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(request) => {
+                size += request.input.size().first().copied().unwrap_or(1) as usize;
+                requests.push(request);
+            }
+            Err(_) => break,
+        }
+    }
+    Some(requests)
+}
+
+/// Runs the requests in one forward pass per input shape, and sends each request its outputs.
+fn run_batch<T: Module + ?Sized>(model: &Mod<T>, mut requests: Vec<Request>) {
+    while !requests.is_empty() {
+        let shape = requests[0].input.size()[1..].to_vec();
+        let (group, rest): (Vec<_>, Vec<_>) = requests
+            .into_iter()
+            .partition(|request| request.input.size()[1..] == shape[..]);
+        requests = rest;
+
+        let device = model.device();
+        let inputs: Vec<_> = group
+            .iter()
+            .map(|request| request.input.to(device))
+            .collect();
+        let sizes: Vec<_> = inputs.iter().map(|input| input.size()[0]).collect();
+        let output = model.infer(&Tensor::cat(&inputs, 0));
+        for (request, output) in group.iter().zip(output.split_with_sizes(&sizes, 0)) {
+            // The requesting thread may have given up on the result.
+            let _ = request.output.send(output);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use tch::{no_grad, Tensor};
+
+use super::Trainable;
+
+/// A scheme to initialize a parameter of a layer, scaled by the fan-in and fan-out of the layer (see [Trainable::fans]).
+///
+/// Layers are initialized with their default scheme by [Trainable::reset_parameters]. Other schemes can be applied selectively to the layers of a model with [Mod::apply](super::Mod::apply).
+///
+/// # Examples
+/// ```
+/// model.apply(|module| {
+///     InitScheme::KaimingNormal.init_parameter(module, "weight");
+///     InitScheme::Constant(0.).init_parameter(module, "bias");
+/// });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InitScheme {
+    /// Uniform in `[-b, b]` with `b = sqrt(6 / fan_in)`, for layers followed by a ReLU. The default for the weights of convolutions.
+    KaimingUniform,
+    /// Normal with standard deviation `sqrt(2 / fan_in)`.
+    KaimingNormal,
+    /// Uniform in `[-b, b]` with `b = sqrt(6 / (fan_in + fan_out))`. The default for the weights of fully-connected layers.
+    XavierUniform,
+    /// Normal with standard deviation `sqrt(2 / (fan_in + fan_out))`.
+    XavierNormal,
+    /// Uniform in `[-b, b]` with `b = 1 / sqrt(fan_in)`. The default for biases.
+    FanInUniform,
+    /// Uniform in `[lo, up]`.
+    Uniform { lo: f64, up: f64 },
+    /// Normal with mean `mean` and standard deviation `stdev`.
+    Normal { mean: f64, stdev: f64 },
+    /// Filled with a constant, e.g. ones for the weights and zeros for the biases of normalization layers.
+    Constant(f64),
+}
+
+impl InitScheme {
+    /// Fill `tensor` in place, without tracking gradients, given the fan-in and fan-out of its layer.
+    pub fn init(&self, tensor: &mut Tensor, (fan_in, fan_out): (i64, i64)) {
+        let fan_in = fan_in.max(1) as f64;
+        let fan_out = fan_out.max(1) as f64;
+        no_grad(|| {
+            let _ = match *self {
+                InitScheme::KaimingUniform => {
+                    let bound = (6. / fan_in).sqrt();
+                    tensor.uniform_(-bound, bound)
+                }
+                InitScheme::KaimingNormal => tensor.normal_(0., (2. / fan_in).sqrt()),
+                InitScheme::XavierUniform => {
+                    let bound = (6. / (fan_in + fan_out)).sqrt();
+                    tensor.uniform_(-bound, bound)
+                }
+                InitScheme::XavierNormal => tensor.normal_(0., (2. / (fan_in + fan_out)).sqrt()),
+                InitScheme::FanInUniform => {
+                    let bound = 1. / fan_in.sqrt();
+                    tensor.uniform_(-bound, bound)
+                }
+                InitScheme::Uniform { lo, up } => tensor.uniform_(lo, up),
+                InitScheme::Normal { mean, stdev } => tensor.normal_(mean, stdev),
+                InitScheme::Constant(value) => tensor.fill_(value),
+            };
+        });
+    }
+
+    /// Initialize the parameter or buffer of `module` named `name`, e.g. `weight` or `bias`, with the fans of `module`. Modules without such a tensor are left unchanged, so this can be called on every layer in [Mod::apply](super::Mod::apply).
+    pub fn init_parameter<T: Trainable + ?Sized>(&self, module: &T, name: &str) {
+        if let Some(tensor) = module.state_dict().get(name) {
+            let fans = module.fans().unwrap_or_else(|| weight_fans(&tensor.lock()));
+            self.init(&mut tensor.lock(), fans);
+        }
+    }
+}
+
+/// Returns the fan-in and fan-out of a weight in the layout of convolutions, `[out, in, *kernel]`.
+pub fn weight_fans(weight: &Tensor) -> (i64, i64) {
+    let size = weight.size();
+    match size.len() {
+        0 => (1, 1),
+        1 => (size[0], size[0]),
+        _ => {
+            let receptive_field: i64 = size[2..].iter().product();
+            (size[1] * receptive_field, size[0] * receptive_field)
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::fmt;
+
+use regex::Regex;
+
+use super::StateDict;
+
+/// A rule of a [KeyMap].
+enum KeyRule {
+    StripPrefix(String),
+    AddPrefix(String),
+    Rename(Regex, String),
+    Skip(Regex),
+}
+
+impl fmt::Debug for KeyRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyRule::StripPrefix(prefix) => write!(f, "StripPrefix({:?})", prefix),
+            KeyRule::AddPrefix(prefix) => write!(f, "AddPrefix({:?})", prefix),
+            KeyRule::Rename(pattern, replacement) => {
+                write!(f, "Rename({:?}, {:?})", pattern.as_str(), replacement)
+            }
+            KeyRule::Skip(pattern) => write!(f, "Skip({:?})", pattern.as_str()),
+        }
+    }
+}
+
+/// Rules renaming the keys of a [StateDict], e.g. to load a checkpoint of another framework whose modules are named differently.
+///
+/// The rules are applied to every key in the order they were added. A key is dropped if it matches a [skip](KeyMap::skip) rule.
+///
+/// # Examples
+/// Loading the backbone of a torchvision ResNet-18 checkpoint into [resnet18](super::resnet18), whose stem and layers are in a `Sequential` named `net`, e.g. from `layer1.0.conv1.weight` to `net.4.0.block.0.weight`:
+/// ```
+/// let key_map = KeyMap::new()
+///     .strip_prefix("module.")
+///     .skip(r"^fc\.")
+///     .rename(r"^conv1\.", "net.0.")
+///     .rename(r"^bn1\.", "net.1.0.")
+///     .rename(r"^layer1\.", "net.4.")
+///     .rename(r"^layer2\.", "net.5.")
+///     .rename(r"^layer3\.", "net.6.")
+///     .rename(r"^layer4\.", "net.7.")
+///     .rename(r"\.conv1\.", ".block.0.")
+///     .rename(r"\.bn1\.", ".block.1.0.")
+///     .rename(r"\.conv2\.", ".block.3.")
+///     .rename(r"\.bn2\.", ".block.4.0.")
+///     .rename(r"\.downsample\.1\.", ".downsample.1.0.");
+/// model.load_mapped(StateDict::from_safetensors("resnet18.safetensors")?, &key_map);
+/// ```
+#[derive(Debug, Default)]
+pub struct KeyMap {
+    rules: Vec<KeyRule>,
+}
+
+impl KeyMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove `prefix` from the keys starting with it, e.g. the `module.` prefix of models saved from a PyTorch `DataParallel`.
+    pub fn strip_prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.rules.push(KeyRule::StripPrefix(prefix.into()));
+        self
+    }
+
+    /// Add `prefix` to all keys, e.g. to load the weights of a backbone into a larger model.
+    pub fn add_prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.rules.push(KeyRule::AddPrefix(prefix.into()));
+        self
+    }
+
+    /// Replace all matches of the regular expression `pattern` with `replacement`, which can refer to capture groups as `$1` or `${name}`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is not a valid regular expression.
+    pub fn rename(mut self, pattern: &str, replacement: &str) -> Self {
+        self.rules.push(KeyRule::Rename(
+            Regex::new(pattern).expect("Invalid key pattern"),
+            replacement.to_owned(),
+        ));
+        self
+    }
+
+    /// Drop the keys matching the regular expression `pattern`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is not a valid regular expression.
+    pub fn skip(mut self, pattern: &str) -> Self {
+        self.rules.push(KeyRule::Skip(
+            Regex::new(pattern).expect("Invalid key pattern"),
+        ));
+        self
+    }
+
+    /// Returns the new name of `key`, or `None` if it is dropped.
+    pub fn map_key(&self, key: &str) -> Option<String> {
+        let mut key = key.to_owned();
+        for rule in &self.rules {
+            key = match rule {
+                KeyRule::StripPrefix(prefix) => match key.strip_prefix(prefix.as_str()) {
+                    Some(stripped) => stripped.to_owned(),
+                    None => key,
+                },
+                KeyRule::AddPrefix(prefix) => format!("{}{}", prefix, key),
+                KeyRule::Rename(pattern, replacement) => {
+                    pattern.replace_all(&key, replacement.as_str()).into_owned()
+                }
+                KeyRule::Skip(pattern) => {
+                    if pattern.is_match(&key) {
+                        return None;
+                    }
+                    key
+                }
+            };
+        }
+        Some(key)
+    }
+
+    /// Renames the keys of a [StateDict], keeping the order of the tensors.
+    pub fn apply(&self, state_dict: StateDict) -> StateDict {
+        state_dict
+            .into_iter()
+            .filter_map(|(key, tensor)| self.map_key(&key).map(|key| (key, tensor)))
+            .collect()
+    }
+}
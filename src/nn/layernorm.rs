@@ -1,12 +1,12 @@
-use super::{module::Module, StateDict, Trainable};
-use crate::core::{Cellable, TensorCell};
-use raddar_derive::{ArchitectureBuilder, CallableModule};
+use super::{module::Module, InitScheme, StateDict, Trainable};
+use crate::core::{Backend, Cellable, DefaultBackend, Dim, RaddarError, Shape, TensorCell};
+use raddar_derive::{ArchitectureBuilder, CallableModule, SaveableModule};
 use tch::{Device, Kind, Tensor};
 
 /// A layer normalization layer.
 ///
 /// See [Layer Normalization](https://arxiv.org/abs/1607.06450).
-#[derive(Debug, CallableModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, ArchitectureBuilder, SaveableModule)]
 pub struct LayerNorm {
     pub ln_weight: Option<TensorCell>,
     pub ln_bias: Option<TensorCell>,
@@ -18,6 +18,11 @@ pub struct LayerNorm {
     pub cudnn_enable: bool,
     #[builder(default = "true")]
     pub elementwise_affine: bool,
+    #[builder(default = "raddar::util::default_dtype()")]
+    pub dtype: Kind,
+    #[builder(default = "raddar::util::default_device()")]
+    #[saveable(skip)]
+    pub device: Device,
 }
 
 impl Trainable for LayerNorm {
@@ -32,6 +37,11 @@ impl Trainable for LayerNorm {
         }
         result
     }
+
+    fn reset_parameters(&self) {
+        InitScheme::Constant(1.).init_parameter(self, "weight");
+        InitScheme::Constant(0.).init_parameter(self, "bias");
+    }
 }
 
 impl Module for LayerNorm {
@@ -40,26 +50,35 @@ impl Module for LayerNorm {
         let ln_weight = ln_weight.as_deref();
         let ln_bias = self.ln_bias.as_ref().map(|bias| bias.lock());
         let ln_bias = ln_bias.as_deref();
-        input.layer_norm(
-            &*self.shape,
+        DefaultBackend::layer_norm(
+            input,
+            &self.shape,
             ln_weight,
             ln_bias,
             self.eps,
             self.cudnn_enable,
         )
     }
+
+    fn try_forward(&self, input: &Tensor) -> Result<Tensor, RaddarError> {
+        let shape = std::iter::once(Dim::Rest)
+            .chain(self.shape.iter().map(|size| Dim::Exact(*size)))
+            .collect();
+        Shape(shape).check("LayerNorm", input)?;
+        Ok(self.forward(input))
+    }
 }
 
 impl LayerNorm {
     pub fn new(config: LayerNormConfig) -> LayerNorm {
         let size = &*config.shape;
         let ln_weight = if config.elementwise_affine {
-            Some(Tensor::ones(size, (Kind::Double, Device::Cpu)).cell())
+            Some(DefaultBackend::ones(size, config.dtype, config.device).cell())
         } else {
             None
         };
         let ln_bias = if config.elementwise_affine {
-            Some(Tensor::zeros(size, (Kind::Double, Device::Cpu)).cell())
+            Some(DefaultBackend::zeros(size, config.dtype, config.device).cell())
         } else {
             None
         };
@@ -70,6 +89,8 @@ impl LayerNorm {
             eps: config.eps,
             cudnn_enable: config.cudnn_enable,
             elementwise_affine: config.elementwise_affine,
+            dtype: config.dtype,
+            device: config.device,
         }
     }
 }
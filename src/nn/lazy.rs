@@ -0,0 +1,261 @@
+use raddar_derive::{ArchitectureBuilder, CallableModule, SaveableModule};
+use tch::{Device, Kind, Tensor};
+
+use crate::core::{Backend, Cellable, DefaultBackend, Dim, RaddarError, Shape, TensorCell};
+
+use super::{weight_fans, InitScheme, Module, StateDict, Trainable};
+
+/// Returns whether `tensor` is the placeholder of a lazy layer whose weight is not created yet.
+///
+/// Loading a state dict into a placeholder creates the weight with the shape in the state dict, without a shape check.
+pub fn is_uninitialized(tensor: &Tensor) -> bool {
+    tensor.size() == [0]
+}
+
+/// Creates the placeholder of a weight which is created by the first forward pass.
+fn placeholder(dtype: Kind, device: Device) -> TensorCell {
+    DefaultBackend::parameter(DefaultBackend::empty(&[0], dtype, device)).cell()
+}
+
+/// Replaces the placeholder in `weight` with a new weight of shape `size`, unless it is already created. Returns whether the weight is created, in which case the layer should reset its parameters.
+fn materialize(weight: &TensorCell, size: &[i64]) -> bool {
+    let mut weight = weight.lock();
+    if is_uninitialized(&weight) {
+        *weight =
+            DefaultBackend::parameter(DefaultBackend::empty(size, weight.kind(), weight.device()));
+        true
+    } else {
+        false
+    }
+}
+
+/// A fully-connected layer whose input dimension is inferred from the first input.
+///
+/// The weight is created by the first forward pass, and is an empty placeholder in the parameters until then. Run a forward pass before creating the optimizer, e.g. with a dummy batch, so that it trains the created weight. Loading a state dict also creates the weight.
+///
+/// # Examples
+/// ```
+/// let classifier = seq!(
+///     LazyLinearBuilder::default().output_dim(4096).build(),
+///     Mod::new(ReLU),
+///     LinearBuilder::default().input_dim(4096).output_dim(10).build(),
+/// );
+/// ```
+#[derive(Debug, CallableModule, ArchitectureBuilder, SaveableModule)]
+pub struct LazyLinear {
+    pub linear_weight: TensorCell,
+    pub linear_bias: Option<TensorCell>,
+    #[builder]
+    pub output_dim: i64,
+    #[builder(default = "true")]
+    pub bias: bool,
+    #[builder(default = "raddar::util::default_dtype()")]
+    pub dtype: Kind,
+    #[builder(default = "raddar::util::default_device()")]
+    #[saveable(skip)]
+    pub device: Device,
+}
+
+impl Trainable for LazyLinear {
+    fn parameters(&self) -> StateDict {
+        let mut result = StateDict::new();
+        result.insert("weight".to_owned(), self.linear_weight.clone());
+        if let Some(bias) = &self.linear_bias {
+            result.insert("bias".to_owned(), bias.clone());
+        }
+        result
+    }
+
+    fn fans(&self) -> Option<(i64, i64)> {
+        self.input_dim()
+            .map(|input_dim| (input_dim, self.output_dim))
+    }
+
+    /// Like [Linear](super::Linear), once the weight is created. Until then, the bias is zero.
+    fn reset_parameters(&self) {
+        if self.input_dim().is_some() {
+            InitScheme::XavierUniform.init_parameter(self, "weight");
+            InitScheme::FanInUniform.init_parameter(self, "bias");
+        }
+    }
+}
+
+impl Module for LazyLinear {
+    fn forward(&self, input: &Tensor) -> Tensor {
+        if materialize(
+            &self.linear_weight,
+            &[input.size()[input.dim() - 1], self.output_dim],
+        ) {
+            self.reset_parameters();
+        }
+        let weight = self.linear_weight.lock();
+        let bias = self.linear_bias.as_ref().map(|bias| bias.lock());
+        DefaultBackend::linear(input, &weight, bias.as_deref())
+    }
+
+    fn try_forward(&self, input: &Tensor) -> Result<Tensor, RaddarError> {
+        let weight = self.linear_weight.lock();
+        let input_dim = if is_uninitialized(&weight) {
+            Dim::Any("in")
+        } else {
+            Dim::Exact(weight.size()[0])
+        };
+        drop(weight);
+        Shape(vec![Dim::Rest, input_dim]).check("LazyLinear", input)?;
+        Ok(self.forward(input))
+    }
+}
+
+impl LazyLinear {
+    pub fn new(config: LazyLinearConfig) -> LazyLinear {
+        let linear_bias = DefaultBackend::parameter(DefaultBackend::zeros(
+            &[config.output_dim],
+            config.dtype,
+            config.device,
+        ));
+        LazyLinear {
+            linear_weight: placeholder(config.dtype, config.device),
+            linear_bias: if config.bias {
+                Some(linear_bias.cell())
+            } else {
+                None
+            },
+            output_dim: config.output_dim,
+            bias: config.bias,
+            dtype: config.dtype,
+            device: config.device,
+        }
+    }
+
+    /// Returns the input dimension, or `None` before the first forward pass.
+    pub fn input_dim(&self) -> Option<i64> {
+        let weight = self.linear_weight.lock();
+        (!is_uninitialized(&weight)).then(|| weight.size()[0])
+    }
+}
+
+/// A convolution layer in 2 dimensions whose number of input channels is inferred from the first input.
+///
+/// Like [LazyLinear], the weight is created by the first forward pass.
+#[derive(Debug, CallableModule, ArchitectureBuilder, SaveableModule)]
+pub struct LazyConv2d {
+    pub conv_weight: TensorCell,
+    pub conv_bias: Option<TensorCell>,
+    #[builder]
+    pub out_channel: i64,
+    #[builder]
+    pub kernel_size: [i64; 2],
+    #[builder(default = "[1, 1]")]
+    pub stride: [i64; 2],
+    #[builder(default = "[0, 0]")]
+    pub padding: [i64; 2],
+    #[builder(default = "[1, 1]")]
+    pub dilation: [i64; 2],
+    #[builder(default = "1")]
+    pub groups: i64,
+    #[builder(default = "true")]
+    pub bias: bool,
+    #[builder(default = "raddar::util::default_dtype()")]
+    pub dtype: Kind,
+    #[builder(default = "raddar::util::default_device()")]
+    #[saveable(skip)]
+    pub device: Device,
+}
+
+impl Trainable for LazyConv2d {
+    fn parameters(&self) -> StateDict {
+        let mut result = StateDict::new();
+        result.insert("weight".to_owned(), self.conv_weight.clone());
+        if let Some(bias) = &self.conv_bias {
+            result.insert("bias".to_owned(), bias.clone());
+        }
+        result
+    }
+
+    fn fans(&self) -> Option<(i64, i64)> {
+        self.in_channel()
+            .map(|_| weight_fans(&self.conv_weight.lock()))
+    }
+
+    /// Like [Conv2d](super::Conv2d), once the weight is created. Until then, the bias is zero.
+    fn reset_parameters(&self) {
+        if self.in_channel().is_some() {
+            InitScheme::KaimingUniform.init_parameter(self, "weight");
+            InitScheme::FanInUniform.init_parameter(self, "bias");
+        }
+    }
+}
+
+impl Module for LazyConv2d {
+    fn forward(&self, input: &Tensor) -> Tensor {
+        if materialize(
+            &self.conv_weight,
+            &[
+                self.out_channel,
+                input.size()[1] / self.groups,
+                self.kernel_size[0],
+                self.kernel_size[1],
+            ],
+        ) {
+            self.reset_parameters();
+        }
+        let weight = self.conv_weight.lock();
+        let bias = self.conv_bias.as_ref().map(|bias| bias.lock());
+        let bias = bias.as_deref();
+        DefaultBackend::conv(
+            input,
+            &weight,
+            bias,
+            &self.stride,
+            &self.padding,
+            &self.dilation,
+            self.groups,
+        )
+    }
+
+    fn try_forward(&self, input: &Tensor) -> Result<Tensor, RaddarError> {
+        let weight = self.conv_weight.lock();
+        let channels = if is_uninitialized(&weight) {
+            Dim::Any("C")
+        } else {
+            Dim::Exact(weight.size()[1] * self.groups)
+        };
+        drop(weight);
+        Shape(vec![Dim::Any("N"), channels, Dim::Any("H"), Dim::Any("W")])
+            .check("LazyConv2d", input)?;
+        Ok(self.forward(input))
+    }
+}
+
+impl LazyConv2d {
+    pub fn new(config: LazyConv2dConfig) -> LazyConv2d {
+        let conv_bias = DefaultBackend::parameter(DefaultBackend::zeros(
+            &[config.out_channel],
+            config.dtype,
+            config.device,
+        ));
+        LazyConv2d {
+            conv_weight: placeholder(config.dtype, config.device),
+            conv_bias: if config.bias {
+                Some(conv_bias.cell())
+            } else {
+                None
+            },
+            out_channel: config.out_channel,
+            kernel_size: config.kernel_size,
+            stride: config.stride,
+            padding: config.padding,
+            dilation: config.dilation,
+            groups: config.groups,
+            bias: config.bias,
+            dtype: config.dtype,
+            device: config.device,
+        }
+    }
+
+    /// Returns the number of input channels, or `None` before the first forward pass.
+    pub fn in_channel(&self) -> Option<i64> {
+        let weight = self.conv_weight.lock();
+        (!is_uninitialized(&weight)).then(|| weight.size()[1] * self.groups)
+    }
+}
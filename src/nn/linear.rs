@@ -1,12 +1,15 @@
-use raddar_derive::{ArchitectureBuilder, CallableModule};
-use tch::{no_grad, Device, Kind, Tensor};
+use raddar_derive::{ArchitectureBuilder, CallableModule, SaveableModule};
+use tch::{Device, Kind, Tensor};
 
-use crate::core::{Cellable, TensorCell};
+use crate::core::{Backend, Cellable, DefaultBackend, Dim, RaddarError, Shape, TensorCell};
 
-use super::{module::Module, Trainable, StateDict};
+use super::{
+    module::Module, quantize_weight, InitScheme, Mod, QParams, QuantizedLinear, StateDict,
+    Trainable,
+};
 
 // A simple fully-connected layer.
-#[derive(Debug, CallableModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, ArchitectureBuilder, SaveableModule)]
 pub struct Linear {
     pub linear_weight: TensorCell,
     pub linear_bias: Option<TensorCell>,
@@ -16,52 +19,127 @@ pub struct Linear {
     pub output_dim: i64,
     #[builder(default = "true")]
     pub bias: bool,
+    #[builder(default = "raddar::util::default_dtype()")]
+    pub dtype: Kind,
+    #[builder(default = "raddar::util::default_device()")]
+    #[saveable(skip)]
+    pub device: Device,
 }
 
 impl Trainable for Linear {
+    /// The weight and the bias are buffers instead once the weight is quantized by [quantize_dynamic](super::quantize_dynamic), as they are not trained anymore.
     fn parameters(&self) -> StateDict {
-        let mut result = StateDict::new();
-        result.insert("weight".to_owned(), self.linear_weight.clone());
+        if self.is_quantized() {
+            return StateDict::new();
+        }
+        self.weight_and_bias()
+    }
+
+    fn buffers(&self) -> StateDict {
+        if self.is_quantized() {
+            return self.weight_and_bias();
+        }
+        StateDict::new()
+    }
+
+    /// The weight is stored as `[input_dim, output_dim]`, transposed from the layout of convolutions.
+    fn fans(&self) -> Option<(i64, i64)> {
+        Some((self.input_dim, self.output_dim))
+    }
+
+    fn reset_parameters(&self) {
+        InitScheme::XavierUniform.init_parameter(self, "weight");
+        InitScheme::FanInUniform.init_parameter(self, "bias");
+    }
+
+    fn quantize(&self, input: QParams) -> Option<Mod<dyn Module>> {
+        let weight = self.linear_weight.lock();
+        let bias = self.linear_bias.as_ref().map(|bias| bias.lock());
+        Some(Mod::new(QuantizedLinear::new(
+            &weight,
+            bias.as_deref(),
+            input,
+        )))
+    }
+
+    /// The weight is replaced with its 8-bit quantization, and dequantized at each forward pass. The weight and the bias become buffers.
+    fn quantize_weights(&self) -> usize {
+        let mut weight = self.linear_weight.lock();
+        if weight.kind() == Kind::QInt8 {
+            return 0;
+        }
+        *weight = quantize_weight(&weight, 1);
         if let Some(bias) = &self.linear_bias {
-            result.insert("bias".to_owned(), bias.clone());
+            let mut bias = bias.lock();
+            *bias = bias.detach();
         }
-        result
+        1
     }
 }
 
 impl Module for Linear {
     fn forward(&self, input: &Tensor) -> Tensor {
-        let weight = &self.linear_weight.lock();
-        if let Some(bias) = &self.linear_bias {
-            let bias = bias.lock();
-            input.matmul(&weight) + &*bias
-        } else {
-            input.matmul(&weight)
+        let weight = self.linear_weight.lock();
+        let bias = self.linear_bias.as_ref().map(|bias| bias.lock());
+        // The weight was quantized by `quantize_dynamic`. It is dequantized for the matrix multiplication, so this is not faster than the floating point layer.
+        if weight.kind() == Kind::QInt8 {
+            let input = QParams::fake_quantize_dynamic(input);
+            let weight = weight.dequantize().to_kind(input.kind());
+            return DefaultBackend::linear(
+                &input,
+                &weight.to_device(input.device()),
+                bias.as_deref(),
+            );
         }
+        DefaultBackend::linear(input, &weight, bias.as_deref())
+    }
+
+    fn try_forward(&self, input: &Tensor) -> Result<Tensor, RaddarError> {
+        Shape(vec![Dim::Rest, Dim::Exact(self.input_dim)]).check("Linear", input)?;
+        Ok(self.forward(input))
     }
 }
 
 impl Linear {
+    fn is_quantized(&self) -> bool {
+        self.linear_weight.lock().kind() == Kind::QInt8
+    }
+
+    fn weight_and_bias(&self) -> StateDict {
+        let mut result = StateDict::new();
+        result.insert("weight".to_owned(), self.linear_weight.clone());
+        if let Some(bias) = &self.linear_bias {
+            result.insert("bias".to_owned(), bias.clone());
+        }
+        result
+    }
+
     pub fn new(config: LinearConfig) -> Linear {
         let input_dim = config.input_dim;
         let output_dim = config.output_dim;
         let bias = config.bias;
-        let mut weight = Tensor::empty(&[input_dim, output_dim], (Kind::Double, Device::Cpu))
-            .set_requires_grad(true);
+        let weight = DefaultBackend::parameter(DefaultBackend::empty(
+            &[input_dim, output_dim],
+            config.dtype,
+            config.device,
+        ));
 
-        let mut linear_bias =
-            Tensor::empty(&[output_dim], (Kind::Double, Device::Cpu)).set_requires_grad(true);
+        let linear_bias = DefaultBackend::parameter(DefaultBackend::empty(
+            &[output_dim],
+            config.dtype,
+            config.device,
+        ));
 
-        no_grad(|| {
-            weight.init(tch::nn::Init::KaimingUniform);
-            linear_bias.init(tch::nn::Init::KaimingUniform);
-        });
-        Linear {
+        let linear = Linear {
             linear_weight: weight.cell(),
             linear_bias: if bias { Some(linear_bias.cell()) } else { None },
             input_dim,
             output_dim,
             bias,
-        }
+            dtype: config.dtype,
+            device: config.device,
+        };
+        linear.reset_parameters();
+        linear
     }
 }
@@ -1,29 +1,72 @@
 pub use act_funcs::*;
 pub use alexnet::*;
 pub use batchnorm::*;
+pub use checkpoint::*;
 pub use conv::*;
+pub use data_parallel::*;
 pub use densenet::*;
 pub use dropout::*;
 pub use embedding::*;
+pub use functional::*;
+pub use fusion::*;
+pub use gguf::*;
+pub use graph::*;
+pub use hooks::*;
+pub use inference_engine::*;
+pub use init::*;
 pub use layernorm::*;
+pub use lazy::*;
+pub use key_map::*;
 pub use linear::*;
 pub use module::*;
 pub use pooling::*;
+pub use profiler::*;
+pub use pruning::*;
+pub use quantization::*;
+pub use registry::*;
 pub use resnet::*;
+pub use safetensors::*;
+pub use saveable::*;
+pub use script_module::*;
 pub use sequential::*;
+pub use sharded::*;
+pub use state_dict::*;
+pub use summary::*;
 pub use vgg::*;
 
 pub mod act_funcs;
 pub mod alexnet;
 pub mod batchnorm;
+pub mod checkpoint;
 pub mod conv;
+pub mod data_parallel;
 pub mod densenet;
 pub mod dropout;
 pub mod embedding;
+pub mod functional;
+pub mod fusion;
+pub mod gguf;
+pub mod graph;
+pub mod hooks;
+pub mod inference_engine;
+pub mod init;
 pub mod layernorm;
+pub mod lazy;
+pub mod key_map;
 pub mod linear;
 pub mod module;
 pub mod pooling;
+pub(crate) mod pretrained;
+pub mod profiler;
+pub mod pruning;
+pub mod quantization;
+pub mod registry;
 pub mod resnet;
+pub mod safetensors;
+pub mod saveable;
+pub mod script_module;
 pub mod sequential;
+pub mod sharded;
+pub mod state_dict;
+pub mod summary;
 pub mod vgg;
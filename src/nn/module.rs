@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     marker::Unsize,
     ops::{CoerceUnsized, Deref},
     path::Path,
@@ -7,12 +8,21 @@ use std::{
 
 use anyhow::Ok;
 use linked_hash_map::LinkedHashMap;
-use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
-use tch::{no_grad, Device, Tensor};
+use parking_lot::{
+    ReentrantMutex, ReentrantMutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard,
+};
+use regex::Regex;
+use tch::{no_grad, Device, Kind, Tensor};
 
 use crate::{
-    core::{Cellable, TensorCell},
-    util::DropGuard,
+    core::{RaddarError, TensorCell},
+    util::{default_device, inference_mode, DropGuard},
+};
+
+use super::{
+    save_sharded, state_dict::load_checked, weight_fans, BackwardHook, ChannelImportance,
+    ForwardHook, ForwardPreHook, Hooks, InitScheme, KeyMap, QParams, ShardedCheckpoint,
+    StateDictExt,
 };
 
 /// A `StateDict` is a collection of named tensors. It uses [LinkedHashMap] to preserve the insertion order of the tensors. This is useful when saving and loading the model.
@@ -35,8 +45,19 @@ pub enum ModuleMode {
     Train,
     Eval,
 }
+/// Converts a module to a `&mut dyn Trainable`, so that [Trainable::apply] can visit modules of any type. This is implemented for every sized [Trainable].
+pub trait AsTrainable {
+    fn as_trainable_mut(&mut self) -> &mut (dyn Trainable + '_);
+}
+
+impl<T: Trainable> AsTrainable for T {
+    fn as_trainable_mut(&mut self) -> &mut (dyn Trainable + '_) {
+        self
+    }
+}
+
 /// A trait for anything that has trainable parameters.
-pub trait Trainable: std::fmt::Debug {
+pub trait Trainable: std::fmt::Debug + AsTrainable {
     /// Defines the trainable parameters of the module. This does not include the parameters in child modules.
     ///
     /// By default, this returns an empty map. If your module has trainable parameters, you should override this method.
@@ -44,13 +65,46 @@ pub trait Trainable: std::fmt::Debug {
         LinkedHashMap::new()
     }
 
-    /// Defines the static tensors of the module. This does not include the static tensors in child modules.
+    /// Returns an iterator over the parameters of the module and their names.
+    ///
+    /// On a [Mod], the parameters of the child modules are included, named by their dotted paths like the keys of [Trainable::parameters], e.g. `layer1.0.conv1.weight`. The child modules are visited lazily, so filtering the parameters by name does not build a [StateDict] of the whole model.
+    ///
+    /// # Examples
+    /// ```
+    /// for (_, parameter) in model.named_parameters().filter(|(name, _)| name.ends_with("bias")) {
+    ///     parameter.lock().set_requires_grad(false);
+    /// }
+    /// ```
+    fn named_parameters(&self) -> Box<dyn Iterator<Item = (String, TensorCell)>> {
+        Box::new(self.parameters().into_iter())
+    }
+
+    /// Defines the buffers of the module, i.e. persistent tensors which are not trained, like the running statistics of batch normalization. This does not include the buffers in child modules.
     ///
-    /// By default, this returns an empty map. If your module has static tensors, you should override this method.
+    /// Buffers are moved along with the parameters, and are saved and loaded in [Trainable::state_dict].
+    ///
+    /// By default, this returns the deprecated [Trainable::static_tensors], so that modules which still override it keep their buffers. If your module has buffers, you should override this method.
+    fn buffers(&self) -> StateDict {
+        #[allow(deprecated)]
+        self.static_tensors()
+    }
+
+    /// Defines the static tensors of the module, which are now called buffers, see [Trainable::buffers].
+    ///
+    /// By default, this returns an empty map.
+    #[deprecated(note = "static tensors are now called buffers, use `buffers` instead")]
     fn static_tensors(&self) -> StateDict {
         LinkedHashMap::new()
     }
 
+    /// Returns the parameters and the buffers of the module, which together describe its state, like the `state_dict` of PyTorch.
+    fn state_dict(&self) -> StateDict {
+        self.parameters()
+            .into_iter()
+            .chain(self.buffers())
+            .collect()
+    }
+
     /// Defines the child modules of the module.
     ///
     /// By default, this returns an empty map. If your module has child modules, you should override this method.
@@ -58,22 +112,69 @@ pub trait Trainable: std::fmt::Debug {
         LinkedHashMap::new()
     }
 
-    /// Returns the size of the parameters of the module.
+    /// Returns the number of parameter tensors of the module. See [Trainable::num_parameters] for the number of scalars in them.
     fn parameter_size(&self) -> usize {
         self.parameters().len()
     }
 
-    /// Load the parameters from another `StateDict`.
+    /// Returns the total number of elements of the parameters of the module, e.g. about 61 million for AlexNet. On a [Mod], this includes the parameters in child modules.
+    fn num_parameters(&self) -> usize {
+        self.parameters()
+            .values()
+            .map(|parameter| parameter.lock().numel())
+            .sum()
+    }
+
+    /// Returns the total number of elements of the parameters which are not frozen, see [Trainable::num_parameters].
+    fn num_trainable_parameters(&self) -> usize {
+        self.training_parameters()
+            .iter()
+            .map(|parameter| parameter.lock().numel())
+            .sum()
+    }
+
+    /// Returns the memory size of the parameters of the module in bytes, which depends on their kind.
+    fn parameter_bytes(&self) -> usize {
+        self.parameters()
+            .values()
+            .map(|parameter| {
+                let parameter = parameter.lock();
+                parameter.numel() * parameter.kind().elt_size_in_bytes()
+            })
+            .sum()
+    }
+
+    /// Load the parameters and buffers from another `StateDict`.
     ///
-    /// This method will load all parameters with the same name from the `StateDict` into the module.
+    /// This method will load all parameters and buffers with the same name from the `StateDict` into the module. Half precision parameters, e.g. from a checkpoint saved with [StateDictExt::cast], are cast back to the kind of the parameters of the module.
     fn load(&self, parameters: StateDict) {
+        let state_dict = self.state_dict();
         for (name, other_parameter) in parameters {
-            if let Some(parameter) = self.parameters().get(&name) {
-                *parameter.lock() = other_parameter.lock().shallow_clone();
+            if let Some(parameter) = state_dict.get(&name) {
+                if Arc::ptr_eq(parameter, &other_parameter) {
+                    continue;
+                }
+                let mut parameter = parameter.lock();
+                let other_parameter = other_parameter.lock();
+                *parameter = match other_parameter.kind() {
+                    Kind::Half | Kind::BFloat16 if other_parameter.kind() != parameter.kind() => {
+                        other_parameter
+                            .to_kind(parameter.kind())
+                            .set_requires_grad(parameter.requires_grad())
+                    }
+                    _ => other_parameter.shallow_clone(),
+                };
             }
         }
     }
 
+    /// Load the parameters from another `StateDict`, after renaming its keys with a [KeyMap].
+    ///
+    /// This is useful to load checkpoints whose modules are named differently, e.g. those of torchvision.
+    fn load_mapped(&self, parameters: StateDict, key_map: &KeyMap) {
+        self.load(key_map.apply(parameters));
+    }
+
     /// Returns all trainable parameters that is not freezed.
     fn training_parameters(&self) -> Vec<TensorCell> {
         self.parameters()
@@ -103,6 +204,26 @@ pub trait Trainable: std::fmt::Debug {
         }
     }
 
+    /// Freeze the parameters whose names match the regular expression `pattern`, e.g. `^(conv1|bn1|layer[1-4])\.` to freeze the backbone of a ResNet and train only its `fc` head. Returns the number of frozen parameters.
+    ///
+    /// On a [Mod], the names are the dotted paths of [Trainable::named_parameters]. Like [KeyMap::skip], the pattern matches anywhere in the name unless anchored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is not a valid regular expression.
+    fn freeze_matching(&self, pattern: &str) -> usize {
+        set_requires_grad_matching(self.named_parameters(), pattern, false)
+    }
+
+    /// Unfreeze the parameters whose names match the regular expression `pattern`, see [Trainable::freeze_matching]. Returns the number of unfrozen parameters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is not a valid regular expression.
+    fn unfreeze_matching(&self, pattern: &str) -> usize {
+        set_requires_grad_matching(self.named_parameters(), pattern, true)
+    }
+
     /// Clear the gradients of the trainable parameters of the module.
     fn zero_grad(&self) {
         self.parameters().values().for_each(|param| {
@@ -111,6 +232,28 @@ pub trait Trainable: std::fmt::Debug {
         });
     }
 
+    /// Returns the L2 norm of the gradient of each parameter, keyed by its name like [Trainable::named_parameters]. Parameters without a gradient, e.g. frozen ones or before the first backward pass, are skipped.
+    ///
+    /// This is handy to debug exploding or vanishing gradients, e.g. by logging the norms after the backward pass in a [Callback](crate::train::Callback).
+    fn grad_norms(&self) -> LinkedHashMap<String, f64> {
+        self.named_parameters()
+            .filter_map(|(name, parameter)| {
+                let grad = parameter.lock().grad();
+                grad.defined()
+                    .then(|| (name, f64::from(grad.norm().to_kind(Kind::Double))))
+            })
+            .collect()
+    }
+
+    /// Returns the total L2 norm of the gradients of the parameters, as if they were concatenated into a single vector, see [Trainable::grad_norms].
+    fn grad_norm(&self) -> f64 {
+        self.grad_norms()
+            .values()
+            .map(|norm| norm * norm)
+            .sum::<f64>()
+            .sqrt()
+    }
+
     /// Initialize the trainable parameters of the module, with a certain distribution from `tch::nn::Init`.
     fn init(&self, init: tch::nn::Init) {
         no_grad(|| {
@@ -119,6 +262,161 @@ pub trait Trainable: std::fmt::Debug {
             }
         });
     }
+
+    /// Returns the fan-in and fan-out of the layer, i.e. the number of inputs and outputs of each of its units, which scale the [InitScheme]s.
+    ///
+    /// By default, this is computed from the shape of the `weight` parameter in the layout of convolutions, `[out, in, *kernel]`, see [weight_fans]. Override it if the weight has another layout.
+    fn fans(&self) -> Option<(i64, i64)> {
+        self.parameters()
+            .get("weight")
+            .map(|weight| weight_fans(&weight.lock()))
+    }
+
+    /// Initialize the parameters of the module with the default scheme of the layer, e.g. [InitScheme::XavierUniform] for the weight of a fully-connected layer and [InitScheme::FanInUniform] for its bias. On a [Mod], this also resets the parameters in child modules, so it resets a whole model.
+    ///
+    /// By default, this does nothing. Layers with parameters override it, and call it when they are built.
+    fn reset_parameters(&self) {}
+
+    /// Returns the scale and shift of the channels computed by the module in evaluation mode, if it is a batch normalization, so that [fuse_conv_bn](super::fuse_conv_bn) can fold it into the convolution before it.
+    ///
+    /// By default, this returns `None`. [BatchNorm2d](super::BatchNorm2d) and the other batch normalizations override it.
+    fn batch_norm_affine(&self) -> Option<(Tensor, Tensor)> {
+        None
+    }
+
+    /// Folds a scale and a shift of the output channels into the parameters of the module, if it is a convolution, and returns whether it did, see [fuse_conv_bn](super::fuse_conv_bn).
+    ///
+    /// By default, this returns `false`. [Conv2d](super::Conv2d) and the other convolutions override it.
+    fn fold_channel_affine(&mut self, _scale: &Tensor, _shift: &Tensor) -> bool {
+        false
+    }
+
+    /// Fuses every convolution followed by a batch normalization among the modules of a container like [Sequential](super::Sequential), and returns the number of fused pairs. On a [Mod], this also fuses the child modules, see [fuse_conv_bn](super::fuse_conv_bn).
+    ///
+    /// By default, this does nothing.
+    fn fuse_conv_bn(&mut self) -> usize {
+        0
+    }
+
+    /// Returns an 8-bit replacement of the module, whose inputs are quantized with `input`, if it is a layer which can be quantized, see [quantize_static](super::quantize_static).
+    ///
+    /// By default, this returns `None`. [Linear](super::Linear) and [Conv2d](super::Conv2d) override it.
+    fn quantize(&self, _input: QParams) -> Option<Mod<dyn Module>> {
+        None
+    }
+
+    /// Replaces the modules of a container like [Sequential](super::Sequential) which can be quantized with their 8-bit replacement, given the quantization parameters of the inputs of the modules by path, and returns the number of replaced modules. On a [Mod], this also quantizes the child modules, see [quantize_static](super::quantize_static).
+    ///
+    /// By default, this does nothing.
+    fn quantize_modules(&mut self, _inputs: &HashMap<String, QParams>) -> usize {
+        0
+    }
+
+    /// Quantizes the weights of the module to 8 bits in place, and returns the number of quantized layers. On a [Mod], this also quantizes the child modules, see [quantize_dynamic](super::quantize_dynamic).
+    ///
+    /// By default, this does nothing. [Linear](super::Linear) overrides it.
+    fn quantize_weights(&self) -> usize {
+        0
+    }
+
+    /// Returns the numbers of input and output channels of a layer whose channels can be pruned, see [prune_channels](super::prune_channels).
+    ///
+    /// By default, this returns `None`. [Conv2d](super::Conv2d) overrides it when it has no groups, and so do the [Sequential](super::Sequential) and the blocks of [ResNet](super::ResNet) whose first and last layers can be pruned.
+    fn prunable_channels(&self) -> Option<(i64, i64)> {
+        None
+    }
+
+    /// Returns whether the output of the module has the channels of its input, unchanged in number and order, like activations, pooling and batch normalizations. Modules which reshape their input do not.
+    ///
+    /// By default, this returns `false`. The channel-wise layers override it, and a [Sequential](super::Sequential) of them keeps the channels.
+    fn keeps_channels(&self) -> bool {
+        false
+    }
+
+    /// Returns whether the module adds its input to its output, like a residual block without downsampling, so that its input and output channels are the same channels, which can only be pruned together, see [prune_channels](super::prune_channels).
+    ///
+    /// By default, this returns `false`. The blocks of [ResNet](super::ResNet) override it.
+    fn is_residual(&self) -> bool {
+        false
+    }
+
+    /// Returns the importance of each output channel of the module with `criterion`, if the module can rank them.
+    ///
+    /// By default, this returns `None`. [Conv2d](super::Conv2d) ranks them by [L1Norm](super::ChannelImportance::L1Norm) and [BatchNorm2d](super::BatchNorm2d) by [BatchNormGamma](super::ChannelImportance::BatchNormGamma).
+    fn channel_importance(&self, _criterion: ChannelImportance) -> Option<Tensor> {
+        None
+    }
+
+    /// Keeps only the output channels at `indices`, removing the others from the parameters and buffers of the module. The input channels of a residual module (see [Trainable::is_residual]) are the same channels, so they are kept with them.
+    ///
+    /// By default, this does nothing. [Conv2d](super::Conv2d) and [BatchNorm2d](super::BatchNorm2d) override it.
+    fn select_output_channels(&mut self, _indices: &Tensor) {}
+
+    /// Keeps only the input channels at `indices`, removing the others from the parameters of the module. The output channels of a residual module are kept with them, like with [Trainable::select_output_channels].
+    ///
+    /// By default, this does nothing. [Conv2d](super::Conv2d) overrides it.
+    fn select_input_channels(&mut self, _indices: &Tensor) {}
+
+    /// Prunes the channels between the modules of a container like [Sequential](super::Sequential), and returns the number of removed channels. On a [Mod], this also prunes the child modules, see [prune_channels](super::prune_channels).
+    ///
+    /// By default, this does nothing.
+    fn prune_channels(&mut self, _amount: f64, _importance: ChannelImportance) -> usize {
+        0
+    }
+
+    /// Convert the floating point parameters and buffers of the module to `kind`, e.g. `Kind::Float` or `Kind::Half`, keeping whether they require gradients. Integer buffers are left unchanged.
+    ///
+    /// Modules are built with `Kind::Double` parameters, so call this on a [Mod] to train or run a model in single or half precision. The inputs should then have the same kind.
+    ///
+    /// # Examples
+    /// ```
+    /// let model = resnet50(1000);
+    /// model.to_kind(Kind::Float);
+    /// let output = model(&input.to_kind(Kind::Float));
+    /// ```
+    fn to_kind(&self, kind: Kind) {
+        for tensor in self.state_dict().values() {
+            let mut tensor = tensor.lock();
+            if tensor.is_floating_point() && tensor.kind() != kind {
+                let requires_grad = tensor.requires_grad();
+                no_grad(|| {
+                    *tensor = tensor.to_kind(kind).set_requires_grad(requires_grad);
+                });
+            }
+        }
+    }
+
+    /// Set the module to training mode if `mode` is `true`, or to evaluation mode otherwise. This does not include child modules, see [Mod::set_training] for setting the mode of a whole model.
+    ///
+    /// By default, this does nothing. If your module behaves differently in training and evaluation, like [Dropout](super::Dropout) or [BatchNorm2d](super::BatchNorm2d), you should override this method and [Trainable::is_training].
+    fn train(&mut self, _mode: bool) {}
+
+    /// Returns whether the module is in training mode.
+    fn is_training(&self) -> bool {
+        true
+    }
+
+    /// Call `f` on the module. On a [Mod], `f` is called on the underlying module, then recursively on every child module, see [Mod::apply].
+    fn apply(&mut self, f: &mut dyn FnMut(&mut dyn Trainable)) {
+        f(self.as_trainable_mut());
+    }
+}
+
+fn set_requires_grad_matching(
+    parameters: impl Iterator<Item = (String, TensorCell)>,
+    pattern: &str,
+    requires_grad: bool,
+) -> usize {
+    let pattern = Regex::new(pattern).expect("Invalid parameter pattern");
+    let mut count = 0;
+    for (_, tensor) in parameters.filter(|(name, _)| pattern.is_match(name)) {
+        let mut tensor = tensor.lock();
+        no_grad(|| {
+            *tensor = tensor.set_requires_grad(requires_grad);
+        });
+        count += 1;
+    }
+    count
 }
 
 /// The wrapper for a trainable module. Any module that implements `Trainable` should be wrapped in this.
@@ -140,9 +438,30 @@ pub struct ModData<T: Trainable + ?Sized> {
     pub children: RwLock<LinkedHashMap<String, Mod<dyn Trainable>>>,
     pub device: RwLock<Device>,
     pub mode: RwLock<ModuleMode>,
+    pub mode_lock: ReentrantMutex<()>,
+    pub forward_hooks: Arc<Hooks<ForwardHook>>,
+    pub forward_pre_hooks: Arc<Hooks<ForwardPreHook>>,
+    pub backward_hooks: Arc<Hooks<BackwardHook>>,
     pub module: RwLock<T>,
 }
 
+/// Restores the mode of a module and of each of its child modules when dropped. This is returned by [Mod::eval_guard].
+pub struct ModeGuard<'a, T: Trainable + ?Sized> {
+    module: &'a Mod<T>,
+    training: bool,
+    modes: Vec<(Mod<dyn Trainable>, bool)>,
+    _lock: ReentrantMutexGuard<'a, ()>,
+}
+
+impl<T: Trainable + ?Sized> Drop for ModeGuard<'_, T> {
+    fn drop(&mut self) {
+        self.module.set_mode(self.training);
+        self.modes
+            .iter()
+            .for_each(|(module, training)| module.set_mode(*training));
+    }
+}
+
 impl<T: Trainable + ?Sized> Clone for Mod<T> {
     fn clone(&self) -> Self {
         Self {
@@ -167,14 +486,31 @@ where
 }
 
 impl<T: Trainable + 'static> Mod<T> {
-    /// Create a [Mod] wrapped module, and update the parent of child modules.
+    /// Create a [Mod] wrapped module, and update the parent of child modules. The device of the [Mod] is the device of the parameters of the module, or of its first child module, or [default_device] if it has neither.
     pub fn new(module: T) -> Mod<T> {
+        let children = module.child_modules();
+        // The device of the module is that of its parameters, which layer builders may create on another device than the CPU.
+        let device = module
+            .state_dict()
+            .values()
+            .map(|tensor| tensor.lock().device())
+            .chain(children.values().map(|child| child.device()))
+            .next()
+            .unwrap_or_else(default_device);
         let this = Mod {
             arc: Arc::new(ModData {
                 parent: RwLock::new(None),
-                children: RwLock::new(module.child_modules()),
-                device: RwLock::new(Device::Cpu),
-                mode: RwLock::new(ModuleMode::Train),
+                children: RwLock::new(children),
+                device: RwLock::new(device),
+                mode: RwLock::new(if module.is_training() {
+                    ModuleMode::Train
+                } else {
+                    ModuleMode::Eval
+                }),
+                mode_lock: ReentrantMutex::new(()),
+                forward_hooks: Arc::new(Hooks::new()),
+                forward_pre_hooks: Arc::new(Hooks::new()),
+                backward_hooks: Arc::new(Hooks::new()),
                 module: RwLock::new(module),
             }),
         };
@@ -231,7 +567,7 @@ impl<T: Trainable + ?Sized> Mod<T> {
     {
         self.parameters()
             .values()
-            .chain(self.static_tensors().values())
+            .chain(self.buffers().values())
             .for_each(|param| {
                 let mut param = param.lock();
                 let requires_grad = param.requires_grad();
@@ -248,7 +584,7 @@ impl<T: Trainable + ?Sized> Mod<T> {
     pub fn to_(&self, device: Device) {
         self.parameters()
             .values()
-            .chain(self.static_tensors().values())
+            .chain(self.buffers().values())
             .for_each(|param| {
                 let mut param = param.lock();
                 let requires_grad = param.requires_grad();
@@ -278,7 +614,7 @@ impl<T: Trainable + ?Sized> Mod<T> {
     ///
     /// If `affect_children` is `true`, the mode of the child modules will also be changed to `Train`. Otherwise, the mode of the child modules will not be changed.
     pub fn train(&self, affect_children: bool) {
-        *self.mode.write() = ModuleMode::Train;
+        self.set_mode(true);
         if affect_children {
             self.children
                 .read()
@@ -290,8 +626,10 @@ impl<T: Trainable + ?Sized> Mod<T> {
     /// Change the mode of the module to `Eval`.
     ///
     /// If `affect_children` is `true`, the mode of the child modules will also be changed to `Eval`. Otherwise, the mode of the child modules will not be changed.
+    ///
+    /// In evaluation mode, dropout is disabled and batch normalization uses its running statistics, so remember to call `eval(true)` before evaluating a model.
     pub fn eval(&self, affect_children: bool) {
-        *self.mode.write() = ModuleMode::Eval;
+        self.set_mode(false);
         if affect_children {
             self.children
                 .read()
@@ -300,6 +638,75 @@ impl<T: Trainable + ?Sized> Mod<T> {
         }
     }
 
+    /// Set the module and its child modules to training mode if `training` is `true`, or to evaluation mode otherwise. This is `train(true)` or `eval(true)` for a mode known at run time, e.g. a saved one.
+    pub fn set_training(&self, training: bool) {
+        if training {
+            self.train(true);
+        } else {
+            self.eval(true);
+        }
+    }
+
+    /// Set the module and its child modules to evaluation mode until the returned [ModeGuard] is dropped, which gives each of them back its own mode. A child module that was frozen in evaluation mode therefore stays in evaluation mode.
+    ///
+    /// The guard holds a lock of the module, so calls from other threads, e.g. two [Mod::infer], wait for each other instead of overwriting the mode.
+    pub fn eval_guard(&self) -> ModeGuard<'_, T> {
+        let lock = self.mode_lock.lock();
+        let training = self.is_training();
+        let mut modes = Vec::new();
+        self.collect_modes(&mut modes);
+        self.eval(true);
+        ModeGuard {
+            module: self,
+            training,
+            modes,
+            _lock: lock,
+        }
+    }
+
+    fn set_mode(&self, mode: bool) {
+        *self.mode.write() = if mode {
+            ModuleMode::Train
+        } else {
+            ModuleMode::Eval
+        };
+        self.module.write().train(mode);
+    }
+
+    fn collect_modes(&self, modes: &mut Vec<(Mod<dyn Trainable>, bool)>) {
+        for child in self.children.read().values() {
+            modes.push((child.clone(), child.is_training()));
+            child.collect_modes(modes);
+        }
+    }
+
+    /// Returns whether the module is in training mode.
+    pub fn is_training(&self) -> bool {
+        matches!(self.mode(), ModuleMode::Train)
+    }
+
+    /// Call `f` on the underlying module, then recursively on every child module, in the order of [Mod::parameters].
+    ///
+    /// Since `f` receives each module without its children, calling [Trainable::parameters] in `f` returns the parameters of a single layer, which is handy for custom per-layer initialization.
+    ///
+    /// # Examples
+    /// ```
+    /// model.apply(|module| {
+    ///     InitScheme::KaimingNormal.init_parameter(module, "weight");
+    /// });
+    /// ```
+    pub fn apply<F: FnMut(&mut dyn Trainable)>(&self, mut f: F) {
+        self.apply_dyn(&mut f);
+    }
+
+    fn apply_dyn(&self, f: &mut dyn FnMut(&mut dyn Trainable)) {
+        self.module.write().apply(f);
+        let children: Vec<_> = self.children.read().values().cloned().collect();
+        for child in children {
+            child.apply_dyn(f);
+        }
+    }
+
     /// Get the mode of the module.
     pub fn mode(&self) -> ModuleMode {
         self.mode.read().clone()
@@ -319,7 +726,7 @@ impl<T: Trainable + ?Sized> Mod<T> {
         self.children.read().clone()
     }
 
-    /// Load parameters from a numpy .npz file. This method won't load static tensors.
+    /// Load parameters and buffers from a numpy .npz file.
     ///
     /// The tensors in the file should be named as the path to them.
     ///
@@ -347,23 +754,92 @@ impl<T: Trainable + ?Sized> Mod<T> {
     /// layer2.bias.npy
     /// ```
     pub fn load_npz<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
-        self.load(
-            Tensor::read_npz(path)?
-                .into_iter()
-                .map(|(key, tensor)| (key, tensor.cell()))
-                .collect(),
-        );
+        self.load(StateDict::from_npz(path)?);
         Ok(())
     }
 
-    /// Load parameters from a .ot file. This type of file is used by OpenTorch. It's also the default format used by `StateDict::save`. This method won't load static tensors.
+    /// Load parameters from a .ot file. This type of file is used by OpenTorch. It's also the default format used by [StateDictExt::to_ot].
     pub fn load_ot<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
-        Ok(self.load(
-            Tensor::load_multi(path)?
-                .into_iter()
-                .map(|(key, tensor)| (key, tensor.cell()))
-                .collect(),
-        ))
+        Ok(self.load(StateDict::from_ot(path)?))
+    }
+
+    /// Load parameters and buffers from a safetensors file, e.g. weights from the HuggingFace hub.
+    ///
+    /// Unlike [Mod::load_npz], every parameter of the module must be in the file with the same shape, otherwise an error naming the parameter is returned and the module is left unchanged. Tensors of another kind are converted to the kind of the parameter.
+    ///
+    /// Buffers missing from the file, e.g. in files saved before buffers were saved in state dicts, keep their values with a warning logged through the [log] facade.
+    pub fn load_safetensors<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let source = StateDict::from_safetensors(path)?;
+        let mut targets = self.parameters();
+        for (name, buffer) in self.buffers() {
+            if source.contains_key(&name) {
+                targets.insert(name, buffer);
+            } else {
+                log::warn!("Buffer {} is missing from the file, keeping its value", name);
+            }
+        }
+        load_checked(&targets, source)
+    }
+
+    /// Load parameters and buffers from the variables of a tch-rs `VarStore` with the same names, e.g. `layer1.weight`, checked like [Mod::load_safetensors].
+    ///
+    /// The parameters then share their storage with the variables, so that training either the module or the tch-rs model of the `VarStore` updates both. See [StateDictExt::to_var_store] for the other direction.
+    pub fn load_var_store(&self, var_store: &tch::nn::VarStore) -> anyhow::Result<()> {
+        load_checked(&self.state_dict(), StateDict::from_var_store(var_store))
+    }
+
+    /// Save parameters and buffers to a numpy .npz file, named as the path to them like in [Mod::load_npz].
+    pub fn save_npz<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        self.state_dict().to_npz(path)
+    }
+
+    /// Save parameters and buffers to a .ot file, which can be loaded with [Mod::load_ot].
+    pub fn save_ot<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        self.state_dict().to_ot(path)
+    }
+
+    /// Save parameters and buffers to a safetensors file, which can be loaded with [Mod::load_safetensors] or by PyTorch.
+    pub fn save_safetensors<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        self.state_dict().to_safetensors(path)
+    }
+
+    /// Load parameters and buffers from a [ShardedCheckpoint], reading one shard at a time so that the whole checkpoint is never held in memory. Every parameter and buffer must be in the checkpoint, with the same shape as in the module.
+    pub fn load_sharded(&self, checkpoint: &ShardedCheckpoint) -> anyhow::Result<()> {
+        let parameters = self.state_dict();
+        if let Some(name) = parameters
+            .keys()
+            .find(|name| !checkpoint.weight_map.contains_key(*name))
+        {
+            anyhow::bail!("Tensor {} is not in the checkpoint", name);
+        }
+        for shard in checkpoint.shards() {
+            let targets: StateDict = parameters
+                .iter()
+                .filter(|(name, _)| checkpoint.weight_map[*name] == shard)
+                .map(|(name, tensor)| (name.clone(), tensor.clone()))
+                .collect();
+            if targets.is_empty() {
+                continue;
+            }
+            let mut tensors = checkpoint.load_shard(&shard)?;
+            load_checked(
+                &targets,
+                targets
+                    .keys()
+                    .map(|name| (name.clone(), tensors.remove(name).unwrap()))
+                    .collect(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Save parameters and buffers as a [ShardedCheckpoint] in `dir`, with shards of at most `max_shard_size` bytes.
+    pub fn save_sharded<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        max_shard_size: u64,
+    ) -> anyhow::Result<ShardedCheckpoint> {
+        save_sharded(&self.state_dict(), dir, max_shard_size)
     }
 }
 
@@ -396,24 +872,153 @@ impl<T: Trainable + ?Sized> Trainable for Mod<T> {
     /// }
     /// ```
     fn parameters(&self) -> StateDict {
-        let mut parameters = self.module().parameters();
+        self.named_parameters().collect()
+    }
+
+    /// Returns an iterator over all parameters in the module and their paths, including the parameters in child modules, in the order of [Mod::parameters].
+    fn named_parameters(&self) -> Box<dyn Iterator<Item = (String, TensorCell)>> {
+        let children: Vec<_> = self
+            .children
+            .read()
+            .iter()
+            .map(|(name, child)| (name.clone(), child.clone()))
+            .collect();
+        Box::new(
+            self.module()
+                .parameters()
+                .into_iter()
+                .chain(children.into_iter().flat_map(|(name, child)| {
+                    child
+                        .named_parameters()
+                        .map(move |(child_name, parameter)| {
+                            (format!("{}.{}", name, child_name), parameter)
+                        })
+                })),
+        )
+    }
+
+    /// Returns all buffers in the module, including the buffers in child modules. The buffers are stored in a `LinkedHashMap` with their path as keys.
+    fn buffers(&self) -> StateDict {
+        let mut buffers = self.module().buffers();
         for (name, child) in self.children.read().iter() {
-            for (child_name, child_parameter) in child.parameters() {
-                parameters.insert(format!("{}.{}", name, child_name), child_parameter);
+            for (child_name, child_buffer) in child.buffers() {
+                buffers.insert(format!("{}.{}", name, child_name), child_buffer);
             }
         }
-        parameters
+        buffers
     }
 
-    /// Returns all static tensors in the module, including the static tensors in child modules. The static tensors are stored in a `LinkedHashMap` with their path as keys.
+    /// Returns all buffers in the module, see [Mod::buffers].
+    #[allow(deprecated)]
     fn static_tensors(&self) -> StateDict {
-        let mut static_tensors = self.module().static_tensors();
-        for (name, child) in self.children.read().iter() {
-            for (child_name, child_static_tensor) in child.static_tensors() {
-                static_tensors.insert(format!("{}.{}", name, child_name), child_static_tensor);
-            }
+        self.buffers()
+    }
+
+    /// Set the module and its child modules to training or evaluation mode, see [Mod::set_training].
+    fn train(&mut self, mode: bool) {
+        Mod::set_training(self, mode);
+    }
+
+    fn is_training(&self) -> bool {
+        Mod::is_training(self)
+    }
+
+    /// Call `f` on the underlying module and its child modules, see [Mod::apply].
+    fn apply(&mut self, f: &mut dyn FnMut(&mut dyn Trainable)) {
+        Mod::apply_dyn(self, f);
+    }
+
+    /// Reset the parameters of the underlying module and its child modules to the defaults of their layers.
+    fn reset_parameters(&self) {
+        Mod::apply(self, |module| module.reset_parameters());
+    }
+
+    fn batch_norm_affine(&self) -> Option<(Tensor, Tensor)> {
+        self.module().batch_norm_affine()
+    }
+
+    fn fold_channel_affine(&mut self, scale: &Tensor, shift: &Tensor) -> bool {
+        self.module.write().fold_channel_affine(scale, shift)
+    }
+
+    /// Fuse the underlying module, then its child modules.
+    fn fuse_conv_bn(&mut self) -> usize {
+        let fused = self.module.write().fuse_conv_bn();
+        if fused > 0 {
+            // The fused batch normalizations were removed, the remaining children keep their parent.
+            let children = self.module().child_modules();
+            *self.children.write() = children;
+        }
+        let children: Vec<_> = self.children.read().values().cloned().collect();
+        fused
+            + children
+                .into_iter()
+                .map(|mut child| child.fuse_conv_bn())
+                .sum::<usize>()
+    }
+
+    fn quantize(&self, input: QParams) -> Option<Mod<dyn Module>> {
+        self.module().quantize(input)
+    }
+
+    /// Quantize the underlying module, then its child modules.
+    fn quantize_modules(&mut self, inputs: &HashMap<String, QParams>) -> usize {
+        let quantized = self.module.write().quantize_modules(inputs);
+        if quantized > 0 {
+            let children = self.module().child_modules();
+            *self.children.write() = children;
         }
-        static_tensors
+        let children: Vec<_> = self.children.read().values().cloned().collect();
+        quantized
+            + children
+                .into_iter()
+                .map(|mut child| child.quantize_modules(inputs))
+                .sum::<usize>()
+    }
+
+    fn quantize_weights(&self) -> usize {
+        self.module().quantize_weights()
+            + self
+                .children
+                .read()
+                .values()
+                .map(|child| child.quantize_weights())
+                .sum::<usize>()
+    }
+
+    fn prunable_channels(&self) -> Option<(i64, i64)> {
+        self.module().prunable_channels()
+    }
+
+    fn keeps_channels(&self) -> bool {
+        self.module().keeps_channels()
+    }
+
+    fn is_residual(&self) -> bool {
+        self.module().is_residual()
+    }
+
+    fn channel_importance(&self, criterion: ChannelImportance) -> Option<Tensor> {
+        self.module().channel_importance(criterion)
+    }
+
+    fn select_output_channels(&mut self, indices: &Tensor) {
+        self.module.write().select_output_channels(indices)
+    }
+
+    fn select_input_channels(&mut self, indices: &Tensor) {
+        self.module.write().select_input_channels(indices)
+    }
+
+    /// Prune the underlying module, then its child modules.
+    fn prune_channels(&mut self, amount: f64, importance: ChannelImportance) -> usize {
+        let pruned = self.module.write().prune_channels(amount, importance);
+        let children: Vec<_> = self.children.read().values().cloned().collect();
+        pruned
+            + children
+                .into_iter()
+                .map(|mut child| child.prune_channels(amount, importance))
+                .sum::<usize>()
     }
 }
 
@@ -427,29 +1032,80 @@ impl<T: Trainable + ?Sized> From<Arc<ModData<T>>> for Mod<T> {
 pub trait Module<InputType = Tensor, OutputType = Tensor>: Trainable {
     /// The forward function for Module.
     fn forward(&self, input: &InputType) -> OutputType;
+
+    /// The forward function, returning an error instead of panicking if the input is invalid.
+    ///
+    /// By default, this calls [Module::forward] without any check. Layers like [Linear](super::Linear) and [Conv2d](super::Conv2d) check the shape of their input, and containers like [Sequential](super::Sequential) call `try_forward` on their layers.
+    ///
+    /// # Examples
+    /// ```
+    /// match model.try_forward(&input) {
+    ///     Ok(output) => println!("{:?}", output.size()),
+    ///     // Conv2d expected an input of shape [N, 3, H, W], got [8, 1, 32, 32]
+    ///     Err(error) => println!("{}", error),
+    /// }
+    /// ```
+    fn try_forward(&self, input: &InputType) -> Result<OutputType, RaddarError> {
+        Ok(self.forward(input))
+    }
 }
 
-impl<T, U> Fn<(&T,)> for Mod<dyn Module<T, U>> {
+impl<T: Trainable + ?Sized> Mod<T> {
+    /// Call [Module::try_forward] on the underlying module, with the forward hooks of the module.
+    pub fn try_forward<I: 'static, O: 'static>(&self, input: &I) -> Result<O, RaddarError>
+    where
+        T: Module<I, O>,
+    {
+        self.call_with_hooks(input, |input| self.module().try_forward(input))
+    }
+
+    /// Call [Module::forward] on the underlying module for inference, i.e. in evaluation mode and without gradients (see [inference_mode]). The mode of the module and of each child module is restored afterwards, see [Mod::eval_guard].
+    ///
+    /// # Examples
+    /// ```
+    /// let prediction = model.infer(&image).argmax(-1, false);
+    /// ```
+    pub fn infer<I: 'static, O: 'static>(&self, input: &I) -> O
+    where
+        T: Module<I, O>,
+    {
+        let _guard = self.eval_guard();
+        inference_mode(|| self.call_with_hooks(input, |input| self.module().forward(input)))
+    }
+}
+
+impl<T: 'static, U: 'static> Fn<(&T,)> for Mod<dyn Module<T, U>> {
     extern "rust-call" fn call(&self, input: (&T,)) -> U {
-        self.module().forward(input.0)
+        self.call_with_hooks(input.0, |input| self.module().forward(input))
     }
 }
 
-impl<T, U> FnMut<(&T,)> for Mod<dyn Module<T, U>> {
+impl<T: 'static, U: 'static> FnMut<(&T,)> for Mod<dyn Module<T, U>> {
     extern "rust-call" fn call_mut(&mut self, input: (&T,)) -> U {
-        self.module().forward(input.0)
+        self.call_with_hooks(input.0, |input| self.module().forward(input))
     }
 }
 
-impl<T, U> FnOnce<(&T,)> for Mod<dyn Module<T, U>> {
+impl<T: 'static, U: 'static> FnOnce<(&T,)> for Mod<dyn Module<T, U>> {
     type Output = U;
 
     extern "rust-call" fn call_once(self, input: (&T,)) -> U {
-        self.module().forward(input.0)
+        self.call_with_hooks(input.0, |input| self.module().forward(input))
     }
 }
 
 /// A module without trainable parameters.
-pub trait NonParameterModule: Module {}
+pub trait NonParameterModule: Module {
+    /// Returns whether the module keeps the channels of its input, see [Trainable::keeps_channels].
+    ///
+    /// By default, this returns `false`. The derive macro implements it for the structs marked with `#[non_parameter(channel_wise)]`.
+    fn keeps_channels(&self) -> bool {
+        false
+    }
+}
 
-impl<T: NonParameterModule> Trainable for T {}
+impl<T: NonParameterModule> Trainable for T {
+    fn keeps_channels(&self) -> bool {
+        NonParameterModule::keeps_channels(self)
+    }
+}
@@ -1,10 +1,12 @@
-use raddar_derive::{CallableModule, NonParameterModule, ArchitectureBuilder};
+use raddar_derive::{ArchitectureBuilder, CallableModule, NonParameterModule, SaveableModule};
 use tch::Tensor;
 
 use super::Module;
+use crate::core::{Backend, DefaultBackend};
 
 /// A max pooling layer in 1 dimension.
-#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder, SaveableModule)]
+#[non_parameter(channel_wise)]
 pub struct MaxPooling1D {
     #[builder]
     pub kernel_size: [i64; 1],
@@ -36,7 +38,8 @@ impl MaxPooling1D {
 
 impl Module for MaxPooling1D {
     fn forward(&self, input: &Tensor) -> Tensor {
-        input.max_pool1d(
+        DefaultBackend::max_pool(
+            input,
             &self.kernel_size,
             &self.stride,
             &self.padding,
@@ -47,7 +50,8 @@ impl Module for MaxPooling1D {
 }
 
 /// A max pooling layer in 2 dimensions.
-#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder, SaveableModule)]
+#[non_parameter(channel_wise)]
 pub struct MaxPooling2D {
     #[builder]
     pub kernel_size: [i64; 2],
@@ -79,7 +83,8 @@ impl MaxPooling2D {
 
 impl Module for MaxPooling2D {
     fn forward(&self, input: &Tensor) -> Tensor {
-        input.max_pool2d(
+        DefaultBackend::max_pool(
+            input,
             &self.kernel_size,
             &self.stride,
             &self.padding,
@@ -90,7 +95,8 @@ impl Module for MaxPooling2D {
 }
 
 /// A max pooling layer in 3 dimensions.
-#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder, SaveableModule)]
+#[non_parameter(channel_wise)]
 pub struct MaxPooling3D {
     #[builder]
     pub kernel_size: [i64; 3],
@@ -122,7 +128,8 @@ impl MaxPooling3D {
 
 impl Module for MaxPooling3D {
     fn forward(&self, input: &Tensor) -> Tensor {
-        input.max_pool3d(
+        DefaultBackend::max_pool(
+            input,
             &self.kernel_size,
             &self.stride,
             &self.padding,
@@ -133,7 +140,8 @@ impl Module for MaxPooling3D {
 }
 
 /// An average pooling layer in 1 dimension.
-#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder, SaveableModule)]
+#[non_parameter(channel_wise)]
 pub struct AveragePooling1D {
     #[builder(default = "[3]")]
     pub kernel_size: [i64; 1],
@@ -165,18 +173,21 @@ impl AveragePooling1D {
 
 impl Module for AveragePooling1D {
     fn forward(&self, input: &Tensor) -> Tensor {
-        input.avg_pool1d(
+        DefaultBackend::avg_pool(
+            input,
             &self.kernel_size,
             &self.stride,
             &self.padding,
             self.ceil_mode,
             self.count_include_pad,
+            None,
         )
     }
 }
 
 /// An average pooling layer in 2 dimensions.
-#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder, SaveableModule)]
+#[non_parameter(channel_wise)]
 
 pub struct AveragePooling2D {
     #[builder(default = "[3, 3]")]
@@ -213,7 +224,8 @@ impl AveragePooling2D {
 
 impl Module for AveragePooling2D {
     fn forward(&self, input: &Tensor) -> Tensor {
-        input.avg_pool2d(
+        DefaultBackend::avg_pool(
+            input,
             &self.kernel_size,
             &self.stride,
             &self.padding,
@@ -225,7 +237,8 @@ impl Module for AveragePooling2D {
 }
 
 /// An average pooling layer in 3 dimensions.
-#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder, SaveableModule)]
+#[non_parameter(channel_wise)]
 pub struct AveragePooling3D {
     #[builder(default = "[3, 3, 3]")]
     pub kernel_size: [i64; 3],
@@ -261,7 +274,8 @@ impl AveragePooling3D {
 
 impl Module for AveragePooling3D {
     fn forward(&self, input: &Tensor) -> Tensor {
-        input.avg_pool3d(
+        DefaultBackend::avg_pool(
+            input,
             &self.kernel_size,
             &self.stride,
             &self.padding,
@@ -273,7 +287,8 @@ impl Module for AveragePooling3D {
 }
 
 /// An adaptive max pooling layer in 1 dimension, which outputs a fixed size vector.
-#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder, SaveableModule)]
+#[non_parameter(channel_wise)]
 pub struct AdaptiveMaxPooling1D {
     #[builder(default = "[1]")]
     pub output_size: [i64; 1],
@@ -289,12 +304,13 @@ impl AdaptiveMaxPooling1D {
 
 impl Module for AdaptiveMaxPooling1D {
     fn forward(&self, input: &Tensor) -> Tensor {
-        input.adaptive_max_pool1d(&self.output_size).0
+        DefaultBackend::adaptive_max_pool(input, &self.output_size)
     }
 }
 
 /// An adaptive max pooling layer in 2 dimensions, which outputs a fixed size vector.
-#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder, SaveableModule)]
+#[non_parameter(channel_wise)]
 pub struct AdaptiveMaxPooling2D {
     #[builder(default = "[1, 1]")]
     pub output_size: [i64; 2],
@@ -310,12 +326,13 @@ impl AdaptiveMaxPooling2D {
 
 impl Module for AdaptiveMaxPooling2D {
     fn forward(&self, input: &Tensor) -> Tensor {
-        input.adaptive_max_pool2d(&self.output_size).0
+        DefaultBackend::adaptive_max_pool(input, &self.output_size)
     }
 }
 
 /// An adaptive max pooling layer in 3 dimensions, which outputs a fixed size vector.
-#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder, SaveableModule)]
+#[non_parameter(channel_wise)]
 pub struct AdaptiveMaxPooling3D {
     #[builder(default = "[1, 1, 1]")]
     pub output_size: [i64; 3],
@@ -331,12 +348,13 @@ impl AdaptiveMaxPooling3D {
 
 impl Module for AdaptiveMaxPooling3D {
     fn forward(&self, input: &Tensor) -> Tensor {
-        input.adaptive_max_pool3d(&self.output_size).0
+        DefaultBackend::adaptive_max_pool(input, &self.output_size)
     }
 }
 
 /// An adaptive average pooling layer in 1 dimension, which outputs a fixed size vector.
-#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder, SaveableModule)]
+#[non_parameter(channel_wise)]
 pub struct AdaptiveAveragePooling1D {
     #[builder(default = "[1]")]
     pub output_size: [i64; 1],
@@ -352,12 +370,13 @@ impl AdaptiveAveragePooling1D {
 
 impl Module for AdaptiveAveragePooling1D {
     fn forward(&self, input: &Tensor) -> Tensor {
-        input.adaptive_avg_pool1d(&self.output_size)
+        DefaultBackend::adaptive_avg_pool(input, &self.output_size)
     }
 }
 
 /// An adaptive average pooling layer in 2 dimensions, which outputs a fixed size vector.
-#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder, SaveableModule)]
+#[non_parameter(channel_wise)]
 pub struct AdaptiveAveragePooling2D {
     #[builder(default = "[1, 1]")]
     pub output_size: [i64; 2],
@@ -373,12 +392,13 @@ impl AdaptiveAveragePooling2D {
 
 impl Module for AdaptiveAveragePooling2D {
     fn forward(&self, input: &Tensor) -> Tensor {
-        input.adaptive_avg_pool2d(&self.output_size)
+        DefaultBackend::adaptive_avg_pool(input, &self.output_size)
     }
 }
 
 /// An adaptive average pooling layer in 3 dimensions, which outputs a fixed size vector.
-#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, NonParameterModule, ArchitectureBuilder, SaveableModule)]
+#[non_parameter(channel_wise)]
 pub struct AdaptiveAveragePooling3D {
     #[builder(default = "[1, 1, 1]")]
     pub output_size: [i64; 3],
@@ -394,6 +414,6 @@ impl AdaptiveAveragePooling3D {
 
 impl Module for AdaptiveAveragePooling3D {
     fn forward(&self, input: &Tensor) -> Tensor {
-        input.adaptive_avg_pool3d(&self.output_size)
+        DefaultBackend::adaptive_avg_pool(input, &self.output_size)
     }
 }
\ No newline at end of file
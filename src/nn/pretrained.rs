@@ -0,0 +1,43 @@
+use crate::hub;
+
+use super::{state_dict::load_checked, KeyMap, Mod, Trainable};
+
+/// The number of classes of the ImageNet weights of torchvision.
+const IMAGENET_CLASSES: i64 = 1000;
+
+/// Describes how the weights of a torchvision model map onto the corresponding raddar model.
+pub(crate) struct TorchvisionWeights<'a> {
+    /// The name of the weights in the [hub].
+    pub name: &'a str,
+    pub key_map: KeyMap,
+    /// The names of the weights of linear layers, which are stored as `[output_dim, input_dim]` by PyTorch but as `[input_dim, output_dim]` by [Linear](super::Linear).
+    pub linear_weights: &'a [&'a str],
+    /// The prefix of the names of the tensors of the classification head, which is left randomly initialized when the number of classes is not that of ImageNet.
+    pub head: &'a str,
+}
+
+impl TorchvisionWeights<'_> {
+    /// Loads the pretrained parameters and buffers into `model`, which classifies `num_classes` classes.
+    pub fn load_into<T: Trainable + ?Sized>(
+        &self,
+        model: &Mod<T>,
+        num_classes: i64,
+    ) -> anyhow::Result<()> {
+        let mut state_dict = self.key_map.apply(hub::load_pretrained(self.name)?);
+        for name in self.linear_weights {
+            if let Some(weight) = state_dict.get(*name) {
+                let transposed = weight.lock().tr();
+                *weight.lock() = transposed;
+            }
+        }
+
+        let mut targets = model.state_dict();
+        if num_classes != IMAGENET_CLASSES {
+            targets = targets
+                .into_iter()
+                .filter(|(name, _)| !name.starts_with(self.head))
+                .collect();
+        }
+        load_checked(&targets, state_dict)
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    fmt::Display,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use linked_hash_map::LinkedHashMap;
+use parking_lot::Mutex;
+use raddar_derive::CallableModule;
+use tch::{Kind, Tensor};
+
+use super::{cut_segment, Mod, Module, ModuleMode, Trainable, TrainableDict};
+
+/// The statistics of a module recorded by a [Profiler].
+#[derive(Debug, Clone, Default)]
+pub struct ProfileRecord {
+    /// The number of forward calls.
+    pub calls: usize,
+    /// The total wall time of the forward calls.
+    pub forward_time: Duration,
+    /// The total wall time of the backward passes. Only recorded if the profiler is created with [Profiler::with_backward], and the backward passes are run by [backward_checkpoints](super::backward_checkpoints).
+    pub backward_time: Duration,
+    /// The size in bytes of the largest output, which approximates the activation memory of the module.
+    pub output_bytes: usize,
+}
+
+impl ProfileRecord {
+    pub fn total_time(&self) -> Duration {
+        self.forward_time + self.backward_time
+    }
+}
+
+#[derive(Default)]
+struct ProfilerState {
+    records: LinkedHashMap<String, ProfileRecord>,
+}
+
+/// Records the forward (and optionally backward) wall time and the output memory of the modules it wraps, to find the bottlenecks of an architecture.
+///
+/// Modules are profiled by wrapping them with [Profiler::wrap]. A [Profiler] is cheap to clone, and all clones share the same records.
+///
+/// To time backward passes per module, create the profiler with [Profiler::with_backward]. The graph is then cut at the boundaries of every wrapped module like with a [Checkpoint](super::Checkpoint), and [backward_checkpoints](super::backward_checkpoints) must be called in place of `loss.backward()` to propagate the gradients through the wrapped modules, one at a time. The gradients are the same as without profiling. A [Trainer](crate::train::Trainer) does this automatically.
+/// In this mode, wrapped modules must not be nested.
+///
+/// libtorch does not expose the memory allocator to Rust, so the memory is approximated by the size of the outputs.
+///
+/// # Examples
+/// ```
+/// let profiler = Profiler::new();
+/// let model = seq!(
+///     profiler.wrap("conv", conv),
+///     profiler.wrap("linear", linear),
+/// );
+/// model(&inputs);
+/// println!("{}", profiler);
+/// ```
+#[derive(Clone, Default)]
+pub struct Profiler {
+    state: Arc<Mutex<ProfilerState>>,
+    backward: bool,
+}
+
+impl Profiler {
+    /// Creates a profiler which only times the forward passes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a profiler which times both the forward and the backward passes.
+    pub fn with_backward() -> Self {
+        Self {
+            state: Default::default(),
+            backward: true,
+        }
+    }
+
+    /// Wraps a module, so that its calls are recorded under `name`.
+    pub fn wrap<T: Module + 'static>(&self, name: &str, module: Mod<T>) -> Mod<Profiled<T>> {
+        Mod::new(Profiled {
+            name: name.to_owned(),
+            module,
+            profiler: self.clone(),
+        })
+    }
+
+    fn record_forward(&self, name: &str, duration: Duration, output: &Tensor) {
+        let bytes = output.numel() * output.kind().elt_size_in_bytes();
+        let mut state = self.state.lock();
+        let record = state.records.entry(name.to_owned()).or_default();
+        record.calls += 1;
+        record.forward_time += duration;
+        record.output_bytes = record.output_bytes.max(bytes);
+    }
+
+    fn record_backward(&self, name: &str, duration: Duration) {
+        let mut state = self.state.lock();
+        state
+            .records
+            .entry(name.to_owned())
+            .or_default()
+            .backward_time += duration;
+    }
+
+    /// Returns the records of all profiled modules, in the order they were first called.
+    pub fn records(&self) -> Vec<(String, ProfileRecord)> {
+        self.state
+            .lock()
+            .records
+            .iter()
+            .map(|(name, record)| (name.clone(), record.clone()))
+            .collect()
+    }
+
+    /// Clears all records.
+    pub fn reset(&self) {
+        self.state.lock().records.clear();
+    }
+}
+
+impl std::fmt::Debug for Profiler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Profiler")
+            .field("backward", &self.backward)
+            .finish()
+    }
+}
+
+impl Display for Profiler {
+    /// Prints the records as a table, sorted by the total time in descending order.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut records = self.records();
+        records.sort_by(|a, b| b.1.total_time().cmp(&a.1.total_time()));
+        let width = records
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0)
+            .max("module".len());
+        writeln!(
+            f,
+            "{:<width$}  {:>8}  {:>12}  {:>12}  {:>12}  {:>12}",
+            "module",
+            "calls",
+            "forward(ms)",
+            "backward(ms)",
+            "total(ms)",
+            "output(KiB)",
+            width = width
+        )?;
+        for (name, record) in records {
+            writeln!(
+                f,
+                "{:<width$}  {:>8}  {:>12.3}  {:>12.3}  {:>12.3}  {:>12.1}",
+                name,
+                record.calls,
+                record.forward_time.as_secs_f64() * 1e3,
+                record.backward_time.as_secs_f64() * 1e3,
+                record.total_time().as_secs_f64() * 1e3,
+                record.output_bytes as f64 / 1024.,
+                width = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A module wrapped by a [Profiler].
+#[derive(Debug, CallableModule)]
+pub struct Profiled<T: Module + 'static> {
+    pub name: String,
+    pub module: Mod<T>,
+    profiler: Profiler,
+}
+
+impl<T: Module + 'static> Trainable for Profiled<T> {
+    fn child_modules(&self) -> TrainableDict {
+        let mut children = TrainableDict::new();
+        children.insert("module".to_owned(), self.module.clone());
+        children
+    }
+}
+
+impl<T: Module + 'static> Module for Profiled<T> {
+    fn forward(&self, input: &Tensor) -> Tensor {
+        let cut = self.profiler.backward && matches!(self.module.mode(), ModuleMode::Train);
+        if !cut {
+            let start = Instant::now();
+            let output = self.module.module().forward(input);
+            self.profiler
+                .record_forward(&self.name, start.elapsed(), &output);
+            return output;
+        }
+        let input_leaf = input.detach().set_requires_grad(input.requires_grad());
+        let start = Instant::now();
+        let output = self.module.module().forward(&input_leaf);
+        self.profiler
+            .record_forward(&self.name, start.elapsed(), &output);
+        if !output.requires_grad() {
+            return output;
+        }
+        let profiler = self.profiler.clone();
+        let name = self.name.clone();
+        let graph = output.shallow_clone();
+        cut_segment(
+            input,
+            input_leaf,
+            &output,
+            Box::new(move |grad| {
+                let start = Instant::now();
+                (graph * grad).sum(Kind::Double).backward();
+                profiler.record_backward(&name, start.elapsed());
+            }),
+        )
+    }
+}
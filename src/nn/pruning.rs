@@ -0,0 +1,192 @@
+use tch::{no_grad, Kind, Tensor};
+
+use crate::core::TensorCell;
+
+use super::{Mod, Module, Trainable};
+
+/// How the output channels of a convolution are ranked by [prune_channels], from least to most important.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelImportance {
+    /// The L1 norm of the filter producing the channel.
+    ///
+    /// See [Pruning Filters for Efficient ConvNets](https://arxiv.org/abs/1608.08710).
+    L1Norm,
+    /// The absolute value of the scale of the channel in the batch normalization after the convolution, which is trained to be sparse in network slimming.
+    ///
+    /// See [Learning Efficient Convolutional Networks through Network Slimming](https://arxiv.org/abs/1708.06519).
+    BatchNormGamma,
+}
+
+/// Removes the least important `amount` (between 0 and 1) of the output channels of the [Conv2d](super::Conv2d) layers of `model`, ranked by `importance`, and returns the number of removed channels.
+///
+/// The pruned layers are rebuilt with fewer channels, so the model is physically smaller and faster, unlike with masks. The output channels of a convolution are only pruned if the next convolution of the same [Sequential](super::Sequential) or [NamedSequential](super::NamedSequential) reads them, with only modules which keep the channels between them, like batch normalizations, activations, pooling and dropout (see [Trainable::keeps_channels]). These batch normalizations and the input channels of the next convolution are pruned with it.
+///
+/// The channels which residual modules add their output to (see [Trainable::is_residual]) are pruned as a group: the blocks of a [ResNet](super::ResNet) add their output to the channels of the residual stream, so the last convolution and batch normalization of each block of a stage, its downsampling, and the input channels of the blocks reading the stream are pruned together, ranked by the sum of the importance of the channels in these layers. The sequentials are pruned as a whole where they are modules of another sequential, like the stages of a ResNet, and then their own modules are pruned.
+///
+/// The channels which leave the outermost sequential are never pruned, since the layer reading them is unknown, like the output channels of the last stage of a ResNet which its classifier reads. Grouped convolutions are not pruned either.
+///
+/// The model should be fine-tuned after pruning to recover its accuracy, and the optimizer should be created again since the parameters are replaced.
+///
+/// # Panics
+///
+/// Panics if `amount` is not in `[0, 1)`.
+///
+/// # Examples
+/// ```
+/// let mut model = resnet50(1000);
+/// model.load_npz("resnet50.npz")?;
+/// prune_channels(&mut model, 0.3, ChannelImportance::L1Norm);
+/// ```
+pub fn prune_channels<T: Trainable + ?Sized>(
+    model: &mut Mod<T>,
+    amount: f64,
+    importance: ChannelImportance,
+) -> usize {
+    assert!(
+        (0.0..1.0).contains(&amount),
+        "The amount of pruned channels should be in [0, 1), got {}",
+        amount
+    );
+    model.prune_channels(amount, importance)
+}
+
+/// Prunes the channels between the convolutions of a sequence of modules, the modules of a sequential. The handles share the modules of the sequential.
+pub(crate) fn prune_sequence(
+    mut modules: Vec<Mod<dyn Module>>,
+    amount: f64,
+    importance: ChannelImportance,
+) -> usize {
+    let mut pruned = 0;
+    for i in 0..modules.len() {
+        // The channels of a residual module are pruned with the module computing its input.
+        if modules[i].is_residual() {
+            continue;
+        }
+        let Some((_, channels)) = modules[i].prunable_channels() else {
+            continue;
+        };
+        // Find the next module reading the channels, they go through the modules before it unchanged, or with the outputs of residual modules added to them.
+        let mut next = i + 1;
+        while next < modules.len() && passes_channels(&modules[next]) {
+            next += 1;
+        }
+        match modules
+            .get(next)
+            .and_then(|module| module.prunable_channels())
+        {
+            Some((in_channels, _)) if in_channels == channels => {}
+            _ => continue,
+        }
+        let Some(scores) = group_importance(&modules[i..next], importance) else {
+            continue;
+        };
+
+        let kept = ((channels as f64) * (1. - amount)).ceil().max(1.) as i64;
+        if kept >= channels {
+            continue;
+        }
+        let (_, indices) = scores.topk(kept, 0, true, false);
+        let (indices, _) = indices.sort(0, false);
+        for module in &mut modules[i..next] {
+            module.select_output_channels(&indices);
+        }
+        modules[next].select_input_channels(&indices);
+        pruned += (channels - kept) as usize;
+    }
+    pruned
+}
+
+/// The channels go through modules which keep them, and through residual modules which add to them.
+fn passes_channels(module: &Mod<dyn Module>) -> bool {
+    module.keeps_channels() || module.is_residual()
+}
+
+/// The importance of the channels computed by the first of `modules` and by the residual modules among them, in which the other modules keep the channels.
+fn group_importance(modules: &[Mod<dyn Module>], criterion: ChannelImportance) -> Option<Tensor> {
+    let mut scores = modules
+        .iter()
+        .filter(|module| !module.is_residual())
+        .find_map(|module| module.channel_importance(criterion));
+    for module in modules.iter().filter(|module| module.is_residual()) {
+        let residual = module.channel_importance(criterion)?;
+        scores = Some(match scores {
+            Some(scores) => scores + residual,
+            None => residual,
+        });
+    }
+    scores
+}
+
+/// The index of the last module of a sequence which computes its output channels, after which the modules keep the channels or add to them.
+fn output_index(modules: &[Mod<dyn Module>]) -> Option<usize> {
+    modules.iter().rposition(|module| !passes_channels(module))
+}
+
+/// The numbers of input and output channels of a sequence of modules, if the modules reading its input and computing its output can be pruned.
+pub(crate) fn sequence_channels(modules: &[Mod<dyn Module>]) -> Option<(i64, i64)> {
+    let input = modules.iter().find(|module| !module.keeps_channels())?;
+    let output = match output_index(modules) {
+        Some(index) => &modules[index],
+        None => input,
+    };
+    Some((input.prunable_channels()?.0, output.prunable_channels()?.1))
+}
+
+/// Whether a sequence of modules keeps the channels of its input, like a sequential of a single batch normalization.
+pub(crate) fn sequence_keeps_channels(modules: &[Mod<dyn Module>]) -> bool {
+    !modules.is_empty() && modules.iter().all(|module| module.keeps_channels())
+}
+
+/// Whether a sequence of modules only adds to the channels of its input, like a stage of a ResNet without downsampling.
+pub(crate) fn sequence_is_residual(modules: &[Mod<dyn Module>]) -> bool {
+    modules.iter().any(|module| module.is_residual()) && modules.iter().all(passes_channels)
+}
+
+/// The importance of the output channels of a sequence of modules.
+pub(crate) fn sequence_importance(
+    modules: &[Mod<dyn Module>],
+    criterion: ChannelImportance,
+) -> Option<Tensor> {
+    group_importance(&modules[output_index(modules).unwrap_or(0)..], criterion)
+}
+
+/// Keeps the output channels at `indices` of a sequence of modules, in the modules computing them.
+pub(crate) fn select_sequence_output_channels(mut modules: Vec<Mod<dyn Module>>, indices: &Tensor) {
+    let start = output_index(&modules).unwrap_or(0);
+    for module in &mut modules[start..] {
+        module.select_output_channels(indices);
+    }
+}
+
+/// Keeps the input channels at `indices` of a sequence of modules, in the modules reading them.
+pub(crate) fn select_sequence_input_channels(modules: Vec<Mod<dyn Module>>, indices: &Tensor) {
+    for mut module in modules {
+        if module.keeps_channels() {
+            module.select_output_channels(indices);
+            continue;
+        }
+        module.select_input_channels(indices);
+        if !module.is_residual() {
+            break;
+        }
+    }
+}
+
+/// Keeps the channels at `indices` along `dim` of a parameter or buffer.
+pub(crate) fn select_channels(tensor: &TensorCell, dim: i64, indices: &Tensor) {
+    let mut tensor = tensor.lock();
+    let indices = indices.to_device(tensor.device());
+    let selected = no_grad(|| tensor.index_select(dim, &indices));
+    *tensor = selected.set_requires_grad(tensor.requires_grad());
+}
+
+/// The L1 norms of the filters of a convolution weight, along its first dimension.
+pub(crate) fn filter_norms(weight: &TensorCell) -> Tensor {
+    no_grad(|| {
+        weight
+            .lock()
+            .abs()
+            .flatten(1, -1)
+            .sum_dim_intlist(&[1], false, Kind::Double)
+    })
+}
@@ -0,0 +1,273 @@
+use std::{collections::HashMap, sync::Arc};
+
+use parking_lot::Mutex;
+use raddar_derive::{CallableModule, Trainable};
+use tch::{Device, Kind, Tensor};
+
+use crate::core::{Backend, Cellable, DefaultBackend, TensorCell};
+
+use super::{HookHandle, Mod, Module, Trainable};
+
+/// The scale and zero point mapping the real values of a tensor to 8-bit integers, as `real = (integer - zero_point) * scale`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QParams {
+    pub scale: f64,
+    pub zero_point: i64,
+}
+
+impl QParams {
+    /// Rounds `input` to the values representable as unsigned 8-bit integers with these parameters, clamping it to their range.
+    pub fn fake_quantize(&self, input: &Tensor) -> Tensor {
+        input
+            .to_kind(Kind::Float)
+            .quantize_per_tensor(self.scale, self.zero_point, Kind::QUInt8)
+            .dequantize()
+            .to_kind(input.kind())
+    }
+
+    /// Rounds `input` to unsigned 8-bit integers with the range of `input` itself, as dynamic quantization does for the inputs of the layers, see [quantize_dynamic].
+    pub fn fake_quantize_dynamic(input: &Tensor) -> Tensor {
+        let mut observer = MinMaxObserver::default();
+        observer.observe(input);
+        observer.qparams().fake_quantize(input)
+    }
+}
+
+/// Records the range of the tensors it observes, e.g. the inputs of a layer during calibration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinMaxObserver {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for MinMaxObserver {
+    fn default() -> Self {
+        Self {
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl MinMaxObserver {
+    /// Extends the observed range with the values of `tensor`.
+    pub fn observe(&mut self, tensor: &Tensor) {
+        if tensor.numel() == 0 {
+            return;
+        }
+        let tensor = tensor.detach().to_kind(Kind::Double);
+        self.min = self.min.min(f64::from(tensor.min()));
+        self.max = self.max.max(f64::from(tensor.max()));
+    }
+
+    /// Returns the parameters quantizing the observed range to unsigned 8-bit integers. The range is extended to contain 0, so that 0 (e.g. padding, or the output of a ReLU) is quantized exactly.
+    pub fn qparams(&self) -> QParams {
+        let min = self.min.min(0.);
+        let max = self.max.max(0.);
+        let scale = ((max - min) / 255.).max(f64::EPSILON);
+        QParams {
+            scale,
+            zero_point: (-min / scale).round() as i64,
+        }
+    }
+}
+
+/// Observes the inputs of every module of a model, to choose how to quantize them.
+///
+/// The calibration registers a forward pre-hook on each module, see [Mod::register_forward_pre_hook], so run the model on representative inputs between [Calibration::new] and [Calibration::finish]. [quantize_static] does both.
+pub struct Calibration {
+    observers: Arc<Mutex<HashMap<String, MinMaxObserver>>>,
+    handles: Vec<HookHandle>,
+}
+
+impl Calibration {
+    /// Starts observing the inputs of `model` and of its child modules.
+    pub fn new<T: Trainable + ?Sized>(model: &Mod<T>) -> Self {
+        let mut calibration = Self {
+            observers: Arc::new(Mutex::new(HashMap::new())),
+            handles: Vec::new(),
+        };
+        calibration.observe(model);
+        calibration
+    }
+
+    fn observe<T: Trainable + ?Sized>(&mut self, module: &Mod<T>) {
+        let observers = self.observers.clone();
+        self.handles
+            .push(module.register_forward_pre_hook(move |path, input| {
+                observers
+                    .lock()
+                    .entry(path.to_owned())
+                    .or_default()
+                    .observe(input);
+            }));
+        for child in module.children().values() {
+            self.observe(child);
+        }
+    }
+
+    /// Stops observing, and returns the quantization parameters of the inputs of the modules which were called, by their path (see [Mod::path]).
+    pub fn finish(self) -> HashMap<String, QParams> {
+        for handle in self.handles {
+            handle.remove();
+        }
+        let observers = self.observers.lock();
+        observers
+            .iter()
+            .map(|(path, observer)| (path.clone(), observer.qparams()))
+            .collect()
+    }
+}
+
+impl std::fmt::Debug for Calibration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Calibration")
+            .field("observers", &self.observers.lock().len())
+            .finish()
+    }
+}
+
+/// Quantizes the [Linear](super::Linear) and [Conv2d](super::Conv2d) layers in the [Sequential](super::Sequential) and [NamedSequential](super::NamedSequential) containers of `model` to 8 bits, after calibrating the quantization of their inputs on `calibration_data`. Returns the number of quantized layers.
+///
+/// The weights are quantized to `i8` per output channel, which makes them 4 times smaller than `f32` weights (8 times smaller than the default `f64`). The inputs of the layers are quantized to `u8` with the range observed during calibration, so a few hundred representative samples should be used. Layers which were not called during calibration are left unchanged.
+///
+/// The quantized layers compute with the dequantized weights and inputs, as the int8 kernels of libtorch are not exposed to tch, so they give the outputs of int8 kernels up to floating point rounding. This makes the model smaller, not faster: the forward passes are slower than before, as the weights are dequantized and the inputs rounded at each call. The model is set to evaluation mode, and cannot be trained afterwards.
+///
+/// # Examples
+/// ```
+/// let mut model = resnet50(1000);
+/// model.load_npz("resnet50.npz")?;
+/// fuse_conv_bn(&mut model);
+/// let batches = calibration_images.chunks(32).map(|images| Tensor::stack(images, 0));
+/// quantize_static(&mut model, batches);
+/// let prediction = model.infer(&image).argmax(-1, false);
+/// ```
+pub fn quantize_static<T, I>(model: &mut Mod<T>, calibration_data: I) -> usize
+where
+    T: Module + ?Sized,
+    I: IntoIterator<Item = Tensor>,
+{
+    let calibration = Calibration::new(model);
+    for input in calibration_data {
+        let _: Tensor = model.infer(&input);
+    }
+    let inputs = calibration.finish();
+    model.eval(true);
+    model.quantize_modules(&inputs)
+}
+
+/// Quantizes the weights of the [Linear](super::Linear) layers of `model` to 8 bits in place, and returns the number of quantized layers.
+///
+/// Unlike [quantize_static], the inputs of the layers are quantized with their own range at each forward pass, so no calibration data is needed, and the layers are quantized wherever they are in the model, e.g. the projections of an attention layer.
+///
+/// The weights are quantized to `i8` per output like with [quantize_static], so they take 4 times less memory than `f32` weights, e.g. to fit a large model in memory or to ship it. The layers compute with the dequantized weights, so the forward passes are not faster, but slightly slower than before.
+///
+/// The weights and the biases of the quantized layers become buffers of the same names, so they are saved and loaded with the model but are not among its parameters anymore. The model is set to evaluation mode and cannot be trained afterwards.
+///
+/// # Examples
+/// ```
+/// let mut model = build_transformer();
+/// model.load_safetensors("transformer.safetensors")?;
+/// quantize_dynamic(&mut model);
+/// let logits = model.infer(&tokens);
+/// ```
+pub fn quantize_dynamic<T: Trainable + ?Sized>(model: &mut Mod<T>) -> usize {
+    model.eval(true);
+    model.quantize_weights()
+}
+
+/// Quantizes `weight` to `i8` per channel along `axis`, with a symmetric range.
+pub(crate) fn quantize_weight(weight: &Tensor, axis: i64) -> Tensor {
+    let weight = weight.detach().to_kind(Kind::Float).to_device(Device::Cpu);
+    let dims: Vec<i64> = (0..weight.dim() as i64)
+        .filter(|dim| *dim != axis)
+        .collect();
+    let scales = (weight.abs().amax(&dims, false).clamp_min(1e-8) / 127.).to_kind(Kind::Double);
+    let zero_points = scales.zeros_like().to_kind(Kind::Int64);
+    weight.quantize_per_channel(&scales, &zero_points, axis, Kind::QInt8)
+}
+
+/// A fully-connected layer with 8-bit weights, created by [quantize_static] from a [Linear](super::Linear).
+#[derive(Debug, CallableModule, Trainable)]
+pub struct QuantizedLinear {
+    #[trainable(buffer)]
+    pub weight: TensorCell,
+    #[trainable(buffer)]
+    pub bias: Option<TensorCell>,
+    pub input: QParams,
+}
+
+impl QuantizedLinear {
+    /// Quantizes a weight of shape `[input_dim, output_dim]` per output, and the inputs with `input`.
+    pub fn new(weight: &Tensor, bias: Option<&Tensor>, input: QParams) -> Self {
+        Self {
+            weight: quantize_weight(weight, 1).cell(),
+            bias: bias.map(|bias| bias.detach().copy().cell()),
+            input,
+        }
+    }
+}
+
+impl Module for QuantizedLinear {
+    fn forward(&self, input: &Tensor) -> Tensor {
+        let input = self.input.fake_quantize(input);
+        let weight = self.weight.lock().dequantize().to_kind(input.kind());
+        let bias = self.bias.as_ref().map(|bias| bias.lock());
+        DefaultBackend::linear(&input, &weight.to_device(input.device()), bias.as_deref())
+    }
+}
+
+/// A convolution in 2 dimensions with 8-bit weights, created by [quantize_static] from a [Conv2d](super::Conv2d).
+#[derive(Debug, CallableModule, Trainable)]
+pub struct QuantizedConv2d {
+    #[trainable(buffer)]
+    pub weight: TensorCell,
+    #[trainable(buffer)]
+    pub bias: Option<TensorCell>,
+    pub stride: [i64; 2],
+    pub padding: [i64; 2],
+    pub dilation: [i64; 2],
+    pub groups: i64,
+    pub input: QParams,
+}
+
+impl QuantizedConv2d {
+    /// Quantizes a weight of shape `[out, in / groups, height, width]` per output channel, and the inputs with `input`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        weight: &Tensor,
+        bias: Option<&Tensor>,
+        stride: [i64; 2],
+        padding: [i64; 2],
+        dilation: [i64; 2],
+        groups: i64,
+        input: QParams,
+    ) -> Self {
+        Self {
+            weight: quantize_weight(weight, 0).cell(),
+            bias: bias.map(|bias| bias.detach().copy().cell()),
+            stride,
+            padding,
+            dilation,
+            groups,
+            input,
+        }
+    }
+}
+
+impl Module for QuantizedConv2d {
+    fn forward(&self, input: &Tensor) -> Tensor {
+        let input = self.input.fake_quantize(input);
+        let weight = self.weight.lock().dequantize().to_kind(input.kind());
+        let bias = self.bias.as_ref().map(|bias| bias.lock());
+        DefaultBackend::conv(
+            &input,
+            &weight.to_device(input.device()),
+            bias.as_deref(),
+            &self.stride,
+            &self.padding,
+            &self.dilation,
+            self.groups,
+        )
+    }
+}
@@ -0,0 +1,151 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{anyhow, Context};
+use parking_lot::{const_rwlock, RwLock};
+use serde_json::Value;
+
+use super::{
+    AdaptiveAveragePooling1D, AdaptiveAveragePooling2D, AdaptiveAveragePooling3D,
+    AdaptiveMaxPooling1D, AdaptiveMaxPooling2D, AdaptiveMaxPooling3D, AlexNet, AveragePooling1D,
+    AveragePooling2D, AveragePooling3D, BatchNorm1d, BatchNorm2d, BatchNorm3d, Conv1d, Conv2d,
+    Conv3d, DenseNet, Dropout, GeLU, LayerNorm, LazyConv2d, LazyLinear, LeakyReLU, Linear,
+    MaxPooling1D, MaxPooling2D, MaxPooling3D, Mod, Module, ReLU, SaveableModule, Sequential,
+};
+
+/// A function building a module from its builder parameters, registered by [register_module].
+pub type ModuleFactory = Arc<dyn Fn(&Value) -> anyhow::Result<Mod<dyn Module>> + Send + Sync>;
+
+/// The registered modules by name, filled with the modules of the library on first use.
+static REGISTRY: RwLock<Option<HashMap<String, ModuleFactory>>> = const_rwlock(None);
+
+/// Registers a module under `name`, so that [build_from_config] can build it. A module already registered under `name` is replaced.
+///
+/// # Examples
+/// ```
+/// register_module("Residual", |config| {
+///     let dim = config["dim"].as_i64().unwrap();
+///     Ok(Mod::new(Residual::new(dim)) as Mod<dyn Module>)
+/// });
+/// ```
+pub fn register_module<F>(name: &str, factory: F)
+where
+    F: Fn(&Value) -> anyhow::Result<Mod<dyn Module>> + Send + Sync + 'static,
+{
+    let mut registry = REGISTRY.write();
+    registry
+        .get_or_insert_with(builtin_modules)
+        .insert(name.to_owned(), Arc::new(factory));
+}
+
+/// Registers a [SaveableModule] under the name of its architecture, building it with [SaveableModule::from_config].
+pub fn register_saveable<T: SaveableModule + Module + 'static>() {
+    let (name, factory) = saveable_factory::<T>();
+    REGISTRY
+        .write()
+        .get_or_insert_with(builtin_modules)
+        .insert(name, factory);
+}
+
+/// Returns the names of the registered modules, sorted.
+pub fn registered_modules() -> Vec<String> {
+    let mut registry = REGISTRY.write();
+    let mut names: Vec<_> = registry
+        .get_or_insert_with(builtin_modules)
+        .keys()
+        .cloned()
+        .collect();
+    names.sort();
+    names
+}
+
+/// Builds a module from a JSON object, whose `type` is the name of a registered module and whose other keys are its builder parameters. Missing parameters take their default value.
+///
+/// A `Sequential` is built from the configs of its modules in `layers`. The layers of the library are registered under the name of their type, e.g. `Conv2d` or `ReLU`, and more can be registered with [register_module].
+///
+/// # Examples
+/// ```
+/// let model = build_from_config(&json!({
+///     "type": "Sequential",
+///     "layers": [
+///         { "type": "Linear", "input_dim": 784, "output_dim": 256 },
+///         { "type": "ReLU" },
+///         { "type": "Dropout", "p": 0.2 },
+///         { "type": "Linear", "input_dim": 256, "output_dim": 10 },
+///     ],
+/// }))?;
+/// ```
+pub fn build_from_config(config: &Value) -> anyhow::Result<Mod<dyn Module>> {
+    let name = config
+        .get("type")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("The module config {} has no type", config))?;
+    let factory = REGISTRY
+        .write()
+        .get_or_insert_with(builtin_modules)
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("No module is registered as {}", name))?;
+    factory(config).with_context(|| format!("Failed to build a {}", name))
+}
+
+fn saveable_factory<T: SaveableModule + Module + 'static>() -> (String, ModuleFactory) {
+    (
+        T::architecture().to_owned(),
+        Arc::new(|config: &Value| Ok(Mod::new(T::from_config(config)?) as Mod<dyn Module>)),
+    )
+}
+
+fn builtin_modules() -> HashMap<String, ModuleFactory> {
+    fn factory<F>(name: &str, factory: F) -> (String, ModuleFactory)
+    where
+        F: Fn(&Value) -> anyhow::Result<Mod<dyn Module>> + Send + Sync + 'static,
+    {
+        (name.to_owned(), Arc::new(factory))
+    }
+
+    HashMap::from([
+        saveable_factory::<Linear>(),
+        saveable_factory::<LazyLinear>(),
+        saveable_factory::<Conv1d>(),
+        saveable_factory::<Conv2d>(),
+        saveable_factory::<Conv3d>(),
+        saveable_factory::<LazyConv2d>(),
+        saveable_factory::<BatchNorm1d>(),
+        saveable_factory::<BatchNorm2d>(),
+        saveable_factory::<BatchNorm3d>(),
+        saveable_factory::<LayerNorm>(),
+        saveable_factory::<Dropout>(),
+        saveable_factory::<MaxPooling1D>(),
+        saveable_factory::<MaxPooling2D>(),
+        saveable_factory::<MaxPooling3D>(),
+        saveable_factory::<AveragePooling1D>(),
+        saveable_factory::<AveragePooling2D>(),
+        saveable_factory::<AveragePooling3D>(),
+        saveable_factory::<AdaptiveMaxPooling1D>(),
+        saveable_factory::<AdaptiveMaxPooling2D>(),
+        saveable_factory::<AdaptiveMaxPooling3D>(),
+        saveable_factory::<AdaptiveAveragePooling1D>(),
+        saveable_factory::<AdaptiveAveragePooling2D>(),
+        saveable_factory::<AdaptiveAveragePooling3D>(),
+        saveable_factory::<AlexNet>(),
+        saveable_factory::<DenseNet>(),
+        factory("ReLU", |_| Ok(Mod::new(ReLU) as Mod<dyn Module>)),
+        factory("GeLU", |_| Ok(Mod::new(GeLU) as Mod<dyn Module>)),
+        factory("LeakyReLU", |config| {
+            let lambda = config.get("lambda").and_then(Value::as_f64).unwrap_or(0.01);
+            Ok(Mod::new(LeakyReLU::new(lambda)) as Mod<dyn Module>)
+        }),
+        factory("Sequential", |config| {
+            let layers = config
+                .get("layers")
+                .and_then(Value::as_array)
+                .ok_or_else(|| anyhow!("A Sequential needs an array of layers"))?;
+            let layers = layers
+                .iter()
+                .enumerate()
+                .map(|(i, layer)| build_from_config(layer).with_context(|| format!("Layer {}", i)))
+                .collect::<anyhow::Result<Sequential>>()?;
+            Ok(Mod::new(layers) as Mod<dyn Module>)
+        }),
+    ])
+}
@@ -1,13 +1,18 @@
 use std::{fmt::Debug, marker::PhantomData};
 
-use raddar_derive::{ArchitectureBuilder, CallableModule};
+use raddar_derive::{ArchitectureBuilder, CallableModule, Trainable};
 use tch::Tensor;
 
-use crate::{nn::ReLU, seq};
+use crate::{
+    core::{Backend, DefaultBackend},
+    nn::ReLU,
+    seq,
+};
 
 use super::{
-    AdaptiveAveragePooling2DBuilder, BatchNorm2dBuilder, Conv2d, Conv2dBuilder, LinearBuilder,
-    MaxPooling2DBuilder, Mod, Module, Sequential, Trainable, TrainableDict,
+    pretrained::TorchvisionWeights, AdaptiveAveragePooling2DBuilder, BatchNorm2dBuilder,
+    ChannelImportance, Conv2d, Conv2dBuilder, KeyMap, LinearBuilder, MaxPooling2DBuilder, Mod,
+    Module, Sequential, Trainable, TrainableDict,
 };
 
 pub trait Block<U: Fn(i64) -> Mod<Sequential> + Send + Debug + Copy>: Module {
@@ -59,33 +64,71 @@ pub fn batchnorm2d(num_features: i64) -> Mod<Sequential> {
         .build())
 }
 
+/// Implements [Trainable] for a block adding its input, or its downsampling, to the output of its `block`. A block without downsampling is residual, so its input and output channels are pruned together, see [prune_channels](super::prune_channels).
+macro_rules! residual_block {
+    ($block:ty) => {
+        impl Trainable for $block {
+            fn child_modules(&self) -> TrainableDict {
+                let mut children = TrainableDict::new();
+                children.insert("block".to_owned(), self.block.clone());
+                if let Some(downsample) = &self.downsample {
+                    children.insert("downsample".to_owned(), downsample.clone());
+                }
+                children
+            }
+
+            fn prunable_channels(&self) -> Option<(i64, i64)> {
+                self.block.prunable_channels()
+            }
+
+            fn is_residual(&self) -> bool {
+                self.downsample.is_none()
+            }
+
+            /// The output channels of the block and of its downsampling are added, so they are ranked together.
+            fn channel_importance(&self, criterion: ChannelImportance) -> Option<Tensor> {
+                let scores = self.block.channel_importance(criterion)?;
+                match &self.downsample {
+                    Some(downsample) => Some(scores + downsample.channel_importance(criterion)?),
+                    None => Some(scores),
+                }
+            }
+
+            fn select_output_channels(&mut self, indices: &Tensor) {
+                self.block.select_output_channels(indices);
+                match &mut self.downsample {
+                    Some(downsample) => downsample.select_output_channels(indices),
+                    None => self.block.select_input_channels(indices),
+                }
+            }
+
+            fn select_input_channels(&mut self, indices: &Tensor) {
+                self.block.select_input_channels(indices);
+                match &mut self.downsample {
+                    Some(downsample) => downsample.select_input_channels(indices),
+                    None => self.block.select_output_channels(indices),
+                }
+            }
+        }
+    };
+}
+
 #[derive(Debug, CallableModule)]
 pub struct BasicBlock {
     pub block: Mod<Sequential>,
     pub downsample: Option<Mod<Sequential>>,
-}
-
-impl Trainable for BasicBlock {
-    fn child_modules(&self) -> TrainableDict {
-        let mut result = TrainableDict::new();
-        result.insert("block".to_owned(), self.block.clone());
-        if let Some(ref downsample) = self.downsample {
-            result.insert("downsample".to_owned(), downsample.clone());
-        }
-        result
-    }
+    pub relu: Mod<ReLU>,
 }
 
 impl Module for BasicBlock {
     fn forward(&self, input: &Tensor) -> Tensor {
-        let mut identity = input.copy();
         let mut output = (self.block)(input);
-        if let Some(downsample) = &self.downsample {
-            identity = (*downsample)(&identity);
+        // The identity path reads the input directly, as the block does not modify it.
+        match &self.downsample {
+            Some(downsample) => output += (*downsample)(input),
+            None => output += input,
         }
-        output += identity;
-        let relu = seq!(Mod::new(ReLU));
-        (relu)(&output)
+        (self.relu)(&output)
     }
 }
 
@@ -115,37 +158,29 @@ impl<U: Fn(i64) -> Mod<Sequential> + Send + Debug + Copy> Block<U> for BasicBloc
         Mod::new(Self {
             block: Mod::new(block),
             downsample,
+            relu: Mod::new(ReLU),
         })
     }
 }
 
+residual_block!(BasicBlock);
+
 #[derive(Debug, CallableModule)]
 pub struct BottleNeck {
     pub block: Mod<Sequential>,
     pub downsample: Option<Mod<Sequential>>,
-}
-
-impl Trainable for BottleNeck {
-    fn child_modules(&self) -> TrainableDict {
-        let mut result = TrainableDict::new();
-        result.insert("block".to_owned(), self.block.clone());
-        if let Some(downsample) = &self.downsample {
-            result.insert("downsample".to_owned(), downsample.clone());
-        }
-        result
-    }
+    pub relu: Mod<ReLU>,
 }
 
 impl Module for BottleNeck {
     fn forward(&self, input: &Tensor) -> Tensor {
-        let mut identity = input.copy();
         let mut output = (self.block)(input);
-        if let Some(downsample) = &self.downsample {
-            identity = (*downsample)(&identity);
+        // The identity path reads the input directly, as the block does not modify it.
+        match &self.downsample {
+            Some(downsample) => output += (*downsample)(input),
+            None => output += input,
         }
-        output += identity;
-        let relu = seq!(Mod::new(ReLU));
-        (relu)(&output)
+        (self.relu)(&output)
     }
 }
 
@@ -180,14 +215,17 @@ impl<U: Fn(i64) -> Mod<Sequential> + Send + Debug + Copy> Block<U> for BottleNec
         Mod::new(Self {
             block: Mod::new(block),
             downsample,
+            relu: Mod::new(ReLU),
         })
     }
 }
 
+residual_block!(BottleNeck);
+
 /// A ResNet model
 ///
 /// See [Deep Residual Learning for Image Recognition](https://arxiv.org/abs/1512.03385).
-#[derive(Debug, CallableModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, ArchitectureBuilder, Trainable)]
 pub struct ResNet<
     T: Block<U> + 'static,
     U: Fn(i64) -> Mod<Sequential> + Send + Debug + Copy + 'static,
@@ -205,12 +243,14 @@ pub struct ResNet<
     #[builder(default = "1")]
     pub groups: i64,
     #[builder(default = "Self::default_norm_layer()")]
+    #[serde(skip, default = "ResNetBuilder::<T, U>::default_norm_layer")]
     pub norm_layer: U,
     #[builder(default = "[1, 1]")]
     pub dilation: [i64; 2],
     #[builder(default = "64")]
     pub inplanes: i64,
     #[builder(default = "PhantomData::<T>")]
+    #[serde(skip)]
     _phantom: PhantomData<T>,
 }
 
@@ -234,19 +274,10 @@ impl<T: Block<fn(i64) -> Mod<Sequential>>> DefaultNormLayer<fn(i64) -> Mod<Seque
     }
 }
 
-impl<T: Block<U>, U: Fn(i64) -> Mod<Sequential> + Send + Debug + Copy> Trainable for ResNet<T, U> {
-    fn child_modules(&self) -> TrainableDict {
-        let mut result = TrainableDict::new();
-        result.insert("net".to_owned(), self.net.clone());
-        result.insert("fc".to_owned(), self.fc.clone());
-        result
-    }
-}
-
 impl<T: Block<U>, U: Fn(i64) -> Mod<Sequential> + Send + Debug + Copy> Module for ResNet<T, U> {
     fn forward(&self, input: &Tensor) -> Tensor {
         let mut output = (self.net)(input);
-        output = output.flatten(1, 3);
+        output = DefaultBackend::flatten(&output, 1, 3);
         output = (self.fc)(&output);
         output
     }
@@ -369,6 +400,17 @@ pub fn resnet18(num_classes: i64) -> Mod<ResNet<BasicBlock, fn(i64) -> Mod<Seque
         .build()
 }
 
+/// ResNet18 model initialized with the ImageNet weights of torchvision, see [resnet18].
+///
+/// If `num_classes` is not 1000, the `fc` layer is left randomly initialized, to be fine-tuned.
+pub fn resnet18_pretrained(
+    num_classes: i64,
+) -> anyhow::Result<Mod<ResNet<BasicBlock, fn(i64) -> Mod<Sequential>>>> {
+    let model = resnet18(num_classes);
+    torchvision_resnet_weights("resnet18").load_into(&model, num_classes)?;
+    Ok(model)
+}
+
 /// ResNet34 model from "Deep Residual Learning for Image Recognition" <https://arxiv.org/pdf/1512.03385.pdf>
 pub fn resnet34(num_classes: i64) -> Mod<ResNet<BasicBlock, fn(i64) -> Mod<Sequential>>> {
     ResNetBuilder::<BasicBlock, fn(i64) -> Mod<Sequential>>::default()
@@ -385,6 +427,17 @@ pub fn resnet50(num_classes: i64) -> Mod<ResNet<BottleNeck, fn(i64) -> Mod<Seque
         .build()
 }
 
+/// ResNet50 model initialized with the ImageNet weights of torchvision, see [resnet50].
+///
+/// If `num_classes` is not 1000, the `fc` layer is left randomly initialized, to be fine-tuned.
+pub fn resnet50_pretrained(
+    num_classes: i64,
+) -> anyhow::Result<Mod<ResNet<BottleNeck, fn(i64) -> Mod<Sequential>>>> {
+    let model = resnet50(num_classes);
+    torchvision_resnet_weights("resnet50").load_into(&model, num_classes)?;
+    Ok(model)
+}
+
 /// ResNet101 model from "Deep Residual Learning for Image Recognition" <https://arxiv.org/pdf/1512.03385.pdf>
 pub fn resnet101(num_classes: i64) -> Mod<ResNet<BottleNeck, fn(i64) -> Mod<Sequential>>> {
     ResNetBuilder::<BottleNeck, fn(i64) -> Mod<Sequential>>::default()
@@ -400,3 +453,29 @@ pub fn resnet152(num_classes: i64) -> Mod<ResNet<BottleNeck, fn(i64) -> Mod<Sequ
         .num_classes(num_classes)
         .build()
 }
+
+/// Maps the names of the torchvision ResNets, e.g. `layer1.0.conv1.weight`, onto those of [ResNet], e.g. `net.4.0.block.0.weight`.
+fn torchvision_resnet_weights(name: &str) -> TorchvisionWeights<'_> {
+    TorchvisionWeights {
+        name,
+        key_map: KeyMap::new()
+            .strip_prefix("module.")
+            .skip(r"num_batches_tracked$")
+            .rename(r"^conv1\.", "net.0.")
+            .rename(r"^bn1\.", "net.1.0.")
+            .rename(r"^layer1\.", "net.4.")
+            .rename(r"^layer2\.", "net.5.")
+            .rename(r"^layer3\.", "net.6.")
+            .rename(r"^layer4\.", "net.7.")
+            .rename(r"\.conv1\.", ".block.0.")
+            .rename(r"\.bn1\.", ".block.1.0.")
+            .rename(r"\.conv2\.", ".block.3.")
+            .rename(r"\.bn2\.", ".block.4.0.")
+            .rename(r"\.conv3\.", ".block.6.")
+            .rename(r"\.bn3\.", ".block.7.0.")
+            .rename(r"\.downsample\.1\.", ".downsample.1.0.")
+            .rename(r"^fc\.", "fc.0."),
+        linear_weights: &["fc.0.weight"],
+        head: "fc.",
+    }
+}
@@ -0,0 +1,196 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Read, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Context};
+use serde_json::{json, Map, Value};
+use tch::{Device, Kind, Tensor};
+
+use crate::core::Cellable;
+
+use super::StateDict;
+
+/// The key of the free-form string metadata in the header of a safetensors file.
+const METADATA_KEY: &str = "__metadata__";
+
+/// Returns the name of `kind` in the safetensors format.
+pub fn safetensors_dtype(kind: Kind) -> anyhow::Result<&'static str> {
+    Ok(match kind {
+        Kind::Bool => "BOOL",
+        Kind::Uint8 => "U8",
+        Kind::Int8 => "I8",
+        Kind::Int16 => "I16",
+        Kind::Int => "I32",
+        Kind::Int64 => "I64",
+        Kind::Half => "F16",
+        Kind::BFloat16 => "BF16",
+        Kind::Float => "F32",
+        Kind::Double => "F64",
+        kind => bail!("Tensors of kind {:?} can not be saved as safetensors", kind),
+    })
+}
+
+/// Returns the kind of a dtype of the safetensors format.
+pub fn safetensors_kind(dtype: &str) -> anyhow::Result<Kind> {
+    Ok(match dtype {
+        "BOOL" => Kind::Bool,
+        "U8" => Kind::Uint8,
+        "I8" => Kind::Int8,
+        "I16" => Kind::Int16,
+        "I32" => Kind::Int,
+        "I64" => Kind::Int64,
+        "F16" => Kind::Half,
+        "BF16" => Kind::BFloat16,
+        "F32" => Kind::Float,
+        "F64" => Kind::Double,
+        dtype => bail!("Unsupported safetensors dtype {}", dtype),
+    })
+}
+
+/// Reads a safetensors file, returning its tensors in the order of their data in the file, and the metadata of its header.
+pub fn read_safetensors<P: AsRef<Path>>(
+    path: P,
+) -> anyhow::Result<(StateDict, HashMap<String, String>)> {
+    let path = path.as_ref();
+    let mut buffer = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut buffer))
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    parse_safetensors(&buffer)
+        .with_context(|| format!("Invalid safetensors file {}", path.display()))
+}
+
+fn parse_safetensors(buffer: &[u8]) -> anyhow::Result<(StateDict, HashMap<String, String>)> {
+    if buffer.len() < 8 {
+        bail!("The file is too short to hold a header");
+    }
+    let header_size = u64::from_le_bytes(buffer[..8].try_into().unwrap()) as usize;
+    if header_size > buffer.len() - 8 {
+        bail!(
+            "The header has {} bytes, but the file only has {} bytes",
+            header_size,
+            buffer.len()
+        );
+    }
+    let header: Map<String, Value> = serde_json::from_slice(&buffer[8..8 + header_size])?;
+    let data = &buffer[8 + header_size..];
+
+    let mut metadata = HashMap::new();
+    let mut entries = Vec::new();
+    for (name, info) in header {
+        if name == METADATA_KEY {
+            for (key, value) in info.as_object().into_iter().flatten() {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| anyhow!("The metadata {} is not a string", key))?;
+                metadata.insert(key.clone(), value.to_owned());
+            }
+            continue;
+        }
+
+        let dtype = info["dtype"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Tensor {} has no dtype", name))?;
+        let kind = safetensors_kind(dtype).with_context(|| format!("Tensor {}", name))?;
+        let shape = info["shape"]
+            .as_array()
+            .and_then(|shape| shape.iter().map(Value::as_i64).collect::<Option<Vec<_>>>())
+            .ok_or_else(|| anyhow!("Tensor {} has an invalid shape {}", name, info["shape"]))?;
+        let offsets = info["data_offsets"]
+            .as_array()
+            .and_then(|offsets| {
+                offsets
+                    .iter()
+                    .map(Value::as_u64)
+                    .collect::<Option<Vec<_>>>()
+            })
+            .filter(|offsets| offsets.len() == 2)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Tensor {} has invalid data offsets {}",
+                    name,
+                    info["data_offsets"]
+                )
+            })?;
+        let (begin, end) = (offsets[0] as usize, offsets[1] as usize);
+        if begin > end || end > data.len() {
+            bail!(
+                "Tensor {} has data offsets [{}, {}] out of the {} bytes of data",
+                name,
+                begin,
+                end,
+                data.len()
+            );
+        }
+        let expected = shape.iter().product::<i64>() as usize * kind.elt_size_in_bytes();
+        if end - begin != expected {
+            bail!(
+                "Tensor {} of dtype {} and shape {:?} should have {} bytes, but has {}",
+                name,
+                dtype,
+                shape,
+                expected,
+                end - begin
+            );
+        }
+        entries.push((
+            begin,
+            name,
+            Tensor::of_data_size(&data[begin..end], &shape, kind),
+        ));
+    }
+
+    entries.sort_by_key(|(begin, _, _)| *begin);
+    let state_dict = entries
+        .into_iter()
+        .map(|(_, name, tensor)| (name, tensor.cell()))
+        .collect();
+    Ok((state_dict, metadata))
+}
+
+/// Writes the tensors of a [StateDict] to a safetensors file, with the given metadata in its header.
+pub fn write_safetensors<P: AsRef<Path>>(
+    state_dict: &StateDict,
+    metadata: &HashMap<String, String>,
+    path: P,
+) -> anyhow::Result<()> {
+    let mut header = Map::new();
+    if !metadata.is_empty() {
+        header.insert(METADATA_KEY.to_owned(), json!(metadata));
+    }
+    let mut data = Vec::new();
+    for (name, tensor) in state_dict {
+        let tensor = tensor.lock().detach().to_device(Device::Cpu).contiguous();
+        let kind = tensor.kind();
+        let dtype = safetensors_dtype(kind).with_context(|| format!("Tensor {}", name))?;
+        let numel = tensor.numel();
+        let begin = data.len();
+        data.resize(begin + numel * kind.elt_size_in_bytes(), 0);
+        tensor.f_copy_data_u8(&mut data[begin..], numel)?;
+        header.insert(
+            name.clone(),
+            json!({
+                "dtype": dtype,
+                "shape": tensor.size(),
+                "data_offsets": [begin, data.len()],
+            }),
+        );
+    }
+
+    let mut header = serde_json::to_vec(&Value::Object(header))?;
+    // Pad the header with spaces, so that the data is aligned to 8 bytes.
+    header.resize((header.len() + 7) / 8 * 8, b' ');
+
+    let path = path.as_ref();
+    let mut writer = BufWriter::new(
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?,
+    );
+    writer.write_all(&(header.len() as u64).to_le_bytes())?;
+    writer.write_all(&header)?;
+    writer.write_all(&data)?;
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{anyhow, bail};
+use serde_json::Value;
+use tch::no_grad;
+
+use crate::util::with_default_device;
+
+use super::{read_safetensors, state_dict::load_checked, write_safetensors, Mod, Trainable};
+
+/// The metadata key holding the name of the architecture in a saved model.
+const ARCHITECTURE_KEY: &str = "raddar.architecture";
+
+/// The metadata key holding the builder parameters in a saved model.
+const CONFIG_KEY: &str = "raddar.config";
+
+/// A module which can be rebuilt from its builder parameters, so that it can be saved along with its architecture.
+///
+/// Derive it with `#[derive(SaveableModule)]` next to `ArchitectureBuilder`, which stores every field marked with `#[builder]`. The fields must implement `serde::Serialize` and `serde::Deserialize`, except fields of type `Kind` or `Device`, which are saved with the helpers of [serde_tch](crate::util::serde_tch). Mark a field with `#[saveable(skip)]` to leave it out, e.g. the device of a layer, which then takes its default value when loaded.
+pub trait SaveableModule: Trainable + Sized {
+    /// The name of the architecture, checked when a model is loaded.
+    fn architecture() -> &'static str;
+
+    /// Returns the builder parameters of the module as a JSON object.
+    fn config(&self) -> Value;
+
+    /// Builds a module from builder parameters given by [SaveableModule::config]. Missing parameters take their default value.
+    fn from_config(config: &Value) -> anyhow::Result<Self>;
+}
+
+impl<T: SaveableModule> Mod<T> {
+    /// Save the model to a safetensors file, with its architecture and builder parameters in the metadata of the file, so that it can be loaded by [Mod::load_model] without building it first. The buffers of the model, e.g. the running statistics of batch normalization, are saved as well.
+    ///
+    /// # Examples
+    /// ```
+    /// let model = AlexNetBuilder::default().num_classes(10).build();
+    /// model.save_model("alexnet.safetensors")?;
+    /// let model = Mod::<AlexNet>::load_model("alexnet.safetensors")?;
+    /// ```
+    pub fn save_model<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let metadata = HashMap::from([
+            ("format".to_owned(), "pt".to_owned()),
+            (ARCHITECTURE_KEY.to_owned(), T::architecture().to_owned()),
+            (CONFIG_KEY.to_owned(), self.module().config().to_string()),
+        ]);
+        write_safetensors(&self.state_dict(), &metadata, path)
+    }
+
+    /// Load a model saved by [Mod::save_model], rebuilding it from the builder parameters in the file.
+    pub fn load_model<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let (state_dict, metadata) = read_safetensors(path)?;
+        let architecture = metadata.get(ARCHITECTURE_KEY).ok_or_else(|| {
+            anyhow!("The file has no architecture, it was not saved by save_model")
+        })?;
+        if architecture != T::architecture() {
+            bail!(
+                "The file holds a {}, but a {} was expected",
+                architecture,
+                T::architecture()
+            );
+        }
+        let config: Value = serde_json::from_str(
+            metadata
+                .get(CONFIG_KEY)
+                .ok_or_else(|| anyhow!("The file has no builder parameters"))?,
+        )?;
+        let model = Mod::new(T::from_config(&config)?);
+        load_checked(&model.state_dict(), state_dict)?;
+        Ok(model)
+    }
+
+    /// Returns a deep copy of the module, rebuilt from its builder parameters, with copies of its parameters and buffers instead of shared tensors. Training the copy does not change the module, which is what an exponential moving average or the target network of a reinforcement learning agent needs.
+    ///
+    /// The copy is on the same device and in the same mode as the module. Hooks are not copied.
+    ///
+    /// # Examples
+    /// ```
+    /// let target = model.clone_module();
+    /// target.freeze();
+    /// ```
+    pub fn clone_module(&self) -> Self {
+        let module = with_default_device(self.device(), || T::from_config(&self.module().config()))
+            .expect("the builder parameters of a module should rebuild it");
+        let clone = Mod::new(module);
+        let state_dict = self.state_dict();
+        for (name, tensor) in clone.state_dict() {
+            if let Some(source) = state_dict.get(&name) {
+                let source = source.lock();
+                no_grad(|| {
+                    *tensor.lock() = source.copy().set_requires_grad(source.requires_grad());
+                });
+            }
+        }
+        clone.to_(self.device());
+        clone.set_training(self.is_training());
+        clone
+    }
+}
@@ -0,0 +1,96 @@
+use std::{fmt, path::Path};
+
+use raddar_derive::CallableModule;
+use tch::{CModule, Device, Tensor};
+
+use crate::core::Cellable;
+
+use super::{Module, StateDict, Trainable};
+
+/// A TorchScript model, e.g. a traced torchvision model saved with `torch.jit.save`, wrapped as a [Module].
+///
+/// The parameters of the scripted model are shared with the [TensorCell](crate::core::TensorCell)s returned by [parameters](Trainable::parameters), so optimizers and [freeze](Trainable::freeze) act on the scripted model directly, and it can be fine-tuned like any other module. Since the parameters are updated in place, a `ScriptModule` should be loaded on its final device with [ScriptModule::load_on_device] rather than moved with [Mod::to](super::Mod::to). For the same reason, [load](Trainable::load) does not affect the scripted model, whose weights should be saved in the TorchScript file.
+///
+/// # Examples
+/// ```
+/// let backbone = Mod::new(ScriptModule::load("resnet18.pt")?);
+/// backbone.freeze();
+/// let model = seq!(backbone, LinearBuilder::default().input_dim(1000).output_dim(10).build());
+/// ```
+#[derive(CallableModule)]
+pub struct ScriptModule {
+    pub module: CModule,
+    parameters: StateDict,
+    training: bool,
+}
+
+impl ScriptModule {
+    /// Loads a TorchScript model on the cpu.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Self::from_cmodule(CModule::load(path)?)
+    }
+
+    /// Loads a TorchScript model on a certain device.
+    pub fn load_on_device<P: AsRef<Path>>(path: P, device: Device) -> anyhow::Result<Self> {
+        Self::from_cmodule(CModule::load_on_device(path, device)?)
+    }
+
+    /// Wraps a loaded TorchScript model, and sets it in training mode like other modules.
+    pub fn from_cmodule(mut module: CModule) -> anyhow::Result<Self> {
+        module.set_train();
+        let parameters = module
+            .named_parameters()?
+            .into_iter()
+            .map(|(name, parameter)| (name, parameter.cell()))
+            .collect();
+        Ok(Self {
+            module,
+            parameters,
+            training: true,
+        })
+    }
+
+    /// Sets the scripted model in training mode, e.g. for its dropout and batch normalization layers.
+    pub fn set_train(&mut self) {
+        self.module.set_train();
+        self.training = true;
+    }
+
+    /// Sets the scripted model in evaluation mode.
+    pub fn set_eval(&mut self) {
+        self.module.set_eval();
+        self.training = false;
+    }
+}
+
+impl fmt::Debug for ScriptModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScriptModule")
+            .field("parameters", &self.parameters.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Trainable for ScriptModule {
+    fn parameters(&self) -> StateDict {
+        self.parameters.clone()
+    }
+
+    fn train(&mut self, mode: bool) {
+        if mode {
+            self.set_train();
+        } else {
+            self.set_eval();
+        }
+    }
+
+    fn is_training(&self) -> bool {
+        self.training
+    }
+}
+
+impl Module for ScriptModule {
+    fn forward(&self, input: &Tensor) -> Tensor {
+        self.module.forward_ts(&[input]).unwrap()
+    }
+}
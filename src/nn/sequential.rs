@@ -1,14 +1,38 @@
-use crate::nn::Module;
+use crate::{core::RaddarError, nn::Module};
 use raddar_derive::CallableModule;
-use std::ops::{Deref, DerefMut};
+use std::{
+    collections::HashMap,
+    ops::{Bound, Deref, DerefMut, RangeBounds},
+};
 use tch::Tensor;
 
-use super::{Mod, Trainable, TrainableDict};
+use super::{
+    prune_sequence, select_sequence_input_channels, select_sequence_output_channels,
+    sequence_channels, sequence_importance, sequence_is_residual, sequence_keeps_channels,
+    ChannelImportance, Mod, QParams, Trainable, TrainableDict,
+};
 
 /// A module composed by a sequential of modules.
+///
+/// A `Sequential` dereferences to a `Vec` of its modules, so it can be edited like one, e.g. with `push`, `insert`, `remove` or `extend`. Edit it through [Mod::module_mut] so that the [Mod] updates its child modules.
+///
+/// # Examples
+/// ```
+/// let model = seq!(
+///     LinearBuilder::default().input_dim(4).output_dim(8).build(),
+///     Mod::new(ReLU),
+/// );
+/// model
+///     .module_mut()
+///     .insert(1, Mod::new(BatchNorm1dBuilder::default().num_features(8).build()));
+/// model.module_mut().remove(2);
+/// ```
 #[derive(Debug, CallableModule, Default)]
 pub struct Sequential(Vec<Mod<dyn Module>>);
 
+/// A module composed by a sequential of named modules, e.g. the `features` of [DenseNet](super::densenet::DenseNet).
+///
+/// Like [Sequential], it dereferences to a `Vec` of its modules and their names. Edit it through [Mod::module_mut] so that the [Mod] updates its child modules.
 #[derive(Debug, CallableModule, Default)]
 pub struct NamedSequential(Vec<(String, Mod<dyn Module>)>);
 
@@ -52,6 +76,12 @@ impl FromIterator<Mod<dyn Module>> for Sequential {
     }
 }
 
+impl Extend<Mod<dyn Module>> for Sequential {
+    fn extend<I: IntoIterator<Item = Mod<dyn Module>>>(&mut self, iter: I) {
+        self.0.extend(iter)
+    }
+}
+
 impl From<Vec<(String, Mod<dyn Module>)>> for NamedSequential {
     fn from(seq: Vec<(String, Mod<dyn Module>)>) -> Self {
         NamedSequential(seq)
@@ -66,6 +96,40 @@ impl FromIterator<(String, Mod<dyn Module>)> for NamedSequential {
     }
 }
 
+impl Sequential {
+    /// Returns a new sequential of the modules in `range`, e.g. `sequential.slice(..3)` for the first three. The modules are shared with this sequential, not copied.
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> Sequential {
+        let bounds: (Bound<usize>, Bound<usize>) =
+            (range.start_bound().cloned(), range.end_bound().cloned());
+        Sequential(self.0[bounds].to_vec())
+    }
+
+    /// Keep the modules up to and including the `n`-th one, and drop the rest, e.g. to cut the classifier off a feature extractor.
+    pub fn truncate_after(&mut self, n: usize) {
+        self.0.truncate(n + 1);
+    }
+
+    /// Run the modules up to and including the `n`-th one, and returns the intermediate output, e.g. the features of a backbone.
+    pub fn forward_until(&self, input: &Tensor, n: usize) -> Tensor {
+        let mut x = input.shallow_clone();
+        for module in self.iter().take(n + 1) {
+            x = module(&x)
+        }
+        x
+    }
+}
+
+/// Returns the quantized replacement of a module of a sequential, if its inputs were observed during calibration. The replacement takes the place of the module in the model, so it has the same parent.
+fn quantize_module(
+    module: &Mod<dyn Module>,
+    inputs: &HashMap<String, QParams>,
+) -> Option<Mod<dyn Module>> {
+    let input = inputs.get(&module.path())?;
+    let replacement = module.quantize(*input)?;
+    *replacement.parent.write() = module.parent.read().clone();
+    Some(replacement)
+}
+
 impl Trainable for Sequential {
     fn child_modules(&self) -> TrainableDict {
         let mut children = TrainableDict::new();
@@ -74,6 +138,69 @@ impl Trainable for Sequential {
         }
         children
     }
+
+    /// A sequential of a single batch normalization, like the norm layers of [ResNet](super::ResNet), is fused as the batch normalization.
+    fn batch_norm_affine(&self) -> Option<(Tensor, Tensor)> {
+        match &self[..] {
+            [module] => module.batch_norm_affine(),
+            _ => None,
+        }
+    }
+
+    fn fuse_conv_bn(&mut self) -> usize {
+        let mut fused = 0;
+        let mut i = 0;
+        while i + 1 < self.len() {
+            if let Some((scale, shift)) = self[i + 1].batch_norm_affine() {
+                if self[i].fold_channel_affine(&scale, &shift) {
+                    self.remove(i + 1);
+                    fused += 1;
+                }
+            }
+            i += 1;
+        }
+        fused
+    }
+
+    fn quantize_modules(&mut self, inputs: &HashMap<String, QParams>) -> usize {
+        let mut quantized = 0;
+        for module in self.iter_mut() {
+            if let Some(replacement) = quantize_module(module, inputs) {
+                *module = replacement;
+                quantized += 1;
+            }
+        }
+        quantized
+    }
+
+    /// The sequential is pruned as a whole where it is a module of another sequential, by its first and last layers, see [prune_channels](super::prune_channels).
+    fn prunable_channels(&self) -> Option<(i64, i64)> {
+        sequence_channels(self)
+    }
+
+    fn keeps_channels(&self) -> bool {
+        sequence_keeps_channels(self)
+    }
+
+    fn is_residual(&self) -> bool {
+        sequence_is_residual(self)
+    }
+
+    fn channel_importance(&self, criterion: ChannelImportance) -> Option<Tensor> {
+        sequence_importance(self, criterion)
+    }
+
+    fn select_output_channels(&mut self, indices: &Tensor) {
+        select_sequence_output_channels(self.to_vec(), indices)
+    }
+
+    fn select_input_channels(&mut self, indices: &Tensor) {
+        select_sequence_input_channels(self.to_vec(), indices)
+    }
+
+    fn prune_channels(&mut self, amount: f64, importance: ChannelImportance) -> usize {
+        prune_sequence(self.to_vec(), amount, importance)
+    }
 }
 
 impl Module for Sequential {
@@ -84,6 +211,44 @@ impl Module for Sequential {
         }
         x
     }
+
+    fn try_forward(&self, input: &Tensor) -> Result<Tensor, RaddarError> {
+        let mut x = input.shallow_clone();
+        for module in self.iter() {
+            x = module.try_forward(&x)?;
+        }
+        Ok(x)
+    }
+}
+
+impl NamedSequential {
+    fn position(&self, name: &str) -> Option<usize> {
+        self.iter().position(|(module_name, _)| module_name == name)
+    }
+
+    /// Returns the module named `name`, e.g. `features.get("denseblock3")`.
+    pub fn get(&self, name: &str) -> Option<Mod<dyn Module>> {
+        self.iter()
+            .find(|(module_name, _)| module_name == name)
+            .map(|(_, module)| module.clone())
+    }
+
+    /// Replace the module named `name` with `module`, keeping its position, and returns the replaced module, or `None` if there is no such module.
+    pub fn replace(&mut self, name: &str, module: Mod<dyn Module>) -> Option<Mod<dyn Module>> {
+        let index = self.position(name)?;
+        Some(std::mem::replace(&mut self.0[index].1, module))
+    }
+
+    /// Returns a new sequential of the modules up to and including the one named `name`, or `None` if there is no such module, e.g. `features.up_to("norm5")` for the backbone of a DenseNet. The modules are shared with this sequential, not copied.
+    pub fn up_to(&self, name: &str) -> Option<NamedSequential> {
+        let index = self.position(name)?;
+        Some(NamedSequential(self.0[..=index].to_vec()))
+    }
+
+    /// The modules, without their names. They are shared with this sequential.
+    fn modules(&self) -> Vec<Mod<dyn Module>> {
+        self.iter().map(|(_, module)| module.clone()).collect()
+    }
 }
 
 impl Trainable for NamedSequential {
@@ -94,6 +259,68 @@ impl Trainable for NamedSequential {
         }
         children
     }
+
+    fn batch_norm_affine(&self) -> Option<(Tensor, Tensor)> {
+        match &self[..] {
+            [(_, module)] => module.batch_norm_affine(),
+            _ => None,
+        }
+    }
+
+    /// The fused convolutions keep their names, e.g. `conv0` in the `features` of a DenseNet, and the batch normalizations are removed.
+    fn fuse_conv_bn(&mut self) -> usize {
+        let mut fused = 0;
+        let mut i = 0;
+        while i + 1 < self.len() {
+            if let Some((scale, shift)) = self[i + 1].1.batch_norm_affine() {
+                if self[i].1.fold_channel_affine(&scale, &shift) {
+                    self.remove(i + 1);
+                    fused += 1;
+                }
+            }
+            i += 1;
+        }
+        fused
+    }
+
+    fn quantize_modules(&mut self, inputs: &HashMap<String, QParams>) -> usize {
+        let mut quantized = 0;
+        for (_, module) in self.iter_mut() {
+            if let Some(replacement) = quantize_module(module, inputs) {
+                *module = replacement;
+                quantized += 1;
+            }
+        }
+        quantized
+    }
+
+    fn prunable_channels(&self) -> Option<(i64, i64)> {
+        sequence_channels(&self.modules())
+    }
+
+    fn keeps_channels(&self) -> bool {
+        sequence_keeps_channels(&self.modules())
+    }
+
+    fn is_residual(&self) -> bool {
+        sequence_is_residual(&self.modules())
+    }
+
+    fn channel_importance(&self, criterion: ChannelImportance) -> Option<Tensor> {
+        sequence_importance(&self.modules(), criterion)
+    }
+
+    fn select_output_channels(&mut self, indices: &Tensor) {
+        select_sequence_output_channels(self.modules(), indices)
+    }
+
+    fn select_input_channels(&mut self, indices: &Tensor) {
+        select_sequence_input_channels(self.modules(), indices)
+    }
+
+    fn prune_channels(&mut self, amount: f64, importance: ChannelImportance) -> usize {
+        prune_sequence(self.modules(), amount, importance)
+    }
 }
 
 impl Module for NamedSequential {
@@ -104,6 +331,14 @@ impl Module for NamedSequential {
         }
         x
     }
+
+    fn try_forward(&self, input: &Tensor) -> Result<Tensor, RaddarError> {
+        let mut x = input.shallow_clone();
+        for (_, module) in self.iter() {
+            x = module.try_forward(&x)?;
+        }
+        Ok(x)
+    }
 }
 
 #[macro_export]
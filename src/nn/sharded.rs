@@ -0,0 +1,186 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use linked_hash_map::LinkedHashMap;
+use serde_json::{json, Value};
+
+use super::{write_safetensors, StateDict, StateDictExt};
+
+/// The name of the index of a sharded checkpoint, as used by the HuggingFace hub.
+pub const SHARD_INDEX_NAME: &str = "model.safetensors.index.json";
+
+/// A checkpoint split across several safetensors files, with an index mapping each tensor to its file.
+///
+/// The layout is that of sharded checkpoints on the HuggingFace hub: the shards are named `model-00001-of-00003.safetensors` and so on, and the index `model.safetensors.index.json` holds a `weight_map` from the names of the tensors to the names of the shards. Shards are only read when their tensors are requested, so a model can be loaded without holding the whole checkpoint in memory.
+///
+/// # Examples
+/// ```
+/// save_sharded(&model.parameters(), "checkpoint", 2 << 30)?;
+/// let checkpoint = ShardedCheckpoint::open("checkpoint")?;
+/// model.load_sharded(&checkpoint)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct ShardedCheckpoint {
+    pub dir: PathBuf,
+    /// The shard holding each tensor, in the order of the tensors.
+    pub weight_map: LinkedHashMap<String, String>,
+    /// The total size of the tensors in bytes.
+    pub total_size: u64,
+}
+
+impl ShardedCheckpoint {
+    /// Opens the sharded checkpoint in `dir`, or whose index is `dir`.
+    pub fn open<P: AsRef<Path>>(dir: P) -> anyhow::Result<Self> {
+        let path = dir.as_ref();
+        let (dir, index_path) = if path.is_dir() {
+            (path.to_owned(), path.join(SHARD_INDEX_NAME))
+        } else {
+            (
+                path.parent().unwrap_or_else(|| Path::new(".")).to_owned(),
+                path.to_owned(),
+            )
+        };
+        let index: Value = serde_json::from_slice(
+            &fs::read(&index_path)
+                .with_context(|| format!("Failed to read {}", index_path.display()))?,
+        )?;
+        let weight_map = index["weight_map"]
+            .as_object()
+            .ok_or_else(|| anyhow!("{} has no weight_map", index_path.display()))?
+            .iter()
+            .map(|(name, shard)| {
+                shard
+                    .as_str()
+                    .map(|shard| (name.clone(), shard.to_owned()))
+                    .ok_or_else(|| anyhow!("Tensor {} has an invalid shard {}", name, shard))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            dir,
+            weight_map,
+            total_size: index["metadata"]["total_size"].as_u64().unwrap_or(0),
+        })
+    }
+
+    /// Returns the names of the shards, in order.
+    pub fn shards(&self) -> Vec<String> {
+        let mut shards: Vec<String> = self.weight_map.values().cloned().collect();
+        shards.sort();
+        shards.dedup();
+        shards
+    }
+
+    /// Reads the tensors of a shard.
+    pub fn load_shard(&self, shard: &str) -> anyhow::Result<StateDict> {
+        StateDict::from_safetensors(self.dir.join(shard))
+    }
+
+    /// Reads the tensors with the given names, reading only the shards holding them.
+    pub fn load_tensors(&self, names: &[&str]) -> anyhow::Result<StateDict> {
+        let mut shards: Vec<&str> = names
+            .iter()
+            .map(|name| {
+                self.weight_map
+                    .get(*name)
+                    .map(String::as_str)
+                    .ok_or_else(|| anyhow!("Tensor {} is not in the checkpoint", name))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        shards.sort();
+        shards.dedup();
+        let mut tensors = HashMap::new();
+        for shard in shards {
+            tensors.extend(self.load_shard(shard)?);
+        }
+        Ok(names
+            .iter()
+            .filter_map(|name| {
+                tensors
+                    .remove(*name)
+                    .map(|tensor| (name.to_string(), tensor))
+            })
+            .collect())
+    }
+
+    /// Returns an iterator reading the shards one after another.
+    pub fn iter_shards(&self) -> impl Iterator<Item = anyhow::Result<StateDict>> + '_ {
+        self.shards()
+            .into_iter()
+            .map(move |shard| self.load_shard(&shard))
+    }
+
+    /// Reads all tensors, in the order of the index.
+    pub fn load_all(&self) -> anyhow::Result<StateDict> {
+        let mut tensors = HashMap::new();
+        for shard in self.iter_shards() {
+            tensors.extend(shard?);
+        }
+        Ok(self
+            .weight_map
+            .keys()
+            .filter_map(|name| tensors.remove(name).map(|tensor| (name.clone(), tensor)))
+            .collect())
+    }
+}
+
+/// Writes a [StateDict] into `dir` as safetensors shards of at most `max_shard_size` bytes each (unless a single tensor is larger), with an index, and returns the checkpoint.
+///
+/// The tensors are assigned to the shards in order, so each shard holds consecutive tensors.
+pub fn save_sharded<P: AsRef<Path>>(
+    state_dict: &StateDict,
+    dir: P,
+    max_shard_size: u64,
+) -> anyhow::Result<ShardedCheckpoint> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+
+    let mut shards: Vec<StateDict> = vec![StateDict::new()];
+    let mut shard_size = 0;
+    let mut total_size = 0;
+    for (name, tensor) in state_dict {
+        let size = {
+            let tensor = tensor.lock();
+            (tensor.numel() * tensor.kind().elt_size_in_bytes()) as u64
+        };
+        if shard_size > 0 && shard_size + size > max_shard_size {
+            shards.push(StateDict::new());
+            shard_size = 0;
+        }
+        shards
+            .last_mut()
+            .unwrap()
+            .insert(name.clone(), tensor.clone());
+        shard_size += size;
+        total_size += size;
+    }
+
+    let metadata = [("format".to_owned(), "pt".to_owned())]
+        .into_iter()
+        .collect();
+    let mut weight_map = LinkedHashMap::new();
+    for (index, shard) in shards.iter().enumerate() {
+        let name = format!("model-{:05}-of-{:05}.safetensors", index + 1, shards.len());
+        write_safetensors(shard, &metadata, dir.join(&name))?;
+        for tensor in shard.keys() {
+            weight_map.insert(tensor.clone(), name.clone());
+        }
+    }
+
+    let index = json!({
+        "metadata": { "total_size": total_size },
+        "weight_map": weight_map.iter().collect::<HashMap<_, _>>(),
+    });
+    fs::write(
+        dir.join(SHARD_INDEX_NAME),
+        serde_json::to_vec_pretty(&index)?,
+    )?;
+    Ok(ShardedCheckpoint {
+        dir: dir.to_owned(),
+        weight_map,
+        total_size,
+    })
+}
@@ -0,0 +1,189 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail};
+use tch::{nn::VarStore, no_grad, Device, Kind, Tensor};
+
+use crate::core::Cellable;
+
+use super::{is_uninitialized, read_gguf, read_safetensors, write_safetensors, StateDict};
+
+/// Reading and writing a [StateDict] in the file formats of other frameworks.
+///
+/// The names of the tensors are the dotted paths of [Trainable::parameters](super::Trainable::parameters), e.g. `layer1.weight`, which is also how PyTorch names the entries of its `state_dict`.
+pub trait StateDictExt: Sized {
+    /// Reads a numpy .npz file, in the order of the entries in the file.
+    fn from_npz<P: AsRef<Path>>(path: P) -> anyhow::Result<Self>;
+
+    /// Writes a numpy .npz file, which can be read with `numpy.load` and turned into a PyTorch `state_dict` with `{k: torch.from_numpy(v) for k, v in numpy.load(path).items()}`.
+    fn to_npz<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()>;
+
+    /// Reads a .ot file, as written by [to_ot](StateDictExt::to_ot).
+    fn from_ot<P: AsRef<Path>>(path: P) -> anyhow::Result<Self>;
+
+    /// Writes a .ot file, the native format of libtorch.
+    fn to_ot<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()>;
+
+    /// Reads a safetensors file, the format of the HuggingFace hub, in the order of the data in the file.
+    ///
+    /// The header of the file is validated, so that a corrupted or truncated file gives an error naming the faulty tensor rather than garbage weights.
+    fn from_safetensors<P: AsRef<Path>>(path: P) -> anyhow::Result<Self>;
+
+    /// Writes a safetensors file, which can be loaded with `safetensors.torch.load_file` in Python.
+    fn to_safetensors<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()>;
+
+    /// Reads the tensors of a GGUF file, the format of llama.cpp, dequantizing the quantized ones. See [read_gguf] for the metadata of the file.
+    fn from_gguf<P: AsRef<Path>>(path: P) -> anyhow::Result<Self>;
+
+    /// Returns the variables of a tch-rs `VarStore`, sorted by name.
+    ///
+    /// The tensors share their storage with the variables, so a module loading them with [Mod::load_var_store](super::Mod::load_var_store) and the tch-rs model of the `VarStore` train the same weights, e.g. with a `tch::nn::Optimizer`.
+    fn from_var_store(var_store: &VarStore) -> Self;
+
+    /// Copies the tensors into a new tch-rs `VarStore` on `device`, e.g. to build a `tch::nn` model or optimizer on the weights of a raddar module.
+    ///
+    /// The tensors which require grad become trainable variables, the others (e.g. the running statistics of a batch norm) are not trained. As every variable of a `VarStore`, they are created as `Kind::Float`, call `VarStore::double` or `VarStore::set_kind` on the result to change that.
+    fn to_var_store(&self, device: Device) -> VarStore;
+
+    /// Returns a `StateDict` whose floating point tensors are cast to `kind`, leaving the other tensors unchanged.
+    ///
+    /// Casting to `Kind::Half` or `Kind::BFloat16` before writing halves the size of a checkpoint of `Kind::Float` weights (or quarters that of the default `Kind::Double`). Half precision tensors are cast back to the kind of the parameters when they are loaded into a module. Note that npz files do not support `Kind::BFloat16`.
+    ///
+    /// # Examples
+    /// ```
+    /// model.parameters().cast(Kind::Half).to_safetensors("model.safetensors")?;
+    /// model.load_safetensors("model.safetensors")?;
+    /// ```
+    fn cast(&self, kind: Kind) -> Self;
+}
+
+/// Returns whether `kind` is a floating point kind.
+pub(crate) fn is_floating_point(kind: Kind) -> bool {
+    matches!(
+        kind,
+        Kind::Half | Kind::BFloat16 | Kind::Float | Kind::Double
+    )
+}
+
+/// Returns detached copies of the tensors of a [StateDict] on the cpu, with their names, so that they can be written.
+pub(crate) fn named_tensors(state_dict: &StateDict) -> Vec<(String, Tensor)> {
+    state_dict
+        .iter()
+        .map(|(name, tensor)| (name.clone(), tensor.lock().detach().to_device(Device::Cpu)))
+        .collect()
+}
+
+/// Loads the tensors of `source` into the tensors of `targets` with the same name, converting them to the kind and device of the targets.
+///
+/// Every target must be in `source` with the same shape, otherwise an error naming the tensor is returned and no tensor is loaded. The weights of lazy layers which are not created yet take the shape in `source`.
+pub(crate) fn load_checked(targets: &StateDict, mut source: StateDict) -> anyhow::Result<()> {
+    let mut loaded = Vec::with_capacity(targets.len());
+    for (name, target) in targets {
+        let tensor = source
+            .remove(name)
+            .ok_or_else(|| anyhow!("Tensor {} is missing from the state dict", name))?;
+        let tensor = tensor.lock();
+        let target_tensor = target.lock();
+        if tensor.size() != target_tensor.size() && !is_uninitialized(&target_tensor) {
+            bail!(
+                "Tensor {} has shape {:?} in the module, but {:?} in the state dict",
+                name,
+                target_tensor.size(),
+                tensor.size()
+            );
+        }
+        let tensor = tensor
+            .to_kind(target_tensor.kind())
+            .to_device(target_tensor.device())
+            .set_requires_grad(target_tensor.requires_grad());
+        loaded.push((target, tensor));
+    }
+    for (target, tensor) in loaded {
+        *target.lock() = tensor;
+    }
+    Ok(())
+}
+
+impl StateDictExt for StateDict {
+    fn from_npz<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Ok(Tensor::read_npz(path)?
+            .into_iter()
+            .map(|(name, tensor)| (name, tensor.cell()))
+            .collect())
+    }
+
+    fn to_npz<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        Ok(Tensor::write_npz(&named_tensors(self), path)?)
+    }
+
+    fn from_ot<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Ok(Tensor::load_multi(path)?
+            .into_iter()
+            .map(|(name, tensor)| (name, tensor.cell()))
+            .collect())
+    }
+
+    fn to_ot<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        Ok(Tensor::save_multi(&named_tensors(self), path)?)
+    }
+
+    fn from_safetensors<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Ok(read_safetensors(path)?.0)
+    }
+
+    fn to_safetensors<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let metadata = [("format".to_owned(), "pt".to_owned())]
+            .into_iter()
+            .collect();
+        write_safetensors(self, &metadata, path)
+    }
+
+    fn from_gguf<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Ok(read_gguf(path)?.0)
+    }
+
+    fn from_var_store(var_store: &VarStore) -> Self {
+        let mut variables: Vec<_> = var_store.variables().into_iter().collect();
+        variables.sort_by(|(a, _), (b, _)| a.cmp(b));
+        variables
+            .into_iter()
+            .map(|(name, tensor)| (name, tensor.cell()))
+            .collect()
+    }
+
+    fn to_var_store(&self, device: Device) -> VarStore {
+        let var_store = VarStore::new(device);
+        for (name, tensor) in self {
+            // A VarStore nests its variables in paths, whose names cannot contain dots.
+            let (path, name) = match name.rsplit_once('.') {
+                Some((path, name)) => (
+                    path.split('.')
+                        .fold(var_store.root(), |path, segment| path.sub(segment)),
+                    name,
+                ),
+                None => (var_store.root(), name.as_str()),
+            };
+            let tensor = tensor.lock();
+            let mut variable = if tensor.requires_grad() {
+                path.zeros(name, &tensor.size())
+            } else {
+                path.zeros_no_train(name, &tensor.size())
+            };
+            no_grad(|| variable.copy_(&tensor));
+        }
+        var_store
+    }
+
+    fn cast(&self, kind: Kind) -> Self {
+        self.iter()
+            .map(|(name, tensor)| {
+                let tensor = tensor.lock();
+                let tensor = if is_floating_point(tensor.kind()) {
+                    tensor.detach().to_kind(kind)
+                } else {
+                    tensor.shallow_clone()
+                };
+                (name.clone(), tensor.cell())
+            })
+            .collect()
+    }
+}
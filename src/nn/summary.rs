@@ -0,0 +1,158 @@
+use std::{fmt::Display, sync::Arc};
+
+use parking_lot::Mutex;
+use tch::{no_grad, Kind, Tensor};
+
+use super::{HookHandle, Mod, Module, Trainable};
+
+/// A layer of a [ModelSummary].
+#[derive(Debug, Clone)]
+pub struct LayerSummary {
+    /// The path of the layer in the model, e.g. `layer1.0.conv1`.
+    pub name: String,
+    /// The type of the layer, e.g. `Linear`.
+    pub kind: String,
+    /// The shape of the output, with `-1` as the batch dimension.
+    pub output_shape: Vec<i64>,
+    /// The number of elements of the parameters of the layer, not including its child modules.
+    pub parameters: i64,
+}
+
+/// The layers and parameter counts of a model, returned by [summary].
+#[derive(Debug, Clone)]
+pub struct ModelSummary {
+    /// The layers in the order their forward passes ended.
+    pub layers: Vec<LayerSummary>,
+    pub total_parameters: i64,
+    pub trainable_parameters: i64,
+}
+
+/// Runs a forward pass of `model` on zeros of shape `input_shape` (without the batch dimension) and of the kind of its parameters, prints the output shape and the number of parameters of each layer, like `torchsummary`, and returns them.
+///
+/// The forward pass is run in evaluation mode without gradients, and the mode of the model is restored afterwards. Layers are recorded by forward hooks (see [Mod::register_forward_hook]), so only layers called through their [Mod] are listed. Containers without parameters of their own, like [Sequential](super::Sequential), are omitted.
+///
+/// # Examples
+/// ```
+/// let model = alexnet(10, 0.5, false);
+/// summary(&model, &[3, 224, 224]);
+/// ```
+pub fn summary<T: Module + ?Sized>(model: &Mod<T>, input_shape: &[i64]) -> ModelSummary {
+    let layers = Arc::new(Mutex::new(Vec::new()));
+    let mut handles = Vec::new();
+    register_summary_hooks(&model.children(), &layers, &mut handles);
+
+    let guard = model.eval_guard();
+    let kind = model
+        .parameters()
+        .values()
+        .map(|parameter| parameter.lock().kind())
+        .next()
+        .unwrap_or(Kind::Double);
+    let input = Tensor::zeros(&[&[1][..], input_shape].concat(), (kind, model.device()));
+    let _output = no_grad(|| model.module().forward(&input));
+    drop(guard);
+    handles.into_iter().for_each(HookHandle::remove);
+
+    let summary = ModelSummary {
+        layers: std::mem::take(&mut *layers.lock()),
+        total_parameters: model.num_parameters() as i64,
+        trainable_parameters: model.num_trainable_parameters() as i64,
+    };
+    print!("{}", summary);
+    summary
+}
+
+fn register_summary_hooks(
+    modules: &linked_hash_map::LinkedHashMap<String, Mod<dyn Trainable>>,
+    layers: &Arc<Mutex<Vec<LayerSummary>>>,
+    handles: &mut Vec<HookHandle>,
+) {
+    for module in modules.values() {
+        let children = module.children();
+        let parameters = module.module().num_parameters() as i64;
+        if children.is_empty() || parameters > 0 {
+            let kind = module_kind(&*module.module());
+            let layers = layers.clone();
+            handles.push(module.register_forward_hook(move |name, _, output| {
+                let mut output_shape = output.size();
+                if let Some(batch) = output_shape.first_mut() {
+                    *batch = -1;
+                }
+                layers.lock().push(LayerSummary {
+                    name: name.to_owned(),
+                    kind: kind.clone(),
+                    output_shape,
+                    parameters,
+                });
+            }));
+        }
+        register_summary_hooks(&children, layers, handles);
+    }
+}
+
+/// Returns the name of the type of a module, from the beginning of its `Debug` output.
+pub(crate) fn module_kind<T: Trainable + ?Sized>(module: &T) -> String {
+    format!("{:?}", module)
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .next()
+        .unwrap_or_default()
+        .to_owned()
+}
+
+impl Display for ModelSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rows: Vec<_> = self
+            .layers
+            .iter()
+            .map(|layer| {
+                (
+                    format!("{} ({})", layer.name, layer.kind),
+                    format!("{:?}", layer.output_shape),
+                    layer.parameters.to_string(),
+                )
+            })
+            .collect();
+        let name_width = rows
+            .iter()
+            .map(|row| row.0.len())
+            .max()
+            .unwrap_or(0)
+            .max("layer (type)".len());
+        let shape_width = rows
+            .iter()
+            .map(|row| row.1.len())
+            .max()
+            .unwrap_or(0)
+            .max("output shape".len());
+        let width = name_width + shape_width + 16;
+        writeln!(
+            f,
+            "{:<name_width$}  {:<shape_width$}  {:>12}",
+            "layer (type)",
+            "output shape",
+            "params",
+            name_width = name_width,
+            shape_width = shape_width
+        )?;
+        writeln!(f, "{}", "=".repeat(width))?;
+        for (name, shape, parameters) in rows {
+            writeln!(
+                f,
+                "{:<name_width$}  {:<shape_width$}  {:>12}",
+                name,
+                shape,
+                parameters,
+                name_width = name_width,
+                shape_width = shape_width
+            )?;
+        }
+        writeln!(f, "{}", "=".repeat(width))?;
+        writeln!(f, "total params: {}", self.total_parameters)?;
+        writeln!(f, "trainable params: {}", self.trainable_parameters)?;
+        writeln!(
+            f,
+            "non-trainable params: {}",
+            self.total_parameters - self.trainable_parameters
+        )
+    }
+}
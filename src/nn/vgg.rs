@@ -1,16 +1,20 @@
 use std::vec;
 
-use raddar_derive::{ArchitectureBuilder, CallableModule};
+use raddar_derive::{ArchitectureBuilder, CallableModule, Trainable};
+use serde::{Deserialize, Serialize};
 use tch::Tensor;
 
-use crate::seq;
+use crate::{
+    core::{Backend, DefaultBackend},
+    seq,
+};
 
 use super::{
     AdaptiveAveragePooling2D, AdaptiveAveragePooling2DBuilder, BatchNorm2dBuilder, Conv2dBuilder,
-    DropoutBuilder, LinearBuilder, MaxPooling2DBuilder, Mod, Module, ReLU, Sequential, Trainable,
-    TrainableDict,
+    DropoutBuilder, LazyLinearBuilder, LinearBuilder, MaxPooling2DBuilder, Mod, Module, ReLU,
+    Sequential,
 };
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum VggType {
     Vgg11,
     Vgg11Bn,
@@ -25,9 +29,10 @@ pub enum VggType {
 /// VGG model
 ///
 /// The VGG model is a convolutional neural network that is 16 or 19 layers deep. See [Very Deep Convolutional Networks for Large-Scale Image Recognition](https://arxiv.org/abs/1409.1556) for more details.
-#[derive(Debug, CallableModule, ArchitectureBuilder)]
+#[derive(Debug, CallableModule, ArchitectureBuilder, Trainable)]
 pub struct Vgg {
     pub features: Mod<Sequential>,
+    #[trainable(skip)]
     pub avgpool: Mod<AdaptiveAveragePooling2D>,
     pub classifier: Mod<Sequential>,
 
@@ -73,20 +78,11 @@ fn make_layer(layer_type: Vec<i64>, batchnorm: bool) -> Mod<Sequential> {
     Mod::new(features)
 }
 
-impl Trainable for Vgg {
-    fn child_modules(&self) -> TrainableDict {
-        let mut result = TrainableDict::new();
-        result.insert("features".to_owned(), self.features.clone());
-        result.insert("classifier".to_owned(), self.classifier.clone());
-        result
-    }
-}
-
 impl Module for Vgg {
     fn forward(&self, input: &Tensor) -> Tensor {
         let mut output = (self.features)(input);
         output = (self.avgpool)(&output);
-        output = output.flatten(1, 3);
+        output = DefaultBackend::flatten(&output, 1, 3);
         output = (self.classifier)(&output);
         output
     }
@@ -147,10 +143,7 @@ impl Vgg {
             .output_size([7, 7])
             .build();
         let classifier = seq!(
-            LinearBuilder::default()
-                .input_dim(512 * 7 * 7)
-                .output_dim(4096)
-                .build(),
+            LazyLinearBuilder::default().output_dim(4096).build(),
             Mod::new(ReLU),
             DropoutBuilder::default().p(config.dropout).build(),
             LinearBuilder::default()
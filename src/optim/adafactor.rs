@@ -0,0 +1,207 @@
+use crate::{
+    core::{Cellable, TensorCell},
+    nn::StateDict,
+    optim::optimizer::{scalar_cell, scalar_value, OptimizerAlgorithm},
+};
+use raddar_derive::PartialBuilder;
+use tch::{no_grad, Tensor};
+
+/// The second moment estimate of a parameter in Adafactor.
+#[derive(Debug)]
+pub enum SecondMoment {
+    /// Row and column running averages, used for parameters with at least 2 dimensions.
+    Factored { row: Tensor, col: Tensor },
+    /// A full running average, used for vectors and scalars.
+    Full(Tensor),
+}
+
+/// Adafactor optimizer.
+///
+/// For parameters with at least 2 dimensions, the second moments are factored into row and column statistics, so the memory cost is sublinear in the number of parameters. This makes Adafactor suitable for large embedding or transformer models.
+///
+/// See [Adafactor: Adaptive Learning Rates with Sublinear Memory Cost](https://arxiv.org/abs/1804.04235).
+#[derive(PartialBuilder)]
+pub struct Adafactor {
+    #[builder(default = "0.001")]
+    learning_rate: f64,
+    #[builder(default = "(1e-30, 1e-3)")]
+    eps: (f64, f64),
+    #[builder(default = "1.0")]
+    clip_threshold: f64,
+    #[builder(default = "-0.8")]
+    decay_rate: f64,
+    #[builder(default = "None")]
+    beta1: Option<f64>,
+    #[builder(default = "0.")]
+    weight_decay: f64,
+    #[builder(default = "true")]
+    scale_parameter: bool,
+    step: i64,
+    second_moments: Option<Vec<SecondMoment>>,
+    m: Option<Vec<Tensor>>,
+}
+
+fn rms(tensor: &Tensor) -> f64 {
+    f64::from(tensor.square().mean(tensor.kind()).sqrt())
+}
+
+impl OptimizerAlgorithm for Adafactor {
+    fn init(&mut self, trainable_parameters: &Vec<TensorCell>) {
+        let mut second_moments = Vec::new();
+        let mut vector_m = Vec::new();
+        for parameter in trainable_parameters {
+            let parameter = parameter.lock();
+            let size = parameter.size();
+            if size.len() >= 2 {
+                let row_size = &size[..size.len() - 1];
+                let col_size = [&size[..size.len() - 2], &size[size.len() - 1..]].concat();
+                second_moments.push(SecondMoment::Factored {
+                    row: Tensor::zeros(row_size, (parameter.kind(), parameter.device())),
+                    col: Tensor::zeros(&col_size, (parameter.kind(), parameter.device())),
+                });
+            } else {
+                second_moments.push(SecondMoment::Full(Tensor::zeros_like(&*parameter)));
+            }
+            vector_m.push(Tensor::zeros_like(&*parameter));
+        }
+        self.second_moments = Some(second_moments);
+        self.m = self.beta1.map(|_| vector_m);
+    }
+
+    fn step(&mut self, trainable_parameters: &Vec<TensorCell>) {
+        self.step += 1;
+        let beta2t = 1. - (self.step as f64).powf(self.decay_rate);
+        for (i, parameter) in trainable_parameters.iter().enumerate() {
+            let mut parameter = parameter.lock();
+            let grad = parameter.grad();
+            no_grad(|| {
+                let mut lr = self.learning_rate;
+                if self.scale_parameter {
+                    lr *= self.eps.1.max(rms(&parameter));
+                }
+                let squared = grad.square() + self.eps.0;
+                let mut update = match &mut self.second_moments.as_mut().unwrap()[i] {
+                    SecondMoment::Factored { row, col } => {
+                        *row = (&*row) * beta2t
+                            + (1. - beta2t) * squared.mean_dim(&[-1], false, squared.kind());
+                        *col = (&*col) * beta2t
+                            + (1. - beta2t) * squared.mean_dim(&[-2], false, squared.kind());
+                        let r_factor = (&*row / row.mean_dim(&[-1], true, row.kind()))
+                            .rsqrt()
+                            .unsqueeze(-1);
+                        let c_factor = col.unsqueeze(-2).rsqrt();
+                        r_factor * c_factor * &grad
+                    }
+                    SecondMoment::Full(v) => {
+                        *v = (&*v) * beta2t + (1. - beta2t) * squared;
+                        v.rsqrt() * &grad
+                    }
+                };
+                update = &update / (rms(&update) / self.clip_threshold).max(1.) * lr;
+                if let Some(beta1) = self.beta1 {
+                    let m = &mut self.m.as_mut().unwrap()[i];
+                    *m = (&*m) * beta1 + (1. - beta1) * &update;
+                    update = m.shallow_clone();
+                }
+                if self.weight_decay != 0. {
+                    *parameter -= &*parameter * self.weight_decay * lr;
+                }
+                *parameter -= update;
+            })
+        }
+    }
+
+    fn learning_rate(&self) -> f64 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, lr: f64) {
+        self.learning_rate = lr;
+    }
+
+    fn momentum(&self) -> Option<f64> {
+        self.beta1
+    }
+
+    fn set_momentum(&mut self, momentum: f64) {
+        if self.beta1.is_some() {
+            self.beta1 = Some(momentum);
+        }
+    }
+
+    fn state_dict(&self) -> StateDict {
+        let mut state = StateDict::new();
+        state.insert("learning_rate".to_owned(), scalar_cell(self.learning_rate));
+        state.insert("step".to_owned(), scalar_cell(self.step as f64));
+        for (i, second_moment) in self.second_moments.iter().flatten().enumerate() {
+            match second_moment {
+                SecondMoment::Factored { row, col } => {
+                    state.insert(format!("row.{}", i), row.shallow_clone().cell());
+                    state.insert(format!("col.{}", i), col.shallow_clone().cell());
+                }
+                SecondMoment::Full(v) => {
+                    state.insert(format!("v.{}", i), v.shallow_clone().cell());
+                }
+            }
+        }
+        for (i, m) in self.m.iter().flatten().enumerate() {
+            state.insert(format!("m.{}", i), m.shallow_clone().cell());
+        }
+        state
+    }
+
+    fn load_state_dict(&mut self, state: StateDict) {
+        if let Some(lr) = scalar_value(&state, "learning_rate") {
+            self.learning_rate = lr;
+        }
+        if let Some(step) = scalar_value(&state, "step") {
+            self.step = step as i64;
+        }
+        let load = |key: String| state.get(&key).map(|value| value.lock().shallow_clone());
+        for (i, second_moment) in self.second_moments.iter_mut().flatten().enumerate() {
+            match second_moment {
+                SecondMoment::Factored { row, col } => {
+                    if let (Some(r), Some(c)) =
+                        (load(format!("row.{}", i)), load(format!("col.{}", i)))
+                    {
+                        *row = r;
+                        *col = c;
+                    }
+                }
+                SecondMoment::Full(v) => {
+                    if let Some(loaded) = load(format!("v.{}", i)) {
+                        *v = loaded;
+                    }
+                }
+            }
+        }
+        for (i, m) in self.m.iter_mut().flatten().enumerate() {
+            if let Some(loaded) = load(format!("m.{}", i)) {
+                *m = loaded;
+            }
+        }
+    }
+}
+
+impl Adafactor {
+    pub fn new(config: AdafactorConfig) -> Adafactor {
+        Adafactor {
+            learning_rate: config.learning_rate,
+            eps: config.eps,
+            clip_threshold: config.clip_threshold,
+            decay_rate: config.decay_rate,
+            beta1: config.beta1,
+            weight_decay: config.weight_decay,
+            scale_parameter: config.scale_parameter,
+            step: 0,
+            second_moments: None,
+            m: None,
+        }
+    }
+}
+
+pub fn adafactor(learning_rate: f64) -> Adafactor {
+    AdafactorBuilder::default()
+        .learning_rate(learning_rate)
+        .build()
+}
@@ -1,4 +1,8 @@
-use crate::{core::TensorCell, optim::optimizer::OptimizerAlgorithm};
+use crate::{
+    core::{Cellable, TensorCell},
+    nn::StateDict,
+    optim::optimizer::{indexed_tensors, scalar_cell, scalar_value, OptimizerAlgorithm},
+};
 use raddar_derive::{PartialBuilder};
 use tch::{no_grad, Tensor};
 #[derive(PartialBuilder)]
@@ -54,6 +58,47 @@ impl OptimizerAlgorithm for Adam {
     fn set_learning_rate(&mut self, lr: f64) {
         self.learning_rate = lr;
     }
+
+    fn momentum(&self) -> Option<f64> {
+        Some(self.betas.0)
+    }
+
+    fn set_momentum(&mut self, momentum: f64) {
+        self.betas.0 = momentum;
+    }
+
+    fn state_dict(&self) -> StateDict {
+        let mut state = StateDict::new();
+        state.insert("learning_rate".to_owned(), scalar_cell(self.learning_rate));
+        state.insert("beta1".to_owned(), scalar_cell(self.betas.0));
+        state.insert("step".to_owned(), scalar_cell(self.step as f64));
+        for (name, buffers) in [("m", &self.m), ("v", &self.v)] {
+            for (i, buffer) in buffers.iter().flatten().enumerate() {
+                state.insert(format!("{}.{}", name, i), buffer.shallow_clone().cell());
+            }
+        }
+        state
+    }
+
+    fn load_state_dict(&mut self, state: StateDict) {
+        if let Some(lr) = scalar_value(&state, "learning_rate") {
+            self.learning_rate = lr;
+        }
+        if let Some(beta1) = scalar_value(&state, "beta1") {
+            self.betas.0 = beta1;
+        }
+        if let Some(step) = scalar_value(&state, "step") {
+            self.step = step as i64;
+        }
+        let m = indexed_tensors(&state, "m");
+        if !m.is_empty() {
+            self.m = Some(m);
+        }
+        let v = indexed_tensors(&state, "v");
+        if !v.is_empty() {
+            self.v = Some(v);
+        }
+    }
 }
 impl Adam {
     pub fn new(config: AdamConfig) -> Adam {
@@ -1,7 +1,13 @@
-use raddar::optim::optimizer::SchedulerAlgorithm;
+use raddar::{
+    nn::StateDict,
+    optim::optimizer::{scalar_cell, scalar_value, SchedulerAlgorithm},
+};
 use raddar_derive::PartialBuilder;
 use std::f64::consts::PI;
 
+/// Cosine annealing schedule with a linear warmup.
+///
+/// See [SGDR: Stochastic Gradient Descent with Warm Restarts](https://arxiv.org/abs/1608.03983).
 #[derive(PartialBuilder)]
 pub struct CosineAnnealingLR {
     #[builder(default = "500")]
@@ -10,21 +16,45 @@ pub struct CosineAnnealingLR {
     eta_min: f64,
     last_step: i64,
     init_lr: f64,
+    last_lr: f64,
     #[builder(default = "100")]
     warmup_step: i64,
 }
 impl SchedulerAlgorithm for CosineAnnealingLR {
     fn init(&mut self, init_lr: f64) {
         self.init_lr = init_lr;
+        self.last_lr = init_lr;
     }
     fn update(&mut self, step: i64, _lr: f64) -> f64 {
         self.last_step = step;
-        if self.warmup_step > step {
+        self.last_lr = if self.warmup_step > step {
             (step as f64) * (self.init_lr - self.eta_min) / (self.warmup_step as f64) + self.eta_min
         } else {
             self.eta_min
                 + (self.init_lr - self.eta_min)
                     * (1. + ((step as f64) / (self.step_size as f64) * PI).cos())
+        };
+        self.last_lr
+    }
+    fn get_last_lr(&self) -> f64 {
+        self.last_lr
+    }
+    fn state_dict(&self) -> StateDict {
+        let mut state = StateDict::new();
+        state.insert("last_step".to_owned(), scalar_cell(self.last_step as f64));
+        state.insert("init_lr".to_owned(), scalar_cell(self.init_lr));
+        state.insert("last_lr".to_owned(), scalar_cell(self.last_lr));
+        state
+    }
+    fn load_state_dict(&mut self, state: StateDict) {
+        if let Some(last_step) = scalar_value(&state, "last_step") {
+            self.last_step = last_step as i64;
+        }
+        if let Some(init_lr) = scalar_value(&state, "init_lr") {
+            self.init_lr = init_lr;
+        }
+        if let Some(last_lr) = scalar_value(&state, "last_lr") {
+            self.last_lr = last_lr;
         }
     }
 }
@@ -35,6 +65,7 @@ impl CosineAnnealingLR {
             eta_min: config.eta_min,
             last_step: 0,
             init_lr: 0.,
+            last_lr: 0.,
             warmup_step: config.warmup_step,
         }
     }
@@ -46,3 +77,97 @@ pub fn cosine_annealing_lr(step_size: i64, warmup_step: i64, eta_min: f64) -> Co
         .eta_min(eta_min)
         .build()
 }
+
+/// Cosine annealing schedule with warm restarts (SGDR).
+///
+/// The learning rate is annealed from the initial value to `eta_min` over `t_0` steps, then restarted. After each restart, the length of the next period is multiplied by `t_mult`.
+///
+/// See [SGDR: Stochastic Gradient Descent with Warm Restarts](https://arxiv.org/abs/1608.03983).
+#[derive(PartialBuilder)]
+pub struct CosineAnnealingWarmRestarts {
+    #[builder(default = "500")]
+    t_0: i64,
+    #[builder(default = "1")]
+    t_mult: i64,
+    #[builder(default = "0.")]
+    eta_min: f64,
+    init_lr: f64,
+    last_lr: f64,
+}
+
+impl CosineAnnealingWarmRestarts {
+    pub fn new(config: CosineAnnealingWarmRestartsConfig) -> CosineAnnealingWarmRestarts {
+        assert!(config.t_0 > 0, "t_0 must be a positive integer.");
+        assert!(
+            config.t_mult >= 1,
+            "t_mult must be an integer not less than 1."
+        );
+        CosineAnnealingWarmRestarts {
+            t_0: config.t_0,
+            t_mult: config.t_mult,
+            eta_min: config.eta_min,
+            init_lr: 0.,
+            last_lr: 0.,
+        }
+    }
+
+    /// Returns the position in the current period and the length of the current period.
+    fn period(&self, step: i64) -> (i64, i64) {
+        if self.t_mult == 1 {
+            (step % self.t_0, self.t_0)
+        } else {
+            let mut t_cur = step;
+            let mut t_i = self.t_0;
+            while t_cur >= t_i {
+                t_cur -= t_i;
+                t_i *= self.t_mult;
+            }
+            (t_cur, t_i)
+        }
+    }
+}
+
+impl SchedulerAlgorithm for CosineAnnealingWarmRestarts {
+    fn init(&mut self, init_lr: f64) {
+        self.init_lr = init_lr;
+        self.last_lr = init_lr;
+    }
+
+    fn update(&mut self, step: i64, _lr: f64) -> f64 {
+        let (t_cur, t_i) = self.period(step);
+        self.last_lr = self.eta_min
+            + (self.init_lr - self.eta_min) * (1. + (PI * t_cur as f64 / t_i as f64).cos()) / 2.;
+        self.last_lr
+    }
+
+    fn get_last_lr(&self) -> f64 {
+        self.last_lr
+    }
+
+    fn state_dict(&self) -> StateDict {
+        let mut state = StateDict::new();
+        state.insert("init_lr".to_owned(), scalar_cell(self.init_lr));
+        state.insert("last_lr".to_owned(), scalar_cell(self.last_lr));
+        state
+    }
+    fn load_state_dict(&mut self, state: StateDict) {
+        if let Some(init_lr) = scalar_value(&state, "init_lr") {
+            self.init_lr = init_lr;
+        }
+        if let Some(last_lr) = scalar_value(&state, "last_lr") {
+            self.last_lr = last_lr;
+        }
+    }
+}
+
+pub fn cosine_annealing_warm_restarts(
+    t_0: i64,
+    t_mult: i64,
+    eta_min: f64,
+) -> CosineAnnealingWarmRestarts {
+    CosineAnnealingWarmRestartsBuilder::default()
+        .t_0(t_0)
+        .t_mult(t_mult)
+        .eta_min(eta_min)
+        .build()
+}
@@ -0,0 +1,108 @@
+use raddar::{
+    nn::StateDict,
+    optim::optimizer::{scalar_cell, scalar_value, SchedulerAlgorithm},
+};
+use raddar_derive::PartialBuilder;
+use serde::{Deserialize, Serialize};
+
+/// The scaling policy of a cyclical learning rate.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum CyclicMode {
+    /// A basic triangular cycle without amplitude scaling.
+    Triangular,
+    /// A triangular cycle that halves the amplitude after each cycle.
+    Triangular2,
+    /// A cycle that scales the amplitude by `gamma^step` at each step.
+    ExpRange(f64),
+}
+
+/// Cyclical learning rate policy, which cycles the learning rate between `base_lr` and `max_lr`.
+///
+/// See [Cyclical Learning Rates for Training Neural Networks](https://arxiv.org/abs/1506.01186).
+#[derive(PartialBuilder)]
+pub struct CyclicLR {
+    #[builder]
+    base_lr: f64,
+    #[builder]
+    max_lr: f64,
+    #[builder(default = "2000")]
+    step_size_up: i64,
+    #[builder(default = "None")]
+    step_size_down: Option<i64>,
+    #[builder(default = "CyclicMode::Triangular")]
+    mode: CyclicMode,
+    last_lr: f64,
+}
+
+impl CyclicLR {
+    pub fn new(config: CyclicLRConfig) -> CyclicLR {
+        assert!(
+            config.step_size_up > 0,
+            "step_size_up must be a positive integer."
+        );
+        CyclicLR {
+            base_lr: config.base_lr,
+            max_lr: config.max_lr,
+            step_size_up: config.step_size_up,
+            step_size_down: config.step_size_down,
+            mode: config.mode,
+            last_lr: 0.,
+        }
+    }
+
+    fn scale(&self, cycle: f64, step_num: f64) -> f64 {
+        match self.mode {
+            CyclicMode::Triangular => 1.,
+            CyclicMode::Triangular2 => 1. / 2f64.powf(cycle - 1.),
+            CyclicMode::ExpRange(gamma) => gamma.powf(step_num),
+        }
+    }
+}
+
+impl SchedulerAlgorithm for CyclicLR {
+    fn init(&mut self, _init_lr: f64) {
+        self.last_lr = self.base_lr;
+    }
+
+    /// The first call of `update` is made with step 1, which corresponds to the beginning of the cycle.
+    fn update(&mut self, step: i64, _lr: f64) -> f64 {
+        let step_num = (step - 1).max(0) as f64;
+        let step_size_up = self.step_size_up as f64;
+        let total_size = step_size_up + self.step_size_down.unwrap_or(self.step_size_up) as f64;
+        let step_ratio = step_size_up / total_size;
+        let cycle = (1. + step_num / total_size).floor();
+        let x = 1. + step_num / total_size - cycle;
+        let scale_factor = if x <= step_ratio {
+            x / step_ratio
+        } else {
+            (x - 1.) / (step_ratio - 1.)
+        };
+        let base_height = (self.max_lr - self.base_lr) * scale_factor;
+        self.last_lr = self.base_lr + base_height * self.scale(cycle, step_num);
+        self.last_lr
+    }
+
+    fn get_last_lr(&self) -> f64 {
+        self.last_lr
+    }
+
+    fn state_dict(&self) -> StateDict {
+        let mut state = StateDict::new();
+        state.insert("last_lr".to_owned(), scalar_cell(self.last_lr));
+        state
+    }
+    fn load_state_dict(&mut self, state: StateDict) {
+        if let Some(last_lr) = scalar_value(&state, "last_lr") {
+            self.last_lr = last_lr;
+        }
+    }
+}
+
+pub fn cyclic_lr(base_lr: f64, max_lr: f64, step_size_up: i64, mode: CyclicMode) -> CyclicLR {
+    CyclicLRBuilder::default()
+        .base_lr(base_lr)
+        .max_lr(max_lr)
+        .step_size_up(step_size_up)
+        .mode(mode)
+        .build()
+}
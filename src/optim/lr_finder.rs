@@ -0,0 +1,114 @@
+use derive_builder::Builder;
+use tch::{no_grad, Tensor};
+
+use crate::{core::TensorCell, optim::optimizer::OptimizerAlgorithm};
+
+/// The configuration for [lr_find].
+#[derive(Debug, Clone, Builder)]
+#[builder(pattern = "owned")]
+pub struct LrFinderConfig {
+    #[builder(default = "1e-7")]
+    pub start_lr: f64,
+
+    #[builder(default = "10.")]
+    pub end_lr: f64,
+
+    #[builder(default = "100")]
+    pub num_steps: usize,
+
+    /// The smoothing factor of the exponential moving average of the loss. `0.` means no smoothing.
+    #[builder(default = "0.05")]
+    pub smooth_factor: f64,
+
+    /// Stop the sweep when the smoothed loss exceeds `diverge_threshold` times the best loss.
+    #[builder(default = "5.")]
+    pub diverge_threshold: f64,
+}
+
+/// The learning rates and the corresponding (smoothed) losses recorded by [lr_find].
+#[derive(Debug, Clone, Default)]
+pub struct LrFinderResult {
+    pub learning_rates: Vec<f64>,
+    pub losses: Vec<f64>,
+}
+
+impl LrFinderResult {
+    /// Suggests the learning rate where the loss decreases the fastest.
+    pub fn suggestion(&self) -> Option<f64> {
+        if self.losses.len() < 2 {
+            return None;
+        }
+        self.losses
+            .windows(2)
+            .enumerate()
+            .map(|(i, pair)| (i, pair[1] - pair[0]))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| self.learning_rates[i])
+    }
+}
+
+/// Sweeps the learning rate exponentially from `start_lr` to `end_lr`, and records the loss at each step. This helps to pick a good learning rate before training.
+///
+/// `loss_fn` should compute the loss of a batch with the model that owns `parameters`. The sweep stops early when the batches are exhausted or the loss diverges.
+///
+/// The parameters are restored to their original values after the sweep.
+///
+/// See [Cyclical Learning Rates for Training Neural Networks](https://arxiv.org/abs/1506.01186).
+pub fn lr_find<T, B, I, F>(
+    parameters: Vec<TensorCell>,
+    mut opt: T,
+    batches: I,
+    mut loss_fn: F,
+    config: LrFinderConfig,
+) -> LrFinderResult
+where
+    T: OptimizerAlgorithm,
+    I: IntoIterator<Item = B>,
+    F: FnMut(B) -> Tensor,
+{
+    let backup: Vec<Tensor> = parameters
+        .iter()
+        .map(|parameter| no_grad(|| parameter.lock().copy()))
+        .collect();
+    opt.init(&parameters);
+
+    let mut result = LrFinderResult::default();
+    let ratio = (config.end_lr / config.start_lr).powf(1. / config.num_steps.max(1) as f64);
+    let mut lr = config.start_lr;
+    let mut best_loss = f64::INFINITY;
+    let mut smoothed_loss = 0.;
+    for (i, batch) in batches.into_iter().take(config.num_steps).enumerate() {
+        opt.set_learning_rate(lr);
+        parameters.iter().for_each(|parameter| {
+            parameter.lock().zero_grad();
+        });
+        let loss = loss_fn(batch);
+        loss.backward();
+        opt.step(&parameters);
+
+        let loss = f64::from(&loss);
+        smoothed_loss = if i == 0 {
+            loss
+        } else {
+            config.smooth_factor * smoothed_loss + (1. - config.smooth_factor) * loss
+        };
+        result.learning_rates.push(lr);
+        result.losses.push(smoothed_loss);
+        if smoothed_loss < best_loss {
+            best_loss = smoothed_loss;
+        }
+        if !smoothed_loss.is_finite() || smoothed_loss > config.diverge_threshold * best_loss {
+            break;
+        }
+        lr *= ratio;
+    }
+
+    for (parameter, original) in parameters.iter().zip(backup) {
+        let mut parameter = parameter.lock();
+        no_grad(|| {
+            parameter.copy_(&original);
+        });
+        parameter.zero_grad();
+    }
+    result
+}
@@ -1,13 +1,23 @@
+pub use adafactor::*;
 pub use adam::*;
 pub use cosine_annealing_lr::*;
+pub use cyclic_lr::*;
 pub use gradient_descent::*;
+pub use lr_finder::*;
+pub use one_cycle_lr::*;
 pub use optimizer::*;
 pub use rms_prop::*;
 pub use steplr::*;
+pub use warmup::*;
 
+pub mod adafactor;
 pub mod adam;
 pub mod cosine_annealing_lr;
+pub mod cyclic_lr;
 pub mod gradient_descent;
+pub mod lr_finder;
+pub mod one_cycle_lr;
 pub mod optimizer;
 pub mod rms_prop;
 pub mod steplr;
+pub mod warmup;
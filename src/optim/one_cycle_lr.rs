@@ -0,0 +1,168 @@
+use raddar::{
+    nn::StateDict,
+    optim::optimizer::{scalar_cell, scalar_value, SchedulerAlgorithm},
+};
+use raddar_derive::PartialBuilder;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// The annealing strategy used between two learning rates.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum AnnealStrategy {
+    Cos,
+    Linear,
+}
+
+impl AnnealStrategy {
+    /// Anneals from `start` to `end` as `pct` goes from 0 to 1.
+    pub fn anneal(&self, start: f64, end: f64, pct: f64) -> f64 {
+        match self {
+            AnnealStrategy::Cos => end + (start - end) / 2. * ((PI * pct).cos() + 1.),
+            AnnealStrategy::Linear => (end - start) * pct + start,
+        }
+    }
+}
+
+/// The 1cycle learning rate policy.
+///
+/// The learning rate is increased from `max_lr / div_factor` to `max_lr` in the first `pct_start` of `total_steps`, then annealed to `max_lr / div_factor / final_div_factor`. If `cycle_momentum` is set, the momentum of the optimizer is cycled inversely between `max_momentum` and `base_momentum`.
+///
+/// See [Super-Convergence: Very Fast Training of Neural Networks Using Large Learning Rates](https://arxiv.org/abs/1708.07120).
+#[derive(PartialBuilder)]
+pub struct OneCycleLR {
+    #[builder]
+    max_lr: f64,
+    #[builder]
+    total_steps: i64,
+    #[builder(default = "0.3")]
+    pct_start: f64,
+    #[builder(default = "AnnealStrategy::Cos")]
+    anneal_strategy: AnnealStrategy,
+    #[builder(default = "true")]
+    cycle_momentum: bool,
+    #[builder(default = "0.85")]
+    base_momentum: f64,
+    #[builder(default = "0.95")]
+    max_momentum: f64,
+    #[builder(default = "25.")]
+    div_factor: f64,
+    #[builder(default = "1e4")]
+    final_div_factor: f64,
+    last_lr: f64,
+}
+
+impl OneCycleLR {
+    pub fn new(config: OneCycleLRConfig) -> OneCycleLR {
+        assert!(
+            config.total_steps > 0,
+            "total_steps must be a positive integer."
+        );
+        assert!(
+            config.pct_start >= 0. && config.pct_start <= 1.,
+            "pct_start must be in [0, 1]."
+        );
+        OneCycleLR {
+            max_lr: config.max_lr,
+            total_steps: config.total_steps,
+            pct_start: config.pct_start,
+            anneal_strategy: config.anneal_strategy,
+            cycle_momentum: config.cycle_momentum,
+            base_momentum: config.base_momentum,
+            max_momentum: config.max_momentum,
+            div_factor: config.div_factor,
+            final_div_factor: config.final_div_factor,
+            last_lr: 0.,
+        }
+    }
+
+    fn initial_lr(&self) -> f64 {
+        self.max_lr / self.div_factor
+    }
+
+    fn min_lr(&self) -> f64 {
+        self.initial_lr() / self.final_div_factor
+    }
+
+    /// Returns the index of the current phase and the percentage of the phase that has passed.
+    ///
+    /// The first call of `update` is made with step 1, which corresponds to the beginning of the cycle.
+    fn phase(&self, step: i64) -> (usize, f64) {
+        let step_num = (step - 1).clamp(0, self.total_steps - 1) as f64;
+        let warmup_end = self.pct_start * self.total_steps as f64 - 1.;
+        let total_end = self.total_steps as f64 - 1.;
+        if step_num <= warmup_end {
+            (
+                0,
+                if warmup_end > 0. {
+                    step_num / warmup_end
+                } else {
+                    1.
+                },
+            )
+        } else {
+            let length = total_end - warmup_end;
+            (
+                1,
+                if length > 0. {
+                    (step_num - warmup_end) / length
+                } else {
+                    1.
+                },
+            )
+        }
+    }
+}
+
+impl SchedulerAlgorithm for OneCycleLR {
+    fn init(&mut self, _init_lr: f64) {
+        self.last_lr = self.initial_lr();
+    }
+
+    fn update(&mut self, step: i64, _lr: f64) -> f64 {
+        let (phase, pct) = self.phase(step);
+        self.last_lr = match phase {
+            0 => self
+                .anneal_strategy
+                .anneal(self.initial_lr(), self.max_lr, pct),
+            _ => self.anneal_strategy.anneal(self.max_lr, self.min_lr(), pct),
+        };
+        self.last_lr
+    }
+
+    fn update_momentum(&mut self, step: i64, momentum: f64) -> f64 {
+        if !self.cycle_momentum {
+            return momentum;
+        }
+        let (phase, pct) = self.phase(step);
+        match phase {
+            0 => self
+                .anneal_strategy
+                .anneal(self.max_momentum, self.base_momentum, pct),
+            _ => self
+                .anneal_strategy
+                .anneal(self.base_momentum, self.max_momentum, pct),
+        }
+    }
+
+    fn get_last_lr(&self) -> f64 {
+        self.last_lr
+    }
+
+    fn state_dict(&self) -> StateDict {
+        let mut state = StateDict::new();
+        state.insert("last_lr".to_owned(), scalar_cell(self.last_lr));
+        state
+    }
+    fn load_state_dict(&mut self, state: StateDict) {
+        if let Some(last_lr) = scalar_value(&state, "last_lr") {
+            self.last_lr = last_lr;
+        }
+    }
+}
+
+pub fn one_cycle_lr(max_lr: f64, total_steps: i64) -> OneCycleLR {
+    OneCycleLRBuilder::default()
+        .max_lr(max_lr)
+        .total_steps(total_steps)
+        .build()
+}
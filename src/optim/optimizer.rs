@@ -1,6 +1,42 @@
-use crate::core::TensorCell;
+use tch::Tensor;
 
-pub struct Optimizer<T, U>
+use crate::{
+    core::{Cellable, TensorCell},
+    nn::StateDict,
+};
+
+/// A common interface for everything that updates a set of parameters, so that trainers and other utilities can be written generically over optimizers.
+pub trait Optimizer {
+    /// Performs a single optimization step.
+    fn step(&mut self);
+
+    /// Returns the groups of parameters updated by the optimizer.
+    fn param_groups(&self) -> Vec<Vec<TensorCell>>;
+
+    /// Clears the gradients of all parameters updated by the optimizer.
+    fn zero_grad(&self) {
+        for group in self.param_groups() {
+            for parameter in group {
+                parameter.lock().zero_grad();
+            }
+        }
+    }
+
+    /// Returns the current learning rate.
+    fn learning_rate(&self) -> f64;
+
+    /// Overrides the current learning rate.
+    fn set_learning_rate(&mut self, lr: f64);
+
+    /// Returns the internal state of the optimizer, so that interrupted training can be resumed.
+    fn state_dict(&self) -> StateDict;
+
+    /// Restores the state returned by `state_dict`.
+    fn load_state_dict(&mut self, state: StateDict);
+}
+
+/// An [OptimizerAlgorithm] driven by an optional [SchedulerAlgorithm] over a single group of parameters.
+pub struct ScheduledOptimizer<T, U>
 where
     T: OptimizerAlgorithm,
     U: SchedulerAlgorithm,
@@ -16,36 +52,155 @@ pub trait OptimizerAlgorithm {
     fn init(&mut self, training_parameters: &Vec<TensorCell>);
     fn learning_rate(&self) -> f64;
     fn set_learning_rate(&mut self, lr: f64);
+
+    /// Returns the momentum of the optimizer, or `None` if the optimizer has no momentum term.
+    fn momentum(&self) -> Option<f64> {
+        None
+    }
+
+    /// Sets the momentum of the optimizer. Optimizers without a momentum term ignore this.
+    fn set_momentum(&mut self, _momentum: f64) {}
+
+    /// Returns the internal state of the optimizer, e.g. the step count and the momentum buffers.
+    ///
+    /// By default, only the learning rate is saved. Optimizers with buffers should override this method.
+    fn state_dict(&self) -> StateDict {
+        let mut state = StateDict::new();
+        state.insert(
+            "learning_rate".to_owned(),
+            scalar_cell(self.learning_rate()),
+        );
+        state
+    }
+
+    /// Restores the internal state of the optimizer from a `StateDict` returned by `state_dict`.
+    fn load_state_dict(&mut self, state: StateDict) {
+        if let Some(lr) = scalar_value(&state, "learning_rate") {
+            self.set_learning_rate(lr);
+        }
+    }
 }
 
 pub trait SchedulerAlgorithm {
     fn init(&mut self, init_lr: f64);
     fn update(&mut self, step: i64, lr: f64) -> f64;
+    /// Computes the momentum for the given step. By default, the momentum is left unchanged.
+    fn update_momentum(&mut self, _step: i64, momentum: f64) -> f64 {
+        momentum
+    }
+    /// Returns the learning rate computed by the last call of `update`, which is useful for logging.
+    fn get_last_lr(&self) -> f64;
+    /// Returns the internal state of the scheduler. By default, the scheduler is stateless.
+    fn state_dict(&self) -> StateDict {
+        StateDict::new()
+    }
+    /// Restores the internal state of the scheduler from a `StateDict` returned by `state_dict`.
+    fn load_state_dict(&mut self, _state: StateDict) {}
 }
-impl<T, U> Optimizer<T, U>
+
+/// Wraps a scalar into a [TensorCell], so that it can be stored in a [StateDict].
+pub fn scalar_cell(value: f64) -> TensorCell {
+    Tensor::from(value).cell()
+}
+
+/// Reads a scalar stored by [scalar_cell] from a [StateDict].
+pub fn scalar_value(state: &StateDict, key: &str) -> Option<f64> {
+    state.get(key).map(|value| f64::from(&*value.lock()))
+}
+
+/// Collects the tensors stored under `prefix.0`, `prefix.1`, ... in a [StateDict], up to the first missing index.
+pub fn indexed_tensors(state: &StateDict, prefix: &str) -> Vec<Tensor> {
+    (0..)
+        .map_while(|i| state.get(&format!("{}.{}", prefix, i)))
+        .map(|value| value.lock().shallow_clone())
+        .collect()
+}
+
+/// Collects the entries of a [StateDict] under `prefix.`, with the prefix stripped.
+pub fn sub_state_dict(state: &StateDict, prefix: &str) -> StateDict {
+    let prefix = format!("{}.", prefix);
+    state
+        .iter()
+        .filter_map(|(key, value)| {
+            key.strip_prefix(&prefix)
+                .map(|key| (key.to_owned(), value.clone()))
+        })
+        .collect()
+}
+impl<T, U> Optimizer for ScheduledOptimizer<T, U>
 where
     T: OptimizerAlgorithm,
     U: SchedulerAlgorithm,
 {
-    pub fn step(&mut self) {
+    fn step(&mut self) {
         self.step += 1;
         if let Some(scheduler) = &mut self.scheduler {
             self.opt
                 .set_learning_rate(scheduler.update(self.step, self.opt.learning_rate()));
+            if let Some(momentum) = self.opt.momentum() {
+                self.opt
+                    .set_momentum(scheduler.update_momentum(self.step, momentum));
+            }
         }
         self.opt.step(&self.parameters);
     }
+
+    fn param_groups(&self) -> Vec<Vec<TensorCell>> {
+        vec![self.parameters.clone()]
+    }
+
+    fn learning_rate(&self) -> f64 {
+        self.opt.learning_rate()
+    }
+
+    fn set_learning_rate(&mut self, lr: f64) {
+        self.opt.set_learning_rate(lr);
+    }
+
+    /// Returns the state of the optimizer and its scheduler.
+    ///
+    /// The state of the optimizer algorithm is prefixed by `opt.`, and the state of the scheduler is prefixed by `scheduler.`.
+    fn state_dict(&self) -> StateDict {
+        let mut state = StateDict::new();
+        state.insert("step".to_owned(), scalar_cell(self.step as f64));
+        for (key, value) in self.opt.state_dict() {
+            state.insert(format!("opt.{}", key), value);
+        }
+        if let Some(scheduler) = &self.scheduler {
+            for (key, value) in scheduler.state_dict() {
+                state.insert(format!("scheduler.{}", key), value);
+            }
+        }
+        state
+    }
+
+    fn load_state_dict(&mut self, state: StateDict) {
+        if let Some(step) = scalar_value(&state, "step") {
+            self.step = step as i64;
+        }
+        self.opt.load_state_dict(sub_state_dict(&state, "opt"));
+        if let Some(scheduler) = &mut self.scheduler {
+            scheduler.load_state_dict(sub_state_dict(&state, "scheduler"));
+        }
+    }
+}
+
+impl<T, U> ScheduledOptimizer<T, U>
+where
+    T: OptimizerAlgorithm,
+    U: SchedulerAlgorithm,
+{
     pub fn new(
         parameters: Vec<TensorCell>,
         mut opt: T,
         mut scheduler: Option<U>,
-    ) -> Optimizer<T, U> {
+    ) -> ScheduledOptimizer<T, U> {
         opt.init(&parameters);
         let init_lr = opt.learning_rate();
         if let Some(sched) = &mut scheduler {
             sched.init(init_lr);
         }
-        Optimizer {
+        ScheduledOptimizer {
             opt,
             parameters,
             scheduler,
@@ -62,25 +217,46 @@ impl SchedulerAlgorithm for ConstantScheduler {
     }
 
     fn update(&mut self, _step: i64, lr: f64) -> f64 {
+        self.lr = lr;
         lr
     }
+
+    fn get_last_lr(&self) -> f64 {
+        self.lr
+    }
+
+    fn state_dict(&self) -> StateDict {
+        let mut state = StateDict::new();
+        state.insert("lr".to_owned(), scalar_cell(self.lr));
+        state
+    }
+
+    fn load_state_dict(&mut self, state: StateDict) {
+        if let Some(lr) = scalar_value(&state, "lr") {
+            self.lr = lr;
+        }
+    }
 }
 impl ConstantScheduler {
     pub fn new() -> ConstantScheduler {
         ConstantScheduler { lr: 0. }
     }
 }
-pub fn opt_with_sched<T, U>(parameters: Vec<TensorCell>, opt: T, sched: U) -> Optimizer<T, U>
+pub fn opt_with_sched<T, U>(
+    parameters: Vec<TensorCell>,
+    opt: T,
+    sched: U,
+) -> ScheduledOptimizer<T, U>
 where
     T: OptimizerAlgorithm,
     U: SchedulerAlgorithm,
 {
-    Optimizer::new(parameters, opt, Some(sched))
+    ScheduledOptimizer::new(parameters, opt, Some(sched))
 }
 
-pub fn opt<T>(parameters: Vec<TensorCell>, opt: T) -> Optimizer<T, ConstantScheduler>
+pub fn opt<T>(parameters: Vec<TensorCell>, opt: T) -> ScheduledOptimizer<T, ConstantScheduler>
 where
     T: OptimizerAlgorithm,
 {
-    Optimizer::new(parameters, opt, Some(ConstantScheduler::new()))
+    ScheduledOptimizer::new(parameters, opt, Some(ConstantScheduler::new()))
 }
@@ -1,4 +1,8 @@
-use crate::{core::TensorCell, optim::optimizer::OptimizerAlgorithm};
+use crate::{
+    core::{Cellable, TensorCell},
+    nn::StateDict,
+    optim::optimizer::{indexed_tensors, scalar_cell, scalar_value, OptimizerAlgorithm},
+};
 use raddar_derive::PartialBuilder;
 use tch::{no_grad, Tensor};
 
@@ -50,6 +54,43 @@ impl OptimizerAlgorithm for RMSProp {
     fn set_learning_rate(&mut self, lr: f64) {
         self.learning_rate = lr;
     }
+
+    fn momentum(&self) -> Option<f64> {
+        Some(self.momentum)
+    }
+
+    fn set_momentum(&mut self, momentum: f64) {
+        self.momentum = momentum;
+    }
+
+    fn state_dict(&self) -> StateDict {
+        let mut state = StateDict::new();
+        state.insert("learning_rate".to_owned(), scalar_cell(self.learning_rate));
+        state.insert("momentum".to_owned(), scalar_cell(self.momentum));
+        for (name, buffers) in [("r1", &self.r1), ("r2", &self.r2)] {
+            for (i, buffer) in buffers.iter().flatten().enumerate() {
+                state.insert(format!("{}.{}", name, i), buffer.shallow_clone().cell());
+            }
+        }
+        state
+    }
+
+    fn load_state_dict(&mut self, state: StateDict) {
+        if let Some(lr) = scalar_value(&state, "learning_rate") {
+            self.learning_rate = lr;
+        }
+        if let Some(momentum) = scalar_value(&state, "momentum") {
+            self.momentum = momentum;
+        }
+        let r1 = indexed_tensors(&state, "r1");
+        if !r1.is_empty() {
+            self.r1 = Some(r1);
+        }
+        let r2 = indexed_tensors(&state, "r2");
+        if !r2.is_empty() {
+            self.r2 = Some(r2);
+        }
+    }
 }
 impl RMSProp {
     pub fn new(config: RMSPropConfig) -> RMSProp {
@@ -1,4 +1,7 @@
-use raddar::optim::optimizer::SchedulerAlgorithm;
+use raddar::{
+    nn::StateDict,
+    optim::optimizer::{scalar_cell, scalar_value, SchedulerAlgorithm},
+};
 use raddar_derive::PartialBuilder;
 #[derive(PartialBuilder)]
 pub struct StepLR {
@@ -8,18 +11,42 @@ pub struct StepLR {
     gamma: f64,
     last_step: i64,
     init_lr: f64,
+    last_lr: f64,
 }
 impl SchedulerAlgorithm for StepLR {
     fn update(&mut self, step: i64, lr: f64) -> f64 {
-        if step - self.last_step >= self.step_size {
+        self.last_lr = if step - self.last_step >= self.step_size {
             self.last_step = step;
             lr * self.gamma
         } else {
             lr
-        }
+        };
+        self.last_lr
     }
     fn init(&mut self, init_lr: f64) {
         self.init_lr = init_lr;
+        self.last_lr = init_lr;
+    }
+    fn get_last_lr(&self) -> f64 {
+        self.last_lr
+    }
+    fn state_dict(&self) -> StateDict {
+        let mut state = StateDict::new();
+        state.insert("last_step".to_owned(), scalar_cell(self.last_step as f64));
+        state.insert("init_lr".to_owned(), scalar_cell(self.init_lr));
+        state.insert("last_lr".to_owned(), scalar_cell(self.last_lr));
+        state
+    }
+    fn load_state_dict(&mut self, state: StateDict) {
+        if let Some(last_step) = scalar_value(&state, "last_step") {
+            self.last_step = last_step as i64;
+        }
+        if let Some(init_lr) = scalar_value(&state, "init_lr") {
+            self.init_lr = init_lr;
+        }
+        if let Some(last_lr) = scalar_value(&state, "last_lr") {
+            self.last_lr = last_lr;
+        }
     }
 }
 impl StepLR {
@@ -29,6 +56,7 @@ impl StepLR {
             gamma: config.gamma,
             last_step: 0,
             init_lr: 0.,
+            last_lr: 0.,
         }
     }
 }
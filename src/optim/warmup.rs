@@ -0,0 +1,101 @@
+use raddar::{
+    nn::StateDict,
+    optim::optimizer::{scalar_cell, scalar_value, sub_state_dict, SchedulerAlgorithm},
+};
+
+/// The way the learning rate behaves during warmup.
+#[derive(Debug, Clone, Copy)]
+pub enum WarmupStrategy {
+    /// Increase the learning rate linearly from 0 to the initial learning rate.
+    Linear,
+    /// Keep the learning rate at a constant fraction of the initial learning rate.
+    Constant(f64),
+}
+
+/// A scheduler combinator that warms up the learning rate for `warmup_steps` steps, then hands over to the inner scheduler.
+///
+/// The inner scheduler sees the steps as if the warmup never happened, i.e. its first step is step 1.
+pub struct Warmup<U: SchedulerAlgorithm> {
+    inner: U,
+    warmup_steps: i64,
+    strategy: WarmupStrategy,
+    init_lr: f64,
+    last_lr: f64,
+}
+
+impl<U: SchedulerAlgorithm> Warmup<U> {
+    pub fn new(inner: U, warmup_steps: i64, strategy: WarmupStrategy) -> Warmup<U> {
+        Warmup {
+            inner,
+            warmup_steps,
+            strategy,
+            init_lr: 0.,
+            last_lr: 0.,
+        }
+    }
+
+    /// Get a reference to the inner scheduler.
+    pub fn inner(&self) -> &U {
+        &self.inner
+    }
+}
+
+impl<U: SchedulerAlgorithm> SchedulerAlgorithm for Warmup<U> {
+    fn init(&mut self, init_lr: f64) {
+        self.init_lr = init_lr;
+        self.last_lr = init_lr;
+        self.inner.init(init_lr);
+    }
+
+    fn update(&mut self, step: i64, lr: f64) -> f64 {
+        self.last_lr = if step <= self.warmup_steps {
+            match self.strategy {
+                WarmupStrategy::Linear => self.init_lr * step as f64 / self.warmup_steps as f64,
+                WarmupStrategy::Constant(factor) => self.init_lr * factor,
+            }
+        } else if step == self.warmup_steps + 1 {
+            // The inner scheduler should start from the initial learning rate, not the warmup one.
+            self.inner.update(1, self.init_lr)
+        } else {
+            self.inner.update(step - self.warmup_steps, lr)
+        };
+        self.last_lr
+    }
+
+    fn update_momentum(&mut self, step: i64, momentum: f64) -> f64 {
+        if step <= self.warmup_steps {
+            momentum
+        } else {
+            self.inner
+                .update_momentum(step - self.warmup_steps, momentum)
+        }
+    }
+
+    fn get_last_lr(&self) -> f64 {
+        self.last_lr
+    }
+
+    fn state_dict(&self) -> StateDict {
+        let mut state = StateDict::new();
+        state.insert("init_lr".to_owned(), scalar_cell(self.init_lr));
+        state.insert("last_lr".to_owned(), scalar_cell(self.last_lr));
+        for (key, value) in self.inner.state_dict() {
+            state.insert(format!("inner.{}", key), value);
+        }
+        state
+    }
+
+    fn load_state_dict(&mut self, state: StateDict) {
+        if let Some(init_lr) = scalar_value(&state, "init_lr") {
+            self.init_lr = init_lr;
+        }
+        if let Some(last_lr) = scalar_value(&state, "last_lr") {
+            self.last_lr = last_lr;
+        }
+        self.inner.load_state_dict(sub_state_dict(&state, "inner"));
+    }
+}
+
+pub fn warmup<U: SchedulerAlgorithm>(inner: U, warmup_steps: i64) -> Warmup<U> {
+    Warmup::new(inner, warmup_steps, WarmupStrategy::Linear)
+}
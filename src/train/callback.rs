@@ -0,0 +1,86 @@
+use linked_hash_map::LinkedHashMap;
+use tch::Tensor;
+
+use crate::{
+    nn::{Mod, Trainable},
+    optim::Optimizer,
+};
+
+/// A collection of named scalar values, such as the loss and metrics of an epoch.
+pub type Logs = LinkedHashMap<String, f64>;
+
+/// Whether a monitored value is better when it is lower or higher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonitorMode {
+    Min,
+    Max,
+}
+
+impl MonitorMode {
+    /// Returns `true` if `current` improves on `best` by more than `min_delta`.
+    pub fn is_improvement(&self, current: f64, best: f64, min_delta: f64) -> bool {
+        match self {
+            MonitorMode::Min => current < best - min_delta,
+            MonitorMode::Max => current > best + min_delta,
+        }
+    }
+
+    /// The initial best value, which any value improves on.
+    pub fn worst(&self) -> f64 {
+        match self {
+            MonitorMode::Min => f64::INFINITY,
+            MonitorMode::Max => f64::NEG_INFINITY,
+        }
+    }
+}
+
+/// The state of a training loop, which is passed to every [Callback] hook.
+pub struct TrainerContext<'a> {
+    /// The model being trained.
+    pub model: Mod<dyn Trainable>,
+    /// The optimizer updating the model.
+    pub optimizer: &'a mut dyn Optimizer,
+    /// The current epoch, starting from 0.
+    pub epoch: usize,
+    /// The index of the current batch in the epoch, starting from 0.
+    pub batch: usize,
+    /// The number of optimization steps performed so far.
+    pub step: usize,
+    /// The loss of the last batch.
+    pub loss: f64,
+    /// The model output of the last batch, detached from the graph.
+    pub output: Option<Tensor>,
+    /// The label of the last batch, on the same device as the output.
+    pub label: Option<Tensor>,
+    /// The values logged in the current epoch. Callbacks may read or add entries, e.g. validation metrics.
+    pub logs: Logs,
+    /// Set this to `true` to stop training at the end of the current batch.
+    pub stop_training: bool,
+}
+
+/// A set of hooks called by the [Trainer](super::Trainer) at certain points of the training loop.
+///
+/// All hooks do nothing by default, so a callback only needs to override the hooks it cares about.
+#[allow(unused_variables)]
+pub trait Callback {
+    /// Called once before the first epoch.
+    fn on_train_begin(&mut self, ctx: &mut TrainerContext) {}
+
+    /// Called once after the last epoch, or after training is stopped.
+    fn on_train_end(&mut self, ctx: &mut TrainerContext) {}
+
+    /// Called at the beginning of each epoch.
+    fn on_epoch_begin(&mut self, ctx: &mut TrainerContext) {}
+
+    /// Called at the end of each epoch. `ctx.logs` contains the mean loss of the epoch under `"loss"`.
+    fn on_epoch_end(&mut self, ctx: &mut TrainerContext) {}
+
+    /// Called before the forward pass of each batch.
+    fn on_batch_begin(&mut self, ctx: &mut TrainerContext) {}
+
+    /// Called after the backward pass of each batch, before the optimizer step. Gradients are available here.
+    fn on_backward(&mut self, ctx: &mut TrainerContext) {}
+
+    /// Called after the optimizer step of each batch.
+    fn on_batch_end(&mut self, ctx: &mut TrainerContext) {}
+}
@@ -0,0 +1,72 @@
+use crate::dataset::{Dataset, KFold};
+
+use super::Logs;
+
+/// The per-fold logs of a cross-validation run, see [cross_validate].
+#[derive(Debug, Clone, Default)]
+pub struct CrossValidationResult {
+    pub folds: Vec<Logs>,
+}
+
+impl CrossValidationResult {
+    /// The mean of each logged value over the folds. Values missing in some folds are averaged over the folds that have them.
+    pub fn mean(&self) -> Logs {
+        let mut sums = Logs::new();
+        let mut counts = Logs::new();
+        for logs in self.folds.iter() {
+            for (key, value) in logs.iter() {
+                *sums.entry(key.clone()).or_insert(0.) += value;
+                *counts.entry(key.clone()).or_insert(0.) += 1.;
+            }
+        }
+        sums.iter()
+            .map(|(key, sum)| (key.clone(), sum / counts[key]))
+            .collect()
+    }
+
+    /// The (population) standard deviation of each logged value over the folds.
+    pub fn std(&self) -> Logs {
+        let mean = self.mean();
+        let mut squares = Logs::new();
+        let mut counts = Logs::new();
+        for logs in self.folds.iter() {
+            for (key, value) in logs.iter() {
+                *squares.entry(key.clone()).or_insert(0.) += (value - mean[key]).powi(2);
+                *counts.entry(key.clone()).or_insert(0.) += 1.;
+            }
+        }
+        squares
+            .iter()
+            .map(|(key, square)| (key.clone(), (square / counts[key]).sqrt()))
+            .collect()
+    }
+}
+
+/// Runs a K-fold cross-validation.
+///
+/// For each fold, `run` is called with the index of the fold and its `(train, val)` datasets. It should build a fresh model and [Trainer](super::Trainer), train it on `train`, evaluate it on `val` and return the resulting logs.
+///
+/// # Examples
+/// ```
+/// let result = cross_validate(&KFold::new(5), dataset, |_, train, val| {
+///     let model = LinearBuilder::default().input_dim(1).output_dim(1).build();
+///     let optimizer = opt(model.training_parameters(), gradient_descent(0.01));
+///     let mut trainer = Trainer::new(model.clone(), optimizer, loss_fn);
+///     trainer.fit(train.into_loader(cfg.clone()), 10);
+///     evaluate_on(model, val)
+/// });
+/// println!("{:?} +- {:?}", result.mean(), result.std());
+/// ```
+pub fn cross_validate<T, F>(kfold: &KFold, dataset: T, mut run: F) -> CrossValidationResult
+where
+    T: Dataset,
+    F: FnMut(usize, T, T) -> Logs,
+{
+    CrossValidationResult {
+        folds: kfold
+            .split(dataset)
+            .enumerate()
+            .map(|(fold, (train, val))| run(fold, train, val))
+            .collect(),
+    }
+}
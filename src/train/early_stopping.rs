@@ -0,0 +1,118 @@
+use derive_builder::Builder;
+use tch::{no_grad, Tensor};
+
+use crate::nn::Trainable;
+
+use super::{Callback, MonitorMode, TrainerContext};
+
+/// A callback that stops training when a monitored value in the epoch logs has stopped improving.
+///
+/// # Examples
+/// ```
+/// let early_stopping = EarlyStoppingBuilder::default()
+///     .monitor("val_loss".to_owned())
+///     .patience(3)
+///     .restore_best_weights(true)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Builder)]
+#[builder(pattern = "owned")]
+pub struct EarlyStopping {
+    /// The key of the monitored value in the epoch logs.
+    #[builder(default = "\"loss\".to_owned()")]
+    pub monitor: String,
+
+    #[builder(default = "MonitorMode::Min")]
+    pub mode: MonitorMode,
+
+    /// The number of epochs without improvement after which training is stopped.
+    #[builder(default = "5")]
+    pub patience: usize,
+
+    /// The minimum change of the monitored value that counts as an improvement.
+    #[builder(default = "0.")]
+    pub min_delta: f64,
+
+    /// Whether to restore the parameters and buffers of the best epoch when training stops.
+    #[builder(default = "false")]
+    pub restore_best_weights: bool,
+
+    #[builder(setter(skip), default = "f64::NAN")]
+    best: f64,
+
+    #[builder(setter(skip))]
+    wait: usize,
+
+    #[builder(setter(skip))]
+    best_weights: Option<Vec<(String, Tensor)>>,
+
+    /// The epoch in which training was stopped, if it was.
+    #[builder(setter(skip))]
+    pub stopped_epoch: Option<usize>,
+}
+
+impl EarlyStopping {
+    /// Returns the best monitored value seen so far.
+    pub fn best(&self) -> f64 {
+        self.best
+    }
+
+    fn snapshot(ctx: &TrainerContext) -> Vec<(String, Tensor)> {
+        no_grad(|| {
+            ctx.model
+                .state_dict()
+                .into_iter()
+                .map(|(name, tensor)| (name, tensor.lock().copy()))
+                .collect()
+        })
+    }
+
+    fn restore(&self, ctx: &TrainerContext) {
+        if let Some(best_weights) = &self.best_weights {
+            let current = ctx.model.state_dict();
+            no_grad(|| {
+                for (name, tensor) in best_weights {
+                    if let Some(target) = current.get(name) {
+                        target.lock().copy_(tensor);
+                    }
+                }
+            });
+        }
+    }
+}
+
+impl Callback for EarlyStopping {
+    fn on_train_begin(&mut self, _ctx: &mut TrainerContext) {
+        self.best = self.mode.worst();
+        self.wait = 0;
+        self.best_weights = None;
+        self.stopped_epoch = None;
+    }
+
+    fn on_epoch_end(&mut self, ctx: &mut TrainerContext) {
+        let current = match ctx.logs.get(&self.monitor) {
+            Some(current) => *current,
+            None => return,
+        };
+        if self.mode.is_improvement(current, self.best, self.min_delta) {
+            self.best = current;
+            self.wait = 0;
+            if self.restore_best_weights {
+                self.best_weights = Some(Self::snapshot(ctx));
+            }
+        } else {
+            self.wait += 1;
+            if self.wait >= self.patience {
+                self.stopped_epoch = Some(ctx.epoch);
+                ctx.stop_training = true;
+            }
+        }
+    }
+
+    fn on_train_end(&mut self, ctx: &mut TrainerContext) {
+        if self.stopped_epoch.is_some() && self.restore_best_weights {
+            self.restore(ctx);
+        }
+    }
+}
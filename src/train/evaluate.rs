@@ -0,0 +1,67 @@
+use tch::{no_grad, Tensor};
+
+use crate::{
+    dataset::{DataLoaderConfigBuilder, Dataset},
+    metrics::Metric,
+    nn::{Mod, Module},
+};
+
+use super::Logs;
+
+/// Evaluates a model on a dataset, in batches of `batch_size`, and returns the value of each metric under its name.
+///
+/// See [evaluate_loader] for details.
+///
+/// # Examples
+/// ```
+/// let logs = evaluate(&model, test_dataset, &mut [Box::new(Accuracy::new()) as _], 32);
+/// println!("accuracy: {}", logs["accuracy"]);
+/// ```
+pub fn evaluate<M, D>(
+    model: &Mod<M>,
+    dataset: D,
+    metrics: &mut [Box<dyn Metric>],
+    batch_size: usize,
+) -> Logs
+where
+    M: Module + ?Sized,
+    D: Dataset<BatchType = (Tensor, Tensor)>,
+{
+    evaluate_loader(
+        model,
+        dataset.into_loader(
+            DataLoaderConfigBuilder::default()
+                .batch_size(batch_size)
+                .build()
+                .unwrap(),
+        ),
+        metrics,
+    )
+}
+
+/// Evaluates a model on batches of `(input, label)`, and returns the value of each metric under its name.
+///
+/// The metrics are reset before the evaluation. The model is run in `Eval` mode without gradients, and the batches are moved to the device of the model.
+/// The mode of the model and of each child module is restored afterwards (see [Mod::eval_guard]), so this can be called in the middle of training.
+pub fn evaluate_loader<M, I>(model: &Mod<M>, loader: I, metrics: &mut [Box<dyn Metric>]) -> Logs
+where
+    M: Module + ?Sized,
+    I: IntoIterator<Item = (Tensor, Tensor)>,
+{
+    let _guard = model.eval_guard();
+    let device = model.device();
+    metrics.iter_mut().for_each(|metric| metric.reset());
+    no_grad(|| {
+        for (input, label) in loader {
+            let output = model.module().forward(&input.to(device));
+            let label = label.to(device);
+            metrics
+                .iter_mut()
+                .for_each(|metric| metric.update(&output, &label));
+        }
+    });
+    metrics
+        .iter()
+        .map(|metric| (metric.name(), metric.compute()))
+        .collect()
+}
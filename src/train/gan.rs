@@ -0,0 +1,180 @@
+use tch::{no_grad, Kind, Reduction, Tensor};
+
+use crate::{
+    nn::{Mod, Module, Trainable},
+    optim::Optimizer,
+};
+
+use super::Logs;
+
+fn bce_with_logits(logits: &Tensor, label: f64) -> Tensor {
+    logits.binary_cross_entropy_with_logits::<Tensor>(
+        &logits.full_like(label),
+        None,
+        None,
+        Reduction::Mean,
+    )
+}
+
+/// A training loop for generative adversarial networks.
+///
+/// The discriminator should output one logit per sample, and is trained with binary cross entropy to tell real samples (label `real_label`) from generated ones (label `fake_label`).
+/// The generator is trained to make the discriminator classify its samples as real, with the non-saturating loss. Each model has its own optimizer.
+///
+/// For each batch of real samples:
+/// 1. The discriminator is updated on the real batch and a generated batch of the same size.
+/// 2. Every `discriminator_steps` batches, the generator is updated on a new generated batch.
+///
+/// # Examples
+/// ```
+/// let g_opt = opt(generator.training_parameters(), adam(0.0002, (0.5, 0.999)));
+/// let d_opt = opt(discriminator.training_parameters(), adam(0.0002, (0.5, 0.999)));
+/// let mut trainer = GanTrainer::new(generator, discriminator, g_opt, d_opt, 100)
+///     .label_smoothing(0.9);
+/// trainer.fit(real_images, 20);
+/// ```
+pub struct GanTrainer<G, D, OG, OD>
+where
+    G: Module + 'static,
+    D: Module + 'static,
+    OG: Optimizer,
+    OD: Optimizer,
+{
+    pub generator: Mod<G>,
+    pub discriminator: Mod<D>,
+    pub generator_optimizer: OG,
+    pub discriminator_optimizer: OD,
+    /// The size of the latent vectors fed to the generator, which are sampled from a standard normal distribution.
+    pub latent_dim: i64,
+    /// The number of discriminator updates per generator update.
+    pub discriminator_steps: usize,
+    /// The label of real samples for the discriminator. One-sided label smoothing sets this below 1, e.g. 0.9.
+    pub real_label: f64,
+    /// The label of generated samples for the discriminator.
+    pub fake_label: f64,
+    /// Called at the end of each epoch with the epoch and its logs, e.g. to save generated samples.
+    pub epoch_end_fn: Option<Box<dyn FnMut(usize, &Logs)>>,
+}
+
+impl<G, D, OG, OD> GanTrainer<G, D, OG, OD>
+where
+    G: Module + 'static,
+    D: Module + 'static,
+    OG: Optimizer,
+    OD: Optimizer,
+{
+    pub fn new(
+        generator: Mod<G>,
+        discriminator: Mod<D>,
+        generator_optimizer: OG,
+        discriminator_optimizer: OD,
+        latent_dim: i64,
+    ) -> Self {
+        Self {
+            generator,
+            discriminator,
+            generator_optimizer,
+            discriminator_optimizer,
+            latent_dim,
+            discriminator_steps: 1,
+            real_label: 1.,
+            fake_label: 0.,
+            epoch_end_fn: None,
+        }
+    }
+
+    /// Set the number of discriminator updates per generator update.
+    pub fn discriminator_steps(mut self, discriminator_steps: usize) -> Self {
+        self.discriminator_steps = discriminator_steps.max(1);
+        self
+    }
+
+    /// Use one-sided label smoothing, i.e. the discriminator is trained to output `real_label` instead of 1 on real samples.
+    pub fn label_smoothing(mut self, real_label: f64) -> Self {
+        self.real_label = real_label;
+        self
+    }
+
+    /// Set the labels of real and generated samples for the discriminator.
+    pub fn labels(mut self, real_label: f64, fake_label: f64) -> Self {
+        self.real_label = real_label;
+        self.fake_label = fake_label;
+        self
+    }
+
+    /// Set a function called at the end of each epoch with the epoch and its logs.
+    pub fn on_epoch_end<F: FnMut(usize, &Logs) + 'static>(mut self, f: F) -> Self {
+        self.epoch_end_fn = Some(Box::new(f));
+        self
+    }
+
+    /// Sample `n` latent vectors on the device of the generator.
+    pub fn sample_latent(&self, n: i64) -> Tensor {
+        Tensor::randn(
+            &[n, self.latent_dim],
+            (Kind::Double, self.generator.device()),
+        )
+    }
+
+    /// Generate `n` samples with the generator, without gradients.
+    pub fn generate(&self, n: i64) -> Tensor {
+        no_grad(|| self.generator.module().forward(&self.sample_latent(n)))
+    }
+
+    /// Train the models for `epochs` epochs on batches of real samples. The loader is cloned at the beginning of each epoch.
+    ///
+    /// Returns the logs of the last epoch, with the mean discriminator loss under `"d_loss"` and the mean generator loss under `"g_loss"`.
+    pub fn fit<I>(&mut self, loader: I, epochs: usize) -> Logs
+    where
+        I: Iterator<Item = Tensor> + Clone,
+    {
+        let device = self.discriminator.device();
+        self.generator.train(true);
+        self.discriminator.train(true);
+        let mut logs = Logs::new();
+        for epoch in 0..epochs {
+            let (mut d_total, mut d_batches) = (0., 0);
+            let (mut g_total, mut g_batches) = (0., 0);
+            for (batch, real) in loader.clone().enumerate() {
+                let real = real.to(device);
+                let batch_size = real.size()[0];
+
+                self.discriminator_optimizer.zero_grad();
+                let fake = self.generate(batch_size).to(device);
+                let d_real = self.discriminator.module().forward(&real);
+                let d_fake = self.discriminator.module().forward(&fake);
+                let d_loss = bce_with_logits(&d_real, self.real_label)
+                    + bce_with_logits(&d_fake, self.fake_label);
+                d_loss.backward();
+                self.discriminator.run_backward_hooks();
+                self.discriminator_optimizer.step();
+                d_total += f64::from(&d_loss);
+                d_batches += 1;
+
+                if (batch + 1) % self.discriminator_steps == 0 {
+                    self.generator_optimizer.zero_grad();
+                    let fake = self
+                        .generator
+                        .module()
+                        .forward(&self.sample_latent(batch_size))
+                        .to(device);
+                    let g_loss = bce_with_logits(&self.discriminator.module().forward(&fake), 1.);
+                    g_loss.backward();
+                    self.generator.run_backward_hooks();
+                    self.generator_optimizer.step();
+                    g_total += f64::from(&g_loss);
+                    g_batches += 1;
+                }
+            }
+            // The generator step also accumulates gradients in the discriminator, which are discarded here.
+            self.discriminator.zero_grad();
+            logs = Logs::new();
+            logs.insert("d_loss".to_owned(), d_total / d_batches.max(1) as f64);
+            logs.insert("g_loss".to_owned(), g_total / g_batches.max(1) as f64);
+            if let Some(f) = self.epoch_end_fn.as_mut() {
+                f(epoch, &logs);
+            }
+        }
+        logs
+    }
+}
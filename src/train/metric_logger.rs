@@ -0,0 +1,150 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use linked_hash_map::LinkedHashMap;
+use serde_json::{Map, Number, Value};
+
+use super::{Callback, TrainerContext};
+
+/// The file format written by a [MetricLogger].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Comma-separated values, with a header row taken from the first record.
+    Csv,
+    /// One JSON object per line.
+    JsonLines,
+}
+
+/// How often a [MetricLogger] writes a record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFrequency {
+    /// Write the logs of each epoch at its end.
+    Epoch,
+    /// Write the batch loss and the learning rate every `n` steps.
+    Step(usize),
+}
+
+/// A callback that appends training metrics to a CSV or JSON-lines file, for offline analysis.
+///
+/// Every record starts with the `epoch` and `step` columns. Per-epoch records contain all entries of the epoch logs,
+/// and per-step records contain the batch `loss` and the learning rate `lr`.
+///
+/// Records that can not be written are logged as warnings through the [log] facade, without interrupting the training.
+pub struct MetricLogger {
+    writer: BufWriter<File>,
+    format: LogFormat,
+    frequency: LogFrequency,
+    columns: Option<Vec<String>>,
+}
+
+impl MetricLogger {
+    /// Creates a logger writing to `path`, creating its parent directories if needed. An existing file is truncated.
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        format: LogFormat,
+        frequency: LogFrequency,
+    ) -> anyhow::Result<Self> {
+        if let Some(parent) = path.as_ref().parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+            format,
+            frequency,
+            columns: None,
+        })
+    }
+
+    /// Creates a per-epoch logger writing CSV.
+    pub fn csv<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Self::new(path, LogFormat::Csv, LogFrequency::Epoch)
+    }
+
+    /// Creates a per-epoch logger writing JSON lines.
+    pub fn json_lines<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Self::new(path, LogFormat::JsonLines, LogFrequency::Epoch)
+    }
+
+    fn write_record(&mut self, record: &LinkedHashMap<String, f64>) -> anyhow::Result<()> {
+        match self.format {
+            LogFormat::Csv => {
+                // The columns are fixed by the first record, so that every row lines up with the header.
+                if self.columns.is_none() {
+                    let columns: Vec<String> = record.keys().cloned().collect();
+                    writeln!(self.writer, "{}", columns.join(","))?;
+                    self.columns = Some(columns);
+                }
+                let row: Vec<String> = self
+                    .columns
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .map(|column| {
+                        record
+                            .get(column)
+                            .map(|value| value.to_string())
+                            .unwrap_or_default()
+                    })
+                    .collect();
+                writeln!(self.writer, "{}", row.join(","))?;
+            }
+            LogFormat::JsonLines => {
+                let object: Map<String, Value> = record
+                    .iter()
+                    .map(|(key, value)| {
+                        (
+                            key.clone(),
+                            Number::from_f64(*value).map_or(Value::Null, Value::Number),
+                        )
+                    })
+                    .collect();
+                writeln!(self.writer, "{}", Value::Object(object))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn record(ctx: &TrainerContext) -> LinkedHashMap<String, f64> {
+        let mut record = LinkedHashMap::new();
+        record.insert("epoch".to_owned(), ctx.epoch as f64);
+        record.insert("step".to_owned(), ctx.step as f64);
+        record
+    }
+}
+
+fn warn_on_error(result: anyhow::Result<()>) {
+    if let Err(err) = result {
+        log::warn!("Failed to write the metric log: {}", err);
+    }
+}
+
+impl Callback for MetricLogger {
+    fn on_batch_end(&mut self, ctx: &mut TrainerContext) {
+        if let LogFrequency::Step(n) = self.frequency {
+            if ctx.step % n.max(1) == 0 {
+                let mut record = Self::record(ctx);
+                record.insert("loss".to_owned(), ctx.loss);
+                record.insert("lr".to_owned(), ctx.optimizer.learning_rate());
+                warn_on_error(self.write_record(&record));
+            }
+        }
+    }
+
+    fn on_epoch_end(&mut self, ctx: &mut TrainerContext) {
+        if self.frequency == LogFrequency::Epoch {
+            let mut record = Self::record(ctx);
+            for (key, value) in ctx.logs.iter() {
+                record.insert(key.clone(), *value);
+            }
+            warn_on_error(self.write_record(&record));
+        }
+        warn_on_error(self.writer.flush().map_err(Into::into));
+    }
+
+    fn on_train_end(&mut self, _ctx: &mut TrainerContext) {
+        warn_on_error(self.writer.flush().map_err(Into::into));
+    }
+}
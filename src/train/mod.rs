@@ -0,0 +1,23 @@
+pub use callback::*;
+pub use cross_validation::*;
+pub use early_stopping::*;
+pub use evaluate::*;
+pub use gan::*;
+pub use metric_logger::*;
+pub use model_checkpoint::*;
+pub use progress::*;
+pub use search::*;
+pub use tensorboard::*;
+pub use trainer::*;
+
+pub mod callback;
+pub mod cross_validation;
+pub mod early_stopping;
+pub mod evaluate;
+pub mod gan;
+pub mod metric_logger;
+pub mod model_checkpoint;
+pub mod progress;
+pub mod search;
+pub mod tensorboard;
+pub mod trainer;
@@ -0,0 +1,156 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use derive_builder::Builder;
+
+use crate::nn::{StateDictExt, Trainable};
+
+use super::{Callback, MonitorMode, TrainerContext};
+
+/// A callback that saves the model every few epochs, and keeps the checkpoints of the best epochs by a monitored value in the epoch logs.
+///
+/// Each checkpoint is a .npz file of the state dict of the model, which can be loaded with [Mod::load_npz](crate::nn::Mod::load_npz), next to a `.optimizer.npz` file of the state dict of the optimizer if `save_optimizer` is set.
+///
+/// A checkpoint that can not be saved is logged as a warning through the [log] facade and skipped, without interrupting the training.
+///
+/// The file names are made from the `filename` template, in which `{epoch}`, `{step}` and the keys of the epoch logs like `{loss}` are replaced with their values, e.g. `epoch=3-loss=0.0123.npz` for `epoch={epoch}-loss={loss}`.
+///
+/// # Examples
+/// ```
+/// let checkpoint = ModelCheckpointBuilder::default()
+///     .dirpath("checkpoints")
+///     .filename("epoch={epoch}-val_loss={val_loss}".to_owned())
+///     .monitor("val_loss".to_owned())
+///     .save_top_k(3)
+///     .build()
+///     .unwrap();
+/// trainer = trainer.callback(checkpoint);
+/// ```
+#[derive(Debug, Builder)]
+#[builder(pattern = "owned")]
+pub struct ModelCheckpoint {
+    /// The directory of the checkpoints, which is created if needed.
+    #[builder(setter(into))]
+    pub dirpath: PathBuf,
+
+    /// The template of the file names, without extension.
+    #[builder(default = "\"epoch={epoch}\".to_owned()")]
+    pub filename: String,
+
+    /// The key of the monitored value in the epoch logs.
+    #[builder(default = "\"loss\".to_owned()")]
+    pub monitor: String,
+
+    /// Whether a lower or a higher monitored value is better.
+    #[builder(default = "MonitorMode::Min")]
+    pub mode: MonitorMode,
+
+    /// The number of best checkpoints to keep. The others are deleted.
+    #[builder(default = "1")]
+    pub save_top_k: usize,
+
+    /// The number of epochs between checkpoints.
+    #[builder(default = "1")]
+    pub every_n_epochs: usize,
+
+    /// Whether to save the state of the optimizer along with the model, to resume training.
+    #[builder(default = "true")]
+    pub save_optimizer: bool,
+
+    /// Whether to also save the last checkpoint as `last.npz`, whatever its monitored value.
+    #[builder(default = "false")]
+    pub save_last: bool,
+
+    #[builder(setter(skip))]
+    best: Vec<(f64, PathBuf)>,
+}
+
+impl ModelCheckpoint {
+    /// Returns the monitored values and paths of the kept checkpoints, from the best to the worst.
+    pub fn best_checkpoints(&self) -> &[(f64, PathBuf)] {
+        &self.best
+    }
+
+    /// Returns the path of the best checkpoint so far, if any.
+    pub fn best_model_path(&self) -> Option<&Path> {
+        self.best.first().map(|(_, path)| path.as_path())
+    }
+
+    fn format_filename(&self, ctx: &TrainerContext) -> String {
+        let mut filename = self
+            .filename
+            .replace("{epoch}", &ctx.epoch.to_string())
+            .replace("{step}", &ctx.step.to_string());
+        for (key, value) in ctx.logs.iter() {
+            filename = filename.replace(&format!("{{{}}}", key), &format!("{:.4}", value));
+        }
+        filename
+    }
+
+    fn save(&self, ctx: &TrainerContext, name: &str) -> anyhow::Result<PathBuf> {
+        fs::create_dir_all(&self.dirpath)?;
+        let path = self.dirpath.join(format!("{}.npz", name));
+        ctx.model.state_dict().to_npz(&path)?;
+        if self.save_optimizer {
+            ctx.optimizer.state_dict().to_npz(optimizer_path(&path))?;
+        }
+        Ok(path)
+    }
+}
+
+/// The path of the optimizer state saved along with the checkpoint at `path`.
+fn optimizer_path(path: &Path) -> PathBuf {
+    path.with_extension("optimizer.npz")
+}
+
+fn remove_checkpoint(path: &Path) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(optimizer_path(path));
+}
+
+impl Callback for ModelCheckpoint {
+    fn on_train_begin(&mut self, _ctx: &mut TrainerContext) {
+        self.best.clear();
+    }
+
+    fn on_epoch_end(&mut self, ctx: &mut TrainerContext) {
+        if (ctx.epoch + 1) % self.every_n_epochs.max(1) != 0 {
+            return;
+        }
+        if self.save_last {
+            if let Err(err) = self.save(ctx, "last") {
+                log::warn!("Failed to save the last checkpoint: {}", err);
+            }
+        }
+        let current = match ctx.logs.get(&self.monitor) {
+            Some(current) => *current,
+            None => return,
+        };
+        // The checkpoints are kept from the best to the worst, so a new checkpoint is saved if it ranks in the top k.
+        let rank = self
+            .best
+            .iter()
+            .position(|(best, _)| self.mode.is_improvement(current, *best, 0.))
+            .unwrap_or(self.best.len());
+        if rank >= self.save_top_k {
+            return;
+        }
+        let path = match self.save(ctx, &self.format_filename(ctx)) {
+            Ok(path) => path,
+            Err(err) => {
+                log::warn!("Failed to save the checkpoint: {}", err);
+                return;
+            }
+        };
+        // A checkpoint overwritten by a file of the same name is not kept twice.
+        self.best.retain(|(_, kept)| *kept != path);
+        let rank = rank.min(self.best.len());
+        self.best.insert(rank, (current, path));
+        while self.best.len() > self.save_top_k {
+            let (_, path) = self.best.pop().unwrap();
+            remove_checkpoint(&path);
+        }
+    }
+}
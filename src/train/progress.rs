@@ -0,0 +1,93 @@
+use indicatif::{ProgressBar, ProgressStyle};
+
+use super::{Callback, TrainerContext};
+
+const BAR_TEMPLATE: &str =
+    "{prefix} [{elapsed_precise}] {bar:30.cyan/blue} {pos}/{len} ({per_sec}, ETA {eta}) {msg}";
+const SPINNER_TEMPLATE: &str =
+    "{prefix} [{elapsed_precise}] {spinner} {pos} batches ({per_sec}) {msg}";
+
+/// A callback that displays a progress bar for each epoch, with the batch throughput, the ETA and the current loss.
+///
+/// The number of batches of an epoch is unknown until the first epoch ends, so the first epoch shows a spinner unless `total_batches` is given.
+/// At the end of each epoch, the bar shows all entries of the epoch logs, e.g. validation metrics.
+pub struct ProgressCallback {
+    bar: Option<ProgressBar>,
+    total_batches: Option<u64>,
+    total_epochs: Option<usize>,
+}
+
+impl ProgressCallback {
+    pub fn new() -> Self {
+        Self {
+            bar: None,
+            total_batches: None,
+            total_epochs: None,
+        }
+    }
+
+    /// Sets the number of batches per epoch, so that the first epoch also shows a bar and an ETA.
+    pub fn total_batches(mut self, total_batches: u64) -> Self {
+        self.total_batches = Some(total_batches);
+        self
+    }
+
+    /// Sets the number of epochs, which is shown in the prefix of the bar.
+    pub fn total_epochs(mut self, total_epochs: usize) -> Self {
+        self.total_epochs = Some(total_epochs);
+        self
+    }
+
+    fn format_logs<'a>(entries: impl Iterator<Item = (&'a String, &'a f64)>) -> String {
+        entries
+            .map(|(key, value)| format!("{}: {:.4}", key, value))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl Default for ProgressCallback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Callback for ProgressCallback {
+    fn on_epoch_begin(&mut self, ctx: &mut TrainerContext) {
+        let bar = match self.total_batches {
+            Some(total) => ProgressBar::new(total)
+                .with_style(ProgressStyle::with_template(BAR_TEMPLATE).unwrap()),
+            None => ProgressBar::new_spinner()
+                .with_style(ProgressStyle::with_template(SPINNER_TEMPLATE).unwrap()),
+        };
+        let prefix = match self.total_epochs {
+            Some(total) => format!("Epoch {}/{}", ctx.epoch + 1, total),
+            None => format!("Epoch {}", ctx.epoch + 1),
+        };
+        bar.set_prefix(prefix);
+        self.bar = Some(bar);
+    }
+
+    fn on_batch_end(&mut self, ctx: &mut TrainerContext) {
+        if let Some(bar) = &self.bar {
+            bar.inc(1);
+            let loss = "loss".to_owned();
+            bar.set_message(Self::format_logs(
+                std::iter::once((&loss, &ctx.loss)).chain(ctx.logs.iter()),
+            ));
+        }
+    }
+
+    fn on_epoch_end(&mut self, ctx: &mut TrainerContext) {
+        if let Some(bar) = self.bar.take() {
+            self.total_batches = Some(bar.position());
+            bar.finish_with_message(Self::format_logs(ctx.logs.iter()));
+        }
+    }
+
+    fn on_train_end(&mut self, _ctx: &mut TrainerContext) {
+        if let Some(bar) = self.bar.take() {
+            bar.abandon();
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use derive_builder::Builder;
+use linked_hash_map::LinkedHashMap;
+use parking_lot::Mutex;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::util::seeded_rng;
+
+use super::{Logs, MonitorMode};
+
+/// A set of hyperparameter values, keyed by name.
+pub type Params = LinkedHashMap<String, f64>;
+
+/// The range of values of a single hyperparameter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamRange {
+    /// One of the given values.
+    Choice(Vec<f64>),
+    /// A value uniformly distributed in `[low, high]`.
+    Uniform(f64, f64),
+    /// A value whose logarithm is uniformly distributed in `[ln(low), ln(high)]`, e.g. a learning rate.
+    LogUniform(f64, f64),
+    /// An integer in `[low, high]`.
+    Int(i64, i64),
+}
+
+impl ParamRange {
+    fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+        match self {
+            ParamRange::Choice(values) => values[rng.gen_range(0..values.len())],
+            ParamRange::Uniform(low, high) => rng.gen_range(*low..=*high),
+            ParamRange::LogUniform(low, high) => rng.gen_range(low.ln()..=high.ln()).exp(),
+            ParamRange::Int(low, high) => rng.gen_range(*low..=*high) as f64,
+        }
+    }
+
+    /// Returns the grid values of the range. Continuous ranges are discretized into `points` evenly spaced values.
+    fn grid(&self, points: usize) -> Vec<f64> {
+        let linspace = |low: f64, high: f64| -> Vec<f64> {
+            if points <= 1 {
+                return vec![low];
+            }
+            (0..points)
+                .map(|i| low + (high - low) * i as f64 / (points - 1) as f64)
+                .collect()
+        };
+        match self {
+            ParamRange::Choice(values) => values.clone(),
+            ParamRange::Uniform(low, high) => linspace(*low, *high),
+            ParamRange::LogUniform(low, high) => linspace(low.ln(), high.ln())
+                .into_iter()
+                .map(f64::exp)
+                .collect(),
+            ParamRange::Int(low, high) => (*low..=*high).map(|value| value as f64).collect(),
+        }
+    }
+}
+
+/// The definition of a hyperparameter search space.
+///
+/// # Examples
+/// ```
+/// let space = SearchSpace::new()
+///     .log_uniform("lr", 1e-4, 1e-1)
+///     .choice("batch_size", vec![16., 32., 64.])
+///     .int("hidden_layers", 1, 3);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SearchSpace {
+    pub ranges: LinkedHashMap<String, ParamRange>,
+}
+
+impl SearchSpace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a hyperparameter with the given range.
+    pub fn param(mut self, name: &str, range: ParamRange) -> Self {
+        self.ranges.insert(name.to_owned(), range);
+        self
+    }
+
+    pub fn choice(self, name: &str, values: Vec<f64>) -> Self {
+        self.param(name, ParamRange::Choice(values))
+    }
+
+    pub fn uniform(self, name: &str, low: f64, high: f64) -> Self {
+        self.param(name, ParamRange::Uniform(low, high))
+    }
+
+    pub fn log_uniform(self, name: &str, low: f64, high: f64) -> Self {
+        self.param(name, ParamRange::LogUniform(low, high))
+    }
+
+    pub fn int(self, name: &str, low: i64, high: i64) -> Self {
+        self.param(name, ParamRange::Int(low, high))
+    }
+
+    /// Returns the cartesian product of the grid values of all hyperparameters.
+    pub fn grid(&self, points: usize) -> Vec<Params> {
+        self.ranges
+            .iter()
+            .fold(vec![Params::new()], |combinations, (name, range)| {
+                let values = range.grid(points);
+                combinations
+                    .iter()
+                    .flat_map(|params| {
+                        values.iter().map(move |value| {
+                            let mut params = params.clone();
+                            params.insert(name.clone(), *value);
+                            params
+                        })
+                    })
+                    .collect()
+            })
+    }
+
+    /// Draws a random set of hyperparameters.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Params {
+        self.ranges
+            .iter()
+            .map(|(name, range)| (name.clone(), range.sample(rng)))
+            .collect()
+    }
+}
+
+/// How the trials of a search are chosen.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchStrategy {
+    /// Try every combination of the grid, where continuous ranges are discretized into `points` values.
+    Grid { points: usize },
+    /// Try `trials` random combinations.
+    Random { trials: usize, seed: Option<u64> },
+}
+
+/// The configuration of a hyperparameter search.
+#[derive(Debug, Clone, Builder)]
+#[builder(pattern = "owned")]
+pub struct SearchConfig {
+    #[builder(default = "SearchStrategy::Grid { points: 3 }")]
+    pub strategy: SearchStrategy,
+
+    /// The key of the value in the logs returned by each trial which is compared between trials.
+    #[builder(default = "\"loss\".to_owned()")]
+    pub monitor: String,
+
+    #[builder(default = "MonitorMode::Min")]
+    pub mode: MonitorMode,
+
+    /// The number of trials run in parallel threads.
+    #[builder(default = "1")]
+    pub parallelism: usize,
+}
+
+/// The hyperparameters of a trial and the logs it returned.
+#[derive(Debug, Clone)]
+pub struct Trial {
+    pub params: Params,
+    pub logs: Logs,
+}
+
+/// The results of a hyperparameter search.
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    /// All trials, in the order they were generated.
+    pub trials: Vec<Trial>,
+    /// The index of the best trial, or `None` if no trial logged the monitored value.
+    pub best_index: Option<usize>,
+}
+
+impl SearchResult {
+    /// Returns the best trial.
+    pub fn best(&self) -> Option<&Trial> {
+        self.best_index.map(|index| &self.trials[index])
+    }
+}
+
+/// Runs a hyperparameter search.
+///
+/// For each trial, `run` is called with a set of hyperparameters. It should build a fresh model and [Trainer](super::Trainer) from them, train and evaluate it, and return the resulting logs.
+/// Since models are created inside `run`, trials can run in parallel threads (see [SearchConfig::parallelism]).
+///
+/// # Examples
+/// ```
+/// let space = SearchSpace::new().log_uniform("lr", 1e-4, 1e-1);
+/// let config = SearchConfigBuilder::default()
+///     .strategy(SearchStrategy::Random { trials: 20, seed: Some(0) })
+///     .monitor("val_loss".to_owned())
+///     .build()
+///     .unwrap();
+/// let result = search(&space, &config, |params| {
+///     let model = LinearBuilder::default().input_dim(1).output_dim(1).build();
+///     let optimizer = opt(model.training_parameters(), gradient_descent(params["lr"]));
+///     let mut trainer = Trainer::new(model, optimizer, loss_fn);
+///     trainer.fit(loader.clone(), 10)
+/// });
+/// println!("{:?}", result.best().unwrap().params);
+/// ```
+pub fn search<F>(space: &SearchSpace, config: &SearchConfig, run: F) -> SearchResult
+where
+    F: Fn(&Params) -> Logs + Sync,
+{
+    let candidates = match config.strategy {
+        SearchStrategy::Grid { points } => space.grid(points),
+        SearchStrategy::Random { trials, seed } => {
+            let mut rng = match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => seeded_rng(),
+            };
+            (0..trials).map(|_| space.sample(&mut rng)).collect()
+        }
+    };
+
+    let results: Mutex<Vec<Option<Logs>>> = Mutex::new(vec![None; candidates.len()]);
+    let next = AtomicUsize::new(0);
+    let worker = || loop {
+        let index = next.fetch_add(1, Ordering::SeqCst);
+        if index >= candidates.len() {
+            break;
+        }
+        let logs = run(&candidates[index]);
+        results.lock()[index] = Some(logs);
+    };
+    if config.parallelism <= 1 {
+        worker();
+    } else {
+        std::thread::scope(|scope| {
+            for _ in 0..config.parallelism {
+                scope.spawn(worker);
+            }
+        });
+    }
+
+    let trials: Vec<Trial> = candidates
+        .into_iter()
+        .zip(results.into_inner())
+        .map(|(params, logs)| Trial {
+            params,
+            logs: logs.unwrap_or_default(),
+        })
+        .collect();
+    let mut best_index = None;
+    let mut best = config.mode.worst();
+    for (index, trial) in trials.iter().enumerate() {
+        if let Some(&value) = trial.logs.get(&config.monitor) {
+            if config.mode.is_improvement(value, best, 0.) {
+                best = value;
+                best_index = Some(index);
+            }
+        }
+    }
+    SearchResult { trials, best_index }
+}
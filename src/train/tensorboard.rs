@@ -0,0 +1,333 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Cursor, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use image::{DynamicImage, ImageBuffer, ImageOutputFormat};
+use tch::{no_grad, Kind, Tensor};
+
+use crate::nn::Trainable;
+
+use super::{Callback, TrainerContext};
+
+/// CRC-32C (Castagnoli), as required by the TFRecord format.
+fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0x82F6_3B78
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn masked_crc32c(data: &[u8]) -> u32 {
+    let crc = crc32c(data);
+    ((crc >> 15) | (crc << 17)).wrapping_add(0xa282_ead8)
+}
+
+/// A minimal protobuf encoder, sufficient for the `Event` and `Summary` messages of TensorBoard.
+#[derive(Default)]
+struct ProtoBuf(Vec<u8>);
+
+impl ProtoBuf {
+    fn varint(&mut self, mut value: u64) -> &mut Self {
+        while value >= 0x80 {
+            self.0.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+        self
+    }
+
+    fn key(&mut self, field: u64, wire_type: u64) -> &mut Self {
+        self.varint((field << 3) | wire_type)
+    }
+
+    fn int(&mut self, field: u64, value: i64) -> &mut Self {
+        self.key(field, 0).varint(value as u64)
+    }
+
+    fn double(&mut self, field: u64, value: f64) -> &mut Self {
+        self.key(field, 1);
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn float(&mut self, field: u64, value: f32) -> &mut Self {
+        self.key(field, 5);
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn bytes(&mut self, field: u64, value: &[u8]) -> &mut Self {
+        self.key(field, 2).varint(value.len() as u64);
+        self.0.extend_from_slice(value);
+        self
+    }
+
+    fn packed_doubles(&mut self, field: u64, values: &[f64]) -> &mut Self {
+        self.key(field, 2).varint(8 * values.len() as u64);
+        for value in values {
+            self.0.extend_from_slice(&value.to_le_bytes());
+        }
+        self
+    }
+}
+
+/// Writes summaries to a TensorBoard event file (`events.out.tfevents.*`).
+pub struct SummaryWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl SummaryWriter {
+    /// Creates a new event file in the given directory, creating the directory if needed.
+    pub fn new<P: AsRef<Path>>(log_dir: P) -> anyhow::Result<Self> {
+        fs::create_dir_all(&log_dir)?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let path = log_dir.as_ref().join(format!(
+            "events.out.tfevents.{}.raddar.{}",
+            now.as_secs(),
+            std::process::id()
+        ));
+        let mut this = Self {
+            writer: BufWriter::new(File::create(&path)?),
+            path,
+        };
+        let mut event = ProtoBuf::default();
+        event
+            .double(1, Self::wall_time())
+            .bytes(3, b"brain.Event:2");
+        this.write_record(&event.0)?;
+        Ok(this)
+    }
+
+    /// The path of the event file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn wall_time() -> f64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs_f64())
+            .unwrap_or(0.)
+    }
+
+    fn write_record(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        let length = (data.len() as u64).to_le_bytes();
+        self.writer.write_all(&length)?;
+        self.writer
+            .write_all(&masked_crc32c(&length).to_le_bytes())?;
+        self.writer.write_all(data)?;
+        self.writer.write_all(&masked_crc32c(data).to_le_bytes())?;
+        Ok(())
+    }
+
+    /// Writes a `Summary` with a single value, where `value` is the encoded `Summary.Value` without its tag.
+    fn write_summary(&mut self, tag: &str, step: i64, value: ProtoBuf) -> anyhow::Result<()> {
+        let mut summary_value = ProtoBuf::default();
+        summary_value.bytes(1, tag.as_bytes());
+        summary_value.0.extend_from_slice(&value.0);
+        let mut summary = ProtoBuf::default();
+        summary.bytes(1, &summary_value.0);
+        let mut event = ProtoBuf::default();
+        event
+            .double(1, Self::wall_time())
+            .int(2, step)
+            .bytes(5, &summary.0);
+        self.write_record(&event.0)
+    }
+
+    /// Logs a scalar value.
+    pub fn add_scalar(&mut self, tag: &str, value: f64, step: i64) -> anyhow::Result<()> {
+        let mut proto = ProtoBuf::default();
+        proto.float(2, value as f32);
+        self.write_summary(tag, step, proto)
+    }
+
+    /// Logs a histogram of the values of a tensor, with `bins` equal-width buckets.
+    pub fn add_histogram(
+        &mut self,
+        tag: &str,
+        values: &Tensor,
+        bins: i64,
+        step: i64,
+    ) -> anyhow::Result<()> {
+        let values = values.detach().to_kind(Kind::Double).flatten(0, -1);
+        if values.numel() == 0 {
+            return Ok(());
+        }
+        let min = f64::from(values.min());
+        let max = f64::from(values.max());
+        let counts = Vec::<f64>::from(&values.histc(bins));
+        let width = (max - min) / bins as f64;
+        let limits: Vec<f64> = (1..=bins).map(|i| min + width * i as f64).collect();
+        let mut histogram = ProtoBuf::default();
+        histogram
+            .double(1, min)
+            .double(2, max)
+            .double(3, values.numel() as f64)
+            .double(4, f64::from(values.sum(Kind::Double)))
+            .double(5, f64::from(values.square().sum(Kind::Double)))
+            .packed_doubles(6, &limits)
+            .packed_doubles(7, &counts);
+        let mut proto = ProtoBuf::default();
+        proto.bytes(5, &histogram.0);
+        self.write_summary(tag, step, proto)
+    }
+
+    /// Logs an image. The tensor should be in shape `[C, H, W]` with 1, 3 or 4 channels, and values in `[0, 1]`.
+    pub fn add_image(&mut self, tag: &str, image: &Tensor, step: i64) -> anyhow::Result<()> {
+        let (channels, height, width) = image.size3()?;
+        let pixels = Vec::<u8>::from(
+            &(image.detach().to_kind(Kind::Double).clamp(0., 1.) * 255.)
+                .round()
+                .to_kind(Kind::Uint8)
+                .permute(&[1, 2, 0])
+                .contiguous()
+                .flatten(0, -1),
+        );
+        let (w, h) = (width as u32, height as u32);
+        let image = match channels {
+            1 => ImageBuffer::from_raw(w, h, pixels).map(DynamicImage::ImageLuma8),
+            3 => ImageBuffer::from_raw(w, h, pixels).map(DynamicImage::ImageRgb8),
+            4 => ImageBuffer::from_raw(w, h, pixels).map(DynamicImage::ImageRgba8),
+            _ => None,
+        }
+        .ok_or_else(|| anyhow::anyhow!("Unsupported image with {} channels", channels))?;
+        let mut encoded = Cursor::new(Vec::new());
+        image.write_to(&mut encoded, ImageOutputFormat::Png)?;
+        let mut image_proto = ProtoBuf::default();
+        image_proto
+            .int(1, height)
+            .int(2, width)
+            .int(3, channels)
+            .bytes(4, encoded.get_ref());
+        let mut proto = ProtoBuf::default();
+        proto.bytes(4, &image_proto.0);
+        self.write_summary(tag, step, proto)
+    }
+
+    /// Flushes the buffered events to the file.
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// A callback that logs the training loss, the epoch logs and optionally histograms of parameters and gradients to TensorBoard.
+///
+/// The batch loss is logged under `train/loss` against the global step, and the epoch logs are logged under `epoch/<key>` against the epoch.
+/// Events that can not be written are logged as warnings through the [log] facade, without interrupting the training.
+pub struct TensorBoardLogger {
+    pub writer: SummaryWriter,
+    /// Log the batch loss every `log_every` steps.
+    pub log_every: usize,
+    /// Whether to log histograms of parameters and their gradients at the end of each epoch.
+    pub histograms: bool,
+    /// Whether to log the total gradient norm along with the batch loss.
+    pub grad_norm: bool,
+}
+
+impl TensorBoardLogger {
+    pub fn new<P: AsRef<Path>>(log_dir: P) -> anyhow::Result<Self> {
+        Ok(Self {
+            writer: SummaryWriter::new(log_dir)?,
+            log_every: 1,
+            histograms: false,
+            grad_norm: false,
+        })
+    }
+
+    /// Log the batch loss every `log_every` steps.
+    pub fn log_every(mut self, log_every: usize) -> Self {
+        self.log_every = log_every.max(1);
+        self
+    }
+
+    /// Log histograms of parameters and gradients at the end of each epoch.
+    pub fn histograms(mut self, histograms: bool) -> Self {
+        self.histograms = histograms;
+        self
+    }
+
+    /// Log the total gradient norm of the model every `log_every` steps, see [Trainable::grad_norm].
+    pub fn grad_norm(mut self, grad_norm: bool) -> Self {
+        self.grad_norm = grad_norm;
+        self
+    }
+}
+
+fn warn_on_error(result: anyhow::Result<()>) {
+    if let Err(err) = result {
+        log::warn!("Failed to write TensorBoard events: {}", err);
+    }
+}
+
+impl Callback for TensorBoardLogger {
+    fn on_backward(&mut self, ctx: &mut TrainerContext) {
+        // The step is incremented after the optimizer step, so this matches the step of the loss logged in `on_batch_end`.
+        if self.grad_norm && (ctx.step + 1) % self.log_every == 0 {
+            warn_on_error(self.writer.add_scalar(
+                "train/grad_norm",
+                ctx.model.grad_norm(),
+                ctx.step as i64 + 1,
+            ));
+        }
+    }
+
+    fn on_batch_end(&mut self, ctx: &mut TrainerContext) {
+        if ctx.step % self.log_every == 0 {
+            warn_on_error(
+                self.writer
+                    .add_scalar("train/loss", ctx.loss, ctx.step as i64),
+            );
+        }
+    }
+
+    fn on_epoch_end(&mut self, ctx: &mut TrainerContext) {
+        let epoch = ctx.epoch as i64;
+        for (key, value) in ctx.logs.iter() {
+            warn_on_error(
+                self.writer
+                    .add_scalar(&format!("epoch/{}", key), *value, epoch),
+            );
+        }
+        if self.histograms {
+            no_grad(|| {
+                for (name, parameter) in ctx.model.parameters() {
+                    let parameter = parameter.lock();
+                    warn_on_error(self.writer.add_histogram(
+                        &format!("parameters/{}", name),
+                        &parameter,
+                        30,
+                        epoch,
+                    ));
+                    let grad = parameter.grad();
+                    if grad.defined() {
+                        warn_on_error(self.writer.add_histogram(
+                            &format!("gradients/{}", name),
+                            &grad,
+                            30,
+                            epoch,
+                        ));
+                    }
+                }
+            });
+        }
+        warn_on_error(self.writer.flush());
+    }
+
+    fn on_train_end(&mut self, _ctx: &mut TrainerContext) {
+        warn_on_error(self.writer.flush());
+    }
+}
@@ -0,0 +1,123 @@
+use tch::Tensor;
+
+use crate::{
+    nn::{backward_checkpoints, Mod, Module, Trainable},
+    optim::Optimizer,
+};
+
+use super::{Callback, Logs, TrainerContext};
+
+/// A generic supervised training loop.
+///
+/// The trainer owns a model, an optimizer and a loss function. Logging, checkpointing, early stopping and other concerns plug into the loop through [Callback]s.
+///
+/// # Examples
+/// ```
+/// let model = LinearBuilder::default().input_dim(1).output_dim(1).build();
+/// let optimizer = opt(model.training_parameters(), gradient_descent(0.01));
+/// let mut trainer = Trainer::new(model, optimizer, |output: &Tensor, label: &Tensor| {
+///     output.mse_loss(label, Reduction::Mean)
+/// });
+/// trainer.fit(loader, 10);
+/// ```
+pub struct Trainer<M: Module + 'static, O: Optimizer> {
+    pub model: Mod<M>,
+    pub optimizer: O,
+    pub loss_fn: Box<dyn FnMut(&Tensor, &Tensor) -> Tensor>,
+    pub callbacks: Vec<Box<dyn Callback>>,
+}
+
+impl<M: Module + 'static, O: Optimizer> Trainer<M, O> {
+    pub fn new<F>(model: Mod<M>, optimizer: O, loss_fn: F) -> Self
+    where
+        F: FnMut(&Tensor, &Tensor) -> Tensor + 'static,
+    {
+        Self {
+            model,
+            optimizer,
+            loss_fn: Box::new(loss_fn),
+            callbacks: Vec::new(),
+        }
+    }
+
+    /// Add a callback to the trainer. Callbacks are called in the order they are added.
+    pub fn callback<C: Callback + 'static>(mut self, callback: C) -> Self {
+        self.callbacks.push(Box::new(callback));
+        self
+    }
+
+    /// Train the model for `epochs` epochs. The loader is cloned at the beginning of each epoch, so a shuffling [DataLoader](crate::dataset::DataLoader) is reshuffled every epoch.
+    ///
+    /// Returns the logs of the last epoch.
+    pub fn fit<I>(&mut self, loader: I, epochs: usize) -> Logs
+    where
+        I: Iterator<Item = (Tensor, Tensor)> + Clone,
+    {
+        let device = self.model.device();
+        let mut ctx = TrainerContext {
+            model: self.model.clone(),
+            optimizer: &mut self.optimizer,
+            epoch: 0,
+            batch: 0,
+            step: 0,
+            loss: 0.,
+            output: None,
+            label: None,
+            logs: Logs::new(),
+            stop_training: false,
+        };
+        self.model.train(true);
+        for callback in self.callbacks.iter_mut() {
+            callback.on_train_begin(&mut ctx);
+        }
+        for epoch in 0..epochs {
+            ctx.epoch = epoch;
+            ctx.logs = Logs::new();
+            for callback in self.callbacks.iter_mut() {
+                callback.on_epoch_begin(&mut ctx);
+            }
+            let mut total_loss = 0.;
+            let mut num_batches = 0;
+            for (batch, (input, label)) in loader.clone().enumerate() {
+                ctx.batch = batch;
+                for callback in self.callbacks.iter_mut() {
+                    callback.on_batch_begin(&mut ctx);
+                }
+                ctx.optimizer.zero_grad();
+                let output = self.model.module().forward(&input.to(device));
+                let label = label.to(device);
+                let loss = (self.loss_fn)(&output, &label);
+                backward_checkpoints(&loss);
+                self.model.run_backward_hooks();
+                for callback in self.callbacks.iter_mut() {
+                    callback.on_backward(&mut ctx);
+                }
+                ctx.optimizer.step();
+                ctx.step += 1;
+                ctx.loss = f64::from(&loss);
+                ctx.output = Some(output.detach());
+                ctx.label = Some(label);
+                total_loss += ctx.loss;
+                num_batches += 1;
+                for callback in self.callbacks.iter_mut() {
+                    callback.on_batch_end(&mut ctx);
+                }
+                if ctx.stop_training {
+                    break;
+                }
+            }
+            ctx.logs
+                .insert("loss".to_owned(), total_loss / num_batches.max(1) as f64);
+            for callback in self.callbacks.iter_mut() {
+                callback.on_epoch_end(&mut ctx);
+            }
+            if ctx.stop_training {
+                break;
+            }
+        }
+        for callback in self.callbacks.iter_mut() {
+            callback.on_train_end(&mut ctx);
+        }
+        ctx.logs
+    }
+}
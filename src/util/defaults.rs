@@ -0,0 +1,87 @@
+use std::cell::Cell;
+
+use parking_lot::{const_mutex, Mutex};
+use tch::{Device, Kind};
+
+use super::DropGuard;
+
+/// The kind of the parameters created by layer builders, set by [set_default_dtype].
+static DEFAULT_DTYPE: Mutex<Kind> = const_mutex(Kind::Double);
+
+/// The device of the parameters created by layer builders, set by [set_default_device].
+static DEFAULT_DEVICE: Mutex<Device> = const_mutex(Device::Cpu);
+
+thread_local! {
+    /// The kind set by [with_default_dtype] on the current thread.
+    static SCOPED_DTYPE: Cell<Option<Kind>> = Cell::new(None);
+
+    /// The device set by [with_default_device] on the current thread.
+    static SCOPED_DEVICE: Cell<Option<Device>> = Cell::new(None);
+}
+
+/// Sets the kind of the parameters of the layers built afterwards, when their builders are not given a `dtype`. It is `Kind::Double` by default.
+///
+/// This changes the default of every thread, see [with_default_dtype] to change it for a single thread, e.g. in tests running in parallel.
+///
+/// # Examples
+/// ```
+/// raddar::set_default_dtype(Kind::Float);
+/// let model = resnet50(1000);
+/// ```
+pub fn set_default_dtype(kind: Kind) {
+    *DEFAULT_DTYPE.lock() = kind;
+}
+
+/// Returns the kind of the parameters of new layers, see [set_default_dtype] and [with_default_dtype].
+pub fn default_dtype() -> Kind {
+    SCOPED_DTYPE
+        .with(Cell::get)
+        .unwrap_or_else(|| *DEFAULT_DTYPE.lock())
+}
+
+/// Runs `f` with [default_dtype] returning `kind` on the current thread.
+///
+/// # Examples
+/// ```
+/// let model = with_default_dtype(Kind::Float, || resnet50(1000));
+/// ```
+pub fn with_default_dtype<R>(kind: Kind, f: impl FnOnce() -> R) -> R {
+    let previous = SCOPED_DTYPE.with(|scoped| scoped.replace(Some(kind)));
+    let _guard = DropGuard::new(
+        previous,
+        Box::new(|previous: &mut Option<Kind>| SCOPED_DTYPE.with(|scoped| scoped.set(*previous))),
+    );
+    f()
+}
+
+/// Sets the device of the parameters of the layers built afterwards, when their builders are not given a `device`. It is `Device::Cpu` by default.
+///
+/// # Examples
+/// ```
+/// raddar::set_default_device(Device::cuda_if_available());
+/// let model = resnet50(1000);
+/// ```
+pub fn set_default_device(device: Device) {
+    *DEFAULT_DEVICE.lock() = device;
+}
+
+/// Returns the device of the parameters of new layers, see [set_default_device] and [with_default_device].
+pub fn default_device() -> Device {
+    SCOPED_DEVICE
+        .with(Cell::get)
+        .unwrap_or_else(|| *DEFAULT_DEVICE.lock())
+}
+
+/// Runs `f` with [default_device] returning `device` on the current thread.
+///
+/// The builders derived by `ArchitectureBuilder` build their module within this, so that the layers of a whole model are created on the device given to its builder.
+pub fn with_default_device<R>(device: Device, f: impl FnOnce() -> R) -> R {
+    let previous = SCOPED_DEVICE.with(|scoped| scoped.replace(Some(device)));
+    let _guard = DropGuard::new(
+        previous,
+        Box::new(|previous: &mut Option<Device>| {
+            SCOPED_DEVICE.with(|scoped| scoped.set(*previous))
+        }),
+    );
+    f()
+}
@@ -0,0 +1,17 @@
+use tch::no_grad;
+
+/// Runs `f` without tracking gradients, so that no graph is built for the backward pass. Use it around evaluation and prediction code to save memory and time.
+///
+/// This does not switch modules to evaluation mode, which changes dropout and batch normalization, so call [Mod::eval](crate::nn::Mod::eval) as well, or use [Mod::infer](crate::nn::Mod::infer) for a single forward pass.
+///
+/// # Examples
+/// ```
+/// model.eval(true);
+/// let accuracy = raddar::inference_mode(|| {
+///     let output = model(&images);
+///     output.argmax(-1, false).eq_tensor(&labels).mean(Kind::Double)
+/// });
+/// ```
+pub fn inference_mode<R>(f: impl FnOnce() -> R) -> R {
+    no_grad(f)
+}
@@ -1,3 +1,10 @@
+pub use defaults::*;
 pub use drop_guard::*;
+pub use inference::*;
+pub use seed::*;
 
-pub mod drop_guard;
\ No newline at end of file
+pub mod defaults;
+pub mod drop_guard;
+pub mod inference;
+pub mod seed;
+pub mod serde_tch;
\ No newline at end of file
@@ -0,0 +1,70 @@
+use std::{
+    cell::Cell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use parking_lot::{const_mutex, Mutex};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::DropGuard;
+
+/// The global generator set by [set_seed], from which the generators of the library are derived.
+static GLOBAL_RNG: Mutex<Option<StdRng>> = const_mutex(None);
+
+static DETERMINISTIC: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// The seed set by [with_seed] on the current thread.
+    static SCOPED_SEED: Cell<Option<u64>> = Cell::new(None);
+}
+
+/// Seeds libtorch and all random number generators of the library, such as the shuffling of data loaders, so that experiments are reproducible.
+///
+/// # Examples
+/// ```
+/// raddar::set_seed(42);
+/// ```
+pub fn set_seed(seed: u64) {
+    tch::manual_seed(seed as i64);
+    *GLOBAL_RNG.lock() = Some(StdRng::seed_from_u64(seed));
+}
+
+/// Returns a new random number generator. After [set_seed], the sequence of generators returned by this function is reproducible. Otherwise, the generator is seeded from the OS.
+///
+/// Any random behaviour of the library should draw from a generator returned by this function, instead of `rand::thread_rng`.
+pub fn seeded_rng() -> StdRng {
+    if let Some(seed) = SCOPED_SEED.with(Cell::get) {
+        return StdRng::seed_from_u64(seed);
+    }
+    match GLOBAL_RNG.lock().as_mut() {
+        Some(rng) => StdRng::seed_from_u64(rng.gen()),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// Runs `f` with every generator returned by [seeded_rng] on the current thread seeded by `seed`.
+///
+/// This replays the same random decisions in several calls, e.g. to apply the same random crop to an image and its segmentation mask.
+pub fn with_seed<R>(seed: u64, f: impl FnOnce() -> R) -> R {
+    let previous = SCOPED_SEED.with(|scoped| scoped.replace(Some(seed)));
+    let _guard = DropGuard::new(
+        previous,
+        Box::new(|previous: &mut Option<u64>| SCOPED_SEED.with(|scoped| scoped.set(*previous))),
+    );
+    f()
+}
+
+/// Trades speed for reproducibility on CUDA devices.
+///
+/// When enabled, cuDNN benchmarking is disabled, so that the same convolution algorithms are picked on every run.
+/// cuBLAS reads its workspace configuration once, when CUDA is initialized, so deterministic cuBLAS matmuls also need `CUBLAS_WORKSPACE_CONFIG=:4096:8` to be set in the environment before the process starts.
+/// Note that libtorch does not expose `use_deterministic_algorithms` through `tch`, so some CUDA kernels (e.g. scatter-add) may still be non-deterministic.
+pub fn deterministic(enabled: bool) {
+    DETERMINISTIC.store(enabled, Ordering::SeqCst);
+    tch::Cuda::cudnn_set_benchmark(!enabled);
+}
+
+/// Returns `true` if [deterministic] mode is enabled.
+pub fn is_deterministic() -> bool {
+    DETERMINISTIC.load(Ordering::SeqCst)
+}
@@ -0,0 +1,60 @@
+//! Serde support for the types of `tch` used in builder configs, for `#[serde(with = "...")]`.
+
+/// Serializes a `Kind` by its name, e.g. `"Float"`.
+pub mod kind {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use tch::Kind;
+
+    pub fn serialize<S: Serializer>(kind: &Kind, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:?}", kind))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Kind, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(match name.as_str() {
+            "Uint8" => Kind::Uint8,
+            "Int8" => Kind::Int8,
+            "Int16" => Kind::Int16,
+            "Int" => Kind::Int,
+            "Int64" => Kind::Int64,
+            "Half" => Kind::Half,
+            "Float" => Kind::Float,
+            "Double" => Kind::Double,
+            "ComplexHalf" => Kind::ComplexHalf,
+            "ComplexFloat" => Kind::ComplexFloat,
+            "ComplexDouble" => Kind::ComplexDouble,
+            "Bool" => Kind::Bool,
+            "QInt8" => Kind::QInt8,
+            "QUInt8" => Kind::QUInt8,
+            "QInt32" => Kind::QInt32,
+            "BFloat16" => Kind::BFloat16,
+            name => return Err(D::Error::custom(format!("unknown kind {}", name))),
+        })
+    }
+}
+
+/// Serializes a `Device` like PyTorch, e.g. `"cpu"` or `"cuda:0"`.
+pub mod device {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use tch::Device;
+
+    pub fn serialize<S: Serializer>(device: &Device, serializer: S) -> Result<S::Ok, S::Error> {
+        match device {
+            Device::Cpu => serializer.serialize_str("cpu"),
+            Device::Cuda(index) => serializer.serialize_str(&format!("cuda:{}", index)),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Device, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        match name.split_once(':') {
+            None if name == "cpu" => Ok(Device::Cpu),
+            None if name == "cuda" => Ok(Device::Cuda(0)),
+            Some(("cuda", index)) => index
+                .parse()
+                .map(Device::Cuda)
+                .map_err(|_| D::Error::custom(format!("invalid device {}", name))),
+            _ => Err(D::Error::custom(format!("unknown device {}", name))),
+        }
+    }
+}
@@ -2,10 +2,18 @@ use std::sync::Arc;
 
 use raddar::{
     assert_tensor_eq,
-    dataset::{DataLoaderConfigBuilder, Dataset, TensorDataset},
+    dataset::{
+        from_fn, from_iter, image_mappings, on_both, on_input, on_sample, tensor_mapping, vision,
+        ClassBalancedSampler, Compose, CsvDataset, CsvDatasetConfigBuilder,
+        DataLoaderConfigBuilder, Dataset, IterableDataset, KFoldBuilder, LengthBucketSampler,
+        MapDataset, MetadataCollate, PadCollate, PaddedBatch, ParquetDataset,
+        ParquetDatasetConfigBuilder, PrefetchToDevice, RandomSampler, SampleMetadata, Sampler,
+        SequentialSampler, StackCollate, StackedTensorDataset, StreamingDataset, TakeDataset,
+        TensorDataset, UnsupervisedDataset, WeightedRandomSampler,
+    },
     tensor, tensor_vec,
 };
-use tch::Tensor;
+use tch::{Device, Tensor};
 
 #[test]
 fn dataset_test() {
@@ -110,3 +118,976 @@ fn dataset_augment_test() {
     let (batch, _) = iter.next().unwrap();
     assert_tensor_eq!(batch, tensor!([[2.0], [2.0], [6.0], [6.0], [10.0], [10.0], [8.0]]));
 }
+
+#[test]
+fn kfold_test() {
+    let inputs = tensor_vec![[1.0], [3.0], [5.0], [4.0], [8.0], [10.0], [2.0]];
+    let labels = tensor_vec![[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.]];
+    let dataset = TensorDataset::from_tensors(inputs, labels);
+
+    let kfold = KFoldBuilder::default().k(3).build().unwrap();
+    let splits: Vec<(TensorDataset, TensorDataset)> = kfold.split(dataset).collect();
+    assert_eq!(splits.len(), 3);
+    let sizes: Vec<(usize, usize)> = splits
+        .iter()
+        .map(|(train, val)| (train.size(), val.size()))
+        .collect();
+    assert_eq!(sizes, vec![(4, 3), (5, 2), (5, 2)]);
+    assert_tensor_eq!(&*splits[1].1.inputs[0], tensor!([4.0]));
+}
+
+#[test]
+fn dataloader_drop_last_and_seed_test() {
+    let inputs = tensor_vec![[1.0], [3.0], [5.0], [4.0], [8.0], [10.0], [2.0], [6.0]];
+    let labels = tensor_vec![[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.], [19.0]];
+    let dataset = TensorDataset::from_tensors(inputs, labels);
+    let cfg = DataLoaderConfigBuilder::default()
+        .batch_size(3)
+        .shuffle(true)
+        .drop_last(true)
+        .seed(Some(7))
+        .build()
+        .unwrap();
+
+    let loader = dataset.clone().into_loader(cfg.clone());
+    assert_eq!(loader.num_batches(), 2);
+
+    // Two loaders with the same seed produce the same sequence of shuffles over epochs.
+    let other = dataset.into_loader(cfg);
+    let epochs: Vec<Vec<Tensor>> = (0..3)
+        .map(|_| loader.clone().map(|(inputs, _)| inputs).collect())
+        .collect();
+    let other_epochs: Vec<Vec<Tensor>> = (0..3)
+        .map(|_| other.clone().map(|(inputs, _)| inputs).collect())
+        .collect();
+    for (epoch, other_epoch) in epochs.iter().zip(other_epochs.iter()) {
+        assert_eq!(epoch.len(), 2);
+        assert_tensor_eq!(&epoch[0], &other_epoch[0]);
+    }
+}
+
+#[test]
+fn dataloader_workers_test() {
+    let inputs = tensor_vec![[1.0], [3.0], [5.0], [4.0], [8.0], [10.0], [2.0], [6.0]];
+    let labels = tensor_vec![[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.], [19.0]];
+    let dataset = TensorDataset::from_tensors(inputs, labels);
+
+    let sequential: Vec<(Tensor, Tensor)> = dataset
+        .clone()
+        .into_loader(
+            DataLoaderConfigBuilder::default()
+                .batch_size(3)
+                .build()
+                .unwrap(),
+        )
+        .collect();
+    let prefetched: Vec<(Tensor, Tensor)> = dataset
+        .into_loader(
+            DataLoaderConfigBuilder::default()
+                .batch_size(3)
+                .num_workers(2)
+                .prefetch_factor(1)
+                .build()
+                .unwrap(),
+        )
+        .collect();
+    assert_eq!(sequential.len(), prefetched.len());
+    for ((a, _), (b, _)) in sequential.iter().zip(prefetched.iter()) {
+        assert_tensor_eq!(a, b);
+    }
+}
+
+#[test]
+fn sampler_test() {
+    assert_eq!(SequentialSampler.indices(3), vec![0, 1, 2]);
+
+    let random = RandomSampler::new().seed(3);
+    let mut indices = random.indices(5);
+    indices.sort();
+    assert_eq!(indices, vec![0, 1, 2, 3, 4]);
+    assert_eq!(
+        RandomSampler::new()
+            .replacement(true)
+            .num_samples(8)
+            .indices(2)
+            .len(),
+        8
+    );
+
+    let weighted = WeightedRandomSampler::new(vec![0., 1., 0., 3.], 100).seed(0);
+    let indices = weighted.indices(4);
+    assert!(indices.iter().all(|&i| i == 1 || i == 3));
+    let mut indices = WeightedRandomSampler::new(vec![1., 0., 2.], 2)
+        .replacement(false)
+        .indices(3);
+    indices.sort();
+    assert_eq!(indices, vec![0, 2]);
+
+    let inputs = tensor_vec![[1.0], [3.0], [5.0], [4.0]];
+    let labels = tensor_vec![[4.0], [10.0], [16.], [13.0]];
+    let loader = TensorDataset::from_tensors(inputs, labels).into_loader(
+        DataLoaderConfigBuilder::default()
+            .batch_size(2)
+            .sampler(Some(Arc::new(WeightedRandomSampler::new(
+                vec![0., 0., 1., 0.],
+                6,
+            ))))
+            .build()
+            .unwrap(),
+    );
+    let batches: Vec<(Tensor, Tensor)> = loader.collect();
+    assert_eq!(batches.len(), 3);
+    for (batch, _) in batches {
+        assert_tensor_eq!(batch, tensor!([[5.0], [5.0]]));
+    }
+}
+
+#[test]
+fn class_balanced_sampler_test() {
+    let labels = vec![0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 2, 3];
+    let sampler = ClassBalancedSampler::new(labels.clone(), 2, 3).seed(1);
+    assert_eq!(sampler.batch_size(), 6);
+    let indices = sampler.indices(labels.len());
+    assert_eq!(indices.len(), 12);
+    for batch in indices.chunks(6) {
+        let mut classes: Vec<i64> = batch.iter().map(|&i| labels[i]).collect();
+        classes.sort();
+        assert_eq!(classes[0], classes[2]);
+        assert_eq!(classes[3], classes[5]);
+        assert_ne!(classes[0], classes[3]);
+    }
+}
+
+#[test]
+fn pad_collate_test() {
+    let sequences = vec![
+        Arc::new(tensor!([1.0, 2.0, 3.0])),
+        Arc::new(tensor!([4.0])),
+        Arc::new(tensor!([5.0, 6.0])),
+    ];
+    let loader = UnsupervisedDataset::from_vectors(sequences)
+        .into_loader(
+            DataLoaderConfigBuilder::default()
+                .batch_size(2)
+                .build()
+                .unwrap(),
+        )
+        .collate_with(PadCollate::new(-1.));
+    let batches: Vec<PaddedBatch> = loader.collect();
+    assert_eq!(batches.len(), 2);
+    assert_tensor_eq!(&batches[0].data, tensor!([[1.0, 2.0, 3.0], [4.0, -1.0, -1.0]]));
+    assert_eq!(Vec::<i64>::from(&batches[0].lengths), vec![3, 1]);
+    assert_eq!(
+        Vec::<bool>::from(&batches[0].mask().flatten(0, -1)),
+        vec![true, true, true, true, false, false]
+    );
+    assert_tensor_eq!(&batches[1].data, tensor!([[5.0, 6.0]]));
+}
+
+#[test]
+fn iterable_dataset_test() {
+    let samples = (0..5).map(|i| Arc::new(Tensor::from(i as f64)));
+    let loader = from_iter(samples).into_loader_with(
+        DataLoaderConfigBuilder::default()
+            .batch_size(2)
+            .build()
+            .unwrap(),
+        StackCollate,
+    );
+    let batches: Vec<Tensor> = loader.collect();
+    assert_eq!(batches.len(), 3);
+    assert_tensor_eq!(&batches[0], tensor!([0.0, 1.0]));
+    assert_tensor_eq!(&batches[2], tensor!([4.0]));
+
+    // An infinite stream keeps going across epochs.
+    let mut counter = 0.;
+    let loader = from_fn(move || {
+        counter += 1.;
+        Some(Arc::new(Tensor::from(counter)))
+    })
+    .into_loader_with(
+        DataLoaderConfigBuilder::default()
+            .batch_size(2)
+            .build()
+            .unwrap(),
+        StackCollate,
+    )
+    .batches_per_epoch(2);
+    let first_epoch: Vec<Tensor> = loader.clone().collect();
+    let second_epoch: Vec<Tensor> = loader.clone().collect();
+    assert_eq!(first_epoch.len(), 2);
+    assert_tensor_eq!(&first_epoch[1], tensor!([3.0, 4.0]));
+    assert_tensor_eq!(&second_epoch[0], tensor!([5.0, 6.0]));
+
+    // Workers read whole batches of consecutive samples, and no more batches than an epoch has.
+    let mut counter = 0.;
+    let loader = from_fn(move || {
+        counter += 1.;
+        Some(Arc::new(Tensor::from(counter)))
+    })
+    .into_loader_with(
+        DataLoaderConfigBuilder::default()
+            .batch_size(2)
+            .num_workers(2)
+            .build()
+            .unwrap(),
+        StackCollate,
+    )
+    .batches_per_epoch(3);
+    let mut first_epoch: Vec<Vec<f64>> = loader.clone().map(|batch| Vec::from(&batch)).collect();
+    first_epoch.sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap());
+    assert_eq!(
+        first_epoch,
+        vec![vec![1.0, 2.0], vec![3.0, 4.0], vec![5.0, 6.0]]
+    );
+    assert_eq!(loader.clone().count(), 3);
+
+    let samples = (0..5).map(|i| Arc::new(Tensor::from(i as f64)));
+    let loader = from_iter(samples).into_loader_with(
+        DataLoaderConfigBuilder::default()
+            .batch_size(2)
+            .drop_last(true)
+            .num_workers(3)
+            .build()
+            .unwrap(),
+        StackCollate,
+    );
+    assert_eq!(loader.count(), 2);
+}
+
+#[test]
+fn map_dataset_test() {
+    let inputs = tensor_vec![[1.0], [2.0], [3.0]];
+    let labels = tensor_vec![[4.0], [5.0], [6.0]];
+    let dataset = TensorDataset::from_tensors(inputs, labels);
+    assert_eq!(MapDataset::len(&dataset), 3);
+    assert_tensor_eq!(&*dataset.get(1).1, tensor!([5.0]));
+
+    let loader = dataset.into_iterable(None).into_loader_with(
+        DataLoaderConfigBuilder::default()
+            .batch_size(2)
+            .drop_last(true)
+            .build()
+            .unwrap(),
+        StackCollate,
+    );
+    let batches: Vec<(Tensor, Tensor)> = loader.collect();
+    assert_eq!(batches.len(), 1);
+    assert_tensor_eq!(&batches[0].0, tensor!([[1.0], [2.0]]));
+}
+
+fn write_idx(path: &std::path::Path, shape: &[u32], data: &[u8]) {
+    let mut bytes = vec![0, 0, 0x08, shape.len() as u8];
+    for dim in shape {
+        bytes.extend(dim.to_be_bytes());
+    }
+    bytes.extend(data);
+    std::fs::write(path, bytes).unwrap();
+}
+
+#[test]
+fn mnist_test() {
+    let dir = std::env::temp_dir().join("raddar_mnist_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let image = [255u8; 4];
+    write_idx(
+        &dir.join("train-images-idx3-ubyte"),
+        &[2, 2, 2],
+        &[image, [0; 4]].concat(),
+    );
+    write_idx(&dir.join("train-labels-idx1-ubyte"), &[2], &[3, 7]);
+    write_idx(&dir.join("t10k-images-idx3-ubyte"), &[1, 2, 2], &image);
+    write_idx(&dir.join("t10k-labels-idx1-ubyte"), &[1], &[1]);
+
+    let mnist = vision::mnist(&dir, false).unwrap();
+    assert_eq!(mnist.train.inputs.len(), 2);
+    assert_eq!(mnist.test.inputs.len(), 1);
+    assert_eq!(mnist.train.inputs[0].size(), vec![1, 2, 2]);
+    assert_tensor_eq!(
+        &*mnist.train.inputs[0],
+        Tensor::ones(&[1, 2, 2], (tch::Kind::Double, tch::Device::Cpu))
+    );
+    assert_eq!(i64::from(&*mnist.train.labels[1]), 7);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn csv_dataset_test() {
+    let path = std::env::temp_dir().join("raddar_csv_dataset_test.csv");
+    std::fs::write(
+        &path,
+        "height,weight,age,species\n1.5,2.0,3,cat\n4.0,,6,dog\n7.0,8.5,9,cat\n",
+    )
+    .unwrap();
+
+    let csv = CsvDataset::from_path(
+        &path,
+        CsvDatasetConfigBuilder::default()
+            .feature_columns(Some(vec!["weight".to_string(), "height".to_string()]))
+            .target_column(Some("species".to_string()))
+            .categorical_target(true)
+            .target_kind(tch::Kind::Int64)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(csv.classes, Some(vec!["cat".to_string(), "dog".to_string()]));
+    assert_tensor_eq!(&*csv.inputs[0], tensor!([2.0, 1.5]));
+    assert!(f64::from(csv.inputs[1].get(0)).is_nan());
+    let dataset = csv.into_tensor_dataset();
+    assert_eq!(
+        dataset
+            .labels
+            .iter()
+            .map(|label| i64::from(&**label))
+            .collect::<Vec<_>>(),
+        vec![0, 1, 0]
+    );
+
+    let csv = CsvDataset::from_path(
+        &path,
+        CsvDatasetConfigBuilder::default()
+            .target_column(Some("age".to_string()))
+            .build()
+            .unwrap(),
+    );
+    assert!(csv.is_err(), "species is not numeric");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn parquet_dataset_test() {
+    use arrow::{
+        array::{ArrayRef, FixedSizeListBuilder, Float32Builder, Int32Array, Int64Array},
+        record_batch::RecordBatch,
+    };
+    use parquet::arrow::ArrowWriter;
+
+    let mut embeddings = FixedSizeListBuilder::new(Float32Builder::new(), 2);
+    for row in 0..5 {
+        embeddings.values().append_value(row as f32);
+        embeddings.values().append_value(-row as f32);
+        embeddings.append(true);
+    }
+    let batch = RecordBatch::try_from_iter(vec![
+        ("embedding", Arc::new(embeddings.finish()) as ArrayRef),
+        (
+            "count",
+            Arc::new(Int32Array::from(vec![1, 2, 3, 4, 5])) as ArrayRef,
+        ),
+        (
+            "label",
+            Arc::new(Int64Array::from(vec![0, 1, 0, 1, 1])) as ArrayRef,
+        ),
+    ])
+    .unwrap();
+    let path = std::env::temp_dir().join("raddar_parquet_dataset_test.parquet");
+    let mut writer =
+        ArrowWriter::try_new(std::fs::File::create(&path).unwrap(), batch.schema(), None).unwrap();
+    writer.write(&batch).unwrap();
+    writer.close().unwrap();
+
+    let dataset = ParquetDataset::from_path(
+        &path,
+        ParquetDatasetConfigBuilder::default()
+            .target_column(Some("label".to_string()))
+            .target_kind(tch::Kind::Int64)
+            .batch_size(2)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(dataset.num_rows().unwrap(), 5);
+    let batches: Vec<(Tensor, Tensor)> = dataset.loader().collect();
+    assert_eq!(batches.len(), 3);
+    assert_tensor_eq!(&batches[0].0, tensor!([[0.0, 0.0, 1.0], [1.0, -1.0, 2.0]]));
+    assert_eq!(Vec::<i64>::from(&batches[0].1), vec![0, 1]);
+
+    let loaded = dataset.load().unwrap();
+    assert_eq!(loaded.inputs.len(), 5);
+    assert_tensor_eq!(&*loaded.inputs[4], tensor!([4.0, -4.0, 5.0]));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn npz_dataset_test() {
+    let inputs = tensor_vec![[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]];
+    let labels = tensor_vec![[0.0], [1.0], [0.0]];
+    let dataset = TensorDataset::from_tensors(inputs, labels);
+    let path = std::env::temp_dir().join("raddar_npz_dataset_test.npz");
+    dataset.save_npz(&path, "x", "y").unwrap();
+
+    let loaded = TensorDataset::from_npz(&path, "x", "y").unwrap();
+    assert_eq!(loaded.inputs.len(), 3);
+    assert_tensor_eq!(&*loaded.inputs[1], tensor!([3.0, 4.0]));
+    assert_tensor_eq!(&*loaded.labels[1], tensor!([1.0]));
+    assert!(TensorDataset::from_npz(&path, "x", "z").is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn streaming_dataset_test() {
+    let (sender, receiver) = std::sync::mpsc::sync_channel(2);
+    let producer = std::thread::spawn(move || {
+        for i in 0..5 {
+            sender.send(Arc::new(Tensor::from(i as f64))).unwrap();
+        }
+    });
+    let loader = StreamingDataset::from_receiver(receiver).into_loader_with(
+        DataLoaderConfigBuilder::default()
+            .batch_size(2)
+            .build()
+            .unwrap(),
+        StackCollate,
+    );
+    let batches: Vec<Tensor> = loader.collect();
+    producer.join().unwrap();
+    assert_eq!(batches.len(), 3);
+    assert_tensor_eq!(&batches[1], tensor!([2.0, 3.0]));
+
+    let loader = StreamingDataset::from_iter((0..).map(|i| Arc::new(Tensor::from(i as f64))))
+        .into_loader_with(
+            DataLoaderConfigBuilder::default()
+                .batch_size(4)
+                .build()
+                .unwrap(),
+            StackCollate,
+        )
+        .batches_per_epoch(3);
+    assert_eq!(loader.clone().count(), 3);
+    assert_tensor_eq!(
+        loader.clone().next().unwrap(),
+        tensor!([12.0, 13.0, 14.0, 15.0])
+    );
+
+    // A stream of pairs read by workers yields the batches of `(input, label)` expected by the trainer.
+    let (sender, receiver) = std::sync::mpsc::sync_channel(2);
+    let producer = std::thread::spawn(move || {
+        for i in 0..7 {
+            let x = Tensor::from(i as f64);
+            let y = &x * 2.;
+            sender.send((Arc::new(x), Arc::new(y))).unwrap();
+        }
+    });
+    let loader = StreamingDataset::from_receiver(receiver).into_loader_with(
+        DataLoaderConfigBuilder::default()
+            .batch_size(2)
+            .drop_last(true)
+            .num_workers(2)
+            .build()
+            .unwrap(),
+        StackCollate,
+    );
+    let batches: Vec<(Tensor, Tensor)> = loader.collect();
+    producer.join().unwrap();
+    assert_eq!(batches.len(), 3);
+    for (x, y) in batches.iter() {
+        assert_tensor_eq!(y, &(x * 2.));
+    }
+    let total: f64 = batches
+        .iter()
+        .map(|(x, _)| f64::from(x.sum(tch::Kind::Double)))
+        .sum();
+    assert_eq!(total, 15.);
+}
+
+#[test]
+fn random_image_mappings_test() {
+    use image::{DynamicImage, GenericImageView, Rgb, RgbImage};
+    use raddar::dataset::DynImageDataset;
+
+    let image = RgbImage::from_fn(4, 3, |x, y| Rgb([x as u8, y as u8, 0]));
+    let dataset = DynImageDataset::from_vectors(vec![Arc::new(DynamicImage::ImageRgb8(image))]);
+
+    let cropped: DynImageDataset = dataset.clone().map(image_mappings::random_crop(2, 2));
+    assert_eq!(cropped.inputs[0].dimensions(), (2, 2));
+
+    let flipped: DynImageDataset = dataset
+        .clone()
+        .map(image_mappings::random_horizontal_flip(1.));
+    assert_eq!(flipped.inputs[0].get_pixel(0, 0).0[0], 3);
+    let unflipped: DynImageDataset = dataset
+        .clone()
+        .map(image_mappings::random_vertical_flip(0.));
+    assert_eq!(unflipped.inputs[0].get_pixel(0, 0).0[1], 0);
+
+    let rotated: DynImageDataset = dataset.map(image_mappings::random_rotation(0.));
+    assert_eq!(rotated.inputs[0].dimensions(), (4, 3));
+    assert_eq!(rotated.inputs[0].get_pixel(3, 2).0[..2], [3, 2]);
+}
+
+#[test]
+fn color_image_mappings_test() {
+    use image::{DynamicImage, GenericImageView, Rgb, RgbImage};
+    use raddar::dataset::DynImageDataset;
+
+    let image = RgbImage::from_fn(4, 4, |x, _| Rgb([60 * x as u8, 100, 200]));
+    let dataset = DynImageDataset::from_vectors(vec![Arc::new(DynamicImage::ImageRgb8(image))]);
+
+    let unchanged: DynImageDataset = dataset
+        .clone()
+        .map(image_mappings::color_jitter(0., 0., 0., 0.));
+    assert_eq!(unchanged.inputs[0].get_pixel(1, 0).0, [60, 100, 200, 255]);
+
+    let gray: DynImageDataset = dataset
+        .clone()
+        .map(image_mappings::color_jitter(0., 0., 1., 0.));
+    assert!(!gray.inputs[0].color().has_alpha());
+
+    let blurred: DynImageDataset = dataset.map(image_mappings::gaussian_blur(1., 2.));
+    assert_eq!(blurred.inputs[0].dimensions(), (4, 4));
+    let [r0, ..] = blurred.inputs[0].get_pixel(0, 0).0;
+    assert!(r0 > 0, "the blur mixes in the neighbouring columns");
+}
+
+#[test]
+fn normalize_image_mapping_test() {
+    use image::{DynamicImage, Rgb, RgbImage};
+    use raddar::dataset::{DynImageDataset, UnsupervisedTensorDataset};
+
+    let image = RgbImage::from_pixel(2, 3, Rgb([255, 0, 51]));
+    let dataset = DynImageDataset::from_vectors(vec![Arc::new(DynamicImage::ImageRgb8(image))]);
+    let tensors: UnsupervisedTensorDataset = dataset.map(image_mappings::to_tensor_with(
+        DynamicImage::into_rgb8,
+        true,
+        true,
+    ));
+    assert_eq!(tensors.inputs[0].size(), vec![3, 3, 2]);
+    assert_eq!(tensors.inputs[0].kind(), tch::Kind::Float);
+
+    let normalized: UnsupervisedTensorDataset = tensors.map(image_mappings::normalize(
+        vec![0.5, 0.0, 0.1],
+        vec![0.5, 1.0, 0.1],
+    ));
+    assert_tensor_eq!(
+        &normalized.inputs[0].mean_dim(&[1, 2], false, tch::Kind::Double),
+        tensor!([1.0, 0.0, 1.0])
+    );
+}
+
+#[test]
+fn compose_test() {
+    let inputs = tensor_vec![[1.0], [2.0]];
+    let labels = tensor_vec![[3.0], [4.0]];
+    let dataset = TensorDataset::from_tensors(inputs, labels);
+    let transform = Compose::new()
+        .then(on_input(tensor_mapping(|x: &Tensor| x * 2.)))
+        .then(on_input(tensor_mapping(|x: &Tensor| x + 1.)));
+    let mapped: TensorDataset = dataset.map(transform);
+    assert_tensor_eq!(&*mapped.inputs[1], tensor!([5.0]));
+    assert_tensor_eq!(&*mapped.labels[1], tensor!([4.0]));
+
+    use image::{DynamicImage, GenericImageView, Luma, Rgb, RgbImage};
+    let image = RgbImage::from_fn(8, 8, |x, y| Rgb([x as u8, y as u8, 0]));
+    let mask = DynamicImage::ImageLuma8(image::GrayImage::from_fn(8, 8, |x, _| Luma([x as u8])));
+    let mut transform = Compose::new().then(on_both(image_mappings::random_crop(3, 3)));
+    for _ in 0..5 {
+        let (image, mask) = transform((
+            Arc::new(DynamicImage::ImageRgb8(image.clone())),
+            Arc::new(mask.clone()),
+        ));
+        assert_eq!(image.get_pixel(0, 0).0[0], mask.get_pixel(0, 0).0[0]);
+    }
+}
+
+#[test]
+fn cached_dataset_test() {
+    use raddar::dataset::CachedDataset;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let inputs = tensor_vec![[1.0], [2.0], [3.0]];
+    let labels = tensor_vec![[4.0], [5.0], [6.0]];
+    let mapped = TensorDataset::from_tensors(inputs, labels).map_samples(
+        move |(x, y): (Arc<Tensor>, Arc<Tensor>)| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            (Arc::new(&*x * 10.), y)
+        },
+    );
+    let dir = std::env::temp_dir().join("raddar_cached_dataset_test");
+    let _ = std::fs::remove_dir_all(&dir);
+    let cached = CachedDataset::new(mapped).with_disk_cache(&dir).unwrap();
+    for _ in 0..3 {
+        assert_tensor_eq!(&*cached.get(1).0, tensor!([20.0]));
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(cached.cached_len(), 1);
+
+    // The samples are loaded from disk once they are evicted from memory.
+    cached.clear();
+    assert_tensor_eq!(&*cached.get(1).1, tensor!([5.0]));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dataset_split_test() {
+    let inputs: Vec<Arc<Tensor>> = (0..10).map(|i| Arc::new(Tensor::from(i as f64))).collect();
+    let labels = inputs.clone();
+    let dataset = TensorDataset::from_tensors(inputs.clone(), labels);
+    let splits = dataset.clone().split(&[0.8, 0.1, 0.1], Some(42));
+    assert_eq!(
+        splits.iter().map(|split| split.size()).collect::<Vec<_>>(),
+        vec![8, 1, 1]
+    );
+
+    // The splits are disjoint and cover the dataset.
+    let mut values: Vec<i64> = splits
+        .iter()
+        .flat_map(|split| split.inputs.iter().map(|input| f64::from(&**input) as i64))
+        .collect();
+    values.sort();
+    assert_eq!(values, (0..10).collect::<Vec<_>>());
+
+    // The samples are shared rather than copied.
+    assert!(splits[0]
+        .inputs
+        .iter()
+        .all(|input| inputs.iter().any(|original| Arc::ptr_eq(input, original))));
+
+    let again = dataset.split(&[0.8, 0.1, 0.1], Some(42));
+    assert!(Arc::ptr_eq(&again[1].inputs[0], &splits[1].inputs[0]));
+}
+
+#[test]
+fn concat_and_chain_dataset_test() {
+    use raddar::dataset::{ChainDataset, ConcatDataset};
+
+    let first = UnsupervisedDataset::from_vectors(vec![Arc::new(0), Arc::new(1)]);
+    let second = UnsupervisedDataset::from_vectors(vec![]);
+    let third = UnsupervisedDataset::from_vectors(vec![Arc::new(2), Arc::new(3), Arc::new(4)]);
+    let dataset = ConcatDataset::new(vec![first, second, third]);
+    assert_eq!(MapDataset::len(&dataset), 5);
+    assert_eq!(dataset.locate(2), (2, 0));
+    assert_eq!(
+        (0..5).map(|i| *dataset.get(i)).collect::<Vec<_>>(),
+        vec![0, 1, 2, 3, 4]
+    );
+
+    let boxed: Vec<Box<dyn IterableDataset<SampleType = i32>>> =
+        vec![Box::new(from_iter(0..2)), Box::new(from_iter(5..7))];
+    let mut chain = ChainDataset::new(boxed);
+    let samples: Vec<i32> = std::iter::from_fn(|| chain.next_sample()).collect();
+    assert_eq!(samples, vec![0, 1, 5, 6]);
+}
+
+#[test]
+fn subset_and_filter_dataset_test() {
+    use raddar::dataset::{FilterDataset, Subset};
+
+    let inputs = tensor_vec![[1.0], [2.0], [3.0], [4.0]];
+    let labels = tensor_vec![[0.0], [1.0], [0.0], [1.0]];
+    let dataset = Arc::new(TensorDataset::from_tensors(inputs, labels));
+
+    let subset = Subset::new(dataset.clone(), vec![3, 0]);
+    assert_eq!(subset.len(), 2);
+    assert!(Arc::ptr_eq(&subset.get(0).0, &dataset.inputs[3]));
+
+    let positives = FilterDataset::new(dataset, |(_, label): &(Arc<Tensor>, Arc<Tensor>)| {
+        f64::from(&**label) > 0.5
+    });
+    assert_eq!(positives.indices, vec![1, 3]);
+    assert_tensor_eq!(&*positives.get(1).0, tensor!([4.0]));
+}
+
+#[test]
+fn lazy_image_dataset_test() {
+    use image::{DynamicImage, Rgb, RgbImage};
+    use raddar::dataset::{LazyImageDataset, LoadFromImageFolder};
+
+    let dir = std::env::temp_dir().join("raddar_lazy_image_dataset_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    for i in 0..3u8 {
+        RgbImage::from_pixel(4, 2, Rgb([i, i, i]))
+            .save(dir.join(format!("{}.png", i)))
+            .unwrap();
+    }
+
+    let dataset = LazyImageDataset::from_image_folder(dir.to_str().unwrap(), ());
+    assert_eq!(dataset.len(), 3);
+    let loader = dataset
+        .map_samples(
+            Compose::new()
+                .then(image_mappings::to_tensor_with(
+                    DynamicImage::into_rgb8,
+                    false,
+                    true,
+                ))
+                .then(tensor_mapping(|x: &Tensor| x.to_kind(tch::Kind::Double))),
+        )
+        .into_loader_with(
+            DataLoaderConfigBuilder::default()
+                .batch_size(2)
+                .num_workers(2)
+                .build()
+                .unwrap(),
+            StackCollate,
+        );
+    assert_eq!(loader.num_batches(), 2);
+    let batches: Vec<Tensor> = loader.collect();
+    assert_eq!(batches[0].size(), vec![2, 3, 2, 4]);
+    assert_eq!(f64::from(batches[1].mean(tch::Kind::Double)), 2.0);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn voc_dataset_test() {
+    use image::{Rgb, RgbImage};
+    use vision::{VocDataset, VocTask, VOC_CLASSES};
+
+    let root = std::env::temp_dir().join("raddar_voc_test");
+    for dir in ["Annotations", "ImageSets/Main", "JPEGImages"] {
+        std::fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    std::fs::write(root.join("ImageSets/Main/train.txt"), "2007_000001\n").unwrap();
+    std::fs::write(
+        root.join("Annotations/2007_000001.xml"),
+        r#"<annotation>
+            <filename>2007_000001.jpg</filename>
+            <size><width>8</width><height>6</height><depth>3</depth></size>
+            <object>
+                <name>dog</name><truncated>1</truncated><difficult>0</difficult>
+                <bndbox><xmin>1</xmin><ymin>2</ymin><xmax>5</xmax><ymax>6</ymax></bndbox>
+            </object>
+            <object>
+                <name>person</name><difficult>1</difficult>
+                <bndbox><xmin>0</xmin><ymin>0</ymin><xmax>3</xmax><ymax>3</ymax></bndbox>
+            </object>
+        </annotation>"#,
+    )
+    .unwrap();
+    RgbImage::from_pixel(8, 6, Rgb([10, 20, 30]))
+        .save(root.join("JPEGImages/2007_000001.jpg"))
+        .unwrap();
+
+    let voc = VocDataset::open(&root, "train", VocTask::Detection).unwrap();
+    assert_eq!(voc.len(), 1);
+    assert_eq!(voc.annotations[0].objects.len(), 2);
+    assert!(voc.annotations[0].objects[0].truncated);
+
+    let sample = voc.get(0);
+    assert_eq!(sample.labels.size(), vec![1]);
+    assert_eq!(VOC_CLASSES[i64::from(&*sample.labels) as usize], "dog");
+    assert_tensor_eq!(&*sample.boxes, tensor!([[1.0, 2.0, 5.0, 6.0]]));
+    assert!(sample.mask.is_none());
+
+    let sample = voc.keep_difficult(true).get(0);
+    assert_eq!(Vec::<i64>::from(&*sample.labels), vec![11, 14]);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn device_prefetch_test() {
+    let batches = (0..5i64).map(|i| Tensor::of_slice(&[i, i + 1]));
+    let loader = batches.prefetch_to(Device::Cpu, 2);
+    for _ in 0..2 {
+        let collected = loader.clone().collect::<Vec<_>>();
+        assert_eq!(collected.len(), 5);
+        assert_eq!(collected[3].device(), Device::Cpu);
+        assert_eq!(Vec::<i64>::from(&collected[3]), vec![3, 4]);
+    }
+
+    let pairs = (0..3i64).map(|i| (Tensor::of_slice(&[i]), Tensor::of_slice(&[-i])));
+    let (_, labels) = pairs.prefetch_to(Device::Cpu, 1).last().unwrap();
+    assert_eq!(i64::from(&labels), -2);
+}
+
+#[test]
+fn stacked_tensor_dataset_test() {
+    let inputs = tensor_vec![[1.0], [2.0], [3.0], [4.0], [5.0]];
+    let labels = tensor_vec![[2.0], [4.0], [6.0], [8.0], [10.0]];
+    let dataset: StackedTensorDataset = TensorDataset::from_tensors(inputs, labels).into();
+    assert_eq!(dataset.len(), 5);
+
+    let loader = dataset.into_loader(
+        DataLoaderConfigBuilder::default()
+            .batch_size(2)
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(loader.num_batches(), 3);
+    let batches = loader.clone().collect::<Vec<_>>();
+    assert_eq!(batches.len(), 3);
+    assert_tensor_eq!(&batches[0].0, tensor!([[1.0], [2.0]]));
+    assert_tensor_eq!(&batches[2].1, tensor!([[10.0]]));
+
+    let dataset = StackedTensorDataset::from_tensors(
+        Tensor::arange(6, (tch::Kind::Double, Device::Cpu)),
+        Tensor::arange(6, (tch::Kind::Double, Device::Cpu)) * 2.0,
+    );
+    let loader = dataset.into_loader(
+        DataLoaderConfigBuilder::default()
+            .batch_size(4)
+            .shuffle(true)
+            .drop_last(true)
+            .seed(Some(0))
+            .build()
+            .unwrap(),
+    );
+    let batches = loader.clone().collect::<Vec<_>>();
+    assert_eq!(batches.len(), 1);
+    let (inputs, labels) = &batches[0];
+    assert_eq!(inputs.size(), vec![4]);
+    assert_tensor_eq!(inputs * 2.0, labels);
+}
+
+#[test]
+fn sample_metadata_test() {
+    let inputs = tensor_vec![[1.0], [2.0], [3.0], [4.0]];
+    let labels = tensor_vec![[2.0], [4.0], [6.0], [8.0]];
+    let dataset = TensorDataset::from_tensors(inputs, labels)
+        .with_metadata(
+            (0..4)
+                .map(|id| {
+                    SampleMetadata::new(id)
+                        .path(format!("sample_{}.png", id))
+                        .weight(id as f64)
+                })
+                .collect(),
+        )
+        .map_samples(on_sample(on_input(tensor_mapping(|x: &Tensor| x * 10.0))));
+
+    let sample = dataset.get(2);
+    assert_tensor_eq!(&*sample.sample.0, tensor!([30.0]));
+    assert_eq!(
+        sample.metadata.path.as_ref().unwrap().to_str(),
+        Some("sample_2.png")
+    );
+
+    let batches = dataset
+        .into_loader_with(
+            DataLoaderConfigBuilder::default()
+                .batch_size(2)
+                .build()
+                .unwrap(),
+            MetadataCollate(StackCollate),
+        )
+        .collect::<Vec<_>>();
+    assert_eq!(batches.len(), 2);
+    assert_eq!(batches[1].ids(), vec![2, 3]);
+    assert_tensor_eq!(&batches[1].weights, tensor!([2.0, 3.0]));
+    assert_tensor_eq!(&batches[1].batch.0, tensor!([[30.0], [40.0]]));
+
+    let loss = tensor!([1.0, 4.0]);
+    assert!((f64::from(batches[1].weighted_mean(&loss)) - 2.8).abs() < 1e-9);
+}
+
+#[test]
+fn repeat_and_take_dataset_test() {
+    let mut dataset = from_iter(0..3).repeat(Some(2));
+    let samples = std::iter::from_fn(|| dataset.next_sample()).collect::<Vec<_>>();
+    assert_eq!(samples, vec![0, 1, 2, 0, 1, 2]);
+
+    let mut dataset = from_iter(0..3).repeat(None).take(7);
+    let samples = std::iter::from_fn(|| dataset.next_sample()).collect::<Vec<_>>();
+    assert_eq!(samples, vec![0, 1, 2, 0, 1, 2, 0]);
+
+    let mut empty = from_iter(0..0).repeat(None);
+    assert!(empty.next_sample().is_none());
+
+    let inputs = tensor_vec![[1.0], [2.0], [3.0], [4.0]];
+    let labels = tensor_vec![[2.0], [4.0], [6.0], [8.0]];
+    let dataset = Arc::new(TensorDataset::from_tensors(inputs, labels));
+    let head = TakeDataset::new(dataset.clone(), 2);
+    assert_eq!(head.len(), 2);
+    assert_tensor_eq!(&*head.get(1).0, tensor!([2.0]));
+
+    let loader = dataset
+        .into_iterable(None)
+        .repeat(None)
+        .into_loader_with(
+            DataLoaderConfigBuilder::default()
+                .batch_size(3)
+                .build()
+                .unwrap(),
+            StackCollate,
+        )
+        .batches_per_epoch(3);
+    let batches = loader.clone().collect::<Vec<_>>();
+    assert_eq!(batches.len(), 3);
+    assert_tensor_eq!(&batches[1].0, tensor!([[4.0], [1.0], [2.0]]));
+}
+
+#[test]
+fn length_bucket_sampler_test() {
+    let lengths = vec![5, 1, 9, 3, 7, 2, 8, 4, 6];
+    let sampler = LengthBucketSampler::new(lengths.clone(), 2)
+        .pool_batches(2)
+        .seed(0);
+    let indices = sampler.indices(lengths.len());
+    let mut sorted = indices.clone();
+    sorted.sort();
+    assert_eq!(sorted, (0..9).collect::<Vec<_>>());
+
+    // The pools of 4 samples are sorted, so every full batch is sorted, and the partial batch is last.
+    for batch in indices[..8].chunks(2) {
+        assert!(lengths[batch[0]] <= lengths[batch[1]]);
+    }
+
+    let sampler = LengthBucketSampler::new(lengths.clone(), 2).shuffle(false);
+    let sorted_lengths = sampler
+        .indices(lengths.len())
+        .into_iter()
+        .map(|index| lengths[index])
+        .collect::<Vec<_>>();
+    assert_eq!(sorted_lengths, (1..=9).collect::<Vec<_>>());
+
+    // With a single pool, the batches are sorted, so the padding is minimal.
+    let sampler = LengthBucketSampler::new(lengths.clone(), 3)
+        .pool_batches(3)
+        .seed(1);
+    let indices = sampler.indices(lengths.len());
+    let mut batches = indices
+        .chunks(3)
+        .map(|batch| {
+            batch
+                .iter()
+                .map(|&index| lengths[index])
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    batches.sort();
+    assert_eq!(batches, vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]]);
+}
+
+#[test]
+fn loader_error_test() {
+    use image::{Rgb, RgbImage};
+    use raddar::{
+        core::RaddarError,
+        dataset::{
+            DynImageDataset, LoadFromImageFolder, LoadFromJson, SimpleDataset,
+            SimpleDatasetJsonConfig,
+        },
+    };
+
+    let dir = std::env::temp_dir().join("raddar_loader_error_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    RgbImage::from_pixel(4, 2, Rgb([1, 2, 3]))
+        .save(dir.join("0.png"))
+        .unwrap();
+    let dataset = DynImageDataset::try_from_image_folder(dir.to_str().unwrap(), ()).unwrap();
+    assert_eq!(dataset.size(), 1);
+
+    std::fs::write(dir.join("1.png"), b"not an image").unwrap();
+    match DynImageDataset::try_from_image_folder(dir.to_str().unwrap(), ()) {
+        Err(RaddarError::Image { path, .. }) => assert_eq!(path, dir.join("1.png")),
+        other => panic!("Expected an image error, got {:?}", other.map(|_| ())),
+    }
+
+    let json = dir.join("data.json");
+    std::fs::write(&json, r#"[{"x": 1.0, "y": 0}, {"x": 2.0}]"#).unwrap();
+    let error = SimpleDataset::<f64, i64>::try_from_json(
+        json.to_str().unwrap(),
+        SimpleDatasetJsonConfig {
+            input_field: "x".to_owned(),
+            label_field: "y".to_owned(),
+        },
+    )
+    .map(|_| ())
+    .unwrap_err();
+    assert!(matches!(error, RaddarError::InvalidData { .. }));
+    assert!(error.to_string().contains("Field y not found"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
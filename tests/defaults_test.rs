@@ -0,0 +1,65 @@
+use raddar::{
+    nn::{AlexNetBuilder, Conv2dBuilder, Linear, LinearBuilder, Mod, SaveableModule, Trainable},
+    util::{default_device, default_dtype, with_default_device, with_default_dtype},
+};
+use tch::{Device, Kind, Tensor};
+
+#[test]
+fn builder_dtype_test() {
+    let conv = Conv2dBuilder::default()
+        .in_channel(3)
+        .out_channel(4)
+        .kernel_size([3, 3])
+        .dtype(Kind::Float)
+        .device(Device::Cpu)
+        .build();
+    for (_, parameter) in conv.parameters() {
+        let parameter = parameter.lock();
+        assert_eq!(parameter.kind(), Kind::Float);
+        assert!(parameter.requires_grad());
+    }
+    let output = conv(&Tensor::ones(&[1, 3, 8, 8], (Kind::Float, Device::Cpu)));
+    assert_eq!(output.kind(), Kind::Float);
+    assert_eq!(conv.module().config()["dtype"], "Float");
+    assert!(conv.module().config().get("device").is_none());
+}
+
+#[test]
+fn default_dtype_test() {
+    let (linear, double) = with_default_dtype(Kind::Float, || {
+        let linear = LinearBuilder::default().input_dim(3).output_dim(2).build();
+        let double = LinearBuilder::default()
+            .input_dim(3)
+            .output_dim(2)
+            .dtype(Kind::Double)
+            .build();
+        (linear, double)
+    });
+    assert_eq!(default_dtype(), Kind::Double);
+
+    assert_eq!(linear.module().dtype, Kind::Float);
+    assert_eq!(linear.module().linear_weight.lock().kind(), Kind::Float);
+    assert_eq!(double.module().linear_weight.lock().kind(), Kind::Double);
+    assert_eq!(linear.device(), Device::Cpu);
+    let loaded = Mod::new(Linear::from_config(&linear.module().config()).unwrap());
+    assert_eq!(loaded.module().dtype, Kind::Float);
+    assert_eq!(loaded.module().linear_weight.lock().kind(), Kind::Float);
+}
+
+#[test]
+fn builder_device_test() {
+    assert_eq!(
+        with_default_device(Device::Cuda(0), default_device),
+        Device::Cuda(0)
+    );
+    assert_eq!(default_device(), Device::Cpu);
+
+    let model = AlexNetBuilder::default()
+        .num_classes(10)
+        .device(Device::Cpu)
+        .build();
+    assert_eq!(model.device(), Device::Cpu);
+    for (_, parameter) in model.parameters() {
+        assert_eq!(parameter.lock().device(), Device::Cpu);
+    }
+}
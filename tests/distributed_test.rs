@@ -0,0 +1,67 @@
+use std::thread;
+
+use raddar::{
+    dataset::{DataLoaderConfigBuilder, Dataset, TensorDataset},
+    distributed::{shard, DistributedDataParallel, ProcessGroup, TcpProcessGroup},
+    nn::{LinearBuilder, Trainable},
+    optim::{gradient_descent, opt},
+    tensor_vec,
+    train::Trainer,
+};
+use tch::{Kind, Reduction, Tensor};
+
+#[test]
+fn tcp_process_group_test() {
+    let world_size = 3;
+    let handles: Vec<_> = (0..world_size)
+        .map(|rank| {
+            thread::spawn(move || {
+                let mut group = TcpProcessGroup::new("127.0.0.1:29517", rank, world_size).unwrap();
+                let mut tensor = Tensor::of_slice(&[rank as f64, 1.]);
+                group.all_reduce(&mut tensor).unwrap();
+                let mut broadcast = Tensor::of_slice(&[rank as f64]).to_kind(Kind::Float);
+                group.broadcast(&mut broadcast, 2).unwrap();
+                group.barrier().unwrap();
+                (Vec::<f64>::from(&tensor), f64::from(&broadcast))
+            })
+        })
+        .collect();
+    for handle in handles {
+        let (reduced, broadcast) = handle.join().unwrap();
+        assert_eq!(reduced, vec![3., 3.]);
+        assert_eq!(broadcast, 2.);
+    }
+}
+
+#[test]
+fn distributed_data_parallel_test() {
+    let world_size = 2;
+    let handles: Vec<_> = (0..world_size)
+        .map(|rank| {
+            thread::spawn(move || {
+                let group = TcpProcessGroup::new("127.0.0.1:29518", rank, world_size).unwrap();
+                let inputs = tensor_vec![[1.0], [3.0], [5.0], [4.0], [8.0], [10.0], [2.0], [6.0]];
+                let labels = tensor_vec![[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.], [19.0]];
+                let loader = TensorDataset::from_tensors(inputs, labels).into_loader(
+                    DataLoaderConfigBuilder::default()
+                        .batch_size(2)
+                        .build()
+                        .unwrap(),
+                );
+                let model = LinearBuilder::default().input_dim(1).output_dim(1).build();
+                let optimizer = opt(model.training_parameters(), gradient_descent(0.01));
+                let mut trainer = Trainer::new(
+                    model.clone(),
+                    optimizer,
+                    |output: &Tensor, label: &Tensor| output.mse_loss(label, Reduction::Mean),
+                )
+                .callback(DistributedDataParallel::new(group).bucket_size(1));
+                trainer.fit(shard(loader, rank, world_size), 5);
+                let weight = model.parameters()["weight"].lock().copy();
+                Vec::<f64>::from(&weight.flatten(0, -1))
+            })
+        })
+        .collect();
+    let weights: Vec<Vec<f64>> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert_eq!(weights[0], weights[1]);
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "hdf5")]
+
+use raddar::{
+    assert_tensor_eq,
+    dataset::{load_hdf5_group, Hdf5Dataset, MapDataset},
+    tensor,
+};
+
+#[test]
+fn hdf5_dataset_test() {
+    let path = std::env::temp_dir().join("raddar_hdf5_test.h5");
+    {
+        let file = hdf5::File::create(&path).unwrap();
+        let group = file.create_group("train").unwrap();
+        group
+            .new_dataset::<f64>()
+            .shape((3, 2))
+            .create("x")
+            .unwrap()
+            .write_raw(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+            .unwrap();
+        group
+            .new_dataset::<i32>()
+            .shape(3)
+            .create("y")
+            .unwrap()
+            .write_raw(&[0, 1, 0])
+            .unwrap();
+    }
+
+    let dataset = Hdf5Dataset::open(&path, "train/x", "train/y").unwrap();
+    assert_eq!(dataset.len(), 3);
+    let (input, label) = dataset.get(1);
+    assert_tensor_eq!(&*input, tensor!([3.0, 4.0]));
+    assert_eq!(f64::from(&*label), 1.0);
+
+    let group = load_hdf5_group(&path, "train").unwrap();
+    assert_eq!(group.inputs["x"].len(), 3);
+    assert_eq!(f64::from(&*group.inputs["y"][2]), 0.0);
+    std::fs::remove_file(&path).unwrap();
+}
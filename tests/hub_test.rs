@@ -0,0 +1,37 @@
+use raddar::{
+    assert_tensor_eq,
+    core::Cellable,
+    hub::{
+        cache_dir, download_cached, load_pretrained, register_weights, sha256_file,
+        PretrainedWeights,
+    },
+    nn::{StateDict, StateDictExt},
+    tensor,
+};
+
+#[test]
+fn hub_cache_test() {
+    let home = std::env::temp_dir().join("raddar_hub_test");
+    std::env::set_var("RADDAR_HOME", &home);
+    assert_eq!(cache_dir(), home.join("hub"));
+    std::fs::create_dir_all(cache_dir()).unwrap();
+
+    let state_dict: StateDict = vec![("weight".to_owned(), tensor!([[1.0, 2.0]]).cell())]
+        .into_iter()
+        .collect();
+    let path = cache_dir().join("tiny.safetensors");
+    state_dict.to_safetensors(&path).unwrap();
+    let sha256 = sha256_file(&path).unwrap();
+    assert_eq!(sha256.len(), 64);
+
+    // The file is cached with the right hash, so nothing is downloaded.
+    let url = "https://example.invalid/weights/tiny.safetensors";
+    assert_eq!(download_cached(url, Some(&sha256)).unwrap(), path);
+
+    register_weights("tiny", PretrainedWeights::new(url).sha256(&sha256));
+    let loaded = load_pretrained("tiny").unwrap();
+    assert_tensor_eq!(&*loaded["weight"].lock(), tensor!([[1.0, 2.0]]));
+    assert!(load_pretrained("unknown").is_err());
+
+    std::fs::remove_dir_all(&home).unwrap();
+}
@@ -0,0 +1,92 @@
+use raddar::{
+    metrics::{
+        Accuracy, Average, ConfusionMatrix, F1Score, MeanAbsoluteError, Metric, Precision, R2Score,
+        Recall, RootMeanSquaredError, TopKAccuracy,
+    },
+    tensor,
+};
+
+#[test]
+fn accuracy_test() {
+    let mut accuracy = Accuracy::new();
+    let mut top2 = TopKAccuracy::new(2);
+    let output = tensor!([[0.1, 0.7, 0.2], [0.5, 0.3, 0.2], [0.2, 0.3, 0.5]]);
+    let target = tensor!([1i64, 1, 0]);
+    accuracy.update(&output, &target);
+    top2.update(&output, &target);
+    assert!((accuracy.compute() - 1. / 3.).abs() < 1e-9);
+    assert!((top2.compute() - 2. / 3.).abs() < 1e-9);
+
+    accuracy.update(&tensor!([2i64]), &tensor!([2i64]));
+    assert!((accuracy.compute() - 0.5).abs() < 1e-9);
+    accuracy.reset();
+    assert_eq!(accuracy.compute(), 0.);
+}
+
+#[test]
+fn precision_recall_f1_test() {
+    let output = tensor!([0i64, 0, 1, 1, 2, 2]);
+    let target = tensor!([0i64, 1, 1, 1, 2, 0]);
+
+    let mut matrix = ConfusionMatrix::new(3);
+    matrix.update(&output, &target);
+    assert_eq!(
+        matrix.to_vec(),
+        vec![vec![1, 0, 1], vec![1, 2, 0], vec![0, 0, 1]]
+    );
+
+    let mut precision = Precision::new(3, Average::Macro);
+    let mut recall = Recall::new(3, Average::Micro);
+    let mut f1 = F1Score::new(3, Average::Weighted);
+    for metric in [
+        &mut precision as &mut dyn Metric,
+        &mut recall as &mut dyn Metric,
+        &mut f1 as &mut dyn Metric,
+    ] {
+        metric.update(&output, &target);
+    }
+    assert!((precision.compute() - (0.5 + 1.0 + 0.5) / 3.).abs() < 1e-9);
+    assert!((recall.compute() - 4. / 6.).abs() < 1e-9);
+    let expected_f1 = (0.5 * 2. + 0.8 * 3. + 2. / 3. * 1.) / 6.;
+    assert!((f1.compute() - expected_f1).abs() < 1e-9);
+}
+
+#[test]
+fn auc_test() {
+    let mut roc = Auc::roc();
+    let mut pr = Auc::pr();
+    for (output, target) in [
+        (tensor!([0.1, 0.4]), tensor!([0i64, 0])),
+        (tensor!([0.35, 0.8]), tensor!([1i64, 1])),
+    ] {
+        roc.update(&output, &target);
+        pr.update(&output, &target);
+    }
+    assert!((roc.compute() - 0.75).abs() < 1e-9);
+    assert!((pr.compute() - (0.5 * 1. + 0.5 * 2. / 3.)).abs() < 1e-9);
+
+    let mut roc = Auc::roc();
+    roc.update(
+        &tensor!([[0.9, 0.1], [0.2, 0.8], [0.6, 0.4]]),
+        &tensor!([0i64, 1, 0]),
+    );
+    assert!((roc.compute() - 1.).abs() < 1e-9);
+}
+
+#[test]
+fn regression_metrics_test() {
+    let mut mae = MeanAbsoluteError::new();
+    let mut rmse = RootMeanSquaredError::new();
+    let mut r2 = R2Score::new();
+    for (output, target) in [
+        (tensor!([2.5, 0.0]), tensor!([3.0, -0.5])),
+        (tensor!([2.0, 8.0]), tensor!([2.0, 7.0])),
+    ] {
+        mae.update(&output, &target);
+        rmse.update(&output, &target);
+        r2.update(&output, &target);
+    }
+    assert!((mae.compute() - 0.5).abs() < 1e-9);
+    assert!((rmse.compute() - 0.375f64.sqrt()).abs() < 1e-9);
+    assert!((r2.compute() - 0.9486081370449679).abs() < 1e-9);
+}
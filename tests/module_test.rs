@@ -1,20 +1,29 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use image::DynamicImage;
 use linked_hash_map::LinkedHashMap;
+use raddar::core::{Cellable, RaddarError, TensorCell};
 use raddar::dataset::{
     image_mappings, DataLoaderConfigBuilder, Dataset, DynImageDataset, LoadFromImageFolder,
     TensorDataset, UnsupervisedTensorDataset,
 };
+use raddar::hub::{register_weights, PretrainedWeights};
 use raddar::nn::embedding::{Embedding, OneHot};
 use raddar::nn::{
-    alexnet, densenet161, resnet50, vgg, BatchNorm1dBuilder, BatchNorm2dBuilder,
-    BatchNorm3dBuilder, LayerNormBuilder, LinearBuilder, MaxPooling1DBuilder, Trainable, VggType,
+    alexnet, alexnet_pretrained, backward_checkpoints, build_from_config, denselayer, densenet161,
+    functional_call, fuse_conv_bn, prune_channels, quantize_dynamic, quantize_static,
+    register_module, registered_modules, resnet50, summary, to_dot, vgg, BatchNorm1dBuilder,
+    BatchNorm2dBuilder, BatchNorm3dBuilder, ChannelImportance, Checkpoint, Conv2dBuilder,
+    DataParallel, DropoutBuilder, InferenceEngine, InitScheme, LayerNormBuilder, LazyConv2dBuilder,
+    LazyLinearBuilder, LeakyReLU, LinearBuilder, MaxPooling1DBuilder, Mod, Module, Profiler, ReLU,
+    StateDict, StateDictExt, Trainable, VggType,
 };
 use raddar::optim::{
-    cosine_annealing_lr, opt_with_sched, rmsprop, Optimizer, RMSPropBuilder, StepLRBuilder,
+    cosine_annealing_lr, opt_with_sched, rmsprop, Optimizer, RMSPropBuilder, ScheduledOptimizer,
+    StepLRBuilder,
 };
 use raddar::{assert_tensor_eq, named_seq, seq, tensor};
+use raddar_derive::{NonParameterModule, Trainable};
 
 use tch::{no_grad, Device, Kind, Reduction, Tensor};
 
@@ -34,7 +43,7 @@ fn sequential_test() {
         .module_mut()
         .push(LinearBuilder::default().input_dim(1).output_dim(1).build());
     assert!(model.parameters().contains_key("1.weight"));
-    let mut optimizer = Optimizer::new(
+    let mut optimizer = ScheduledOptimizer::new(
         // TODO: Replace training parameters with all the parameters of the model
         model.training_parameters(),
         RMSPropBuilder::default().build(),
@@ -53,7 +62,7 @@ fn sequential_test() {
         "linear2" => LinearBuilder::default().input_dim(1).output_dim(1).build(),
     )
     .to(tch::Device::Cuda(0));
-    let mut optimizer = Optimizer::new(
+    let mut optimizer = ScheduledOptimizer::new(
         model.training_parameters(),
         RMSPropBuilder::default().build(),
         Some(StepLRBuilder::default().build()),
@@ -91,9 +100,50 @@ fn pooling_test() {
 fn alexnet_test() {
     let num_classes = 100;
     let inputs = Tensor::rand(&[1, 3, 224, 224], (Kind::Double, Device::Cpu));
-    let net = alexnet(num_classes, 0.5, true);
+    let error = alexnet_pretrained(num_classes, 0.5)
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("alexnet"), "{}", error);
+
+    // Cache weights in the layout of torchvision, so that nothing is downloaded.
+    let dir = std::env::temp_dir().join("raddar_alexnet_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let source = alexnet(1000, 0.5, false);
+    source(&inputs);
+    let linear_weights = [
+        "classifier.1.weight",
+        "classifier.4.weight",
+        "classifier.6.weight",
+    ];
+    let weights: StateDict = source
+        .state_dict()
+        .into_iter()
+        .map(|(name, tensor)| {
+            let tensor = tensor.lock();
+            let tensor = if linear_weights.contains(&name.as_str()) {
+                tensor.tr().contiguous()
+            } else {
+                tensor.copy()
+            };
+            (name, tensor.cell())
+        })
+        .collect();
+    weights
+        .to_safetensors(dir.join("alexnet.safetensors"))
+        .unwrap();
+    register_weights(
+        "alexnet",
+        PretrainedWeights::new("https://example.invalid/alexnet.safetensors").cache_dir(&dir),
+    );
+
+    let net = alexnet_pretrained(num_classes, 0.5).unwrap();
+    assert_tensor_eq!(
+        &*net.parameters()["features.0.weight"].lock(),
+        &*source.parameters()["features.0.weight"].lock()
+    );
     let output = net(&inputs);
     assert!(output.size2().unwrap().1 == num_classes);
+    std::fs::remove_dir_all(&dir).unwrap();
 }
 
 #[test]
@@ -121,6 +171,46 @@ fn batchnorm_test() {
     let _output4 = bn3d(&input4);
 }
 #[test]
+fn train_eval_test() {
+    let model = seq!(
+        DropoutBuilder::default().p(0.5).build(),
+        BatchNorm2dBuilder::default().num_features(2).build(),
+    );
+    let input = Tensor::ones(&[4, 2, 3, 3], (Kind::Double, Device::Cpu)) * 2;
+    model.eval(true);
+    assert!(!model.is_training());
+    assert!(model
+        .children
+        .read()
+        .values()
+        .all(|child| !child.is_training()));
+    // Without dropout and with the initial running statistics, the input is left unchanged.
+    assert_tensor_eq!(&model(&input), &input, 1e-4);
+    let running_mean = model.buffers()["1.running_mean"].clone();
+    assert_tensor_eq!(
+        &*running_mean.lock(),
+        &Tensor::zeros(&[2], (Kind::Double, Device::Cpu))
+    );
+
+    model.train(true);
+    assert!(model
+        .children
+        .read()
+        .values()
+        .all(|child| child.is_training()));
+    let _output = model(&input);
+    assert!(f64::from(running_mean.lock().sum(Kind::Double)) > 0.);
+
+    // Without `affect_children`, only the mode of the model itself changes.
+    model.eval(false);
+    assert!(!model.is_training());
+    assert!(model.children()["1"].is_training());
+    model.set_training(false);
+    assert!(!model.children()["1"].module().is_training());
+    model.set_training(true);
+    assert!(model.children()["1"].is_training());
+}
+#[test]
 fn layernorm() {
     let ln = LayerNormBuilder::default().shape(vec![3, 5, 2]).build();
     let input = Tensor::ones(&[6, 3, 5, 2], (Kind::Double, Device::Cpu));
@@ -152,6 +242,15 @@ fn densenet_test() {
     let net = densenet161(num_classes, 0.5);
     let output = net(&inputs);
     assert!(output.size2().unwrap().1 == num_classes);
+
+    let parameters = net.parameters();
+    assert!(parameters.contains_key("features.conv0.weight"));
+    assert!(parameters.contains_key("features.denseblock1.denselayer1.conv1.weight"));
+    assert_eq!(
+        parameters["classifier.weight"].lock().size(),
+        vec![2208, num_classes]
+    );
+    assert!(net.buffers().contains_key("features.norm5.running_mean"));
 }
 
 #[test]
@@ -268,3 +367,969 @@ fn cifar10_test() {
         // break;
     }
 }
+
+#[test]
+fn data_parallel_test() {
+    let model = Mod::new(DataParallel::new(
+        || LinearBuilder::default().input_dim(3).output_dim(2).build(),
+        vec![Device::Cpu, Device::Cpu],
+    ));
+    assert!(model.parameters().contains_key("module.weight"));
+    let inputs = Tensor::rand(&[5, 3], (Kind::Double, Device::Cpu));
+    let output = model(&inputs);
+    assert_eq!(output.size(), vec![5, 2]);
+
+    let inner = model.module().module.clone();
+    let expected = inner(&inputs);
+    assert_tensor_eq!(output, expected);
+
+    let weight = inner.parameters()["weight"].clone();
+    output.sum(Kind::Double).backward();
+    let grad = weight.lock().grad().copy();
+    model.zero_grad();
+    expected.sum(Kind::Double).backward();
+    assert_tensor_eq!(grad, weight.lock().grad());
+}
+
+#[test]
+fn checkpoint_test() {
+    let first = LinearBuilder::default().input_dim(3).output_dim(4).build();
+    let second = LinearBuilder::default().input_dim(4).output_dim(2).build();
+    let checkpointed = seq!(
+        Mod::new(Checkpoint::new(first.clone())),
+        Mod::new(Checkpoint::new(second.clone())),
+    );
+    let plain = seq!(first.clone(), second.clone());
+    let weight = first.parameters()["weight"].clone();
+
+    let inputs = Tensor::rand(&[5, 3], (Kind::Double, Device::Cpu)).set_requires_grad(true);
+    let output = checkpointed(&inputs);
+    backward_checkpoints(&output.tanh().sum(Kind::Double));
+    let weight_grad = weight.lock().grad().copy();
+    let input_grad = inputs.grad().copy();
+
+    checkpointed.zero_grad();
+    let mut inputs_grad = inputs.grad();
+    inputs_grad.zero_();
+    let expected = plain(&inputs);
+    assert_tensor_eq!(output, expected);
+    expected.tanh().sum(Kind::Double).backward();
+    assert_tensor_eq!(weight_grad, weight.lock().grad());
+    assert_tensor_eq!(input_grad, inputs.grad());
+}
+
+#[test]
+fn checkpoint_skip_connection_test() {
+    let stem = LinearBuilder::default().input_dim(3).output_dim(4).build();
+    let block = LinearBuilder::default().input_dim(4).output_dim(4).build();
+    let checkpointed = Mod::new(Checkpoint::new(block.clone()));
+    let weight = stem.parameters()["weight"].clone();
+
+    // The input of the checkpoint also reaches the loss through the skip connection, so the graph of the stem is reached twice.
+    let inputs = Tensor::rand(&[5, 3], (Kind::Double, Device::Cpu));
+    let hidden = stem(&inputs);
+    let output = &hidden + checkpointed(&hidden);
+    backward_checkpoints(&output.tanh().sum(Kind::Double));
+    let weight_grad = weight.lock().grad().copy();
+    let block_grad = block.parameters()["weight"].lock().grad().copy();
+
+    stem.zero_grad();
+    block.zero_grad();
+    let hidden = stem(&inputs);
+    let expected = &hidden + block(&hidden);
+    assert_tensor_eq!(output, expected);
+    expected.tanh().sum(Kind::Double).backward();
+    assert_tensor_eq!(weight_grad, weight.lock().grad());
+    assert_tensor_eq!(block_grad, block.parameters()["weight"].lock().grad());
+}
+
+#[test]
+fn profiler_test() {
+    let first = LinearBuilder::default().input_dim(3).output_dim(4).build();
+    let second = LinearBuilder::default().input_dim(4).output_dim(2).build();
+    let profiler = Profiler::with_backward();
+    let model = seq!(
+        profiler.wrap("first", first.clone()),
+        profiler.wrap("second", second.clone()),
+    );
+    let inputs = Tensor::rand(&[5, 3], (Kind::Double, Device::Cpu));
+    let output = model(&inputs);
+    backward_checkpoints(&output.sum(Kind::Double));
+    let weight = first.parameters()["weight"].clone();
+    let grad = weight.lock().grad().copy();
+
+    model.zero_grad();
+    second(&first(&inputs)).sum(Kind::Double).backward();
+    assert_tensor_eq!(grad, weight.lock().grad());
+
+    let records = profiler.records();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].0, "first");
+    assert_eq!(records[0].1.calls, 1);
+    assert_eq!(records[1].1.output_bytes, 5 * 2 * 8);
+    assert!(profiler.to_string().contains("second"));
+
+    // The input of a profiled block also reaches the loss through its skip connection.
+    let block = LinearBuilder::default().input_dim(4).output_dim(4).build();
+    let profiled = profiler.wrap("block", block.clone());
+    first.zero_grad();
+    let hidden = first(&inputs);
+    backward_checkpoints(&(&hidden + profiled(&hidden)).sum(Kind::Double));
+    let grad = weight.lock().grad().copy();
+    first.zero_grad();
+    block.zero_grad();
+    let hidden = first(&inputs);
+    (&hidden + block(&hidden)).sum(Kind::Double).backward();
+    assert_tensor_eq!(grad, weight.lock().grad());
+}
+
+#[test]
+fn forward_hook_test() {
+    let inner = LinearBuilder::default().input_dim(2).output_dim(1).build();
+    let model = seq!(
+        LinearBuilder::default().input_dim(1).output_dim(2).build(),
+        seq!(inner.clone()),
+    );
+    assert_eq!(inner.path(), "1.0");
+
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let captured = calls.clone();
+    let handle = inner.register_forward_hook(move |path, input, output| {
+        captured
+            .lock()
+            .unwrap()
+            .push((path.to_owned(), input.size(), output.size()));
+    });
+    let pre_calls = Arc::new(Mutex::new(0));
+    let counter = pre_calls.clone();
+    model.register_forward_pre_hook(move |path, _| {
+        assert_eq!(path, "");
+        *counter.lock().unwrap() += 1;
+    });
+
+    let _output = model(&tensor!([[1.0], [2.0], [3.0]]));
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec![("1.0".to_owned(), vec![3, 2], vec![3, 1])]
+    );
+    handle.remove();
+    let _output = model(&tensor!([[1.0]]));
+    assert_eq!(calls.lock().unwrap().len(), 1);
+    assert_eq!(*pre_calls.lock().unwrap(), 2);
+}
+
+#[test]
+fn backward_hook_test() {
+    let model = seq!(
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+    );
+    let names = Arc::new(Mutex::new(Vec::new()));
+    let captured = names.clone();
+    model.register_backward_hook(move |name, _| captured.lock().unwrap().push(name.to_owned()));
+    model.register_parameter_hook("1.weight", |grad| {
+        let _ = grad.zero_();
+    });
+
+    let loss = model(&tensor!([[1.0], [2.0]])).sum(Kind::Double);
+    loss.backward();
+    model.run_backward_hooks();
+    assert_eq!(
+        *names.lock().unwrap(),
+        vec!["0.weight", "0.bias", "1.weight", "1.bias"]
+    );
+    let parameters = model.parameters();
+    assert_eq!(
+        f64::from(parameters["1.weight"].lock().grad().abs().sum(Kind::Double)),
+        0.
+    );
+    assert_ne!(f64::from(parameters["1.bias"].lock().grad()), 0.);
+}
+
+#[test]
+fn named_parameters_test() {
+    let model = seq!(
+        LinearBuilder::default().input_dim(1).output_dim(2).build(),
+        seq!(LinearBuilder::default()
+            .input_dim(2)
+            .output_dim(1)
+            .bias(false)
+            .build()),
+    );
+    let names: Vec<String> = model.named_parameters().map(|(name, _)| name).collect();
+    assert_eq!(names, vec!["0.weight", "0.bias", "1.0.weight"]);
+    assert_eq!(
+        names,
+        model.parameters().keys().cloned().collect::<Vec<_>>()
+    );
+    let biases = model
+        .named_parameters()
+        .filter(|(name, _)| name.ends_with("bias"))
+        .count();
+    assert_eq!(biases, 1);
+}
+
+#[test]
+fn apply_test() {
+    let model = seq!(
+        LinearBuilder::default().input_dim(1).output_dim(2).build(),
+        seq!(BatchNorm1dBuilder::default().num_features(2).build()),
+    );
+    let mut visited = Vec::new();
+    model.apply(|module| {
+        visited.push(module.parameters().len());
+        if let Some(weight) = module.parameters().get("weight") {
+            no_grad(|| {
+                let _ = weight.lock().fill_(2.);
+            });
+        }
+        module.train(false);
+    });
+    // The sequentials have no parameters of their own.
+    assert_eq!(visited, vec![0, 2, 0, 2]);
+    let parameters = model.parameters();
+    assert_eq!(
+        f64::from(parameters["0.weight"].lock().sum(Kind::Double)),
+        4.
+    );
+    assert_eq!(
+        f64::from(parameters["1.0.weight"].lock().sum(Kind::Double)),
+        4.
+    );
+    assert!(!model.children()["1"].children()["0"].module().is_training());
+}
+
+#[test]
+fn summary_test() {
+    let model = seq!(
+        LinearBuilder::default().input_dim(4).output_dim(3).build(),
+        seq!(
+            BatchNorm1dBuilder::default().num_features(3).build(),
+            LinearBuilder::default().input_dim(3).output_dim(2).build(),
+        ),
+    );
+    model.children()["0"].freeze();
+    let summary = summary(&model, &[4]);
+    let layers: Vec<_> = summary
+        .layers
+        .iter()
+        .map(|layer| {
+            (
+                layer.name.as_str(),
+                layer.kind.as_str(),
+                layer.output_shape.clone(),
+                layer.parameters,
+            )
+        })
+        .collect();
+    assert_eq!(
+        layers,
+        vec![
+            ("0", "Linear", vec![-1, 3], 15),
+            ("1.0", "BatchNorm1d", vec![-1, 3], 6),
+            ("1.1", "Linear", vec![-1, 2], 8),
+        ]
+    );
+    assert_eq!(summary.total_parameters, 29);
+    assert_eq!(summary.trainable_parameters, 14);
+    assert!(model.is_training());
+}
+
+#[test]
+fn to_kind_test() {
+    let model = seq!(
+        LinearBuilder::default().input_dim(3).output_dim(2).build(),
+        BatchNorm1dBuilder::default().num_features(2).build(),
+    );
+    model.children()["0"].freeze();
+    model.to_kind(Kind::Float);
+    for (_, tensor) in model.state_dict() {
+        assert_eq!(tensor.lock().kind(), Kind::Float);
+    }
+    let requires_grad: Vec<_> = model
+        .parameters()
+        .values()
+        .map(|parameter| parameter.lock().requires_grad())
+        .collect();
+    assert_eq!(requires_grad, vec![false, false, true, true]);
+
+    let output = model(&Tensor::ones(&[4, 3], (Kind::Float, Device::Cpu)));
+    assert_eq!(output.kind(), Kind::Float);
+    assert_eq!(output.size(), vec![4, 2]);
+}
+
+#[test]
+fn try_forward_test() {
+    let model = seq!(
+        Conv2dBuilder::default()
+            .in_channel(3)
+            .out_channel(4)
+            .kernel_size([3, 3])
+            .build(),
+        LinearBuilder::default().input_dim(6).output_dim(2).build(),
+    );
+    let error = model
+        .try_forward(&Tensor::ones(&[2, 1, 8, 8], (Kind::Double, Device::Cpu)))
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Conv2d expected an input of shape [N, 3, H, W], got [2, 1, 8, 8]"
+    );
+
+    let error = model
+        .try_forward(&Tensor::ones(&[2, 3, 7, 7], (Kind::Double, Device::Cpu)))
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Linear expected an input of shape [*, 6], got [2, 4, 5, 5]"
+    );
+
+    let shapes = Arc::new(Mutex::new(Vec::new()));
+    let captured = shapes.clone();
+    model.register_forward_hook(move |_, _, output| captured.lock().unwrap().push(output.size()));
+    let output = model
+        .try_forward(&Tensor::ones(&[2, 3, 8, 8], (Kind::Double, Device::Cpu)))
+        .unwrap();
+    assert_eq!(output.size(), vec![2, 4, 6, 2]);
+    assert_eq!(*shapes.lock().unwrap(), vec![vec![2, 4, 6, 2]]);
+}
+
+#[test]
+fn lazy_layers_test() {
+    let linear = LazyLinearBuilder::default().output_dim(2).build();
+    assert_eq!(linear.module().input_dim(), None);
+    let output = linear(&Tensor::ones(&[4, 3], (Kind::Double, Device::Cpu)));
+    assert_eq!(output.size(), vec![4, 2]);
+    assert_eq!(linear.module().input_dim(), Some(3));
+    let weight = linear.parameters()["weight"].clone();
+    assert_eq!(weight.lock().size(), vec![3, 2]);
+    assert!(weight.lock().requires_grad());
+    assert!(linear
+        .try_forward(&Tensor::ones(&[4, 5], (Kind::Double, Device::Cpu)))
+        .is_err());
+
+    let path = std::env::temp_dir().join("raddar_lazy_layers_test.safetensors");
+    linear.save_safetensors(&path).unwrap();
+    let loaded = LazyLinearBuilder::default().output_dim(2).build();
+    loaded.load_safetensors(&path).unwrap();
+    assert_eq!(loaded.module().input_dim(), Some(3));
+    std::fs::remove_file(&path).unwrap();
+
+    let conv = LazyConv2dBuilder::default()
+        .out_channel(4)
+        .kernel_size([3, 3])
+        .build();
+    let output = conv(&Tensor::ones(&[1, 5, 8, 8], (Kind::Double, Device::Cpu)));
+    assert_eq!(output.size(), vec![1, 4, 6, 6]);
+    assert_eq!(conv.module().in_channel(), Some(5));
+}
+
+#[test]
+fn build_from_config_test() {
+    let model = build_from_config(&serde_json::json!({
+        "type": "Sequential",
+        "layers": [
+            { "type": "Linear", "input_dim": 3, "output_dim": 4 },
+            { "type": "ReLU" },
+            { "type": "Dropout", "p": 0.2 },
+            { "type": "Linear", "input_dim": 4, "output_dim": 2, "bias": false },
+        ],
+    }))
+    .unwrap();
+    assert_eq!(model.parameters().len(), 3);
+    let output = model(&Tensor::ones(&[5, 3], (Kind::Double, Device::Cpu)));
+    assert_eq!(output.size(), vec![5, 2]);
+
+    let error = build_from_config(&serde_json::json!({
+        "type": "Sequential",
+        "layers": [{ "type": "Unknown" }],
+    }))
+    .unwrap_err();
+    assert!(format!("{:#}", error).contains("No module is registered as Unknown"));
+
+    register_module("Scale", |config| {
+        let scale = config["scale"].as_f64().unwrap();
+        Ok(Mod::new(LeakyReLU::new(-scale)) as Mod<dyn Module>)
+    });
+    assert!(registered_modules().contains(&"Scale".to_owned()));
+    let model = build_from_config(&serde_json::json!({ "type": "Scale", "scale": 2.0 })).unwrap();
+    assert_tensor_eq!(&model(&tensor!([-1.0, -2.0])), &tensor!([-2.0, -4.0]));
+}
+
+#[test]
+fn clone_module_test() {
+    let model = LinearBuilder::default().input_dim(3).output_dim(2).build();
+    model.eval(true);
+    let clone = model.clone_module();
+    assert!(!clone.is_training());
+    let input = Tensor::ones(&[4, 3], (Kind::Double, Device::Cpu));
+    assert_tensor_eq!(&model(&input), &clone(&input));
+
+    let weight = model.parameters()["weight"].clone();
+    no_grad(|| {
+        let _ = weight.lock().fill_(1.0);
+    });
+    let clone_weight = clone.parameters()["weight"].lock().shallow_clone();
+    assert!(clone_weight.requires_grad());
+    assert_ne!(f64::from(clone_weight.sum(Kind::Double)), 6.0);
+}
+
+#[test]
+fn to_dot_test() {
+    let model = seq!(
+        LinearBuilder::default().input_dim(3).output_dim(4).build(),
+        Mod::new(LeakyReLU::new(0.1)),
+        LinearBuilder::default()
+            .input_dim(4)
+            .output_dim(2)
+            .bias(false)
+            .build(),
+    );
+    let dot = to_dot(&model);
+    assert!(dot.starts_with("digraph model {"));
+    assert!(dot.contains("n0 [label=\"Sequential\"];"));
+    assert!(dot.contains("n1 [label=\"0 (Linear)\\nweight: [3, 4]\\nbias: [4]\"];"));
+    assert!(dot.contains("n2 [label=\"1 (LeakyReLU)\"];"));
+    assert!(dot.contains("n3 [label=\"2 (Linear)\\nweight: [4, 2]\"];"));
+    assert!(dot.contains("n0 -> n3;"));
+}
+
+#[test]
+fn functional_call_test() {
+    let model = LinearBuilder::default().input_dim(2).output_dim(1).build();
+    let weight = model.parameters()["weight"].lock().copy();
+
+    let adapted_weight = Tensor::ones(&[2, 1], (Kind::Double, Device::Cpu)).set_requires_grad(true);
+    let mut parameters = StateDict::new();
+    parameters.insert("weight".to_owned(), adapted_weight.shallow_clone().cell());
+    parameters.insert("bias".to_owned(), tensor!([0.0]).cell());
+    let output = functional_call(&model, &parameters, &tensor!([[1.0, 2.0]]));
+    assert_tensor_eq!(&output, &tensor!([[3.0]]));
+
+    output.sum(Kind::Double).backward();
+    assert_tensor_eq!(&adapted_weight.grad(), &tensor!([[1.0], [2.0]]));
+    let model_weight = model.parameters()["weight"].lock().shallow_clone();
+    assert_tensor_eq!(&model_weight, &weight);
+    assert!(!model_weight.grad().defined());
+}
+
+#[test]
+fn init_scheme_test() {
+    let model = seq!(
+        Conv2dBuilder::default()
+            .in_channel(3)
+            .out_channel(8)
+            .kernel_size([3, 3])
+            .build(),
+        BatchNorm2dBuilder::default().num_features(8).build(),
+        LinearBuilder::default()
+            .input_dim(100)
+            .output_dim(10)
+            .build(),
+    );
+    let parameters = model.parameters();
+    let max = |name: &str| f64::from(parameters[name].lock().abs().max());
+    // Kaiming for the convolution weight, and fan-in bounds for the biases.
+    assert!(max("0.weight") <= (6. / 27f64).sqrt());
+    assert!(max("0.bias") <= 1. / 27f64.sqrt());
+    // Xavier for the linear weight.
+    assert!(max("2.weight") <= (6. / 110f64).sqrt());
+    assert!(max("2.bias") <= 0.1);
+
+    model.apply(|module| {
+        InitScheme::Constant(0.5).init_parameter(module, "weight");
+        InitScheme::Constant(0.).init_parameter(module, "bias");
+    });
+    assert_eq!(max("0.weight"), 0.5);
+    assert_eq!(max("1.weight"), 0.5);
+    assert_eq!(max("2.bias"), 0.);
+
+    model.reset_parameters();
+    assert_eq!(max("1.weight"), 1.);
+    assert!(max("2.weight") < 0.5);
+}
+
+#[test]
+fn freeze_matching_test() {
+    let model = seq!(
+        LinearBuilder::default().input_dim(3).output_dim(4).build(),
+        Mod::new(LeakyReLU::new(0.1)),
+        LinearBuilder::default().input_dim(4).output_dim(2).build(),
+    );
+    assert_eq!(model.freeze_matching(r"^0\."), 2);
+    assert_eq!(model.training_parameters().len(), 2);
+    let parameters = model.parameters();
+    assert!(!parameters["0.weight"].lock().requires_grad());
+    assert!(parameters["2.weight"].lock().requires_grad());
+
+    assert_eq!(model.unfreeze_matching("weight$"), 2);
+    assert!(parameters["0.weight"].lock().requires_grad());
+    assert!(!parameters["0.bias"].lock().requires_grad());
+}
+
+#[test]
+fn sequential_editing_test() {
+    let model = seq!(
+        LinearBuilder::default().input_dim(3).output_dim(4).build(),
+        Mod::new(LeakyReLU::new(0.1)),
+        LinearBuilder::default().input_dim(4).output_dim(2).build(),
+    );
+    let input = Tensor::ones(&[1, 3], (Kind::Double, Device::Cpu));
+    let features = model.module().forward_until(&input, 1);
+    assert_eq!(features.size(), vec![1, 4]);
+    assert_tensor_eq!(&model.module().slice(..2).forward(&input), &features);
+
+    model
+        .module_mut()
+        .insert(1, Mod::new(DropoutBuilder::default().build()));
+    assert_eq!(model.module().len(), 4);
+    assert!(model.parameters().contains_key("3.weight"));
+    model.module_mut().remove(1);
+    model.module_mut().truncate_after(1);
+    assert_eq!(model.module().len(), 2);
+    assert!(!model.parameters().contains_key("2.weight"));
+
+    model.module_mut().extend(vec![
+        LinearBuilder::default().input_dim(4).output_dim(1).build() as Mod<dyn Module>,
+    ]);
+    assert_eq!(model(&input).size(), vec![1, 1]);
+}
+
+#[test]
+fn named_sequential_test() {
+    let model = named_seq!(
+        "linear1" => LinearBuilder::default().input_dim(3).output_dim(4).build(),
+        "relu" => Mod::new(LeakyReLU::new(0.1)),
+        "classifier" => LinearBuilder::default().input_dim(4).output_dim(2).build(),
+    );
+    assert!(model.module().get("relu").is_some());
+    assert!(model.module().get("fc").is_none());
+
+    let backbone = model.module().up_to("relu").unwrap();
+    let input = Tensor::ones(&[1, 3], (Kind::Double, Device::Cpu));
+    assert_eq!(backbone.forward(&input).size(), vec![1, 4]);
+
+    let old = model.module_mut().replace(
+        "classifier",
+        LinearBuilder::default().input_dim(4).output_dim(5).build(),
+    );
+    assert!(old.is_some());
+    assert_eq!(model(&input).size(), vec![1, 5]);
+    assert_eq!(
+        model.parameters()["classifier.weight"].lock().size(),
+        vec![4, 5]
+    );
+}
+
+#[derive(Debug, Trainable)]
+struct Head {
+    scale: TensorCell,
+    #[trainable(buffer)]
+    count: TensorCell,
+    shift: Option<TensorCell>,
+    fc: Mod<raddar::nn::Linear>,
+    extra: Option<Mod<raddar::nn::Linear>>,
+    #[trainable(prefixed)]
+    blocks: raddar::nn::ModuleDict,
+    #[trainable(skip)]
+    _cache: Option<Mod<raddar::nn::Linear>>,
+}
+
+#[test]
+fn derive_trainable_test() {
+    let mut blocks = raddar::nn::ModuleDict::new();
+    blocks.insert(
+        "block1".to_owned(),
+        LinearBuilder::default().input_dim(2).output_dim(2).build(),
+    );
+    let head = Mod::new(Head {
+        scale: tensor!([1.0]).cell(),
+        count: tensor!([0.0]).cell(),
+        shift: None,
+        fc: LinearBuilder::default().input_dim(2).output_dim(2).build(),
+        extra: None,
+        blocks,
+        _cache: Some(LinearBuilder::default().input_dim(2).output_dim(2).build()),
+    });
+    assert_eq!(
+        head.parameters().keys().cloned().collect::<Vec<_>>(),
+        vec![
+            "scale",
+            "fc.weight",
+            "fc.bias",
+            "blocks.block1.weight",
+            "blocks.block1.bias"
+        ]
+    );
+    assert!(head.buffers().contains_key("count"));
+}
+
+#[test]
+fn builder_validation_test() {
+    let config = Conv2dBuilder::default()
+        .in_channel(4)
+        .out_channel(6)
+        .kernel_size([3, 3])
+        .groups(2)
+        .config();
+    assert!(config.validate().is_ok());
+    let conv = config.build();
+    assert_eq!(conv.parameters()["weight"].lock().size(), vec![6, 2, 3, 3]);
+
+    let error = std::panic::catch_unwind(|| {
+        Conv2dBuilder::default()
+            .in_channel(4)
+            .out_channel(6)
+            .kernel_size([3, 3])
+            .groups(3)
+            .build()
+    })
+    .unwrap_err();
+    let message = error.downcast_ref::<String>().unwrap();
+    assert!(message.contains("groups = 3 must divide in_channel = 4"));
+    assert!(std::panic::catch_unwind(|| DropoutBuilder::default().p(1.5).build()).is_err());
+
+    let error = Conv2dBuilder::default()
+        .in_channel(4)
+        .out_channel(6)
+        .kernel_size([3, 3])
+        .groups(3)
+        .try_build()
+        .map(|_| ())
+        .unwrap_err();
+    assert!(matches!(error, RaddarError::InvalidConfig { .. }));
+    assert!(error.to_string().starts_with("Invalid Conv2dConfig"));
+    let error = Conv2dBuilder::default()
+        .in_channel(4)
+        .try_config()
+        .unwrap_err();
+    assert!(error.to_string().contains("out_channel"));
+    assert!(DropoutBuilder::default().p(0.5).try_build().is_ok());
+}
+
+#[test]
+fn num_parameters_test() {
+    let model = seq!(
+        LinearBuilder::default().input_dim(3).output_dim(4).build(),
+        LinearBuilder::default().input_dim(4).output_dim(2).build(),
+    );
+    assert_eq!(model.parameter_size(), 4);
+    assert_eq!(model.num_parameters(), 3 * 4 + 4 + 4 * 2 + 2);
+    assert_eq!(model.parameter_bytes(), model.num_parameters() * 8);
+    model.children()["0"].freeze();
+    assert_eq!(model.num_trainable_parameters(), 4 * 2 + 2);
+}
+
+#[test]
+fn grad_norms_test() {
+    let model = seq!(
+        LinearBuilder::default().input_dim(2).output_dim(1).build(),
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+    );
+    assert!(model.grad_norms().is_empty());
+    model.children()["1"].freeze();
+    model(&tensor!([[3.0, 4.0]])).sum(Kind::Double).backward();
+    let norms = model.grad_norms();
+    assert_eq!(
+        norms.keys().cloned().collect::<Vec<_>>(),
+        vec!["0.weight", "0.bias"]
+    );
+    let weight = f64::from(&model.parameters()["1.weight"].lock().abs());
+    assert!((norms["0.weight"] - 5. * weight).abs() < 1e-9);
+    assert!((norms["0.bias"] - weight).abs() < 1e-9);
+    assert!((model.grad_norm() - 26f64.sqrt() * weight).abs() < 1e-9);
+}
+
+#[test]
+fn infer_test() {
+    let model = seq!(
+        LinearBuilder::default().input_dim(2).output_dim(2).build(),
+        DropoutBuilder::default().p(0.5).build(),
+    );
+    let input = tensor!([[1.0, 2.0]]);
+    let output = model.infer(&input);
+    assert!(!output.requires_grad());
+    assert_tensor_eq!(&output, &model.infer(&input));
+    assert!(model.is_training());
+
+    let output = raddar::inference_mode(|| model(&input));
+    assert!(!output.requires_grad());
+
+    let model = seq!(
+        LinearBuilder::default().input_dim(2).output_dim(2).build(),
+        BatchNorm1dBuilder::default().num_features(2).build(),
+    );
+    model.children()["1"].eval(true);
+    model.infer(&input);
+    assert!(model.is_training());
+    assert!(model.children()["0"].is_training());
+    assert!(!model.children()["1"].is_training());
+    assert!(!model.children()["1"].module().is_training());
+    summary(&model, &[2]);
+    assert!(model.is_training());
+    assert!(!model.children()["1"].is_training());
+}
+
+#[test]
+fn inference_engine_test() {
+    let model = LinearBuilder::default().input_dim(2).output_dim(1).build();
+    let parameters = model.parameters();
+    let engine = Arc::new(InferenceEngine::new(
+        move || {
+            let model = LinearBuilder::default().input_dim(2).output_dim(1).build();
+            model.load(parameters);
+            model
+        },
+        4,
+        std::time::Duration::from_millis(50),
+    ));
+    let handles: Vec<_> = (0..6)
+        .map(|i| {
+            let engine = engine.clone();
+            std::thread::spawn(move || {
+                let input = Tensor::of_slice(&[i as f64, 1.]).reshape(&[1, 2]);
+                (input.shallow_clone(), engine.infer(&input).unwrap())
+            })
+        })
+        .collect();
+    for handle in handles {
+        let (input, output) = handle.join().unwrap();
+        assert_tensor_eq!(&output, &model.infer(&input));
+    }
+    assert!(engine.submit(&Tensor::from(1.)).is_err());
+}
+
+#[test]
+fn fuse_conv_bn_test() {
+    let conv = || {
+        Conv2dBuilder::default()
+            .in_channel(2)
+            .out_channel(3)
+            .kernel_size([3, 3])
+            .bias(false)
+            .build()
+    };
+    let bn = || BatchNorm2dBuilder::default().num_features(3).build();
+    let mut model = seq!(
+        conv(),
+        bn(),
+        Mod::new(ReLU),
+        conv_with_bias(3),
+        seq!(bn()),
+        named_seq!("conv" => conv_with_bias(3), "bn" => bn()),
+    );
+
+    // Train the batch normalizations for a few steps, so that their statistics are not the defaults.
+    for _ in 0..3 {
+        model(&Tensor::randn(&[4, 2, 8, 8], (Kind::Double, Device::Cpu)));
+    }
+    model.eval(true);
+    let input = Tensor::randn(&[2, 2, 8, 8], (Kind::Double, Device::Cpu));
+    let expected = model(&input);
+
+    assert_eq!(fuse_conv_bn(&mut model), 3);
+    assert_eq!(model.module().len(), 4);
+    assert_eq!(model.children().len(), 4);
+    assert!(model.parameters().contains_key("0.bias"));
+    assert!(!model
+        .buffers()
+        .keys()
+        .any(|name| name.ends_with("running_mean")));
+    assert_tensor_eq!(&model(&input), &expected);
+}
+
+#[test]
+fn quantize_static_test() {
+    let mut model = seq!(
+        LinearBuilder::default().input_dim(4).output_dim(8).build(),
+        Mod::new(ReLU),
+        LinearBuilder::default().input_dim(8).output_dim(2).build(),
+    );
+    let input = Tensor::randn(&[16, 4], (Kind::Double, Device::Cpu));
+    let expected = model(&input);
+
+    let batches = (0..8).map(|_| Tensor::randn(&[16, 4], (Kind::Double, Device::Cpu)));
+    assert_eq!(quantize_static(&mut model, batches), 2);
+    assert_eq!(model.buffers()["0.weight"].lock().kind(), Kind::QInt8);
+    assert!(model.parameters().is_empty());
+    assert!(model(&input).allclose(&expected, 0.1, 0.1, false));
+
+    // The weights are dequantized to the kind of the inputs.
+    model.to_kind(Kind::Float);
+    let output = model(&input.to_kind(Kind::Float));
+    assert_eq!(output.kind(), Kind::Float);
+    assert!(output.allclose(&expected.to_kind(Kind::Float), 0.1, 0.1, false));
+}
+
+#[test]
+fn quantize_dynamic_test() {
+    let model = seq!(
+        LinearBuilder::default().input_dim(4).output_dim(8).build(),
+        Mod::new(ReLU),
+        LinearBuilder::default().input_dim(8).output_dim(2).build(),
+    );
+    let mut model = Mod::new(Encoder {
+        embed: LinearBuilder::default().input_dim(3).output_dim(4).build(),
+        layers: model,
+    });
+    let input = Tensor::randn(&[16, 3], (Kind::Double, Device::Cpu));
+    let expected = model(&input);
+
+    assert_eq!(quantize_dynamic(&mut model), 3);
+    assert_eq!(quantize_dynamic(&mut model), 0);
+    assert!(model.parameters().is_empty());
+    let buffers = model.buffers();
+    assert_eq!(buffers.len(), 6);
+    assert_eq!(buffers["embed.weight"].lock().kind(), Kind::QInt8);
+    assert_eq!(buffers["layers.2.weight"].lock().kind(), Kind::QInt8);
+    assert!(model(&input).allclose(&expected, 0.1, 0.1, false));
+
+    // A model converted to another kind before quantization computes in that kind.
+    let mut model = seq!(LinearBuilder::default().input_dim(3).output_dim(2).build());
+    let input = input.to_kind(Kind::Float);
+    model.to_kind(Kind::Float);
+    let expected = model(&input);
+    assert_eq!(quantize_dynamic(&mut model), 1);
+    let output = model(&input);
+    assert_eq!(output.kind(), Kind::Float);
+    assert!(output.allclose(&expected, 0.1, 0.1, false));
+}
+
+#[derive(Debug, Trainable)]
+struct Encoder {
+    embed: Mod<raddar::nn::Linear>,
+    layers: Mod<raddar::nn::Sequential>,
+}
+
+impl Module for Encoder {
+    fn forward(&self, input: &Tensor) -> Tensor {
+        (self.layers)(&(self.embed)(input))
+    }
+}
+
+#[test]
+fn prune_channels_test() {
+    let conv = |in_channel, out_channel| {
+        Conv2dBuilder::default()
+            .in_channel(in_channel)
+            .out_channel(out_channel)
+            .kernel_size([3, 3])
+            .padding([1, 1])
+            .build()
+    };
+    let mut model = seq!(
+        conv(3, 8),
+        BatchNorm2dBuilder::default().num_features(8).build(),
+        Mod::new(ReLU),
+        conv(8, 4),
+        seq!(BatchNorm2dBuilder::default().num_features(4).build()),
+    );
+    model.eval(true);
+    let input = Tensor::randn(&[2, 3, 8, 8], (Kind::Double, Device::Cpu));
+
+    assert_eq!(
+        prune_channels(&mut model, 0.3, ChannelImportance::L1Norm),
+        2
+    );
+    let parameters = model.parameters();
+    assert_eq!(parameters["0.weight"].lock().size(), vec![6, 3, 3, 3]);
+    assert_eq!(parameters["0.bias"].lock().size(), vec![6]);
+    assert_eq!(parameters["1.weight"].lock().size(), vec![6]);
+    assert_eq!(model.buffers()["1.running_var"].lock().size(), vec![6]);
+    // The output channels of the sequential are kept.
+    assert_eq!(parameters["3.weight"].lock().size(), vec![4, 6, 3, 3]);
+    assert_eq!(model(&input).size(), vec![2, 4, 8, 8]);
+
+    // A module without parameters which reorders the channels stops the pruning.
+    let mut model = seq!(conv(3, 8), Mod::new(FlipChannels), conv(8, 4));
+    assert_eq!(
+        prune_channels(&mut model, 0.5, ChannelImportance::L1Norm),
+        0
+    );
+
+    // The residual stream of each stage is pruned as a group, except the output of the last stage.
+    let mut model = resnet50(10);
+    let pruned = prune_channels(&mut model, 0.5, ChannelImportance::BatchNormGamma);
+    let inner = 64 * 3 + 128 * 4 + 256 * 6 + 512 * 3;
+    assert_eq!(pruned, inner + 32 + 128 + 256 + 512);
+    let parameters = model.parameters();
+    assert_eq!(parameters["net.0.weight"].lock().size(), vec![32, 3, 7, 7]);
+    assert_eq!(
+        parameters["net.4.0.downsample.0.weight"].lock().size(),
+        vec![128, 32, 1, 1]
+    );
+    assert_eq!(
+        parameters["net.4.2.block.0.weight"].lock().size(),
+        vec![32, 128, 1, 1]
+    );
+    assert_eq!(
+        parameters["net.4.2.block.6.weight"].lock().size(),
+        vec![128, 32, 1, 1]
+    );
+    assert_eq!(
+        parameters["net.4.2.block.7.0.weight"].lock().size(),
+        vec![128]
+    );
+    assert_eq!(
+        parameters["net.7.2.block.6.weight"].lock().size(),
+        vec![2048, 256, 1, 1]
+    );
+    model.eval(true);
+    let input = Tensor::randn(&[1, 3, 64, 64], (Kind::Double, Device::Cpu));
+    assert_eq!(model(&input).size(), vec![1, 10]);
+}
+
+#[derive(Debug, NonParameterModule)]
+struct FlipChannels;
+
+impl Module for FlipChannels {
+    fn forward(&self, input: &Tensor) -> Tensor {
+        input.flip(&[1])
+    }
+}
+
+fn conv_with_bias(channels: i64) -> Mod<raddar::nn::Conv2d> {
+    Conv2dBuilder::default()
+        .in_channel(channels)
+        .out_channel(channels)
+        .kernel_size([1, 1])
+        .build()
+}
+
+#[test]
+fn dense_layer_dropout_test() {
+    let layer = denselayer(4, 2, 2, 0.5);
+    assert!(layer.children().contains_key("dropout"));
+    let input = Tensor::randn(&[2, 4, 5, 5], (Kind::Double, Device::Cpu));
+
+    // The dropout of the layer follows the mode of the model, instead of always dropping.
+    layer.eval(true);
+    assert_tensor_eq!(&layer(&input), &layer(&input));
+    layer.train(true);
+    assert!(layer.children()["dropout"].is_training());
+    assert_eq!(denselayer(4, 2, 2, 0.).children().len(), 6);
+}
+
+#[test]
+fn residual_block_test() {
+    use raddar::nn::{batchnorm2d, BasicBlock, Block, Sequential};
+
+    let block = <BasicBlock as Block<fn(i64) -> Mod<Sequential>>>::new_block(
+        4,
+        4,
+        [1, 1],
+        1,
+        64,
+        [1, 1],
+        None,
+        batchnorm2d,
+    );
+    block.eval(true);
+    let input = Tensor::randn(&[2, 4, 5, 5], (Kind::Double, Device::Cpu));
+    let original = input.copy();
+    let output = block(&input);
+    let expected = ((block.module().block)(&input) + &input).relu();
+    assert_tensor_eq!(&output, &expected);
+    // The identity path does not modify the input.
+    assert_tensor_eq!(&input, &original);
+}
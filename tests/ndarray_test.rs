@@ -0,0 +1,45 @@
+#![cfg(feature = "ndarray")]
+
+use ndarray::{array, Array3, ArrayD, IxDyn};
+use raddar::{
+    assert_tensor_eq,
+    core::{StateDictArrayExt, ToArray, ToTensor},
+    dataset::{Dataset, TensorDataset, UnsupervisedTensorDataset},
+    nn::StateDict,
+    tensor,
+};
+
+#[test]
+fn ndarray_test() {
+    let array = array![[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]];
+    let tensor = array.to_tensor();
+    assert_tensor_eq!(&tensor, tensor!([[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]));
+    // The transposed view is copied in logical order.
+    assert_tensor_eq!(
+        &array.t().to_tensor(),
+        tensor!([[1.0, 4.0], [2.0, 5.0], [3.0, 6.0]])
+    );
+    assert_eq!(tensor.to_array::<f64>(), array.clone().into_dyn());
+    assert_eq!(
+        tensor!([1.5, 2.5]).to_array::<i64>(),
+        ArrayD::from_shape_vec(IxDyn(&[2]), vec![1, 2]).unwrap()
+    );
+
+    let state_dict = StateDict::from_arrays(vec![("weight".to_owned(), array.clone())]);
+    assert_tensor_eq!(&*state_dict["weight"].lock(), &tensor);
+    assert_eq!(
+        state_dict.to_arrays::<f64>()["weight"],
+        array.clone().into_dyn()
+    );
+
+    let dataset = TensorDataset::from((Array3::<f32>::zeros((4, 2, 2)), array![0i64, 1, 0, 1]));
+    assert_eq!(dataset.size(), 4);
+    assert_eq!(dataset.inputs[0].size(), vec![2, 2]);
+    let (inputs, labels) = dataset.to_arrays::<f32>();
+    assert_eq!(inputs.shape(), &[4, 2, 2]);
+    assert_eq!(labels.into_raw_vec(), vec![0.0, 1.0, 0.0, 1.0]);
+
+    let dataset = UnsupervisedTensorDataset::from_array(&array);
+    assert_eq!(dataset.size(), 2);
+    assert_eq!(dataset.to_array::<f64>(), array.into_dyn());
+}
@@ -1,8 +1,11 @@
 use raddar::nn::{LinearBuilder, Trainable};
 use raddar::optim::{
-    AdamBuilder, CosineAnnealingLRBuilder, GradientDescent, Optimizer, StepLRBuilder,
+    cosine_annealing_warm_restarts, cyclic_lr, lr_find, one_cycle_lr, step_lr, warmup,
+    AdafactorBuilder, AdamBuilder, CosineAnnealingLRBuilder, CyclicMode, GradientDescent,
+    LrFinderConfigBuilder, Optimizer, OptimizerAlgorithm, ScheduledOptimizer, SchedulerAlgorithm,
+    StepLRBuilder,
 };
-use raddar::tensor;
+use raddar::{assert_tensor_eq, tensor};
 use tch::Reduction;
 
 #[test]
@@ -11,7 +14,7 @@ fn gradient_descent_test() {
     let labels = tensor!([[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.], [19.0]]);
 
     let model = LinearBuilder::default().input_dim(1).output_dim(1).build();
-    let mut optimizer = Optimizer::new(
+    let mut optimizer = ScheduledOptimizer::new(
         model.training_parameters(),
         GradientDescent::new(0.01),
         Some(StepLRBuilder::default().build()),
@@ -37,7 +40,7 @@ fn rmsprop_test() {
     let labels = tensor!([[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.], [19.0]]);
 
     let model = LinearBuilder::default().input_dim(1).output_dim(1).build();
-    let mut optimizer = Optimizer::new(
+    let mut optimizer = ScheduledOptimizer::new(
         model.training_parameters(),
         AdamBuilder::default().learning_rate(0.01).build(),
         Some(CosineAnnealingLRBuilder::default().build()),
@@ -56,3 +59,127 @@ fn rmsprop_test() {
         f64::from(&*model.module().linear_bias.as_ref().unwrap().lock())
     );
 }
+
+#[test]
+fn warm_restarts_test() {
+    let mut scheduler = cosine_annealing_warm_restarts(10, 2, 0.);
+    scheduler.init(0.1);
+    assert!((scheduler.update(5, 0.1) - 0.05).abs() < 1e-9);
+    assert!((scheduler.update(10, 0.1) - 0.1).abs() < 1e-9);
+    assert!((scheduler.update(20, 0.1) - 0.05).abs() < 1e-9);
+    assert!((scheduler.update(30, 0.1) - 0.1).abs() < 1e-9);
+    assert!((scheduler.get_last_lr() - 0.1).abs() < 1e-9);
+}
+
+#[test]
+fn one_cycle_lr_test() {
+    let mut scheduler = one_cycle_lr(1.0, 10);
+    scheduler.init(0.1);
+    assert!((scheduler.update(1, 0.1) - 0.04).abs() < 1e-9);
+    assert!((scheduler.update_momentum(1, 0.9) - 0.95).abs() < 1e-9);
+    let peak = (1..=10)
+        .map(|step| scheduler.update(step, 0.1))
+        .fold(0., f64::max);
+    assert!((peak - 1.0).abs() < 1e-9);
+    assert!((scheduler.update(10, 0.1) - 0.04 / 1e4).abs() < 1e-9);
+    assert!((scheduler.update_momentum(10, 0.9) - 0.95).abs() < 1e-9);
+}
+
+#[test]
+fn warmup_test() {
+    let mut scheduler = warmup(step_lr(2, 0.5), 4);
+    scheduler.init(0.1);
+    assert!((scheduler.update(2, 0.1) - 0.05).abs() < 1e-9);
+    assert!((scheduler.update(4, 0.05) - 0.1).abs() < 1e-9);
+    assert!((scheduler.update(5, 0.1) - 0.1).abs() < 1e-9);
+    assert!((scheduler.update(6, 0.1) - 0.05).abs() < 1e-9);
+}
+
+#[test]
+fn cyclic_lr_test() {
+    let mut scheduler = cyclic_lr(0.1, 0.5, 2, CyclicMode::Triangular2);
+    scheduler.init(0.1);
+    assert!((scheduler.update(1, 0.1) - 0.1).abs() < 1e-9);
+    assert!((scheduler.update(3, 0.1) - 0.5).abs() < 1e-9);
+    assert!((scheduler.update(5, 0.1) - 0.1).abs() < 1e-9);
+    assert!((scheduler.update(7, 0.1) - 0.3).abs() < 1e-9);
+}
+
+#[test]
+fn lr_find_test() {
+    let inputs = tensor!([[1.0], [3.0], [5.0], [4.0], [8.0], [10.0], [2.0], [6.0]]);
+    let labels = tensor!([[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.], [19.0]]);
+
+    let model = LinearBuilder::default().input_dim(1).output_dim(1).build();
+    let weight = model.module().linear_weight.lock().copy();
+    let result = lr_find(
+        model.training_parameters(),
+        GradientDescent::new(0.),
+        std::iter::repeat(()),
+        |_| model(&inputs).mse_loss(&labels, Reduction::Mean),
+        LrFinderConfigBuilder::default()
+            .start_lr(1e-5)
+            .end_lr(1.)
+            .num_steps(50)
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(result.learning_rates.len(), result.losses.len());
+    assert!(result.suggestion().is_some());
+    assert_tensor_eq!(&*model.module().linear_weight.lock(), &weight);
+}
+
+#[test]
+fn optimizer_state_dict_test() {
+    let inputs = tensor!([[1.0], [3.0], [5.0], [4.0], [8.0], [10.0], [2.0], [6.0]]);
+    let labels = tensor!([[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.], [19.0]]);
+
+    let model = LinearBuilder::default().input_dim(1).output_dim(1).build();
+    let mut optimizer = ScheduledOptimizer::new(
+        model.training_parameters(),
+        AdamBuilder::default().learning_rate(0.01).build(),
+        Some(step_lr(3, 0.5)),
+    );
+    for _ in 0..5 {
+        model.zero_grad();
+        let loss = model(&inputs).mse_loss(&labels, Reduction::Mean);
+        loss.backward();
+        optimizer.step();
+    }
+    let state = optimizer.state_dict();
+    assert_eq!(f64::from(&*state["step"].lock()), 5.);
+
+    let mut resumed = ScheduledOptimizer::new(
+        model.training_parameters(),
+        AdamBuilder::default().learning_rate(0.01).build(),
+        Some(step_lr(3, 0.5)),
+    );
+    resumed.load_state_dict(state);
+    assert_eq!(resumed.step, 5);
+    assert!((resumed.opt.learning_rate() - optimizer.opt.learning_rate()).abs() < 1e-12);
+    let (original, loaded) = (optimizer.state_dict(), resumed.state_dict());
+    assert_tensor_eq!(&*original["opt.m.0"].lock(), &*loaded["opt.m.0"].lock());
+    assert_tensor_eq!(&*original["opt.v.1"].lock(), &*loaded["opt.v.1"].lock());
+}
+
+#[test]
+fn adafactor_test() {
+    let inputs = tensor!([[1.0], [3.0], [5.0], [4.0], [8.0], [10.0], [2.0], [6.0]]);
+    let labels = tensor!([[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.], [19.0]]);
+
+    let model = LinearBuilder::default().input_dim(1).output_dim(1).build();
+    let mut optimizer = ScheduledOptimizer::new(
+        model.training_parameters(),
+        AdafactorBuilder::default().learning_rate(0.1).build(),
+        Some(StepLRBuilder::default().build()),
+    );
+    let initial_loss = f64::from(model(&inputs).mse_loss(&labels, Reduction::Mean));
+    for _ in 1..=500 {
+        model.zero_grad();
+        let loss = model(&inputs).mse_loss(&labels, Reduction::Mean);
+        loss.backward();
+        optimizer.step();
+    }
+    let final_loss = f64::from(model(&inputs).mse_loss(&labels, Reduction::Mean));
+    assert!(final_loss < initial_loss);
+}
@@ -0,0 +1,33 @@
+use raddar::{
+    assert_tensor_eq,
+    dataset::{DataLoaderConfigBuilder, Dataset, TensorDataset},
+    set_seed, tensor_vec,
+};
+use tch::{Device, Kind, Tensor};
+
+fn shuffled_inputs() -> Vec<Tensor> {
+    let inputs = tensor_vec![[1.0], [3.0], [5.0], [4.0], [8.0], [10.0], [2.0], [6.0]];
+    let labels = tensor_vec![[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.], [19.0]];
+    TensorDataset::from_tensors(inputs, labels)
+        .into_loader(
+            DataLoaderConfigBuilder::default()
+                .batch_size(8)
+                .shuffle(true)
+                .build()
+                .unwrap(),
+        )
+        .map(|(inputs, _)| inputs)
+        .collect()
+}
+
+#[test]
+fn set_seed_test() {
+    set_seed(42);
+    let first_inputs = shuffled_inputs();
+    let first_rand = Tensor::rand(&[3], (Kind::Double, Device::Cpu));
+    set_seed(42);
+    let second_inputs = shuffled_inputs();
+    let second_rand = Tensor::rand(&[3], (Kind::Double, Device::Cpu));
+    assert_tensor_eq!(&first_inputs[0], &second_inputs[0]);
+    assert_tensor_eq!(first_rand, second_rand);
+}
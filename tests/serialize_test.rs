@@ -3,9 +3,14 @@ use std::path::Path;
 use raddar::{
     assert_tensor_eq,
     core::Cellable,
-    nn::{LinearBuilder, Trainable},
+    nn::{
+        read_gguf, read_safetensors, BatchNorm1dBuilder, Conv2dBuilder, Conv2dConfig, KeyMap,
+        LayerNorm, Linear, LinearBuilder, Mod, ShardedCheckpoint, StateDict, StateDictExt,
+        Trainable, VggBuilder, VggConfig, VggType,
+    },
     seq, tensor,
 };
+use tch::{no_grad, Device, Kind, Tensor};
 
 #[test]
 fn load_parameter_test() {
@@ -48,3 +53,370 @@ fn load_ot_test() {
     let output = model(&tensor!([2.0f32]));
     assert_tensor_eq!(&output, &tensor!([0.1818f32]));
 }
+
+#[test]
+fn save_npz_test() {
+    let model = seq!(
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+    );
+    model.load_npz("./tests/serialize_test.npz").unwrap();
+    let path = std::env::temp_dir().join("raddar_save_npz_test.npz");
+    model.save_npz(&path).unwrap();
+
+    let state_dict = StateDict::from_npz(&path).unwrap();
+    assert_eq!(
+        state_dict.keys().collect::<Vec<_>>(),
+        vec!["0.weight", "0.bias", "1.weight", "1.bias"]
+    );
+
+    let other = seq!(
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+    );
+    other.load_npz(&path).unwrap();
+    let output = other(&tensor!([2.0f32]));
+    assert_tensor_eq!(&output, &tensor!([0.1818f32]));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn safetensors_test() {
+    let model = seq!(
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+    );
+    model.load_npz("./tests/serialize_test.npz").unwrap();
+    let path = std::env::temp_dir().join("raddar_safetensors_test.safetensors");
+    model.save_safetensors(&path).unwrap();
+
+    let (state_dict, metadata) = read_safetensors(&path).unwrap();
+    assert_eq!(
+        state_dict.keys().collect::<Vec<_>>(),
+        vec!["0.weight", "0.bias", "1.weight", "1.bias"]
+    );
+    assert_eq!(metadata["format"], "pt");
+
+    let other = seq!(
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+    );
+    other.load_safetensors(&path).unwrap();
+    let output = other(&tensor!([2.0]));
+    assert_tensor_eq!(&output, &tensor!([0.1818]));
+
+    let wider = seq!(
+        LinearBuilder::default().input_dim(2).output_dim(1).build(),
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+    );
+    let error = wider.load_safetensors(&path).unwrap_err().to_string();
+    assert!(error.contains("0.weight"), "{}", error);
+
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &bytes[..bytes.len() - 4]).unwrap();
+    assert!(StateDict::from_safetensors(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn key_map_test() {
+    let key_map = KeyMap::new()
+        .strip_prefix("module.")
+        .skip(r"num_batches_tracked$")
+        .rename(r"^layer(\d)\.", "net.$1.")
+        .add_prefix("backbone.");
+    assert_eq!(
+        key_map.map_key("module.layer1.0.weight"),
+        Some("backbone.net.1.0.weight".to_owned())
+    );
+    assert_eq!(
+        key_map.map_key("fc.bias"),
+        Some("backbone.fc.bias".to_owned())
+    );
+    assert_eq!(key_map.map_key("module.bn.num_batches_tracked"), None);
+
+    let model = seq!(
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+    );
+    let state_dict = vec![
+        ("module.first.weight".to_owned(), tensor!([[1.0]]).cell()),
+        ("module.first.bias".to_owned(), tensor!([2.0]).cell()),
+        ("module.second.weight".to_owned(), tensor!([[3.0]]).cell()),
+        ("module.second.bias".to_owned(), tensor!([2.0]).cell()),
+    ]
+    .into_iter()
+    .collect();
+    let key_map = KeyMap::new()
+        .strip_prefix("module.")
+        .rename(r"^first\.", "0.")
+        .rename(r"^second\.", "1.");
+    model.load_mapped(state_dict, &key_map);
+    let output = model(&tensor!([1.0]));
+    assert_tensor_eq!(&output, &tensor!([11.0]));
+}
+
+#[test]
+fn half_precision_test() {
+    let model = seq!(
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+    );
+    model.load_npz("./tests/serialize_test.npz").unwrap();
+    let path = std::env::temp_dir().join("raddar_half_precision_test.safetensors");
+    let half = model.parameters().cast(Kind::Half);
+    assert_eq!(half["0.weight"].lock().kind(), Kind::Half);
+    half.to_safetensors(&path).unwrap();
+
+    let state_dict = StateDict::from_safetensors(&path).unwrap();
+    assert_eq!(state_dict["1.bias"].lock().kind(), Kind::Half);
+
+    let other = seq!(
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+    );
+    other.load(state_dict);
+    assert_eq!(other.parameters()["0.weight"].lock().kind(), Kind::Double);
+    let output = other(&tensor!([2.0]));
+    assert_tensor_eq!(&output, &tensor!([0.1818]), 1e-4);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn sharded_checkpoint_test() {
+    let model = seq!(
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+    );
+    model.load_npz("./tests/serialize_test.npz").unwrap();
+    let dir = std::env::temp_dir().join("raddar_sharded_checkpoint_test");
+    let checkpoint = model.save_sharded(&dir, 16).unwrap();
+    assert_eq!(
+        checkpoint.shards(),
+        vec![
+            "model-00001-of-00002.safetensors",
+            "model-00002-of-00002.safetensors"
+        ]
+    );
+
+    let checkpoint = ShardedCheckpoint::open(&dir).unwrap();
+    assert_eq!(checkpoint.total_size, 32);
+    assert_eq!(
+        checkpoint.weight_map["1.bias"],
+        "model-00002-of-00002.safetensors"
+    );
+    let tensors = checkpoint.load_tensors(&["0.weight"]).unwrap();
+    assert_eq!(tensors.len(), 1);
+
+    let other = seq!(
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+        LinearBuilder::default().input_dim(1).output_dim(1).build(),
+    );
+    other.load_sharded(&checkpoint).unwrap();
+    let output = other(&tensor!([2.0]));
+    assert_tensor_eq!(&output, &tensor!([0.1818]), 1e-4);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn gguf_test() {
+    fn string(bytes: &mut Vec<u8>, s: &str) {
+        bytes.extend((s.len() as u64).to_le_bytes());
+        bytes.extend(s.as_bytes());
+    }
+    let mut bytes = Vec::new();
+    bytes.extend(b"GGUF");
+    bytes.extend(3u32.to_le_bytes());
+    bytes.extend(4u64.to_le_bytes());
+    bytes.extend(1u64.to_le_bytes());
+    string(&mut bytes, "general.architecture");
+    bytes.extend(8u32.to_le_bytes());
+    string(&mut bytes, "llama");
+    // An F32 tensor of shape [2], a Q8_0 tensor of shape [1, 32], and Q4_K and Q6_K tensors of shape [256].
+    string(&mut bytes, "a");
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend(2u64.to_le_bytes());
+    bytes.extend(0u32.to_le_bytes());
+    bytes.extend(0u64.to_le_bytes());
+    string(&mut bytes, "b");
+    bytes.extend(2u32.to_le_bytes());
+    bytes.extend(32u64.to_le_bytes());
+    bytes.extend(1u64.to_le_bytes());
+    bytes.extend(8u32.to_le_bytes());
+    bytes.extend(32u64.to_le_bytes());
+    string(&mut bytes, "c");
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend(256u64.to_le_bytes());
+    bytes.extend(12u32.to_le_bytes());
+    bytes.extend(96u64.to_le_bytes());
+    string(&mut bytes, "d");
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend(256u64.to_le_bytes());
+    bytes.extend(14u32.to_le_bytes());
+    bytes.extend(256u64.to_le_bytes());
+    bytes.resize((bytes.len() + 31) / 32 * 32, 0);
+    bytes.extend(1.0f32.to_le_bytes());
+    bytes.extend(2.0f32.to_le_bytes());
+    bytes.resize(bytes.len() + 24, 0);
+    // The scale 0.5 in half precision, then the quantized values 0..32.
+    bytes.extend(0x3800u16.to_le_bytes());
+    bytes.extend(0..32u8);
+    bytes.resize(bytes.len() + 30, 0);
+    // The scales 0.5 and 1.0, the 6-bit scales 1 and mins 0 except the min 2 of the first sub-block, then the quantized values 1 and 2.
+    bytes.extend(0x3800u16.to_le_bytes());
+    bytes.extend(0x3C00u16.to_le_bytes());
+    bytes.extend([1, 1, 1, 1, 2, 0, 0, 0, 1, 1, 1, 1]);
+    bytes.extend([0x21; 128]);
+    bytes.resize(bytes.len() + 16, 0);
+    // The low bits 5 for the first value and 0 for the others, the high bits 0, the scales 2 for the first 16 values and 1 for the others, then the scale 0.5.
+    bytes.push(5);
+    bytes.extend([0; 127 + 64]);
+    bytes.push(2);
+    bytes.extend([1; 15]);
+    bytes.extend(0x3800u16.to_le_bytes());
+
+    let path = std::env::temp_dir().join("raddar_gguf_test.gguf");
+    std::fs::write(&path, bytes).unwrap();
+    let (state_dict, metadata) = read_gguf(&path).unwrap();
+    assert_eq!(metadata["general.architecture"], "llama");
+    assert_eq!(
+        state_dict.keys().collect::<Vec<_>>(),
+        vec!["a", "b", "c", "d"]
+    );
+    assert_tensor_eq!(&*state_dict["a"].lock(), &Tensor::of_slice(&[1.0f32, 2.0]));
+    let b = state_dict["b"].lock();
+    assert_eq!(b.size(), vec![1, 32]);
+    assert_eq!(b.kind(), Kind::Float);
+    assert_eq!(f64::from(b.get(0).get(31)), 15.5);
+    let c = state_dict["c"].lock();
+    assert_eq!(c.size(), vec![256]);
+    assert_eq!(f64::from(c.get(0)), -1.5);
+    assert_eq!(f64::from(c.get(32)), 1.0);
+    assert_eq!(f64::from(c.get(255)), 1.0);
+    let d = state_dict["d"].lock();
+    assert_eq!(f64::from(d.get(0)), -27.0);
+    assert_eq!(f64::from(d.get(16)), -16.0);
+    assert_eq!(f64::from(d.get(255)), -16.0);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn save_model_test() {
+    let model = LinearBuilder::default()
+        .input_dim(2)
+        .output_dim(3)
+        .bias(false)
+        .build();
+    let path = std::env::temp_dir().join("raddar_save_model_test.safetensors");
+    model.save_model(&path).unwrap();
+
+    let loaded = Mod::<Linear>::load_model(&path).unwrap();
+    assert_eq!(loaded.module().input_dim, 2);
+    assert_eq!(loaded.module().output_dim, 3);
+    assert!(!loaded.module().bias);
+    let input = tensor!([[1.0, 2.0]]);
+    assert_tensor_eq!(&loaded(&input), &model(&input));
+    assert!(Mod::<LayerNorm>::load_model(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn buffers_test() {
+    let bn = BatchNorm1dBuilder::default().num_features(2).build();
+    let _output = bn(&tensor!([[1.0, 2.0], [3.0, 4.0]]));
+    let state_dict = bn.state_dict();
+    assert_eq!(
+        state_dict.keys().collect::<Vec<_>>(),
+        vec!["weight", "bias", "running_mean", "running_var"]
+    );
+    assert_tensor_eq!(&*state_dict["running_mean"].lock(), &tensor!([0.2, 0.3]));
+
+    let path = std::env::temp_dir().join("raddar_buffers_test.safetensors");
+    bn.save_safetensors(&path).unwrap();
+    let other = BatchNorm1dBuilder::default().num_features(2).build();
+    other.load_safetensors(&path).unwrap();
+    assert_tensor_eq!(
+        &*other.buffers()["running_mean"].lock(),
+        &tensor!([0.2, 0.3])
+    );
+
+    // Files without buffers still load, and the buffers keep their values.
+    bn.parameters().to_safetensors(&path).unwrap();
+    let older = BatchNorm1dBuilder::default().num_features(2).build();
+    older.load_safetensors(&path).unwrap();
+    assert_tensor_eq!(
+        &*older.buffers()["running_mean"].lock(),
+        &tensor!([0.0, 0.0])
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn config_serde_test() {
+    let config = Conv2dBuilder::default()
+        .in_channel(3)
+        .out_channel(4)
+        .kernel_size([3, 3])
+        .dtype(Kind::Float)
+        .config();
+    let value = serde_json::to_value(&config).unwrap();
+    assert_eq!(value["kernel_size"], serde_json::json!([3, 3]));
+    assert_eq!(value["dtype"], "Float");
+    assert_eq!(value["device"], "cpu");
+
+    let config: Conv2dConfig = serde_json::from_value(value).unwrap();
+    assert_eq!(config.out_channel, 4);
+    let conv = config.build();
+    assert_eq!(conv.module().conv_weight.lock().size(), vec![4, 3, 3, 3]);
+    assert_eq!(conv.module().conv_weight.lock().kind(), Kind::Float);
+
+    let config = VggBuilder::default()
+        .num_classes(10)
+        .vgg_type(VggType::Vgg11)
+        .config();
+    let json = serde_json::to_string(&config).unwrap();
+    let config: VggConfig = serde_json::from_str(&json).unwrap();
+    assert_eq!(config.num_classes, 10);
+}
+
+#[test]
+fn var_store_test() {
+    let model = seq!(
+        LinearBuilder::default()
+            .input_dim(2)
+            .output_dim(1)
+            .dtype(Kind::Float)
+            .build(),
+        BatchNorm1dBuilder::default()
+            .num_features(1)
+            .dtype(Kind::Float)
+            .build(),
+    );
+    let var_store = model.state_dict().to_var_store(Device::Cpu);
+    assert_eq!(var_store.trainable_variables().len(), 4);
+    let variables = var_store.variables();
+    assert_eq!(variables.len(), model.state_dict().len());
+    assert_tensor_eq!(
+        &variables["0.weight"],
+        &*model.parameters()["0.weight"].lock()
+    );
+
+    let state_dict = StateDict::from_var_store(&var_store);
+    assert_eq!(state_dict.keys().next().unwrap(), "0.bias");
+
+    let other = seq!(
+        LinearBuilder::default()
+            .input_dim(2)
+            .output_dim(1)
+            .dtype(Kind::Float)
+            .build(),
+        BatchNorm1dBuilder::default()
+            .num_features(1)
+            .dtype(Kind::Float)
+            .build(),
+    );
+    other.load_var_store(&var_store).unwrap();
+    // The loaded parameters share their storage with the variables.
+    no_grad(|| variables["0.bias"].shallow_clone().fill_(5.0));
+    assert_tensor_eq!(&*other.parameters()["0.bias"].lock(), &tensor!([5.0f32]));
+}
@@ -0,0 +1,358 @@
+use std::{cell::RefCell, rc::Rc};
+
+use raddar::{
+    dataset::{DataLoaderConfigBuilder, Dataset, KFold, TensorDataset, UnsupervisedTensorDataset},
+    metrics::{MeanAbsoluteError, Metric},
+    nn::{LinearBuilder, ModuleMode, Trainable},
+    optim::{gradient_descent, opt},
+    tensor_vec,
+    train::{
+        cross_validate, evaluate, search, Callback, EarlyStoppingBuilder, GanTrainer, LogFormat,
+        LogFrequency, Logs, MetricLogger, ModelCheckpointBuilder, Params, ProgressCallback,
+        SearchConfigBuilder, SearchSpace, SearchStrategy, TensorBoardLogger, Trainer,
+        TrainerContext,
+    },
+};
+use tch::{Kind, Reduction, Tensor};
+
+#[derive(Default)]
+struct CountingCallback {
+    events: Rc<RefCell<Vec<&'static str>>>,
+}
+
+impl Callback for CountingCallback {
+    fn on_epoch_end(&mut self, ctx: &mut TrainerContext) {
+        assert!(ctx.logs.contains_key("loss"));
+        self.events.borrow_mut().push("epoch_end");
+    }
+
+    fn on_backward(&mut self, _ctx: &mut TrainerContext) {
+        self.events.borrow_mut().push("backward");
+    }
+
+    fn on_batch_end(&mut self, ctx: &mut TrainerContext) {
+        self.events.borrow_mut().push("batch_end");
+        if ctx.step == 5 {
+            ctx.stop_training = true;
+        }
+    }
+}
+
+#[test]
+fn trainer_callback_test() {
+    let inputs = tensor_vec![[1.0], [3.0], [5.0], [4.0], [8.0], [10.0], [2.0], [6.0]];
+    let labels = tensor_vec![[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.], [19.0]];
+    let loader = TensorDataset::from_tensors(inputs, labels).into_loader(
+        DataLoaderConfigBuilder::default()
+            .batch_size(4)
+            .build()
+            .unwrap(),
+    );
+
+    let model = LinearBuilder::default().input_dim(1).output_dim(1).build();
+    let optimizer = opt(model.training_parameters(), gradient_descent(0.01));
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let mut trainer = Trainer::new(model, optimizer, |output: &Tensor, label: &Tensor| {
+        output.mse_loss(label, Reduction::Mean)
+    })
+    .callback(CountingCallback {
+        events: events.clone(),
+    });
+    let logs = trainer.fit(loader, 10);
+    assert!(logs["loss"].is_finite());
+    let events = events.borrow();
+    assert_eq!(events.iter().filter(|e| **e == "batch_end").count(), 5);
+    assert_eq!(events.iter().filter(|e| **e == "backward").count(), 5);
+    assert_eq!(events.iter().filter(|e| **e == "epoch_end").count(), 3);
+}
+
+#[test]
+fn early_stopping_test() {
+    let inputs = tensor_vec![[1.0], [3.0], [5.0], [4.0], [8.0], [10.0], [2.0], [6.0]];
+    let labels = tensor_vec![[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.], [19.0]];
+    let loader = TensorDataset::from_tensors(inputs, labels).into_loader(
+        DataLoaderConfigBuilder::default()
+            .batch_size(8)
+            .build()
+            .unwrap(),
+    );
+
+    // With a zero learning rate, the loss never improves after the first epoch.
+    let model = LinearBuilder::default().input_dim(1).output_dim(1).build();
+    let optimizer = opt(model.training_parameters(), gradient_descent(0.));
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let mut trainer = Trainer::new(model, optimizer, |output: &Tensor, label: &Tensor| {
+        output.mse_loss(label, Reduction::Mean)
+    })
+    .callback(
+        EarlyStoppingBuilder::default()
+            .patience(2)
+            .restore_best_weights(true)
+            .build()
+            .unwrap(),
+    )
+    .callback(CountingCallback {
+        events: events.clone(),
+    });
+    trainer.fit(loader, 100);
+    assert_eq!(
+        events
+            .borrow()
+            .iter()
+            .filter(|e| **e == "epoch_end")
+            .count(),
+        3
+    );
+}
+
+#[test]
+fn tensorboard_logger_test() {
+    let inputs = tensor_vec![[1.0], [3.0], [5.0], [4.0], [8.0], [10.0], [2.0], [6.0]];
+    let labels = tensor_vec![[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.], [19.0]];
+    let loader = TensorDataset::from_tensors(inputs, labels).into_loader(
+        DataLoaderConfigBuilder::default()
+            .batch_size(4)
+            .build()
+            .unwrap(),
+    );
+
+    let log_dir = std::env::temp_dir().join("raddar_tensorboard_test");
+    let _ = std::fs::remove_dir_all(&log_dir);
+    let logger = TensorBoardLogger::new(&log_dir).unwrap().histograms(true);
+    let path = logger.writer.path().to_owned();
+    let model = LinearBuilder::default().input_dim(1).output_dim(1).build();
+    let optimizer = opt(model.training_parameters(), gradient_descent(0.01));
+    let mut trainer = Trainer::new(model, optimizer, |output: &Tensor, label: &Tensor| {
+        output.mse_loss(label, Reduction::Mean)
+    })
+    .callback(logger);
+    trainer.fit(loader, 2);
+
+    let content = std::fs::read(path).unwrap();
+    let length = u64::from_le_bytes(content[..8].try_into().unwrap()) as usize;
+    assert!(content.len() > length + 16);
+    assert!(content
+        .windows(b"train/loss".len())
+        .any(|window| window == b"train/loss"));
+}
+
+#[test]
+fn metric_logger_test() {
+    let inputs = tensor_vec![[1.0], [3.0], [5.0], [4.0], [8.0], [10.0], [2.0], [6.0]];
+    let labels = tensor_vec![[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.], [19.0]];
+    let loader = TensorDataset::from_tensors(inputs, labels).into_loader(
+        DataLoaderConfigBuilder::default()
+            .batch_size(4)
+            .build()
+            .unwrap(),
+    );
+
+    let log_dir = std::env::temp_dir().join("raddar_metric_logger_test");
+    let csv_path = log_dir.join("epochs.csv");
+    let jsonl_path = log_dir.join("steps.jsonl");
+    let model = LinearBuilder::default().input_dim(1).output_dim(1).build();
+    let optimizer = opt(model.training_parameters(), gradient_descent(0.01));
+    let mut trainer = Trainer::new(model, optimizer, |output: &Tensor, label: &Tensor| {
+        output.mse_loss(label, Reduction::Mean)
+    })
+    .callback(MetricLogger::csv(&csv_path).unwrap())
+    .callback(MetricLogger::new(&jsonl_path, LogFormat::JsonLines, LogFrequency::Step(1)).unwrap());
+    trainer.fit(loader, 3);
+
+    let csv = std::fs::read_to_string(csv_path).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "epoch,step,loss");
+    assert_eq!(lines.len(), 4);
+    assert!(lines[3].starts_with("2,6,"));
+
+    let jsonl = std::fs::read_to_string(jsonl_path).unwrap();
+    assert_eq!(jsonl.lines().count(), 6);
+    let record: serde_json::Value = serde_json::from_str(jsonl.lines().last().unwrap()).unwrap();
+    assert_eq!(record["step"], 6.0);
+    assert_eq!(record["lr"], 0.01);
+}
+
+#[test]
+fn model_checkpoint_test() {
+    let inputs = tensor_vec![[1.0], [3.0], [5.0], [4.0], [8.0], [10.0], [2.0], [6.0]];
+    let labels = tensor_vec![[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.], [19.0]];
+    let loader = TensorDataset::from_tensors(inputs, labels).into_loader(
+        DataLoaderConfigBuilder::default()
+            .batch_size(4)
+            .build()
+            .unwrap(),
+    );
+
+    let dir = std::env::temp_dir().join("raddar_model_checkpoint_test");
+    let _ = std::fs::remove_dir_all(&dir);
+    let model = LinearBuilder::default().input_dim(1).output_dim(1).build();
+    let optimizer = opt(model.training_parameters(), gradient_descent(0.01));
+    let mut trainer = Trainer::new(model, optimizer, |output: &Tensor, label: &Tensor| {
+        output.mse_loss(label, Reduction::Mean)
+    })
+    .callback(
+        ModelCheckpointBuilder::default()
+            .dirpath(&dir)
+            .filename("model-{epoch}-{step}".to_owned())
+            .save_top_k(2)
+            .save_last(true)
+            .build()
+            .unwrap(),
+    );
+    trainer.fit(loader, 5);
+
+    let checkpoints: Vec<String> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.starts_with("model-") && !name.contains("optimizer"))
+        .collect();
+    assert_eq!(checkpoints.len(), 2);
+    // The loss decreases during training, so the first epochs are deleted.
+    assert!(!checkpoints.contains(&"model-0-2.npz".to_owned()));
+    assert!(dir.join("model-4-10.optimizer.npz").exists());
+    let last = Tensor::read_npz(dir.join("last.npz")).unwrap();
+    let names: Vec<&str> = last.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, vec!["weight", "bias"]);
+}
+
+#[test]
+fn progress_callback_test() {
+    let inputs = tensor_vec![[1.0], [3.0], [5.0], [4.0], [8.0], [10.0], [2.0], [6.0]];
+    let labels = tensor_vec![[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.], [19.0]];
+    let loader = TensorDataset::from_tensors(inputs, labels).into_loader(
+        DataLoaderConfigBuilder::default()
+            .batch_size(4)
+            .build()
+            .unwrap(),
+    );
+
+    let model = LinearBuilder::default().input_dim(1).output_dim(1).build();
+    let optimizer = opt(model.training_parameters(), gradient_descent(0.01));
+    let mut trainer = Trainer::new(model, optimizer, |output: &Tensor, label: &Tensor| {
+        output.mse_loss(label, Reduction::Mean)
+    })
+    .callback(ProgressCallback::new().total_epochs(3));
+    let logs = trainer.fit(loader, 3);
+    assert!(logs["loss"].is_finite());
+}
+
+#[test]
+fn cross_validate_test() {
+    let inputs = tensor_vec![[1.0], [3.0], [5.0], [4.0], [8.0], [10.0], [2.0], [6.0]];
+    let labels = tensor_vec![[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.], [19.0]];
+    let dataset = TensorDataset::from_tensors(inputs, labels);
+
+    let result = cross_validate(&KFold::new(4), dataset, |_, train, val| {
+        assert_eq!((train.size(), val.size()), (6, 2));
+        let model = LinearBuilder::default().input_dim(1).output_dim(1).build();
+        let optimizer = opt(model.training_parameters(), gradient_descent(0.01));
+        let mut trainer = Trainer::new(model, optimizer, |output: &Tensor, label: &Tensor| {
+            output.mse_loss(label, Reduction::Mean)
+        });
+        trainer.fit(
+            train.into_loader(
+                DataLoaderConfigBuilder::default()
+                    .batch_size(2)
+                    .build()
+                    .unwrap(),
+            ),
+            2,
+        )
+    });
+    assert_eq!(result.folds.len(), 4);
+    assert!(result.mean()["loss"].is_finite());
+    assert!(result.std()["loss"] >= 0.);
+}
+
+#[test]
+fn evaluate_test() {
+    let inputs = tensor_vec![[1.0], [3.0], [5.0], [4.0], [8.0], [10.0], [2.0], [6.0]];
+    let labels = tensor_vec![[4.0], [10.0], [16.], [13.0], [25.], [31.], [7.], [19.0]];
+    let dataset = TensorDataset::from_tensors(inputs.clone(), labels.clone());
+
+    let model = LinearBuilder::default().input_dim(1).output_dim(1).build();
+    let mut metrics: Vec<Box<dyn Metric>> = vec![Box::new(MeanAbsoluteError::new())];
+    let logs = evaluate(&model, dataset, &mut metrics, 3);
+    assert!(matches!(model.mode(), ModuleMode::Train));
+
+    let inputs = Tensor::stack(&inputs, 0);
+    let labels = Tensor::stack(&labels, 0);
+    let expected = f64::from((model(&inputs) - labels).abs().mean(Kind::Double));
+    assert!((logs["mae"] - expected).abs() < 1e-9);
+}
+
+#[test]
+fn search_test() {
+    let space = SearchSpace::new()
+        .uniform("x", 0., 1.)
+        .choice("y", vec![-1., 2.]);
+    let objective = |params: &Params| {
+        let mut logs = Logs::new();
+        logs.insert(
+            "loss".to_owned(),
+            (params["x"] - 0.3).powi(2) + (params["y"] - 2.).powi(2),
+        );
+        logs
+    };
+
+    let grid = search(
+        &space,
+        &SearchConfigBuilder::default()
+            .strategy(SearchStrategy::Grid { points: 5 })
+            .build()
+            .unwrap(),
+        objective,
+    );
+    assert_eq!(grid.trials.len(), 10);
+    let best = grid.best().unwrap();
+    assert_eq!(best.params["x"], 0.25);
+    assert_eq!(best.params["y"], 2.);
+
+    let random = search(
+        &space,
+        &SearchConfigBuilder::default()
+            .strategy(SearchStrategy::Random {
+                trials: 16,
+                seed: Some(0),
+            })
+            .parallelism(4)
+            .build()
+            .unwrap(),
+        objective,
+    );
+    assert_eq!(random.trials.len(), 16);
+    assert!(random
+        .trials
+        .iter()
+        .all(|trial| trial.logs.contains_key("loss")));
+    assert!(random.best().unwrap().logs["loss"] <= random.trials[0].logs["loss"]);
+}
+
+#[test]
+fn gan_trainer_test() {
+    let samples = tensor_vec![[1.0], [1.2], [0.8], [1.1], [0.9], [1.0], [1.3], [0.7]];
+    let loader = UnsupervisedTensorDataset::from_tensors(samples).into_loader(
+        DataLoaderConfigBuilder::default()
+            .batch_size(4)
+            .build()
+            .unwrap(),
+    );
+
+    let generator = LinearBuilder::default().input_dim(2).output_dim(1).build();
+    let discriminator = LinearBuilder::default().input_dim(1).output_dim(1).build();
+    let g_opt = opt(generator.training_parameters(), gradient_descent(0.01));
+    let d_opt = opt(discriminator.training_parameters(), gradient_descent(0.01));
+    let epochs = Rc::new(RefCell::new(0));
+    let epochs_clone = epochs.clone();
+    let mut trainer = GanTrainer::new(generator, discriminator, g_opt, d_opt, 2)
+        .label_smoothing(0.9)
+        .discriminator_steps(2)
+        .on_epoch_end(move |_, logs| {
+            assert!(logs["d_loss"].is_finite());
+            *epochs_clone.borrow_mut() += 1;
+        });
+    let logs = trainer.fit(loader, 3);
+    assert_eq!(*epochs.borrow(), 3);
+    assert!(logs["g_loss"].is_finite());
+    assert_eq!(trainer.generate(5).size(), vec![5, 1]);
+}